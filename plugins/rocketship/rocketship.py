@@ -16,8 +16,9 @@ class WitWorld(WitWorld):
         """
         host_api.call_host(invocation_id, "Rocketship plugin active!")
         match event:
-            case event_api.Event.BeforeSend(content):
-                # Simply append a rocket emoji and return the modified event
-                return event_api.Event.BeforeSend(content + " 🚀")
+            case event_api.Event.BeforeSend(message):
+                # Simply append a rocket emoji to the body and return the modified event
+                message.body += " 🚀"
+                return event_api.Event.BeforeSend(message)
             case _:
                 return event
\ No newline at end of file
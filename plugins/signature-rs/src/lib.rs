@@ -17,8 +17,9 @@ impl Guest for SignaturePlugin {
     // when a specific event is triggered.
     fn on_notify(_invocation_id: String, event: event_api::Event) -> event_api::Event {
         let return_event = match event {
-            event_api::Event::BeforeSend(content) => {
-                event_api::Event::BeforeSend(content + "\n\n--\nSent from signature-rs!")
+            event_api::Event::BeforeSend(mut message) => {
+                message.body += "\n\n--\nSent from signature-rs!";
+                event_api::Event::BeforeSend(message)
             }
             // If the event is not a BeforeSend event, return it as-is
             // Of course, termail will never trigger an unsubscribed 
@@ -49,3 +49,28 @@ pub async fn authenticate(_config: &OAuth2Config) -> Result<OAuth2Token, Error>
     })
 }
 
+/// Checks whether a yup-oauth2 token cache can satisfy `scopes` without the
+/// interactive (browser) flow, i.e. it holds a token covering every scope with a
+/// refresh token that can be exchanged for a fresh access token.
+///
+/// This only inspects the cache. The refresh itself may still fail (e.g. revoked
+/// grant), in which case yup-oauth2 falls back to the interactive flow.
+pub fn has_refreshable_token(cache_contents: &str, scopes: &[&str]) -> bool {
+    let entries: Vec<serde_json::Value> = match serde_json::from_str(cache_contents) {
+        Ok(entries) => entries,
+        Err(_) => return false,
+    };
+
+    entries.iter().any(|entry| {
+        let cached_scopes: Vec<&str> = entry["scopes"]
+            .as_array()
+            .map(|s| s.iter().filter_map(|scope| scope.as_str()).collect())
+            .unwrap_or_default();
+        let covers_scopes = scopes.iter().all(|scope| cached_scopes.contains(scope));
+        let has_refresh_token = entry["token"]["refresh_token"]
+            .as_str()
+            .map(|token| !token.is_empty())
+            .unwrap_or(false);
+        covers_scopes && has_refresh_token
+    })
+}
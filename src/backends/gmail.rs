@@ -1,34 +1,194 @@
-use super::{Backend, Error};
-use crate::config::BackendConfig;
+use super::{Backend, BackendCapabilities, Error, refresh};
+use crate::config::{BackendConfig, DeleteBehavior, legacy_token_cache_paths, migrate_token_cache};
 use crate::plugins::events::Hook;
 use crate::cli::command::{Command, CommandResult};
-use crate::core::{email::{EmailMessage, EmailSender, MimeType}, label::Label, editor::Editor};
+use crate::core::mailto::parse_mailto;
+use crate::core::charset::{charset_from_content_type, decode_header_value, decode_text};
+use crate::core::{email::{AttachmentStats, EmailMessage, EmailSender, MimeType, ReplyHeaders, SNIPPET_MAX_LENGTH, make_snippet}, label::{Label, is_label_shown, move_label_changes, spam_label_changes}, editor::Editor};
 use std::collections::{HashMap, HashSet};
-use google_gmail1::{Gmail, hyper_rustls, hyper_util, yup_oauth2, api::Message};
+use std::path::{Path, PathBuf};
+use google_gmail1::{Gmail, hyper_rustls, hyper_util, yup_oauth2, api::{BatchModifyMessagesRequest, Message, ModifyMessageRequest}};
 use yup_oauth2::{InstalledFlowAuthenticator, InstalledFlowReturnMethod};
+use yup_oauth2::authenticator_delegate::InstalledFlowDelegate;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
 use async_trait::async_trait;
 use hyper_rustls::HttpsConnector;
-use futures::future;
+use futures::{future, stream, StreamExt};
 use crate::plugins::plugins::{PluginManager};
-use crate::maildir::MaildirManager;
+use crate::maildir::{MaildirManager, is_local_id};
+use crate::net::{ProxyConfig, ProxyConnector};
+use crate::auth::oauth::has_refreshable_token;
 
 const SYNC_SOURCE: &str = "INBOX";
 
-type GmailHub = Gmail<HttpsConnector<hyper_util::client::legacy::connect::HttpConnector>>;
+/// Maximum number of `labels_get` requests in flight at once.
+const LABEL_FETCH_CONCURRENCY: usize = 8;
+
+/// Default cap on `FetchInbox` when `max_fetch` is not set in the config.
+const DEFAULT_MAX_FETCH: usize = 100;
+
+/// Most message ids Gmail accepts in a single `batchModify` request.
+const BATCH_MODIFY_LIMIT: usize = 1000;
+
+/// Messages `Upload` inserts before pausing for `UPLOAD_CHUNK_PAUSE`, to stay under
+/// Gmail's per-user rate limit during a large backfill.
+const UPLOAD_CHUNK_SIZE: usize = 50;
+const UPLOAD_CHUNK_PAUSE: std::time::Duration = std::time::Duration::from_secs(1);
+
+// TODO: use a better way to get the scopes
+// Should be defined in the config file maybe?
+const SCOPES: &[&str] = &[
+    "https://www.googleapis.com/auth/gmail.readonly",
+    "https://www.googleapis.com/auth/gmail.addons.current.message.readonly",
+    "https://www.googleapis.com/auth/gmail.send",
+    "https://www.googleapis.com/auth/gmail.modify",
+];
+
+/// Full mailbox access, only needed for permanent deletion (`delete_behavior = "delete"`).
+const FULL_ACCESS_SCOPE: &str = "https://mail.google.com/";
+
+/// The OAuth2 scopes to request. Full mailbox access is only asked for when
+/// messages are deleted permanently, so other users are not prompted for it.
+pub fn scopes_for(delete_behavior: DeleteBehavior) -> Vec<&'static str> {
+    let mut scopes = SCOPES.to_vec();
+    if delete_behavior == DeleteBehavior::Delete {
+        scopes.push(FULL_ACCESS_SCOPE);
+    }
+    scopes
+}
+
+/// Evaluates a Gmail API call, refreshing the token and retrying once if it reported expiry.
+///
+/// `$call` must be an expression yielding `Result<T, Error>` (with the `.await` included).
+/// It is evaluated a second time for the retry, so it should rebuild the request itself.
+/// Calls running concurrently that all find the token expired refresh it only once,
+/// see `TokenRefresher`.
+macro_rules! with_token_retry {
+    ($backend:expr, $call:expr) => {{
+        let generation = $backend.token_refresher.generation();
+        match $call {
+            Err(Error::TokenExpired(reason)) => {
+                tracing::warn!("Gmail token expired ({}), refreshing and retrying once", reason);
+                match $backend.refresh_token(generation).await {
+                    Ok(()) => $call,
+                    Err(e) => Err(e),
+                }
+            }
+            result => result,
+        }
+    }};
+}
+
+/// Lets concurrent requests that all saw an expired token refresh it only once:
+/// the first one refreshes, the others wait for it and then simply retry.
+#[derive(Debug, Default)]
+pub struct TokenRefresher {
+    /// Number of refreshes done so far.
+    refreshes: AtomicU64,
+    /// Held while refreshing.
+    lock: tokio::sync::Mutex<()>,
+}
+
+impl TokenRefresher {
+    /// Read before making a request; hand it to `refresh` if the request then fails.
+    pub fn generation(&self) -> u64 {
+        self.refreshes.load(Ordering::SeqCst)
+    }
+
+    /// Runs `refresh` unless another refresh finished after `generation` was read.
+    pub async fn refresh<F, Fut>(&self, generation: u64, refresh: F) -> Result<(), Error>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<(), Error>>,
+    {
+        let _guard = self.lock.lock().await;
+        if self.generation() != generation {
+            return Ok(());
+        }
+        refresh().await?;
+        self.refreshes.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+/// Flow delegate that tells the user why they are being sent to the browser.
+///
+/// yup-oauth2 only calls this when the cached token is missing or cannot be refreshed.
+struct ReauthFlowDelegate;
+
+impl InstalledFlowDelegate for ReauthFlowDelegate {
+    fn present_user_url<'a>(
+        &'a self,
+        url: &'a str,
+        need_code: bool,
+    ) -> Pin<Box<dyn Future<Output = Result<String, String>> + Send + 'a>> {
+        Box::pin(async move {
+            tracing::warn!("No usable cached Gmail token, interactive authentication required");
+            eprintln!("termail needs you to sign in to Gmail. Open this URL in your browser:\n\n{}\n", url);
+            if need_code {
+                eprintln!("Paste the authorization code here:");
+                let mut code = String::new();
+                std::io::stdin().read_line(&mut code).map_err(|e| e.to_string())?;
+                Ok(code.trim().to_string())
+            } else {
+                Ok(String::new())
+            }
+        })
+    }
+}
+
+type GmailConnector = HttpsConnector<ProxyConnector>;
+type GmailHub = Gmail<GmailConnector>;
+type GmailAuthenticator = yup_oauth2::authenticator::Authenticator<GmailConnector>;
 pub struct GmailBackend {
     oauth2_client_secret_file: Option<String>,
+    /// File the OAuth2 tokens are persisted to, so later runs can skip the browser flow.
+    token_cache_path: PathBuf,
     hub: Option<Box<GmailHub>>,
+    /// Kept alongside the hub so an expired token can be force-refreshed.
+    auth: Option<GmailAuthenticator>,
+    /// Makes concurrent requests share one forced refresh.
+    token_refresher: TokenRefresher,
+    /// Proxy the API and OAuth2 requests go through, if any.
+    proxy: Option<ProxyConfig>,
+    /// Bounds connecting to Gmail, or to the proxy.
+    connect_timeout: Option<Duration>,
     filter_labels: Option<Vec<String>>,
+    show_only_labels: Option<Vec<String>>,
+    max_fetch: usize,
+    delete_behavior: DeleteBehavior,
     editor: String,
     maildir_manager: MaildirManager,
 }
 
 impl GmailBackend {
     pub fn new(config: &BackendConfig, editor: String) -> Self {
+        let token_cache_path = config.token_cache_path("gmail");
+        if config.token_cache_path.is_none() {
+            match migrate_token_cache(&legacy_token_cache_paths("gmail"), &token_cache_path) {
+                Ok(Some(legacy)) => tracing::info!("Moved Gmail token cache {:?} to {:?}", legacy, token_cache_path),
+                Ok(None) => {}
+                Err(e) => tracing::warn!("{}", e),
+            }
+        }
         Self {
             oauth2_client_secret_file: config.oauth2_client_secret_file.clone(),
+            token_cache_path,
+            connect_timeout: config.connect_timeout(),
+            proxy: config.proxy().unwrap_or_else(|e| {
+                tracing::error!("{}", e);
+                std::process::exit(1);
+            }),
             hub: None,
+            auth: None,
+            token_refresher: TokenRefresher::default(),
             filter_labels: config.filter_labels.clone(),
+            show_only_labels: config.show_only_labels.clone(),
+            max_fetch: config.max_fetch.unwrap_or(DEFAULT_MAX_FETCH),
+            delete_behavior: config.delete_behavior,
             editor,
             maildir_manager: MaildirManager::new(config.maildir_path.clone()).unwrap_or_else(|e| {
                 tracing::error!("Failed to create maildir manager: {}", e);
@@ -37,18 +197,36 @@ impl GmailBackend {
         }
     }
 
+    /// Forces the authenticator to refresh the access token, bypassing its cache,
+    /// unless another request refreshed it since `generation` (see `TokenRefresher`).
+    ///
+    /// The hub reads tokens through the same authenticator, so later calls pick up the new one.
+    async fn refresh_token(&self, generation: u64) -> Result<(), Error> {
+        let auth = self.auth.as_ref()
+            .ok_or_else(|| Error::TokenExpired("Gmail backend is not authenticated".to_string()))?;
+        self.token_refresher.refresh(generation, || async {
+            auth.force_refreshed_token(&scopes_for(self.delete_behavior))
+                .await
+                .map_err(|e| Error::TokenExpired(format!("Failed to refresh token: {}", e)))?;
+            Ok(())
+        }).await
+    }
+
     /// Fetches the inbox emails from the Gmail backend.
     /// 
     /// There is a chance that you will be rate limited by Gmail if you fetch too 
-    /// many emails at once. 
+    /// many emails at once, so `count` is clamped to `max_fetch`.
     async fn fetch_inbox_emails(&self, count: usize) -> Result<Vec<EmailMessage>, Error> {
-        let result = self.hub.as_ref().unwrap()
-            .users()
-            .messages_list("me")
-            .max_results(count as u32)
-            .doit()
-            .await
-            .map_err(|e| Error::Connection(format!("Failed to fetch inbox: {}", e)))?;
+        let count = clamp_fetch_count(count, self.max_fetch);
+        let result = with_token_retry!(self,
+            self.hub.as_ref().unwrap()
+                .users()
+                .messages_list("me")
+                .max_results(count as u32)
+                .doit()
+                .await
+                .map_err(|e| gmail_error("Failed to fetch inbox", e))
+        )?;
         
         let messages: Vec<Message> = result.1.messages.unwrap_or_default();
 
@@ -60,13 +238,15 @@ impl GmailBackend {
             .filter_map(|message| {
                 message.id.map(|message_id| {
                     async move {
-                        let message_response = self.hub.as_ref().unwrap()
-                            .users()
-                            .messages_get("me", message_id.as_str())
-                            .format("full")
-                            .doit()
-                            .await
-                            .map_err(|e| Error::Connection(format!("Failed to fetch message_id ({}): {}", message_id, e)));
+                        let message_response = with_token_retry!(self,
+                            self.hub.as_ref().unwrap()
+                                .users()
+                                .messages_get("me", message_id.as_str())
+                                .format("full")
+                                .doit()
+                                .await
+                                .map_err(|e| gmail_error(&format!("Failed to fetch message_id ({})", message_id), e))
+                        );
                         
                         // Return the result (either Ok or Err) along with the message_id
                         message_response.map(|resp| (message_id, resp.1))
@@ -75,75 +255,28 @@ impl GmailBackend {
             })
             .collect::<Vec<_>>();
 
-        let message_results = future::join_all(futures).await;
-        if message_results.iter().any(|result| result.is_err()) {
-            return Err(Error::Connection("Rate limited by Gmail".to_string()));
-        }
+        // Fail with the first error so callers can tell e.g. rate limiting apart
+        let message_results = future::join_all(futures).await
+            .into_iter()
+            .collect::<Result<Vec<_>, Error>>()?;
         
-        // We might be able to use an array here instead of a vector here in the future.
-        let mut emails = Vec::new();
-        for result in message_results {
-            match result {
-                Ok((message_id, message)) => {
-                    let payload: google_gmail1::api::MessagePart = message.payload.unwrap();
-                    let headers = payload.headers.unwrap();
-
-                    // Helper function to extract header value by name
-                    let get_header = |name: &str| -> String {
-                        headers.iter()
-                            .find(|h| h.name.as_ref().map_or(false, |n| n == name))
-                            .and_then(|h| h.value.as_ref())
-                            .cloned()
-                            .unwrap_or_default()
-                    };
+        Ok(message_results.into_iter()
+            .map(|(message_id, message)| parse_full_message(message_id, message))
+            .collect())
+    }
 
-                    // Extract body and mime type from parts
-                    let (body, mime_type) = if let Some(parts) = &payload.parts {
-                        let mut body = String::new();
-                        let mut mime_type = Default::default();
-                        
-                        for part in parts {
-                            if let Some(text) = part.body.as_ref()
-                                .and_then(|b| b.data.as_ref())
-                                .and_then(|data| std::str::from_utf8(data).ok())
-                            {
-                                body.push_str(text);
-                            }
-                            
-                            if let Some(part_mime) = &part.mime_type {
-                                if part_mime.contains("html") {
-                                    mime_type = MimeType::TextHtml;
-                                }
-                            }
-                        }
-                        
-                        (body, mime_type)
-                    } else {
-                        // fallback
-                        let body = payload.body.as_ref()
-                            .and_then(|b| b.data.as_ref())
-                            .and_then(|data| std::str::from_utf8(data).ok())
-                            .unwrap_or("")
-                            .to_string();
-                        (body, MimeType::TextPlain)
-                    };
-                    
-                    emails.push(EmailMessage { 
-                        id: message_id, 
-                        subject: get_header("Subject"),
-                        from: EmailSender::from(get_header("From")),
-                        to: get_header("To"),
-                        date: get_header("Date"),
-                        body,
-                        mime_type,
-                        email_attachments: Vec::new(),
-                        is_unread: false,
-                    });
-                }
-                Err(e) => tracing::error!("Failed to fetch message: {}", e),
-            }
-        }
-        Ok(emails)
+    /// Fetches one message straight from Gmail by its Gmail id, bypassing the maildir.
+    async fn fetch_message(&self, message_id: &str) -> Result<EmailMessage, Error> {
+        let (_, message) = with_token_retry!(self,
+            self.hub.as_ref().unwrap()
+                .users()
+                .messages_get("me", message_id)
+                .format("full")
+                .doit()
+                .await
+                .map_err(|e| gmail_error(&format!("Failed to fetch message_id ({})", message_id), e))
+        )?;
+        Ok(parse_full_message(message_id.to_string(), message))
     }
 
     /// Views emails from the local maildir (reads from synced emails).
@@ -161,71 +294,312 @@ impl GmailBackend {
     }
 
     async fn list_labels(&self) -> Result<Vec<Label>, Error> {
-        let result = self.hub.as_ref().unwrap()
-            .users()
-            .labels_list("me")
-            .doit()
-            .await
-            .map_err(|e| Error::Connection(format!("Failed to fetch labels: {}", e)))?;
-
-        let partial_labels: Vec<google_gmail1::api::Label> = result.1.labels.unwrap();
-        let futures = partial_labels.into_iter()
-            .filter_map(|partial_label| {
-                partial_label.id.map(|label_id| {
-                    // Create an async task for each label_get request.
-                    async move {
-                        let result = self.hub.as_ref().unwrap()
+        let result = with_token_retry!(self,
+            self.hub.as_ref().unwrap()
+                .users()
+                .labels_list("me")
+                .doit()
+                .await
+                .map_err(|e| gmail_error("Failed to fetch labels", e))
+        )?;
+
+        let partial_labels: Vec<google_gmail1::api::Label> = result.1.labels.unwrap_or_default();
+        let requests = partial_labels.into_iter()
+            .filter_map(|partial_label| partial_label.id)
+            .map(|label_id| {
+                // One labels_get request per label; at most LABEL_FETCH_CONCURRENCY run at once.
+                async move {
+                    let result = with_token_retry!(self,
+                        self.hub.as_ref().unwrap()
                             .users()
                             .labels_get("me", &label_id)
                             .doit()
                             .await
-                            .map_err(|e| Error::Connection(format!("Failed to fetch label {}: {}", label_id, e)));
-                        result.unwrap().1
-                    }
-                })
-            })
-            .collect::<Vec<_>>();
-        let detailed_labels: Vec<google_gmail1::api::Label> = future::join_all(futures).await;
-        let output = detailed_labels.iter().map(|label| Label {
-            color: label.color.clone(),
-            id: label.id.clone(),
-            messages_total: label.messages_total.map(|x| x as usize),
-            messages_unread: label.messages_unread.map(|x| x as usize),
-            name: label.name.clone(),
-        }).collect::<Vec<Label>>();
-        
-        Ok(output)
+                            .map_err(|e| gmail_error(&format!("Failed to fetch label {}", label_id), e))
+                    );
+                    (label_id, result.map(|(_, label)| to_label(&label)))
+                }
+            });
+        // `buffered` (unlike `buffer_unordered`) yields results in request order, so the
+        // folder list keeps Gmail's ordering.
+        let results: Vec<(String, Result<Label, Error>)> = stream::iter(requests)
+            .buffered(LABEL_FETCH_CONCURRENCY)
+            .collect()
+            .await;
+
+        Ok(collect_label_results(results))
     }
 
-    async fn incremental_sync(&self, last_sync_id: u64) -> Result<(), Error> {
-        let result = self.hub.as_ref().unwrap()
-            .users()
-            .history_list("me")
-            .start_history_id(last_sync_id)
-            .doit()
-            .await;
+    /// Marks a message read in the local maildir, then removes the UNREAD label on Gmail
+    /// if we are online and the message came from Gmail (i.e. was not imported).
+    async fn mark_read(&self, maildir_id: &str) -> Result<(), Error> {
+        if !self.maildir_manager.mark_read(maildir_id)? {
+            return Ok(());
+        }
+        if self.hub.is_none() {
+            tracing::info!("Offline, {} was only marked read locally", maildir_id);
+            return Ok(());
+        }
+        let gmail_id = match self.maildir_manager.get_gmail_id(maildir_id)? {
+            Some(id) if !is_local_id(&id) => id,
+            _ => return Ok(()),
+        };
 
-        if let Err(e) = result {
-            if e.to_string().contains("404") {
-                // means that not enough history is available, so we need to do a smart sync
-                return self.smart_sync().await;
-            } else {
-                return Err(Error::Connection(format!("Failed to fetch history: {}", e)));
+        with_token_retry!(self,
+            self.hub.as_ref().unwrap()
+                .users()
+                .messages_modify(
+                    ModifyMessageRequest {
+                        remove_label_ids: Some(vec!["UNREAD".to_string()]),
+                        ..Default::default()
+                    },
+                    "me",
+                    &gmail_id,
+                )
+                .doit()
+                .await
+                .map_err(|e| gmail_error("Failed to mark message as read", e))
+        )?;
+        Ok(())
+    }
+
+    /// Trashes or permanently deletes a message, depending on `delete_behavior`.
+    ///
+    /// The server is updated first so that a failed request leaves the local copy
+    /// untouched. Imported (local-only) messages skip the server entirely.
+    async fn delete(&self, maildir_id: &str) -> Result<(), Error> {
+        let gmail_id = self.maildir_manager.get_gmail_id(maildir_id)?
+            .filter(|id| !is_local_id(id));
+
+        if let Some(gmail_id) = gmail_id {
+            match self.delete_behavior {
+                DeleteBehavior::Trash => {
+                    with_token_retry!(self,
+                        self.hub.as_ref().unwrap()
+                            .users()
+                            .messages_trash("me", &gmail_id)
+                            .doit()
+                            .await
+                            .map_err(|e| gmail_error("Failed to trash message", e))
+                    )?;
+                }
+                DeleteBehavior::Delete => {
+                    with_token_retry!(self,
+                        self.hub.as_ref().unwrap()
+                            .users()
+                            .messages_delete("me", &gmail_id)
+                            .doit()
+                            .await
+                            .map_err(|e| gmail_error("Failed to delete message", e))
+                    )?;
+                }
+            }
+        }
+
+        match self.delete_behavior {
+            DeleteBehavior::Trash => self.maildir_manager.trash_message(maildir_id),
+            DeleteBehavior::Delete => self.maildir_manager.purge_message(maildir_id),
+        }
+    }
+
+    /// Adds and removes labels on Gmail, then mirrors the change locally.
+    ///
+    /// Gmail does not let `modify` take a message out of Trash, so removing TRASH
+    /// goes through `untrash` instead.
+    async fn modify_labels(&self, maildir_id: &str, add: &[String], remove: &[String]) -> Result<(), Error> {
+        let gmail_id = self.maildir_manager.get_gmail_id(maildir_id)?
+            .filter(|id| !is_local_id(id));
+
+        if let Some(gmail_id) = gmail_id {
+            // Gmail moves messages in and out of Trash with their own calls
+            if add.iter().any(|label| label == "TRASH") {
+                with_token_retry!(self,
+                    self.hub.as_ref().unwrap()
+                        .users()
+                        .messages_trash("me", &gmail_id)
+                        .doit()
+                        .await
+                        .map_err(|e| gmail_error("Failed to trash message", e))
+                )?;
+            }
+            if remove.iter().any(|label| label == "TRASH") {
+                with_token_retry!(self,
+                    self.hub.as_ref().unwrap()
+                        .users()
+                        .messages_untrash("me", &gmail_id)
+                        .doit()
+                        .await
+                        .map_err(|e| gmail_error("Failed to untrash message", e))
+                )?;
+            }
+
+            let add_label_ids: Vec<String> = add.iter().filter(|label| *label != "TRASH").cloned().collect();
+            let remove_label_ids: Vec<String> = remove.iter().filter(|label| *label != "TRASH").cloned().collect();
+            if !add_label_ids.is_empty() || !remove_label_ids.is_empty() {
+                with_token_retry!(self,
+                    self.hub.as_ref().unwrap()
+                        .users()
+                        .messages_modify(
+                            ModifyMessageRequest {
+                                add_label_ids: Some(add_label_ids.clone()),
+                                remove_label_ids: Some(remove_label_ids.clone()),
+                            },
+                            "me",
+                            &gmail_id,
+                        )
+                        .doit()
+                        .await
+                        .map_err(|e| gmail_error("Failed to modify labels", e))
+                )?;
+            }
+        }
+
+        self.maildir_manager.modify_labels(maildir_id, add, remove)
+    }
+
+    /// Adds and removes labels on many messages with as few `batchModify` requests as
+    /// possible, then mirrors the change locally. Removing UNREAD also moves the
+    /// local files to `cur`, like `mark_read`.
+    ///
+    /// TRASH is rejected: `batchModify` cannot untrash, use `delete`/`modify_labels`.
+    async fn batch_modify(&self, maildir_ids: &[String], add: &[String], remove: &[String]) -> Result<(), Error> {
+        if add.iter().chain(remove).any(|label| label == "TRASH") {
+            return Err(Error::InvalidInput("TRASH cannot be changed in a batch, use delete or modify-labels".to_string()));
+        }
+
+        if let Some(hub) = &self.hub {
+            let mut gmail_ids = Vec::new();
+            for maildir_id in maildir_ids {
+                if let Some(gmail_id) = self.maildir_manager.get_gmail_id(maildir_id)?.filter(|id| !is_local_id(id)) {
+                    gmail_ids.push(gmail_id);
+                }
             }
+            for request in batch_modify_requests(&gmail_ids, add, remove, BATCH_MODIFY_LIMIT) {
+                with_token_retry!(self,
+                    hub
+                        .users()
+                        .messages_batch_modify(request.clone(), "me")
+                        .doit()
+                        .await
+                        .map_err(|e| gmail_error("Failed to batch modify labels", e))
+                )?;
+            }
+        } else {
+            tracing::info!("Offline, {} message(s) were only modified locally", maildir_ids.len());
+        }
+
+        let marks_read = remove.iter().any(|label| label == "UNREAD");
+        for maildir_id in maildir_ids {
+            if marks_read {
+                self.maildir_manager.mark_read(maildir_id)?;
+            }
+            self.maildir_manager.modify_labels(maildir_id, add, remove)?;
         }
+        Ok(())
+    }
+
+    /// Inserts every local-only message (see `MaildirManager::local_only_maildir_ids`)
+    /// into Gmail under `label` (default INBOX), recording the returned Gmail ids so
+    /// later syncs treat them like any other message. Unread messages stay unread.
+    /// Stopping part way is safe: a rerun only uploads what is still missing.
+    async fn upload(&self, label: Option<&str>) -> Result<usize, Error> {
+        let label_id = match label {
+            Some(label) => self.resolve_label_id(label).await?,
+            None => SYNC_SOURCE.to_string(),
+        };
+        let maildir_ids = self.maildir_manager.local_only_maildir_ids()?;
+        tracing::info!("Uploading {} local message(s) to {}", maildir_ids.len(), label_id);
+
+        let mut uploaded = 0;
+        for (index, chunk) in maildir_ids.chunks(UPLOAD_CHUNK_SIZE).enumerate() {
+            if index > 0 {
+                tokio::time::sleep(UPLOAD_CHUNK_PAUSE).await;
+            }
+            for maildir_id in chunk {
+                let raw = self.maildir_manager.read_raw_message(maildir_id)?;
+                let mut label_ids = vec![label_id.clone()];
+                if self.maildir_manager.get_message_directory(maildir_id)? == "new" {
+                    label_ids.push("UNREAD".to_string());
+                }
+                let request = Message { label_ids: Some(label_ids), ..Default::default() };
+
+                let (_, inserted) = with_token_retry!(self,
+                    self.hub.as_ref().unwrap()
+                        .users()
+                        .messages_insert(request.clone(), "me")
+                        .internal_date_source("dateHeader")
+                        .upload(std::io::Cursor::new(raw.clone()), "message/rfc822".parse().unwrap())
+                        .await
+                        .map_err(|e| gmail_error(&format!("Failed to upload {}", maildir_id), e))
+                )?;
+                let gmail_id = inserted.id
+                    .ok_or_else(|| Error::Other(format!("Gmail returned no id for uploaded {}", maildir_id)))?;
+                self.maildir_manager.record_upload(
+                    maildir_id,
+                    &gmail_id,
+                    inserted.thread_id.as_deref(),
+                    &inserted.label_ids.unwrap_or_default(),
+                )?;
+                uploaded += 1;
+            }
+            tracing::info!("Uploaded {}/{} message(s)", uploaded, maildir_ids.len());
+        }
+        Ok(uploaded)
+    }
+
+    /// The id of the label called (or with the id) `label`.
+    async fn resolve_label_id(&self, label: &str) -> Result<String, Error> {
+        self.list_labels().await?
+            .into_iter()
+            .find(|l| l.id.as_deref() == Some(label) || l.name.as_deref() == Some(label))
+            .and_then(|l| l.id)
+            .ok_or_else(|| Error::NotFound(format!("Label {} does not exist", label)))
+    }
+
+    /// Fetches the profile of the authenticated user.
+    async fn whoami(&self) -> Result<String, Error> {
+        let profile = with_token_retry!(self,
+            self.hub.as_ref().unwrap()
+                .users()
+                .get_profile("me")
+                .doit()
+                .await
+                .map_err(|e| gmail_error("Failed to get profile", e))
+        )?.1;
+
+        Ok(format_profile(&profile))
+    }
+
+    async fn incremental_sync(&self, last_sync_id: u64) -> Result<(), Error> {
+        let result = with_token_retry!(self,
+            self.hub.as_ref().unwrap()
+                .users()
+                .history_list("me")
+                .start_history_id(last_sync_id)
+                .doit()
+                .await
+                .map_err(|e| gmail_error("Failed to fetch history", e))
+        );
 
-        let curr_history_id = result.as_ref().unwrap().1.history_id.unwrap();
+        let curr_history_id = match result {
+            Ok(response) => response.1.history_id.unwrap(),
+            // means that not enough history is available, so we need to do a smart sync
+            Err(Error::NotFound(_)) => return self.smart_sync().await,
+            Err(e) => return Err(e),
+        };
 
         // iterate thru all the history records starting at last_sync_id
         // make sure to go to all pages
 
-        let history_records = self.hub.as_ref().unwrap()
-            .users()
-            .history_list("me")
-            .start_history_id(last_sync_id)
-            .doit()
-            .await
-            .map_err(|e| Error::Connection(format!("Failed to fetch history: {}", e)))?;
+        let history_records = with_token_retry!(self,
+            self.hub.as_ref().unwrap()
+                .users()
+                .history_list("me")
+                .start_history_id(last_sync_id)
+                .doit()
+                .await
+                .map_err(|e| gmail_error("Failed to fetch history", e))
+        )?;
 
         if history_records.1.history.is_none() {
             return Ok(());
@@ -294,19 +668,24 @@ impl GmailBackend {
                     self.maildir_manager.add_mapping(message_id.clone(), new_maildir_id.clone())?;
                     
                     // Fetch current labels from Gmail and add to new maildir_id
-                    let metadata_response = self.hub.as_ref().unwrap()
-                        .users()
-                        .messages_get("me", message_id.as_str())
-                        .format("metadata")
-                        .doit()
-                        .await
-                        .map_err(|e| Error::Connection(format!("Failed to fetch message metadata: {}", e)))?;
+                    let metadata_response = with_token_retry!(self,
+                        self.hub.as_ref().unwrap()
+                            .users()
+                            .messages_get("me", message_id.as_str())
+                            .format("metadata")
+                            .doit()
+                            .await
+                            .map_err(|e| gmail_error("Failed to fetch message metadata", e))
+                    )?;
                     let mut labels: Vec<String> = metadata_response.1.label_ids.clone().unwrap_or_default();
                     // Ensure UNREAD label is present (Gmail should include it, but be explicit)
                     if !labels.contains(&"UNREAD".to_string()) {
                         labels.push("UNREAD".to_string());
                     }
                     self.maildir_manager.add_label_mappings(&new_maildir_id, &labels)?;
+                    if let Some(thread_id) = &metadata_response.1.thread_id {
+                        self.maildir_manager.set_thread_id(message_id, thread_id)?;
+                    }
                 }
                 "move_to_cur" => {
                     // move message from new to cur in maildir (email was marked as read)
@@ -315,13 +694,15 @@ impl GmailBackend {
                     // Update label mappings: remove UNREAD label
                     self.maildir_manager.remove_label_mappings(&[maildir_id.clone()])?;
                     // Fetch current labels from Gmail to update all labels
-                    let metadata_response = self.hub.as_ref().unwrap()
-                        .users()
-                        .messages_get("me", message_id.as_str())
-                        .format("metadata")
-                        .doit()
-                        .await
-                        .map_err(|e| Error::Connection(format!("Failed to fetch message metadata: {}", e)))?;
+                    let metadata_response = with_token_retry!(self,
+                        self.hub.as_ref().unwrap()
+                            .users()
+                            .messages_get("me", message_id.as_str())
+                            .format("metadata")
+                            .doit()
+                            .await
+                            .map_err(|e| gmail_error("Failed to fetch message metadata", e))
+                    )?;
                     let labels: Vec<String> = metadata_response.1.label_ids.clone().unwrap_or_default();
                     self.maildir_manager.add_label_mappings(&maildir_id, &labels)?;
                 }
@@ -359,7 +740,7 @@ impl GmailBackend {
             
             // send request
             let result = request.doit().await
-                .map_err(|e| Error::Connection(format!("Failed to list messages: {}", e)))?;
+                .map_err(|e| gmail_error("Failed to list messages", e))?;
             
             // add messages to set
             if let Some(messages) = result.1.messages {
@@ -379,7 +760,11 @@ impl GmailBackend {
         }
         // Get all current maildir message ids
         let mapping = self.maildir_manager.get_all_mappings()?;
-        let local_ids: HashSet<String> = mapping.keys().cloned().collect();
+        // Imported messages are local-only and must not be deleted as "missing from Gmail"
+        let local_ids: HashSet<String> = mapping.keys()
+            .filter(|id| !is_local_id(id))
+            .cloned()
+            .collect();
     
         // Find differences
         let to_add_ids = &all_gmail_ids - &local_ids;
@@ -392,13 +777,15 @@ impl GmailBackend {
 
         // Downlaod new messages
         for id in to_add_ids {
-            let message_response = self.hub.as_ref().unwrap()
-                .users()
-                .messages_get("me", id.as_str())
-                .format("raw")
-                .doit()
-                .await
-                .map_err(|e| Error::Connection(format!("Failed to fetch message: {}", e)));
+            let message_response = with_token_retry!(self,
+                self.hub.as_ref().unwrap()
+                    .users()
+                    .messages_get("me", id.as_str())
+                    .format("raw")
+                    .doit()
+                    .await
+                    .map_err(|e| gmail_error("Failed to fetch message", e))
+            );
             
             match message_response {
                 Ok(message) => {
@@ -417,7 +804,7 @@ impl GmailBackend {
                     self.maildir_manager.add_mapping(id.clone(), maildir_id.clone()).unwrap();
                 }
                 Err(e) => {
-                    return Err(Error::Connection(format!("Failed to fetch message: {}", e)));
+                    return Err(e);
                 }
             }
         }
@@ -437,13 +824,15 @@ impl GmailBackend {
         // Update existing messagse if needed
         for gmail_id in to_update_ids {
             // if message was updated (read or unread) then we need to update the message in the maildir
-            let metadata_response = self.hub.as_ref().unwrap()
-                .users()
-                .messages_get("me", gmail_id.as_str())
-                .format("metadata")
-                .doit()
-                .await
-                .map_err(|e| Error::Connection(format!("Failed to fetch message: {}", e)));
+            let metadata_response = with_token_retry!(self,
+                self.hub.as_ref().unwrap()
+                    .users()
+                    .messages_get("me", gmail_id.as_str())
+                    .format("metadata")
+                    .doit()
+                    .await
+                    .map_err(|e| gmail_error("Failed to fetch message", e))
+            );
 
             // get maildir id form gmail id
             let maildir_id = mapping.get(&gmail_id).unwrap();
@@ -454,6 +843,7 @@ impl GmailBackend {
             // add new label mappings to db
             let new_labels: Vec<String> = metadata_response.as_ref().unwrap().1.label_ids.clone().unwrap_or_default();
             self.maildir_manager.add_label_mappings(&maildir_id, &new_labels)?;
+            let thread_id = metadata_response.as_ref().ok().and_then(|response| response.1.thread_id.clone());
 
             // figure out if message is read or unread
             let is_read = !metadata_response.unwrap().1.label_ids.clone().unwrap_or_default().contains(&"UNREAD".to_string());
@@ -467,6 +857,9 @@ impl GmailBackend {
                 // update mapping in db
                 self.maildir_manager.remove_mappings(&[gmail_id.clone()]).unwrap();
                 self.maildir_manager.add_mapping(gmail_id.clone(), new_maildir_id).unwrap();
+                if let Some(thread_id) = &thread_id {
+                    self.maildir_manager.set_thread_id(&gmail_id, thread_id)?;
+                }
             } else if is_read && maildir_directory == "new" {
                 // if read in cloud but in new then move message to cur in maildir
                 self.maildir_manager.maildir_move_new_to_cur(&maildir_id).unwrap();
@@ -474,12 +867,14 @@ impl GmailBackend {
         }
 
         // Update last_sync_id 
-        let profile_result = self.hub.as_ref().unwrap()
-            .users()
-            .get_profile("me")
-            .doit()
-            .await
-            .map_err(|e| Error::Connection(format!("Failed to get profile: {}", e)))?;
+        let profile_result = with_token_retry!(self,
+            self.hub.as_ref().unwrap()
+                .users()
+                .get_profile("me")
+                .doit()
+                .await
+                .map_err(|e| gmail_error("Failed to get profile", e))
+        )?;
         
         let last_sync_id = profile_result.1.history_id.unwrap();
         self.maildir_manager.save_last_sync_id(last_sync_id)?;
@@ -487,11 +882,45 @@ impl GmailBackend {
         Ok(())
     }
 
+    /// Whether the next sync has to download everything: nothing was synced yet,
+    /// or an earlier full sync was interrupted.
+    fn needs_full_sync(&self) -> Result<bool, Error> {
+        if self.maildir_manager.get_sync_checkpoint()?.is_some() {
+            return Ok(true);
+        }
+        Ok(self.maildir_manager.get_last_sync_id() == 0 && !self.maildir_manager.has_synced_emails()?)
+    }
+
+    /// Number of messages a full sync downloads, read from the label's counters
+    /// rather than by listing every message.
+    async fn full_sync_total(&self) -> Result<usize, Error> {
+        let (_, label) = with_token_retry!(self,
+            self.hub.as_ref().unwrap()
+                .users()
+                .labels_get("me", SYNC_SOURCE)
+                .doit()
+                .await
+                .map_err(|e| gmail_error("Failed to get label", e))
+        )?;
+        Ok(label.messages_total.unwrap_or_default().max(0) as usize)
+    }
+
     async fn full_sync(&self) -> Result<(), Error> {
         // println!("Starting full sync");
         // println!("SYNC_SOURCE: {:?}", SYNC_SOURCE);
         // TODO: can later get progress to show easily later
-        let mut page_token: Option<String> = None;
+        // Resume an interrupted full sync from the page it stopped on. Messages it
+        // already saved on that page are skipped below.
+        let mut page_token: Option<String> = match self.maildir_manager.get_sync_checkpoint()? {
+            Some(page_token) => {
+                tracing::info!("Resuming interrupted full sync from page {:?}", page_token);
+                page_token
+            }
+            None => {
+                self.maildir_manager.save_sync_checkpoint(None)?;
+                None
+            }
+        };
 
         loop {
             // build request
@@ -508,7 +937,7 @@ impl GmailBackend {
             
             // send request
             let result = request.doit().await
-                .map_err(|e| Error::Connection(format!("Failed to fetch messages: {}", e)))?;
+                .map_err(|e| gmail_error("Failed to fetch messages", e))?;
             
             // update page token
             page_token = result.1.next_page_token;
@@ -517,15 +946,20 @@ impl GmailBackend {
 
             // iterate through messages
             for message in messages {
-                
+                if self.maildir_manager.get_maildir_id(message.id.as_deref().unwrap())?.is_some() {
+                    continue;
+                }
+
                 // fetch message
-                let message_response = self.hub.as_ref().unwrap()
-                    .users()
-                    .messages_get("me", message.id.unwrap().as_str())
-                    .format("raw")
-                    .doit()
-                    .await
-                    .map_err(|e| Error::Connection(format!("Failed to fetch message: {}", e)));
+                let message_response = with_token_retry!(self,
+                    self.hub.as_ref().unwrap()
+                        .users()
+                        .messages_get("me", message.id.as_deref().unwrap())
+                        .format("raw")
+                        .doit()
+                        .await
+                        .map_err(|e| gmail_error("Failed to fetch message", e))
+                );
 
                 match message_response {
                     Ok(message) => {
@@ -542,7 +976,7 @@ impl GmailBackend {
 
                     }
                     Err(e) => {
-                        return Err(Error::Connection(format!("Failed to fetch message: {}", e)));
+                        return Err(e);
                     }
                 }
 
@@ -552,29 +986,127 @@ impl GmailBackend {
             if page_token.is_none() {
                 break;
             }
+            // Everything up to here is saved; an interrupted run continues with the next page
+            self.maildir_manager.save_sync_checkpoint(page_token.as_deref())?;
         }
 
         // Update last_sync_id 
-        let profile_result = self.hub.as_ref().unwrap()
-            .users()
-            .get_profile("me")
-            .doit()
-            .await
-            .map_err(|e| Error::Connection(format!("Failed to get profile: {}", e)))?;
+        let profile_result = with_token_retry!(self,
+            self.hub.as_ref().unwrap()
+                .users()
+                .get_profile("me")
+                .doit()
+                .await
+                .map_err(|e| gmail_error("Failed to get profile", e))
+        )?;
         
         let last_sync_id = profile_result.1.history_id.unwrap();
         self.maildir_manager.save_last_sync_id(last_sync_id)?;
+        self.maildir_manager.clear_sync_checkpoint()?;
 
         Ok(())
     }
 }
 
+/// Converts a Gmail API error into a structured `Error` (see `classify_gmail_error`).
+fn gmail_error(context: &str, e: google_gmail1::Error) -> Error {
+    let status = match &e {
+        google_gmail1::Error::Failure(response) => Some(response.status().as_u16()),
+        google_gmail1::Error::BadRequest(value) => value["error"]["code"].as_u64().map(|code| code as u16),
+        google_gmail1::Error::MissingToken(_) => Some(401),
+        _ => None,
+    };
+    classify_gmail_error(context, status, &e.to_string())
+}
+
+/// Maps an HTTP status (if known) and error message from the Gmail API to an `Error`
+/// variant, so callers can tell auth expiry, rate limiting and missing messages apart.
+pub fn classify_gmail_error(context: &str, status: Option<u16>, message: &str) -> Error {
+    let detail = format!("{}: {}", context, message);
+    let lowercase = message.to_lowercase();
+
+    match status {
+        Some(401) => Error::TokenExpired(detail),
+        Some(429) => Error::RateLimited(detail),
+        Some(403) if lowercase.contains("ratelimitexceeded") => Error::RateLimited(detail),
+        Some(404) => Error::NotFound(detail),
+        _ if lowercase.contains("invalid_grant") || lowercase.contains("token has been expired") => {
+            Error::TokenExpired(detail)
+        }
+        _ if lowercase.contains("ratelimitexceeded") || lowercase.contains("rate limit") => {
+            Error::RateLimited(detail)
+        }
+        _ => Error::Connection(detail),
+    }
+}
+
+fn to_label(label: &google_gmail1::api::Label) -> Label {
+    Label {
+        color: label.color.clone(),
+        id: label.id.clone(),
+        messages_total: label.messages_total.map(|x| x as usize),
+        messages_unread: label.messages_unread.map(|x| x as usize),
+        name: label.name.clone(),
+    }
+}
+
+/// Keeps the labels that were fetched, in order, logging and skipping the failures
+/// so one bad label does not take down the whole folder list.
+pub fn collect_label_results(results: Vec<(String, Result<Label, Error>)>) -> Vec<Label> {
+    results.into_iter()
+        .filter_map(|(label_id, result)| match result {
+            Ok(label) => Some(label),
+            Err(e) => {
+                tracing::warn!("Skipping label {}: {}", label_id, e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Formats a Gmail profile for `Command::Whoami`.
+pub fn format_profile(profile: &google_gmail1::api::Profile) -> String {
+    format!(
+        "Email: {}\nTotal messages: {}\nHistory id: {}",
+        profile.email_address.as_deref().unwrap_or("unknown"),
+        profile.messages_total.map(|n| n.to_string()).unwrap_or_else(|| "unknown".to_string()),
+        profile.history_id.map(|id| id.to_string()).unwrap_or_else(|| "unknown".to_string()),
+    )
+}
+
+/// Clamps a requested fetch count to `max_fetch`, warning when the request is too large.
+///
+/// Every fetched message is a separate concurrent `messages_get`, so an accidental
+/// `FetchInbox 10000` would otherwise fan out ten thousand requests.
+pub fn clamp_fetch_count(requested: usize, max_fetch: usize) -> usize {
+    if requested > max_fetch {
+        tracing::warn!(
+            "Requested {} emails but max_fetch is {}; only fetching {}",
+            requested, max_fetch, max_fetch
+        );
+        max_fetch
+    } else {
+        requested
+    }
+}
+
 #[async_trait]
 impl Backend for GmailBackend {
     fn needs_oauth(&self) -> bool {
         true
     }
 
+    fn capabilities(&self) -> BackendCapabilities {
+        BackendCapabilities {
+            supports_labels: true,
+            supports_sync: true,
+            supports_send: true,
+            supports_search: true,
+            supports_login: true,
+            supports_move: true,
+        }
+    }
+
     async fn authenticate(&mut self) -> Result<(), Error> {
         let secret_file = self.oauth2_client_secret_file.as_ref()
             .ok_or_else(|| Error::Config(
@@ -586,44 +1118,92 @@ impl Backend for GmailBackend {
             .map_err(|e| Error::Config(format!("Failed to read OAuth2 secret file: {}", e)))?;
 
         // Set up the OAuth2 authenticator with installed flow (opens browser)
-        // TODO: use a better way to get the scopes
-        // Should be defined in the config file maybe?
-        let scopes = &[
-            "https://www.googleapis.com/auth/gmail.readonly",
-            "https://www.googleapis.com/auth/gmail.addons.current.message.readonly",
-            "https://www.googleapis.com/auth/gmail.send",
-        ];
-        
-        let auth = InstalledFlowAuthenticator::builder(secret,InstalledFlowReturnMethod::HTTPRedirect)
-            .persist_tokens_to_disk("tokencache.json")
-            .build()
-            .await
-            .map_err(|e| Error::Config(format!("Failed to build authenticator: {}", e)))?;
-        auth.token(scopes).await.map_err(|e| Error::Config(format!("Failed to get token: {}", e)))?;
-        
+        let scopes: &[&str] = &scopes_for(self.delete_behavior);
+
+        // The authenticator reuses (and refreshes) the cached token on its own. We only
+        // check the cache here to tell the user up front whether a browser is coming.
+        let cached = std::fs::read_to_string(&self.token_cache_path).unwrap_or_default();
+        if has_refreshable_token(&cached, scopes) {
+            tracing::info!("Reusing cached Gmail token from {:?}", self.token_cache_path);
+        } else {
+            tracing::info!("No reusable Gmail token in {:?}, starting the installed flow", self.token_cache_path);
+        }
+
+        if let Some(parent) = self.token_cache_path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| Error::Config(format!("Failed to create token cache directory: {}", e)))?;
+        }
+
+        // Both the OAuth2 flow and the API calls go through the configured proxy, if any
+        if let Some(proxy) = &self.proxy {
+            tracing::info!("Connecting to Gmail through {:?} proxy {}:{}", proxy.kind, proxy.host, proxy.port);
+        }
         let https = hyper_rustls::HttpsConnectorBuilder::new()
             .with_native_roots()
             .map_err(|e| Error::Config(format!("Failed to load native roots: {}", e)))?
             .https_or_http()
             .enable_http1()
-            .build();
+            .wrap_connector(ProxyConnector::new(self.proxy.clone(), self.connect_timeout));
+
+        let auth_client = hyper_util::client::legacy::Client::builder(
+            hyper_util::rt::TokioExecutor::new()
+        ).build::<_, String>(https.clone());
+
+        let auth = InstalledFlowAuthenticator::with_client(
+            secret,
+            InstalledFlowReturnMethod::HTTPRedirect,
+            auth_client,
+        )
+            .persist_tokens_to_disk(&self.token_cache_path)
+            .flow_delegate(Box::new(ReauthFlowDelegate))
+            .build()
+            .await
+            .map_err(|e| Error::Config(format!("Failed to build authenticator: {}", e)))?;
+        auth.token(scopes).await.map_err(|e| Error::Config(format!("Failed to get token: {}", e)))?;
 
         let client = hyper_util::client::legacy::Client::builder(
             hyper_util::rt::TokioExecutor::new()
         ).build(https);
 
+        self.auth = Some(auth.clone());
         self.hub = Some(Box::new(Gmail::new(client, auth)));
         Ok(())
     }
 
+    /// Runs the installed flow into a fresh token cache next to the real one, which
+    /// is only replaced once that succeeded. A cancelled or failed login leaves the
+    /// old token, and the session using it, in place.
+    async fn reauthenticate(&mut self) -> Result<(), Error> {
+        let fresh_path = fresh_token_cache_path(&self.token_cache_path);
+        let _ = std::fs::remove_file(&fresh_path);
+
+        let cache_path = std::mem::replace(&mut self.token_cache_path, fresh_path.clone());
+        let result = self.authenticate().await;
+        self.token_cache_path = cache_path;
+        if let Err(e) = result {
+            let _ = std::fs::remove_file(&fresh_path);
+            return Err(e);
+        }
+
+        std::fs::rename(&fresh_path, &self.token_cache_path)
+            .map_err(|e| Error::Config(format!("Failed to replace token cache {:?}: {}", self.token_cache_path, e)))?;
+        tracing::info!("Replaced cached Gmail token {:?}", self.token_cache_path);
+        // Sign in again from the replaced cache, so refreshed tokens are written there
+        self.authenticate().await
+    }
+
     async fn do_command(&self, cmd: Command, plugin_manager: Option<&mut PluginManager>) -> Result<CommandResult, Error> {
+        // Commands that talk to Gmail need the hub `authenticate` sets up
+        if self.hub.is_none() && self.requires_authentication(&cmd) == Some(true) {
+            return Err(Error::Authentication(format!("{} needs a signed-in Gmail account, please authenticate", cmd.name())));
+        }
         match cmd {
             Command::FetchInbox { count } => {
-                let emails = self.fetch_inbox_emails(count).await.unwrap();
+                let emails = self.fetch_inbox_emails(count).await?;
                 if emails.is_empty() {
                     Ok(CommandResult::Empty)
                 } else if count == 1 {
-                    Ok(CommandResult::Email(emails.into_iter().next().unwrap()))
+                    Ok(CommandResult::Email(Box::new(emails.into_iter().next().unwrap())))
                 } else {
                     Ok(CommandResult::Emails(emails))
                 }
@@ -637,20 +1217,23 @@ impl Backend for GmailBackend {
             //     return Err(Error::Other("FetchInbox is deprecated for Gmail backend. Use 'sync-from-cloud' to download emails to maildir, then 'view-mailbox' to view them.".to_string()));
             // },
             Command::ListLabels => {
-                let mut labels = self.list_labels().await.unwrap();
-                if let Some(filter_labels) = self.filter_labels.as_ref() {
-                    labels = labels.into_iter()
-                        .filter(|label| !filter_labels
-                            .contains(&label.name.as_ref().unwrap().to_string()))
-                        .collect();
-                }
+                let labels = self.list_labels().await?
+                    .into_iter()
+                    .filter(|label| is_label_shown(
+                        label.name.as_deref().unwrap_or_default(),
+                        self.show_only_labels.as_deref(),
+                        self.filter_labels.as_deref(),
+                    ))
+                    .collect();
                 Ok(CommandResult::Labels(labels))
             },
-            Command::SendEmail {to,subject, body } => {
+            Command::SendEmail { to, subject, body, cc, bcc, reply_to } => {
                 let mut draft = EmailMessage {
                     to: to.unwrap_or_default(),
                     subject: subject.unwrap_or_default(),
                     body: body.unwrap_or_default(),
+                    cc: cc.unwrap_or_default(),
+                    bcc: bcc.unwrap_or_default(),
                     ..EmailMessage::new()
                 };
 
@@ -666,34 +1249,48 @@ impl Backend for GmailBackend {
                 // Plugin hook-point: Hook::BeforeSend
                 if let Some(plugin_manager) = plugin_manager {
                     let updated_body = plugin_manager.dispatch(
-                        Hook::BeforeSend.to_wit_event(draft.body.clone())
+                        Hook::BeforeSend.to_wit_event((&draft).into())
                     ).await?;
                     draft.body = updated_body;
                 }
 
-                let email = draft.to_lettre_email()?;
+                // Replies carry the threading headers and the original's thread id,
+                // otherwise Gmail starts a new conversation
+                let original = reply_to.as_deref()
+                    .map(|email_id| self.maildir_manager.load_email(email_id))
+                    .transpose()?;
+                let reply = original.as_ref().and_then(ReplyHeaders::for_message);
+                let thread_id = original.and_then(|original| original.thread_id);
+
+                let email = draft.to_lettre_email(reply.as_ref())?;
                 let raw_bytes = email.formatted();
 
-                let _result = self.hub.as_ref().unwrap()
-                    .users()
-                    .messages_send(google_gmail1::api::Message::default(), "me") // See documentation of this method for Gmail's API docs.
-                    .upload(
-                        std::io::Cursor::new(raw_bytes), 
-                        "message/rfc822".parse().unwrap()
-                    )
-                    .await
-                    .map_err(|e| Error::Connection(format!("Failed to send email: {}", e)))?;
+                let _result = with_token_retry!(self,
+                    self.hub.as_ref().unwrap()
+                        .users()
+                        .messages_send(outgoing_message(thread_id.clone()), "me") // See documentation of this method for Gmail's API docs.
+                        .upload(
+                            std::io::Cursor::new(raw_bytes.clone()),
+                            "message/rfc822".parse().unwrap()
+                        )
+                        .await
+                        .map_err(|e| gmail_error("Failed to send email", e))
+                )?;
 
                 // println!("Email sent successfully! Message ID: {:?}", result.1.id);
 
                 Ok(CommandResult::Empty)
             }
-            Command::SyncFromCloud => {
+            Command::Compose { uri } => {
+                let command = parse_mailto(&uri)?.into_command();
+                self.do_command(command, plugin_manager).await
+            }
+            Command::SyncFromCloud { .. } => {
                 
                 let last_sync_id = self.maildir_manager.get_last_sync_id();
                 tracing::info!("Last sync id: {:?}", last_sync_id);
 
-                if last_sync_id == 0 && !self.maildir_manager.has_synced_emails()? {
+                if self.needs_full_sync()? {
                     tracing::info!("Last sync id is 0 and no emails have been synced yet, doing full sync");
                     self.full_sync().await?;
                     tracing::info!("Full sync completed");
@@ -704,9 +1301,20 @@ impl Backend for GmailBackend {
 
                 Ok(CommandResult::Empty)
             },
+            Command::SyncPlan => {
+                let full = self.needs_full_sync()?;
+                let total = if full { Some(self.full_sync_total().await?) } else { None };
+                Ok(CommandResult::SyncPlan { full, total })
+            },
+            // Handled by the CLI before a backend is involved
+            Command::ListPlugins | Command::Version | Command::TestPlugins { .. } => Err(Error::Unimplemented {
+                backend: "gmail".to_string(),
+                feature: cmd.name().to_string(),
+            }),
+            Command::Refresh { count, yes } => refresh(self, count, yes, plugin_manager).await,
             Command::ViewMailbox { count, label } => {
                 let label_ref = label.as_deref();
-                let emails = self.view_mailbox(count, label_ref).await.unwrap();
+                let emails = self.view_mailbox(count, label_ref).await?;
                 // filter emails to the ones that only have image attachments
                 let filtered_emails: Vec<EmailMessage> = emails.into_iter()
                     .filter(|email| email.get_image_attachments().is_empty())
@@ -714,14 +1322,94 @@ impl Backend for GmailBackend {
                 if filtered_emails.is_empty() {
                     Ok(CommandResult::Empty)
                 } else if count == 1 {
-                    Ok(CommandResult::Email(filtered_emails.into_iter().next().unwrap()))
+                    Ok(CommandResult::Email(Box::new(filtered_emails.into_iter().next().unwrap())))
                 } else {
                     Ok(CommandResult::Emails(filtered_emails))
                 }
             },
             Command::LoadEmail { email_id } => {
                 let email = self.maildir_manager.load_email_with_attachments(&email_id)?;
-                Ok(CommandResult::Email(email))
+                Ok(CommandResult::Email(Box::new(email)))
+            },
+            Command::MarkRead { email_id } => {
+                self.mark_read(&email_id).await?;
+                Ok(CommandResult::Empty)
+            }
+            Command::Delete { email_id } => {
+                self.delete(&email_id).await?;
+                Ok(CommandResult::Empty)
+            }
+            Command::MarkSpam { email_id } => {
+                let (add, remove) = spam_label_changes(true);
+                self.modify_labels(&email_id, &add, &remove).await?;
+                Ok(CommandResult::Empty)
+            }
+            Command::NotSpam { email_id } => {
+                let (add, remove) = spam_label_changes(false);
+                self.modify_labels(&email_id, &add, &remove).await?;
+                Ok(CommandResult::Empty)
+            }
+            Command::ModifyLabels { email_id, add, remove } => {
+                self.modify_labels(&email_id, &add, &remove).await?;
+                Ok(CommandResult::Empty)
+            }
+            Command::Move { email_id, to, from } => {
+                let (add, remove) = move_label_changes(from.as_deref(), &to);
+                self.modify_labels(&email_id, &add, &remove).await?;
+                Ok(CommandResult::Empty)
+            }
+            Command::BatchModify { email_ids, add, remove } => {
+                self.batch_modify(&email_ids, &add, &remove).await?;
+                Ok(CommandResult::Empty)
+            }
+            Command::Reindex => {
+                let (indexed, skipped) = self.maildir_manager.reindex()?;
+                Ok(CommandResult::Success(format!("Reindexed {} message(s), skipped {}", indexed, skipped)))
+            }
+            Command::Compact => Ok(CommandResult::Success(self.maildir_manager.compact()?.to_string())),
+            Command::CheckDb { repair } => {
+                let orphans = self.maildir_manager.find_orphans()?;
+                if !repair || orphans.is_empty() {
+                    return Ok(CommandResult::Success(orphans.to_string()));
+                }
+                let (labels, metadata, mappings) = self.maildir_manager.prune_orphans(&orphans)?;
+                Ok(CommandResult::Success(format!(
+                    "{}Removed {} label, {} metadata and {} mapping row(s)",
+                    orphans, labels, metadata, mappings
+                )))
+            }
+            Command::LabelSummary { label } => {
+                let count = self.maildir_manager.count_with_label(&label)?;
+                let latest_subject = self.maildir_manager.latest_subject_for_label(&label)?;
+                Ok(CommandResult::LabelSummary { label, count, latest_subject })
+            }
+            Command::Search { query, count } => {
+                let emails = self.maildir_manager.search(&query, count)?;
+                if emails.is_empty() {
+                    Ok(CommandResult::Empty)
+                } else {
+                    Ok(CommandResult::Emails(emails))
+                }
+            }
+            Command::MimeTree { email_id } => {
+                Ok(CommandResult::Success(self.maildir_manager.mime_tree(&email_id)?))
+            }
+            Command::Export { email_id, out_path, force } => {
+                let path = self.maildir_manager.export_message(&email_id, &out_path, force)?;
+                Ok(CommandResult::Success(format!("Exported {} to {:?}", email_id, path)))
+            },
+            Command::Import { path } => {
+                let count = self.maildir_manager.import_file(&path)?;
+                Ok(CommandResult::Success(format!("Imported {} message(s) from {:?}", count, path)))
+            },
+            Command::Whoami => Ok(CommandResult::Success(self.whoami().await?)),
+            // `reauthenticate` has run before; this reports who it signed in as
+            Command::Login if self.hub.is_none() => Err(Error::Authentication("Not signed in, login failed".to_string())),
+            Command::Login => Ok(CommandResult::Success(format!("Signed in\n{}", self.whoami().await?))),
+            Command::FetchMessage { id } => Ok(CommandResult::Email(Box::new(self.fetch_message(&id).await?))),
+            Command::Upload { label } => {
+                let uploaded = self.upload(label.as_deref()).await?;
+                Ok(CommandResult::Success(format!("Uploaded {} message(s)", uploaded)))
             },
             Command::Null => Ok(CommandResult::Empty)
         }
@@ -730,15 +1418,506 @@ impl Backend for GmailBackend {
     /// Defines which commands require authentication to the Gmail service.
     fn requires_authentication(&self, cmd: &Command) -> Option<bool> {
         match cmd {
-            Command::SyncFromCloud => Some(true),
+            Command::SyncFromCloud { .. } => Some(true),
+            Command::SyncPlan => Some(true),
+            Command::Refresh { .. } => Some(true),
+            Command::ListPlugins => Some(false),
+            Command::Version => Some(false),
+            Command::Compose { .. } => Some(true),
             Command::ViewMailbox { count: _, label: _ } => Some(false),
             Command::LoadEmail { email_id: _ } => Some(false),
-            Command::SendEmail { to: _, subject: _, body: _ } => Some(true),
-            // Command::FetchInbox { count: _ } => None, // TODO: deprecate fetch inbox for gmail backend
+            // The remote half is skipped when offline, so these work unauthenticated
+            // too; see `uses_authentication`.
+            Command::MarkRead { .. } => Some(false),
+            Command::Delete { .. } => Some(true),
+            Command::MarkSpam { .. } | Command::NotSpam { .. } => Some(true),
+            Command::ModifyLabels { .. } => Some(true),
+            Command::Move { .. } => Some(true),
+            Command::BatchModify { .. } => Some(false),
+            Command::Reindex => Some(false),
+            Command::Compact => Some(false),
+            Command::CheckDb { .. } => Some(false),
+            Command::LabelSummary { .. } => Some(false),
+            Command::Search { .. } => Some(false),
+            Command::MimeTree { .. } => Some(false),
+            Command::Export { .. } => Some(false),
+            Command::Import { .. } => Some(false),
+            Command::SendEmail { .. } => Some(true),
+            Command::FetchInbox { count: _ } => Some(true), // TODO: deprecate fetch inbox for gmail backend
             Command::ListLabels => Some(true),
+            Command::Whoami => Some(true),
+            // Signs in itself, see `reauthenticate`
+            Command::Login => Some(false),
+            Command::FetchMessage { .. } => Some(true),
+            Command::Upload { .. } => Some(true),
+            Command::TestPlugins { .. } => Some(false),
             Command::Null => Some(false),
-            _ => None
         }
     }
+
+    fn uses_authentication(&self, cmd: &Command) -> bool {
+        matches!(cmd, Command::MarkRead { .. } | Command::BatchModify { .. })
+    }
+}
+
+/// Where `reauthenticate` caches the token of a new login until it succeeded:
+/// `cache_path` with `.new` appended.
+pub fn fresh_token_cache_path(cache_path: &Path) -> PathBuf {
+    let mut name = cache_path.file_name().unwrap_or_default().to_os_string();
+    name.push(".new");
+    cache_path.with_file_name(name)
+}
+
+/// Splits one label change on `ids` into `batchModify` requests of at most `limit`
+/// ids each, in order.
+pub fn batch_modify_requests(ids: &[String], add: &[String], remove: &[String], limit: usize) -> Vec<BatchModifyMessagesRequest> {
+    ids.chunks(limit.max(1))
+        .map(|chunk| BatchModifyMessagesRequest {
+            ids: Some(chunk.to_vec()),
+            add_label_ids: Some(add.to_vec()),
+            remove_label_ids: Some(remove.to_vec()),
+        })
+        .collect()
+}
+
+/// The metadata sent alongside the raw message. A thread id files the message
+/// into that existing conversation.
+pub fn outgoing_message(thread_id: Option<String>) -> Message {
+    Message {
+        thread_id,
+        ..Default::default()
+    }
+}
+
+/// Builds an `EmailMessage` from a message fetched with `format("full")`. Headers
+/// are decoded and the text parts concatenated into the body.
+pub fn parse_full_message(message_id: String, message: Message) -> EmailMessage {
+    let payload: google_gmail1::api::MessagePart = message.payload.unwrap_or_default();
+    let headers = payload.headers.clone().unwrap_or_default();
+
+    // Helper function to extract header value by name
+    let get_header = |name: &str| -> String {
+        headers.iter()
+            .find(|h| h.name.as_ref().is_some_and(|n| n.eq_ignore_ascii_case(name)))
+            .and_then(|h| h.value.as_deref())
+            .map(decode_header_value)
+            .unwrap_or_default()
+    };
+
+    // Extract body and mime type from parts
+    let (body, mime_type) = if let Some(parts) = &payload.parts {
+        let mut body = String::new();
+        let mut mime_type = Default::default();
+
+        for part in parts {
+            if let Some(data) = part.body.as_ref().and_then(|b| b.data.as_ref()) {
+                body.push_str(&decode_text(data, part_charset(part).as_deref()));
+            }
+
+            if let Some(part_mime) = &part.mime_type {
+                if part_mime.contains("html") {
+                    mime_type = MimeType::TextHtml;
+                }
+            }
+        }
+
+        (body, mime_type)
+    } else {
+        // fallback
+        let body = payload.body.as_ref()
+            .and_then(|b| b.data.as_ref())
+            .map(|data| decode_text(data, part_charset(&payload).as_deref()))
+            .unwrap_or_default();
+        (body, MimeType::TextPlain)
+    };
+
+    let snippet = make_snippet(&body, SNIPPET_MAX_LENGTH);
+    EmailMessage {
+        id: message_id,
+        subject: get_header("Subject"),
+        from: EmailSender::from(get_header("From")),
+        to: get_header("To"),
+        cc: get_header("Cc"),
+        bcc: String::new(),
+        date: get_header("Date"),
+        body,
+        mime_type,
+        email_attachments: Vec::new(),
+        is_unread: message.label_ids.as_ref().is_some_and(|labels| labels.iter().any(|l| l == "UNREAD")),
+        thread_id: message.thread_id.clone(),
+        message_id: Some(get_header("Message-ID")).filter(|id| !id.is_empty()),
+        labels: message.label_ids.clone().unwrap_or_default(),
+        attachment_stats: Some(attachment_stats(&payload)),
+        references: Some(get_header("References")).filter(|references| !references.is_empty()),
+        snippet,
+    }
+}
+
+/// Counts the parts of `part` that are attachments (they carry a file name and
+/// their data is fetched separately), with their sizes as Gmail reports them.
+fn attachment_stats(part: &google_gmail1::api::MessagePart) -> AttachmentStats {
+    let own = match (&part.filename, &part.body) {
+        (Some(filename), Some(body)) if !filename.is_empty() && body.attachment_id.is_some() => AttachmentStats {
+            count: 1,
+            bytes: body.size.map_or(0, |size| size.max(0) as usize),
+        },
+        _ => AttachmentStats::default(),
+    };
+    part.parts.iter().flatten().map(attachment_stats).fold(own, |total, stats| AttachmentStats {
+        count: total.count + stats.count,
+        bytes: total.bytes + stats.bytes,
+    })
+}
+
+/// The charset declared in a message part's `Content-Type` header, if any.
+fn part_charset(part: &google_gmail1::api::MessagePart) -> Option<String> {
+    part.headers.as_ref()?
+        .iter()
+        .find(|h| h.name.as_deref().is_some_and(|n| n.eq_ignore_ascii_case("Content-Type")))
+        .and_then(|h| h.value.as_deref())
+        .and_then(charset_from_content_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A signed-out backend keeping its maildir and tokens in `dir`.
+    fn backend(dir: &Path) -> GmailBackend {
+        let config: BackendConfig = toml::from_str(&format!(
+            "host = \"gmail.com\"\nport = 993\nssl = true\nmaildir_path = {:?}\ntoken_cache_path = {:?}\n",
+            dir.join("Maildir").to_string_lossy(),
+            dir.join("tokens.json").to_string_lossy(),
+        )).unwrap();
+        GmailBackend::new(&config, "vim".to_string())
+    }
+
+    #[tokio::test]
+    async fn cli_only_commands_are_unimplemented() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = backend(dir.path());
+        let commands = [
+            Command::ListPlugins,
+            Command::Version,
+            Command::TestPlugins { hook: Hook::BeforeSend, content: String::new() },
+        ];
+        for command in commands {
+            let name = command.name();
+            match backend.do_command(command, None).await {
+                Err(Error::Unimplemented { backend, feature }) => {
+                    assert_eq!(backend, "gmail");
+                    assert_eq!(feature, name);
+                }
+                other => panic!("{}: {:?}", name, other),
+            }
+        }
+    }
+
+    #[test]
+    fn fresh_token_cache_sits_next_to_the_real_one() {
+        assert_eq!(
+            fresh_token_cache_path(Path::new("/tmp/tokens/gmail.json")),
+            PathBuf::from("/tmp/tokens/gmail.json.new")
+        );
+    }
+
+    #[test]
+    fn full_access_is_only_requested_for_permanent_deletion() {
+        assert!(!scopes_for(DeleteBehavior::Trash).contains(&FULL_ACCESS_SCOPE));
+        assert!(scopes_for(DeleteBehavior::Delete).contains(&FULL_ACCESS_SCOPE));
+    }
+
+    #[test]
+    fn gmail_failures_map_to_error_variants() {
+        let cases = [
+            (Some(401), "Invalid Credentials", Error::TokenExpired(String::new())),
+            (Some(404), "Requested entity was not found.", Error::NotFound(String::new())),
+            (Some(429), "Too many requests", Error::RateLimited(String::new())),
+            (Some(403), "rateLimitExceeded", Error::RateLimited(String::new())),
+            (Some(403), "Insufficient Permission", Error::Connection(String::new())),
+            (Some(500), "Backend Error", Error::Connection(String::new())),
+            (Some(503), "Service unavailable", Error::Connection(String::new())),
+            (None, "invalid_grant: Token has been expired or revoked.", Error::TokenExpired(String::new())),
+            (None, "connection reset", Error::Connection(String::new())),
+        ];
+        for (status, message, expected) in cases {
+            let error = classify_gmail_error("Fetching message", status, message);
+            assert_eq!(std::mem::discriminant(&error), std::mem::discriminant(&expected), "{:?} {}", status, message);
+            assert!(error.to_string().contains(&format!("Fetching message: {}", message)));
+        }
+    }
+
+    #[test]
+    fn fetch_counts_above_max_fetch_are_clamped() {
+        assert_eq!(clamp_fetch_count(0, 100), 0);
+        assert_eq!(clamp_fetch_count(20, 100), 20);
+        assert_eq!(clamp_fetch_count(100, 100), 100);
+        assert_eq!(clamp_fetch_count(101, 100), 100);
+        assert_eq!(clamp_fetch_count(10_000, 100), 100);
+    }
+
+    /// Stands in for `GmailBackend` in `with_token_retry!`, counting forced refreshes.
+    #[derive(Default)]
+    struct Refreshing {
+        token_refresher: TokenRefresher,
+        forced: AtomicU64,
+    }
+
+    impl Refreshing {
+        async fn refresh_token(&self, generation: u64) -> Result<(), Error> {
+            self.token_refresher.refresh(generation, || async {
+                self.forced.fetch_add(1, Ordering::SeqCst);
+                // Let concurrent requests pile up behind the refresh
+                tokio::task::yield_now().await;
+                Ok(())
+            }).await
+        }
+    }
+
+    #[tokio::test]
+    async fn expired_tokens_are_refreshed_and_retried_once() {
+        let backend = Refreshing::default();
+        let attempts = AtomicU64::new(0);
+        let result: Result<(), Error> = with_token_retry!(backend, async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err(Error::TokenExpired("expired".to_string()))
+        }.await);
+        assert!(matches!(result, Err(Error::TokenExpired(_))));
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        assert_eq!(backend.forced.load(Ordering::SeqCst), 1);
+
+        // Other failures are not retried
+        let attempts = AtomicU64::new(0);
+        let result: Result<(), Error> = with_token_retry!(backend, async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err(Error::NotFound("gone".to_string()))
+        }.await);
+        assert!(matches!(result, Err(Error::NotFound(_))));
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+        assert_eq!(backend.forced.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn concurrent_expiries_share_one_refresh() {
+        let backend = Refreshing::default();
+        let requests = (0..8).map(|_| async {
+            let backend = &backend;
+            // Fails until the token has been refreshed
+            with_token_retry!(backend, async {
+                if backend.token_refresher.generation() == 0 {
+                    Err(Error::TokenExpired("expired".to_string()))
+                } else {
+                    Ok(())
+                }
+            }.await)
+        });
+        let results = future::join_all(requests).await;
+        assert!(results.iter().all(Result::is_ok));
+        assert_eq!(backend.forced.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn labels_that_failed_to_load_are_skipped() {
+        let label = |id: &str| Label { id: Some(id.to_string()), name: Some(id.to_string()), ..Label::new() };
+        let labels = collect_label_results(vec![
+            ("INBOX".to_string(), Ok(label("INBOX"))),
+            ("Label_1".to_string(), Err(Error::RateLimited("slow down".to_string()))),
+            ("Label_2".to_string(), Ok(label("Label_2"))),
+            ("Label_3".to_string(), Err(Error::NotFound("gone".to_string()))),
+        ]);
+        let ids: Vec<_> = labels.iter().map(|label| label.id.as_deref().unwrap()).collect();
+        assert_eq!(ids, vec!["INBOX", "Label_2"]);
+        assert!(collect_label_results(Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn batch_modify_is_split_into_chunks_of_the_limit() {
+        let ids: Vec<String> = (0..1001).map(|n| n.to_string()).collect();
+        let requests = batch_modify_requests(&ids, &["Label_1".to_string()], &["INBOX".to_string()], 1000);
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].ids.as_ref().unwrap().len(), 1000);
+        assert_eq!(requests[1].ids.as_deref(), Some(&["1000".to_string()][..]));
+        assert!(requests.iter().all(|request| {
+            request.add_label_ids.as_deref() == Some(&["Label_1".to_string()][..])
+                && request.remove_label_ids.as_deref() == Some(&["INBOX".to_string()][..])
+        }));
+
+        assert_eq!(batch_modify_requests(&ids[..1000], &[], &[], 1000).len(), 1);
+        assert!(batch_modify_requests(&[], &[], &[], 1000).is_empty());
+    }
+
+    #[test]
+    fn replies_carry_threading_headers_and_the_thread_id() {
+        let original = EmailMessage {
+            message_id: Some("<orig@example.com>".to_string()),
+            references: Some("<root@example.com>".to_string()),
+            thread_id: Some("thread-1".to_string()),
+            ..EmailMessage::new()
+        };
+        let draft = EmailMessage {
+            to: "ann@example.com".to_string(),
+            subject: "Re: Plans".to_string(),
+            body: "Sounds good".to_string(),
+            ..EmailMessage::new()
+        };
+
+        let reply = ReplyHeaders::for_message(&original);
+        let raw = String::from_utf8(draft.to_lettre_email(reply.as_ref()).unwrap().formatted()).unwrap();
+        assert!(raw.contains("In-Reply-To: <orig@example.com>"), "{}", raw);
+        assert!(raw.contains("References: <root@example.com> <orig@example.com>"), "{}", raw);
+        assert_eq!(outgoing_message(original.thread_id.clone()).thread_id.as_deref(), Some("thread-1"));
+
+        // A new message starts its own thread
+        let raw = String::from_utf8(draft.to_lettre_email(None).unwrap().formatted()).unwrap();
+        assert!(!raw.contains("In-Reply-To"));
+        assert_eq!(outgoing_message(None).thread_id, None);
+    }
+
+    #[test]
+    fn profiles_are_formatted_for_whoami() {
+        let profile = google_gmail1::api::Profile {
+            email_address: Some("ann@gmail.com".to_string()),
+            messages_total: Some(1234),
+            history_id: Some(98765),
+            ..Default::default()
+        };
+        assert_eq!(format_profile(&profile), "Email: ann@gmail.com\nTotal messages: 1234\nHistory id: 98765");
+        assert_eq!(
+            format_profile(&google_gmail1::api::Profile::default()),
+            "Email: unknown\nTotal messages: unknown\nHistory id: unknown"
+        );
+    }
+
+    #[test]
+    fn attachments_are_counted_from_the_part_tree() {
+        use google_gmail1::api::{MessagePart, MessagePartBody};
+        let part = |filename: &str, attachment_id: Option<&str>, size: i32, parts: Vec<MessagePart>| MessagePart {
+            filename: Some(filename.to_string()),
+            body: Some(MessagePartBody {
+                attachment_id: attachment_id.map(str::to_string),
+                size: Some(size),
+                ..Default::default()
+            }),
+            parts: Some(parts).filter(|parts| !parts.is_empty()),
+            ..Default::default()
+        };
+        let message = part("", None, 0, vec![
+            part("", None, 0, vec![
+                part("", None, 40, vec![]),
+                // Inline image without a file name
+                part("", Some("inline"), 300, vec![]),
+            ]),
+            part("report.pdf", Some("att-1"), 1000, vec![]),
+            part("", None, 0, vec![part("data.csv", Some("att-2"), 500, vec![])]),
+        ]);
+        assert_eq!(attachment_stats(&message), AttachmentStats { count: 2, bytes: 1500 });
+        assert_eq!(attachment_stats(&MessagePart::default()), AttachmentStats::default());
+    }
+
+    #[test]
+    fn encoded_subjects_are_decoded_when_parsing_messages() {
+        use google_gmail1::api::{MessagePart, MessagePartBody, MessagePartHeader};
+        let header = |name: &str, value: &str| MessagePartHeader {
+            name: Some(name.to_string()),
+            value: Some(value.to_string()),
+        };
+        let message = Message {
+            payload: Some(MessagePart {
+                headers: Some(vec![
+                    header("Subject", "=?UTF-8?B?w6l0w6k=?="),
+                    header("To", "=?ISO-8859-1?Q?Andr=E9?= <andre@example.com>"),
+                ]),
+                body: Some(MessagePartBody { data: Some(b"hello".to_vec()), ..Default::default() }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let email = parse_full_message("m1".to_string(), message);
+        assert_eq!(email.subject, "été");
+        assert_eq!(email.to, "André <andre@example.com>");
+        assert_eq!(email.body, "hello");
+    }
+
+    #[test]
+    fn fetched_messages_are_parsed_into_emails() {
+        use google_gmail1::api::{MessagePart, MessagePartBody, MessagePartHeader};
+        let header = |name: &str, value: &str| MessagePartHeader {
+            name: Some(name.to_string()),
+            value: Some(value.to_string()),
+        };
+        let text = |mime_type: &str, data: &[u8]| MessagePart {
+            mime_type: Some(mime_type.to_string()),
+            body: Some(MessagePartBody { data: Some(data.to_vec()), ..Default::default() }),
+            ..Default::default()
+        };
+        let message = Message {
+            thread_id: Some("t1".to_string()),
+            label_ids: Some(vec!["INBOX".to_string(), "UNREAD".to_string()]),
+            payload: Some(MessagePart {
+                mime_type: Some("multipart/alternative".to_string()),
+                headers: Some(vec![
+                    header("From", "Ann Lee <ann@example.com>"),
+                    header("To", "bob@example.com"),
+                    header("Subject", "Lunch?"),
+                    header("Date", "Tue, 02 Jan 2024 10:00:00 +0000"),
+                    header("Message-ID", "<m1@example.com>"),
+                ]),
+                parts: Some(vec![text("text/plain", b"Noon "), text("text/html", b"<p>Noon</p>")]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let email = parse_full_message("m1".to_string(), message);
+        assert_eq!(email.id, "m1");
+        assert_eq!(email.from.name.as_deref(), Some("Ann Lee"));
+        assert_eq!(email.from.email, "ann@example.com");
+        assert_eq!(email.to, "bob@example.com");
+        assert_eq!(email.subject, "Lunch?");
+        assert_eq!(email.date, "Tue, 02 Jan 2024 10:00:00 +0000");
+        assert_eq!(email.message_id.as_deref(), Some("<m1@example.com>"));
+        assert_eq!(email.thread_id.as_deref(), Some("t1"));
+        assert!(email.is_unread);
+        assert_eq!(email.labels, vec!["INBOX", "UNREAD"]);
+        assert_eq!(email.body, "Noon <p>Noon</p>");
+        assert!(matches!(email.mime_type, MimeType::TextHtml));
+        assert_eq!(email.attachment_stats, Some(AttachmentStats::default()));
+    }
+
+    #[tokio::test]
+    async fn capabilities_match_the_commands_that_work() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = backend(dir.path());
+        assert_eq!(backend.capabilities(), BackendCapabilities {
+            supports_labels: true,
+            supports_sync: true,
+            supports_send: true,
+            supports_search: true,
+            supports_login: true,
+            supports_move: true,
+        });
+
+        // Supported, though signed out they need authenticating first
+        for command in [Command::ListLabels, Command::SyncFromCloud { yes: true }, Command::Search { query: "lunch".to_string(), count: 5 }] {
+            let name = command.name();
+            let result = backend.do_command(command, None).await;
+            assert!(!matches!(result, Err(Error::Unimplemented { .. })), "{}: {:?}", name, result);
+        }
+    }
+
+    #[tokio::test]
+    async fn signed_out_backends_ask_to_authenticate() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = backend(dir.path());
+        match backend.do_command(Command::ListLabels, None).await {
+            Err(Error::Authentication(message)) => {
+                assert!(message.contains("list-labels"), "{}", message);
+                assert!(message.contains("please authenticate"), "{}", message);
+            }
+            other => panic!("{:?}", other),
+        }
+        // Local commands still work
+        assert!(backend.do_command(Command::ViewMailbox { count: 5, label: None }, None).await.is_ok());
+    }
 }
 
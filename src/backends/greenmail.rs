@@ -1,17 +1,45 @@
 extern crate imap;
 
-use super::{Backend, Error};
+use super::{Backend, BackendCapabilities, Error, refresh};
 use crate::auth::Credentials;
-use crate::config::BackendConfig;
+use crate::config::{BackendConfig, DeleteBehavior};
 use crate::cli::command::{Command, CommandResult};
-use crate::core::{email::{EmailMessage, EmailSender}, label::Label};
+use crate::core::mailto::parse_mailto;
+use crate::core::editor::Editor;
+use crate::core::charset::{charset_from_content_type, decode_text};
+use crate::core::email::{EmailMessage, EmailSender, ReplyHeaders};
+use crate::core::label::move_label_changes;
 use crate::maildir::MaildirManager;
 use async_trait::async_trait;
 use lettre::{Transport, Message, SmtpTransport};
-use tempfile::NamedTempFile;
-use std::io::Write;
 use crate::plugins::plugins::PluginManager;
 use maildir::Maildir;
+use crate::net::{ProxyConfig, connect_tcp, relay_through_proxy};
+use std::net::TcpStream;
+use std::time::Duration;
+
+/// IMAP mailbox sent messages are recorded in.
+const SENT_MAILBOX: &str = "Sent";
+
+/// Port Greenmail serves SMTP on, next to IMAP on `host`.
+const SMTP_PORT: u16 = 1025;
+
+/// The IMAP mailbox holding the folder with label id `label`. System labels get
+/// the mailbox names common on IMAP servers; other labels are mailbox names already.
+pub fn imap_mailbox(label: &str) -> &str {
+    match label {
+        "SENT" => SENT_MAILBOX,
+        "SPAM" => "Junk",
+        "TRASH" => "Trash",
+        "DRAFT" => "Drafts",
+        other => other,
+    }
+}
+
+/// `value` as an IMAP quoted string.
+pub fn imap_quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
 
 pub struct GreenmailBackend {
     host: String,
@@ -19,8 +47,14 @@ pub struct GreenmailBackend {
     _ssl: bool, // TODO: remove this once we have a proper SSL implementation
     credentials: Credentials,
     editor: String,
+    delete_behavior: DeleteBehavior,
+    /// Applied to connecting and to every read/write on IMAP and SMTP connections.
+    connect_timeout: Option<Duration>,
+    /// Proxy the IMAP and SMTP connections are tunnelled through, if any.
+    proxy: Option<ProxyConfig>,
+    /// Append sent messages to `SENT_MAILBOX`.
+    record_sent: bool,
     maildir_manager: MaildirManager,
-    maildir: Maildir,
 }
 
 impl GreenmailBackend {
@@ -40,7 +74,13 @@ impl GreenmailBackend {
             _ssl: config.ssl,
             credentials,
             editor,
-            maildir: Maildir::from(config.maildir_path.clone()),
+            delete_behavior: config.delete_behavior,
+            connect_timeout: config.connect_timeout(),
+            record_sent: config.record_sent(),
+            proxy: config.proxy().unwrap_or_else(|e| {
+                tracing::error!("{}", e);
+                std::process::exit(1);
+            }),
             maildir_manager: MaildirManager::new(config.maildir_path.clone()).unwrap_or_else(|e| {
                 tracing::error!("Failed to create maildir manager: {}", e);
                 std::process::exit(1);
@@ -50,19 +90,48 @@ impl GreenmailBackend {
 }
 
 impl GreenmailBackend {
-    /// Syncs emails from IMAP server to local maildir
-    /// Returns the number of messages synced
-    fn sync_from_imap(&self) -> Result<usize, Error> {
+    /// Opens an unauthenticated IMAP connection over TLS. Connecting, and every read
+    /// and write afterwards, is bounded by `connect_timeout`, so a dead server gives
+    /// `Error::Connection("timeout")` instead of hanging.
+    fn connect_imap(&self) -> Result<imap::Client<native_tls::TlsStream<TcpStream>>, Error> {
         let domain = self.host.as_str();
-        
-        // Connect with TLS (accepting self-signed certs for local testing)
+
+        // For local testing with self-signed certificates, we need to accept invalid certs
+        // while still maintaining TLS encryption
         let tls = native_tls::TlsConnector::builder()
             .danger_accept_invalid_certs(true)
             .danger_accept_invalid_hostnames(true)
             .build()
-            .unwrap();
-    
-        let client = imap::connect((domain, self.port), domain, &tls).unwrap();
+            .map_err(|e| Error::Connection(format!("Failed to set up TLS: {}", e)))?;
+
+        let stream = match &self.proxy {
+            Some(proxy) => proxy.connect(domain, self.port, self.connect_timeout)?,
+            None => connect_tcp((domain, self.port), self.connect_timeout)?,
+        };
+        // we pass in the domain to check that the server's TLS
+        // certificate is valid for the domain we're connecting to.
+        let tls_stream = tls.connect(domain, stream)
+            .map_err(|e| Error::Connection(format!("TLS handshake failed: {}", e)))?;
+
+        let mut client = imap::Client::new(tls_stream);
+        client.read_greeting()?;
+        Ok(client)
+    }
+
+    /// Syncs emails from IMAP server to local maildir
+    /// Returns the number of messages synced
+    /// Number of messages in the INBOX, without fetching any of them.
+    fn inbox_count(&self) -> Result<usize, Error> {
+        let mut imap_session = self.connect_imap()?
+            .login(&self.credentials.username, &self.credentials.password)
+            .map_err(|e| e.0)?;
+        let mailbox = imap_session.examine("INBOX")?;
+        imap_session.logout()?;
+        Ok(mailbox.exists as usize)
+    }
+
+    fn sync_from_imap(&self) -> Result<usize, Error> {
+        let client = self.connect_imap()?;
     
         let mut imap_session = client
             .login(&self.credentials.username, &self.credentials.password)
@@ -101,14 +170,8 @@ impl GreenmailBackend {
                         
                         tracing::info!("Message {} - Unread: {}, Size: {} bytes", msg_num, is_unread, raw_content.len());
                         
-                        // Store in maildir (using raw RFC822 bytes)
-                        if is_unread {
-                            self.maildir.store_new(raw_content)
-                                .map_err(|e| Error::Other(format!("Failed to store message in new: {}", e)))?;
-                        } else {
-                            self.maildir.store_cur_with_flags(raw_content, "")
-                                .map_err(|e| Error::Other(format!("Failed to store message in cur: {}", e)))?;
-                        }
+                        // Store in maildir (using raw RFC822 bytes), indexed for search
+                        self.maildir_manager.store_message(raw_content, if is_unread { "new" } else { "cur" })?;
                         
                         synced_count += 1;
                         tracing::info!("Synced message {}/{}", synced_count, num_messages);
@@ -127,19 +190,7 @@ impl GreenmailBackend {
     }
 
     fn fetch_inbox_emails(&self, count: usize) -> Result<Vec<EmailMessage>, Error> {
-        let domain = self.host.as_str();
-        
-        // For local testing with self-signed certificates, we need to accept invalid certs
-        // while still maintaining TLS encryption
-        let tls = native_tls::TlsConnector::builder()
-            .danger_accept_invalid_certs(true)
-            .danger_accept_invalid_hostnames(true)
-            .build()
-            .unwrap();
-    
-        // we pass in the domain twice to check that the server's TLS
-        // certificate is valid for the domain we're connecting to.
-        let client = imap::connect((domain, self.port), domain, &tls).unwrap();
+        let client = self.connect_imap()?;
     
         // the client we have here is unauthenticated.
         // to do anything useful with the e-mails, we need to log in
@@ -180,31 +231,28 @@ impl GreenmailBackend {
         Ok(emails)
     }
 
-    fn list_labels(&self) -> Result<Vec<Label>, Error> {
-        tracing::error!("unimplemented!");
-        return Err(Error::Unimplemented {
-            backend: "greenmail".to_string(),
-                feature: "list_labels".to_string(),
-            });
-    }
-
     /// Greenmail (or the library?) parses emails in a weird way. This method provides a layer to our
     /// `EmailMessage` type api.
     fn parse_email_message(&self, message: &imap::types::Fetch) -> Result<EmailMessage, Error> {
-        let body = message.body().unwrap_or(&[]);
-        let body_str = std::str::from_utf8(body)
-            .unwrap_or("(invalid utf-8)")
-            .to_string();
+        let raw = message.body().unwrap_or(&[]);
 
         let mut output = EmailMessage::new();
 
-        // need to split body_str into headers and body
-        let (headers, body) = body_str.split_once("\r\n\r\n").unwrap();
+        // need to split the raw message into headers and body. Headers are ASCII; the
+        // body is in whatever charset its Content-Type declares.
+        let split = raw.windows(4).position(|w| w == b"\r\n\r\n").unwrap_or(raw.len());
+        let headers = String::from_utf8_lossy(&raw[..split]);
+        let body = raw.get(split + 4..).unwrap_or(&[]);
+        let mut charset = None;
         for header in headers.lines() {
-            let (name, value) = header.split_once(": ").unwrap();
+            let Some((name, value)) = header.split_once(": ") else {
+                continue;
+            };
             match name {
+                "Content-Type" => charset = charset_from_content_type(value),
                 "Subject" => output.subject = value.to_string(),
                 "To" => output.to = value.to_string(),
+                "Cc" => output.cc = value.to_string(),
                 "From" => output.from = EmailSender::from(value.to_string()),
                 "Received" => {
                     output.date = value.split_once(";").unwrap().1.trim().to_string();
@@ -213,92 +261,115 @@ impl GreenmailBackend {
             }
         }
 
-        output.body = body.to_string();
+        output.body = decode_text(body, charset.as_deref());
         Ok(output)
     }
 
 
-    /// Opens the provided editor (e.g., vim, code) to allow the user to edit the email draft.
-    /// Prefills the email with any available information (to, subject, body) from cli and writes it as template to a temporary file.
-    /// After the user edits the email and exits the editor, the function reads the updated content and returns the modified `EmailMessage`.
-    fn edit_email_with_prefill(editor: &str, mut draft: EmailMessage) -> std::io::Result<EmailMessage> {
-        
-        // Create a new temp file to be used by editor
-        // File gets deleted once out of scope
-        let mut temp_file = NamedTempFile::new()?;
-
-        // Write draft information into temp file
-        writeln!(temp_file, "To: {}", draft.to)?;
-        writeln!(temp_file, "Subject: {}", draft.subject)?;
-        writeln!(temp_file, "Body:\n{}", draft.body)?;
-
-        // Get temp file path        
-        let temp_file_path = temp_file.path().to_owned();
-
-        // Create command to run editor with path as arg
-        let mut command = std::process::Command::new(editor);
-        if editor.contains("code") {
-            // Add wait arg for vscode to ensure file is saved before returning
-            command.arg("--wait").arg(&temp_file_path);
+    /// Appends a sent message to `SENT_MAILBOX`, marked read, creating the mailbox
+    /// if the server does not have one yet. Sync only reads INBOX, so the copy is
+    /// also stored in the local maildir under the `SENT` label.
+    fn record_sent_message(&self, raw: &[u8]) -> Result<(), Error> {
+        if let Err(e) = self.store_sent_locally(raw) {
+            tracing::warn!("Failed to keep a local copy of the sent message: {}", e);
         }
-        else {
-            command.arg(&temp_file_path);
+
+        let mut imap_session = self.connect_imap()?
+            .login(&self.credentials.username, &self.credentials.password)
+            .map_err(|e| e.0)?;
+        if imap_session.examine(SENT_MAILBOX).is_err() {
+            imap_session.create(SENT_MAILBOX)?;
         }
+        imap_session.append_with_flags(SENT_MAILBOX, raw, &[imap::types::Flag::Seen])?;
+        imap_session.logout()?;
+        Ok(())
+    }
 
-        // Run the editor and check if it was successful
-        let status = command.status()?;
-        if !status.success() {
-            tracing::error!("Editor failed with status: {:?}", status);
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                "Editor failed",
-            ));
+    /// Stores a sent message in the local maildir under the `SENT` label, already read.
+    fn store_sent_locally(&self, raw: &[u8]) -> Result<(), Error> {
+        let maildir_id = self.maildir_manager.store_message(raw, "cur")?;
+        self.maildir_manager.add_label_mappings(&maildir_id, &["SENT".to_string()])
+    }
+
+    /// Moves a message from the mailbox of `from` (the inbox by default) to the
+    /// mailbox of `to` on the server, creating it if needed, then mirrors the move
+    /// locally. Sync keeps no IMAP UIDs, so the message is found by its Message-ID.
+    fn move_message(&self, maildir_id: &str, from: Option<&str>, to: &str) -> Result<(), Error> {
+        let email = self.maildir_manager.load_email(maildir_id)?;
+        let message_id = email.message_id.as_deref()
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .ok_or_else(|| Error::InvalidInput(format!("{} has no Message-ID to find it on the server by", maildir_id)))?;
+        let source = imap_mailbox(from.unwrap_or("INBOX"));
+        let target = imap_mailbox(to);
+
+        let mut imap_session = self.connect_imap()?
+            .login(&self.credentials.username, &self.credentials.password)
+            .map_err(|e| e.0)?;
+        if imap_session.examine(target).is_err() {
+            imap_session.create(target)?;
+        }
+        imap_session.select(source)?;
+        let uids = imap_session.uid_search(format!("HEADER Message-ID {}", imap_quote(message_id)))?;
+        if uids.is_empty() {
+            imap_session.logout()?;
+            return Err(Error::Other(format!("{} is not in {} on the server", message_id, source)));
         }
+        let uid_set = uids.iter().map(u32::to_string).collect::<Vec<_>>().join(",");
 
-        // After the user exits the editor, read contents of temp file
-        let contents = std::fs::read_to_string(&temp_file_path)?;
-        let mut in_body = false;
-        let mut body_lines = Vec::new();
-
-        // Iterate through the lines of the file and parse the email fields
-        // Evertyhing after Body: goes into body_lines
-        for line in contents.lines() {
-            if in_body {
-                body_lines.push(line);
-            } else if line.starts_with("To:") {
-                draft.to = line["To:".len()..].trim().to_string();
-            } else if line.starts_with("Subject:") {
-                draft.subject = line["Subject:".len()..].trim().to_string();
-            } else if line.starts_with("Body:") {
-                in_body = true;
-                body_lines.push(line["Body:".len()..].trim());
-            }
+        // MOVE (RFC 6851) is atomic; without it the message is copied, then expunged
+        if imap_session.capabilities()?.has_str("MOVE") {
+            imap_session.uid_mv(&uid_set, target)?;
+        } else {
+            imap_session.uid_copy(&uid_set, target)?;
+            imap_session.uid_store(&uid_set, "+FLAGS (\\Deleted)")?;
+            imap_session.expunge()?;
         }
-        draft.body = body_lines.join("\n");
-        Ok(draft)
+        imap_session.logout()?;
+
+        let (add, remove) = move_label_changes(from, to);
+        self.maildir_manager.modify_labels(maildir_id, &add, &remove)
     }
 
-    /// Send an email using the `lettre` library.
-    fn send_email(&self, draft: &EmailMessage) -> Result<CommandResult, Error> {
+    /// Send an email using the `lettre` library. `reply` threads it under the
+    /// message it answers.
+    fn send_email(&self, draft: &EmailMessage, reply: Option<&ReplyHeaders>) -> Result<CommandResult, Error> {
         // Build the email message
-        let email = Message::builder()
-            .from("GreenMailTester <greenmail@domain.tester>".parse().unwrap())
-            .to(draft.to.parse().unwrap())
+        let builder = Message::builder()
+            .from("GreenMailTester <greenmail@domain.tester>".parse().unwrap());
+        let builder = ReplyHeaders::apply(reply, builder);
+        let email = draft.with_recipients(builder)?
             .subject(draft.subject.clone())
             .body(draft.body.clone())
-            .unwrap();
+            .map_err(|e| Error::InvalidInput(format!("Failed to build email: {}", e)))?;
 
-        // Create an SMTP transport (for local testing)
-        let mailer = SmtpTransport::builder_dangerous("127.0.0.1")
-            .port(1025)
+        // Create an SMTP transport (for local testing). lettre connects by itself, so
+        // through a proxy it is pointed at a local relay into the tunnel
+        let (smtp_host, smtp_port) = match &self.proxy {
+            Some(proxy) => ("127.0.0.1".to_string(), relay_through_proxy(proxy, &self.host, SMTP_PORT, self.connect_timeout)?),
+            None => (self.host.clone(), SMTP_PORT),
+        };
+        let mailer = SmtpTransport::builder_dangerous(smtp_host)
+            .port(smtp_port)
+            .timeout(self.connect_timeout)
             .build();
 
         // Send the email
         match mailer.send(&email) {
             Ok(_) => {
                 tracing::info!("Email sent successfully.");
+                // The message is out; failing to keep a copy must not report it as unsent
+                if self.record_sent {
+                    if let Err(e) = self.record_sent_message(&email.formatted()) {
+                        tracing::warn!("Sent, but failed to record the message in {}: {}", SENT_MAILBOX, e);
+                    }
+                }
                 Ok(CommandResult::Empty)
             },
+            Err(e) if e.is_timeout() => {
+                tracing::error!("Failed to send email: {}", e);
+                Err(Error::Connection("timeout".to_string()))
+            },
             Err(e) => {
                 tracing::error!("Failed to send email: {}", e);
                 Err(Error::Connection(e.to_string()))
@@ -314,30 +385,43 @@ impl Backend for GreenmailBackend {
         false 
     }
 
-    async fn do_command(&self, cmd: Command, _plugin_manager: Option<&mut PluginManager>) -> Result<CommandResult, Error> {
+    fn capabilities(&self) -> BackendCapabilities {
+        BackendCapabilities {
+            supports_labels: false,
+            supports_sync: true,
+            supports_send: true,
+            supports_search: true,
+            supports_login: false,
+            supports_move: true,
+        }
+    }
+
+    async fn do_command(&self, cmd: Command, plugin_manager: Option<&mut PluginManager>) -> Result<CommandResult, Error> {
         match cmd {
             Command::FetchInbox { count } => {
                 let emails = self.fetch_inbox_emails(count)?;
                 if emails.is_empty() {
                     Ok(CommandResult::Empty)
                 } else if count == 1 {
-                    Ok(CommandResult::Email(emails.into_iter().next().unwrap()))
+                    Ok(CommandResult::Email(Box::new(emails.into_iter().next().unwrap())))
                 } else {
                     Ok(CommandResult::Emails(emails))
                 }
             },
-            Command::ListLabels => {
-                let labels = self.list_labels()?;
-                Ok(CommandResult::Labels(labels))
-            }
-            Command::SendEmail { to, subject, body } => {
+            Command::ListLabels | Command::Whoami | Command::Login | Command::FetchMessage { .. } | Command::Upload { .. } => Err(Error::Unimplemented {
+                backend: "greenmail".to_string(),
+                feature: cmd.name().to_string(),
+            }),
+            Command::SendEmail { to, subject, body, cc, bcc, reply_to } => {
                 let mut draft = EmailMessage::new();
                 draft.to = to.unwrap_or_default();
                 draft.subject = subject.unwrap_or_default();
                 draft.body = body.unwrap_or_default();
+                draft.cc = cc.unwrap_or_default();
+                draft.bcc = bcc.unwrap_or_default();
 
-                let draft = if draft.to.is_empty() || draft.subject.is_empty() || draft.body.is_empty() {
-                    Self::edit_email_with_prefill(&self.editor, draft)?
+                let draft = if draft.is_partially_empty() {
+                    Editor::open(&self.editor, draft)?
                 } else {
                     draft
                 };
@@ -346,9 +430,17 @@ impl Backend for GreenmailBackend {
                     return Err(Error::InvalidInput("To field cannot be empty".to_string()));
                 }
 
-                self.send_email(&draft)
+                let original = reply_to.as_deref()
+                    .map(|email_id| self.maildir_manager.load_email(email_id))
+                    .transpose()?;
+                let reply = original.as_ref().and_then(ReplyHeaders::for_message);
+                self.send_email(&draft, reply.as_ref())
             }
-            Command::SyncFromCloud => {
+            Command::Compose { uri } => {
+                let command = parse_mailto(&uri)?.into_command();
+                self.do_command(command, plugin_manager).await
+            }
+            Command::SyncFromCloud { .. } => {
                 tracing::info!("Syncing from Greenmail IMAP server...");
 
                 let synced_count = self.sync_from_imap()?;
@@ -356,6 +448,19 @@ impl Backend for GreenmailBackend {
 
                 Ok(CommandResult::Empty)
             }
+            // Every sync downloads the whole INBOX again
+            // Only the first sync, into an empty maildir, is worth asking about
+            Command::SyncPlan if self.maildir_manager.maildir_ids_on_disk()?.is_empty() => Ok(CommandResult::SyncPlan {
+                full: true,
+                total: Some(self.inbox_count()?),
+            }),
+            Command::SyncPlan => Ok(CommandResult::SyncPlan { full: false, total: None }),
+            // Handled by the CLI before a backend is involved
+            Command::ListPlugins | Command::Version | Command::TestPlugins { .. } => Err(Error::Unimplemented {
+                backend: "greenmail".to_string(),
+                feature: cmd.name().to_string(),
+            }),
+            Command::Refresh { count, yes } => refresh(self, count, yes, plugin_manager).await,
             Command::ViewMailbox { count, label } => {
                 tracing::info!("Viewing mailbox, count: {}, label: {:?}", count, label);
                 let label_ref = label.as_deref();
@@ -364,7 +469,7 @@ impl Backend for GreenmailBackend {
                 if emails.is_empty() {
                     Ok(CommandResult::Empty)
                 } else if count == 1 {
-                    Ok(CommandResult::Email(emails.into_iter().next().unwrap()))
+                    Ok(CommandResult::Email(Box::new(emails.into_iter().next().unwrap())))
                 } else {
                     Ok(CommandResult::Emails(emails))
                 }
@@ -372,7 +477,86 @@ impl Backend for GreenmailBackend {
             Command::Null => Ok(CommandResult::Empty),
             Command::LoadEmail { email_id } => {
                 let email = self.maildir_manager.load_email_with_attachments(&email_id)?;
-                Ok(CommandResult::Email(email))
+                Ok(CommandResult::Email(Box::new(email)))
+            }
+            Command::MarkRead { email_id } => {
+                // TODO: set the Seen flag on the IMAP server as well
+                self.maildir_manager.mark_read(&email_id)?;
+                Ok(CommandResult::Empty)
+            }
+            Command::Delete { email_id } => {
+                // TODO: mirror this on the IMAP server
+                match self.delete_behavior {
+                    DeleteBehavior::Trash => self.maildir_manager.trash_message(&email_id)?,
+                    DeleteBehavior::Delete => self.maildir_manager.purge_message(&email_id)?,
+                }
+                Ok(CommandResult::Empty)
+            }
+            Command::MarkSpam { email_id } => {
+                self.maildir_manager.set_spam(&email_id, true)?;
+                Ok(CommandResult::Empty)
+            }
+            Command::NotSpam { email_id } => {
+                self.maildir_manager.set_spam(&email_id, false)?;
+                Ok(CommandResult::Empty)
+            }
+            Command::ModifyLabels { email_id, add, remove } => {
+                self.maildir_manager.modify_labels(&email_id, &add, &remove)?;
+                Ok(CommandResult::Empty)
+            }
+            Command::Move { email_id, to, from } => {
+                self.move_message(&email_id, from.as_deref(), &to)?;
+                Ok(CommandResult::Empty)
+            }
+            Command::BatchModify { email_ids, add, remove } => {
+                let marks_read = remove.iter().any(|label| label == "UNREAD");
+                for email_id in &email_ids {
+                    if marks_read {
+                        self.maildir_manager.mark_read(email_id)?;
+                    }
+                    self.maildir_manager.modify_labels(email_id, &add, &remove)?;
+                }
+                Ok(CommandResult::Empty)
+            }
+            Command::Reindex => {
+                let (indexed, skipped) = self.maildir_manager.reindex()?;
+                Ok(CommandResult::Success(format!("Reindexed {} message(s), skipped {}", indexed, skipped)))
+            }
+            Command::Compact => Ok(CommandResult::Success(self.maildir_manager.compact()?.to_string())),
+            Command::CheckDb { repair } => {
+                let orphans = self.maildir_manager.find_orphans()?;
+                if !repair || orphans.is_empty() {
+                    return Ok(CommandResult::Success(orphans.to_string()));
+                }
+                let (labels, metadata, mappings) = self.maildir_manager.prune_orphans(&orphans)?;
+                Ok(CommandResult::Success(format!(
+                    "{}Removed {} label, {} metadata and {} mapping row(s)",
+                    orphans, labels, metadata, mappings
+                )))
+            }
+            Command::LabelSummary { label } => {
+                let count = self.maildir_manager.count_with_label(&label)?;
+                let latest_subject = self.maildir_manager.latest_subject_for_label(&label)?;
+                Ok(CommandResult::LabelSummary { label, count, latest_subject })
+            }
+            Command::Search { query, count } => {
+                let emails = self.maildir_manager.search(&query, count)?;
+                if emails.is_empty() {
+                    Ok(CommandResult::Empty)
+                } else {
+                    Ok(CommandResult::Emails(emails))
+                }
+            }
+            Command::MimeTree { email_id } => {
+                Ok(CommandResult::Success(self.maildir_manager.mime_tree(&email_id)?))
+            }
+            Command::Export { email_id, out_path, force } => {
+                let path = self.maildir_manager.export_message(&email_id, &out_path, force)?;
+                Ok(CommandResult::Success(format!("Exported {} to {:?}", email_id, path)))
+            }
+            Command::Import { path } => {
+                let count = self.maildir_manager.import_file(&path)?;
+                Ok(CommandResult::Success(format!("Imported {} message(s) from {:?}", count, path)))
             }
         }
     }
@@ -380,13 +564,161 @@ impl Backend for GreenmailBackend {
     /// Defines which commands require authentication to the Greenmail service.
     fn requires_authentication(&self, cmd: &Command) -> Option<bool> {
         match cmd {
-            Command::SyncFromCloud => Some(true),
+            Command::SyncFromCloud { .. } => Some(true),
+            Command::SyncPlan => Some(true),
+            Command::Refresh { .. } => Some(true),
+            Command::ListPlugins => Some(false),
+            Command::Version => Some(false),
+            Command::Compose { .. } => Some(true),
+            Command::FetchInbox { count: _ } => Some(true), // TODO: deprecate fetch inbox for greenmail backend
             Command::ViewMailbox { count: _, label: _ } => Some(false),
-            Command::SendEmail { to: _, subject: _, body: _ } => Some(true),
-            // Command::FetchInbox { count: _ } => None, // TODO: deprecate fetch inbox for greenmail backend
+            Command::LoadEmail { email_id: _ } => Some(false),
+            Command::SendEmail { .. } => Some(true),
             Command::ListLabels => Some(false),
+            Command::Whoami => Some(false),
+            Command::Login => Some(false),
+            Command::FetchMessage { .. } => Some(false),
+            Command::Upload { .. } => Some(false),
+            Command::MarkRead { .. } => Some(false),
+            Command::Delete { .. } => Some(false),
+            Command::MarkSpam { .. } | Command::NotSpam { .. } => Some(false),
+            Command::ModifyLabels { .. } => Some(false),
+            Command::Move { .. } => Some(true),
+            Command::BatchModify { .. } => Some(false),
+            Command::Reindex => Some(false),
+            Command::Compact => Some(false),
+            Command::CheckDb { .. } => Some(false),
+            Command::LabelSummary { .. } => Some(false),
+            Command::Search { .. } => Some(false),
+            Command::MimeTree { .. } => Some(false),
+            Command::Export { .. } => Some(false),
+            Command::Import { .. } => Some(false),
+            Command::TestPlugins { .. } => Some(false),
             Command::Null => Some(false),
-            _ => None
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::events::Hook;
+    use std::path::Path;
+
+    /// A backend for a server that is never contacted, keeping its maildir in `dir`.
+    fn backend(dir: &Path) -> GreenmailBackend {
+        let config: BackendConfig = toml::from_str(&format!(
+            "host = \"127.0.0.1\"\nport = 1993\nssl = true\nmaildir_path = {:?}\nauth_credentials = {{ username = \"ann\", password = \"secret\" }}\n",
+            dir.to_string_lossy(),
+        )).unwrap();
+        GreenmailBackend::new(&config, "vim".to_string())
+    }
+
+    #[tokio::test]
+    async fn unsupported_commands_are_unimplemented() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = backend(dir.path());
+        let commands = [
+            Command::ListLabels,
+            Command::Whoami,
+            Command::Login,
+            Command::FetchMessage { id: "abc".to_string() },
+            Command::Upload { label: None },
+            Command::ListPlugins,
+            Command::Version,
+            Command::TestPlugins { hook: Hook::BeforeSend, content: String::new() },
+        ];
+        for command in commands {
+            let name = command.name();
+            match backend.do_command(command, None).await {
+                Err(Error::Unimplemented { backend, feature }) => {
+                    assert_eq!(backend, "greenmail");
+                    assert_eq!(feature, name);
+                }
+                other => panic!("{}: {:?}", name, other),
+            }
+        }
+    }
+
+    #[test]
+    fn system_labels_map_to_common_mailbox_names() {
+        assert_eq!(imap_mailbox("TRASH"), "Trash");
+        assert_eq!(imap_mailbox("SPAM"), "Junk");
+        assert_eq!(imap_mailbox("INBOX"), "INBOX");
+        assert_eq!(imap_mailbox("Projects/2025"), "Projects/2025");
+    }
+
+    #[test]
+    fn quoted_message_ids_escape_quotes_and_backslashes() {
+        assert_eq!(imap_quote("<abc@example.com>"), "\"<abc@example.com>\"");
+        assert_eq!(imap_quote("a\"b\\c"), "\"a\\\"b\\\\c\"");
+    }
+
+    async fn check_db(backend: &GreenmailBackend, repair: bool) -> String {
+        match backend.do_command(Command::CheckDb { repair }, None).await {
+            Ok(CommandResult::Success(report)) => report,
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn check_db_only_repairs_when_asked() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = backend(dir.path());
+        let manager = &backend.maildir_manager;
+        let maildir_id = manager.store_message(b"Subject: Gone\r\nDate: Mon, 6 Jan 2025 10:00:00 +0000\r\n\r\nbody\r\n", "new").unwrap();
+        manager.add_label_mappings(&maildir_id, &["INBOX".to_string()]).unwrap();
+        std::fs::remove_file(manager.find_message_path(&maildir_id).unwrap()).unwrap();
+
+        let report = check_db(&backend, false).await;
+        assert_eq!(report, format!(
+            "1 orphaned label row(s)\n  {id}\n1 orphaned metadata row(s)\n  {id}\n1 orphaned mapping row(s)\n  {id}\n",
+            id = maildir_id
+        ));
+        assert!(!manager.find_orphans().unwrap().is_empty());
+
+        let repaired = check_db(&backend, true).await;
+        assert_eq!(repaired, format!("{}Removed 1 label, 1 metadata and 1 mapping row(s)", report));
+        assert_eq!(check_db(&backend, true).await, "Database is consistent with the maildir");
+    }
+
+    #[test]
+    fn sent_messages_are_kept_under_the_sent_label() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = backend(dir.path());
+        backend.store_sent_locally(b"Subject: Sent one\r\nDate: Mon, 6 Jan 2025 10:00:00 +0000\r\n\r\nbody\r\n").unwrap();
+
+        let sent = backend.maildir_manager.list_emails_by_label(10, Some("SENT")).unwrap();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].subject, "Sent one");
+        assert!(backend.maildir_manager.has_label(&sent[0].id, "SENT").unwrap());
+        assert!(!sent[0].is_unread);
+        assert!(backend.maildir_manager.list_emails_by_label(10, Some("INBOX")).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn capabilities_match_the_commands_that_work() {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = backend(dir.path());
+        let capabilities = backend.capabilities();
+        assert_eq!(capabilities, BackendCapabilities {
+            supports_labels: false,
+            supports_sync: true,
+            supports_send: true,
+            supports_search: true,
+            supports_login: false,
+            supports_move: true,
+        });
+
+        let commands = [
+            (capabilities.supports_labels, Command::ListLabels),
+            (capabilities.supports_login, Command::Login),
+            (capabilities.supports_search, Command::Search { query: "lunch".to_string(), count: 5 }),
+        ];
+        for (supported, command) in commands {
+            let name = command.name();
+            let result = backend.do_command(command, None).await;
+            assert_eq!(!matches!(result, Err(Error::Unimplemented { .. })), supported, "{}: {:?}", name, result);
+        }
+    }
+}
@@ -9,17 +9,44 @@ use async_trait::async_trait;
 use crate::plugins::plugins::PluginManager;
 use std::fmt;
 
+/// What a backend can do, so the UI can leave out what it cannot instead of
+/// failing with `Error::Unimplemented` at runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackendCapabilities {
+    /// Lists labels (folders) with `ListLabels`.
+    pub supports_labels: bool,
+    /// Syncs from the server with `SyncFromCloud`.
+    pub supports_sync: bool,
+    /// Sends mail with `SendEmail`.
+    pub supports_send: bool,
+    /// Searches the local mail with `Search`.
+    pub supports_search: bool,
+    /// Signs in again from scratch with `Login` (see `Backend::reauthenticate`).
+    pub supports_login: bool,
+    /// Moves emails between folders with `Move`.
+    pub supports_move: bool,
+}
+
 #[async_trait]
 pub trait Backend: Send {    
     /// Check if this backend requires OAuth2 authentication
     fn needs_oauth(&self) -> bool;
 
+    /// What this backend supports.
+    fn capabilities(&self) -> BackendCapabilities;
+
     /// Perform authentication (if needed). This is a sync wrapper that may spawn async tasks.
     /// Returns Ok(()) if authentication succeeded or wasn't needed.
     async fn authenticate(&mut self) -> Result<(), Error> {
         Ok(())
     }
 
+    /// Authenticate from scratch, discarding any cached credentials first. Backends
+    /// without cached credentials simply authenticate again.
+    async fn reauthenticate(&mut self) -> Result<(), Error> {
+        self.authenticate().await
+    }
+
     /// Execute a command and return a structured result
     /// 
     /// The plugin_manager is optional - only pass it for commands that need plugin dispatch
@@ -29,6 +56,26 @@ pub trait Backend: Send {
     /// 
     /// This function WILL NOT authenticate the backend and `authenticate()` should be called after.
     fn requires_authentication(&self, cmd: &Command) -> Option<bool>;
+
+    /// Whether a command that works without authentication (`requires_authentication`
+    /// is `Some(false)`) still reaches the server when signed in, e.g. marking read
+    /// locally and remotely. The CLI then tries to authenticate and carries on
+    /// offline if that fails.
+    fn uses_authentication(&self, _cmd: &Command) -> bool {
+        false
+    }
+}
+
+/// Runs `Command::Refresh` on `backend`: a sync from the cloud, then a view of the
+/// newest `count` emails. Shared by the backends so the order cannot drift apart.
+pub async fn refresh<B: Backend + Sync + ?Sized>(
+    backend: &B,
+    count: usize,
+    yes: bool,
+    mut plugin_manager: Option<&mut PluginManager>,
+) -> Result<CommandResult, Error> {
+    backend.do_command(Command::SyncFromCloud { yes }, plugin_manager.as_deref_mut()).await?;
+    backend.do_command(Command::ViewMailbox { count, label: None }, plugin_manager).await
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Deserialize)]
@@ -44,12 +91,12 @@ impl std::str::FromStr for BackendType {
     type Err = String;
     
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.to_lowercase().as_str() {
-            "greenmail" => Ok(BackendType::GreenMail),
-            "gmail" => Ok(BackendType::Gmail),
-            // this will need a way to list all available backends without having to hardcode them here
-            _ => Err(format!("Invalid backend: {}. Available backends are: greenmail, gmail", s)),
-        }
+        let name = s.to_lowercase();
+        BackendType::all()
+            .iter()
+            .find(|backend| backend.to_string() == name)
+            .copied()
+            .ok_or_else(|| format!("Invalid backend: {}. Available backends are: {}", s, BackendType::names()))
     }
 }
 
@@ -63,6 +110,21 @@ impl fmt::Display for BackendType {
 }
 
 impl BackendType {
+    /// Every backend compiled into termail. Adding a variant here is enough for the
+    /// `FromStr` error message and the `--backend` help to pick it up.
+    pub fn all() -> &'static [BackendType] {
+        &[BackendType::GreenMail, BackendType::Gmail]
+    }
+
+    /// Comma separated list of the available backend names, e.g. "greenmail, gmail".
+    pub fn names() -> String {
+        BackendType::all()
+            .iter()
+            .map(|backend| backend.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
     /// Get a trait object for this backend, initialized with its configuration
     pub fn get_backend(&self, config: &BackendConfig, editor: &str) -> Box<dyn Backend> {
         match self {
@@ -72,3 +134,68 @@ impl BackendType {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Records the commands it is given, failing `SyncFromCloud` if `sync_fails`.
+    #[derive(Default)]
+    struct RecordingBackend {
+        commands: std::sync::Mutex<Vec<&'static str>>,
+        sync_fails: bool,
+    }
+
+    #[async_trait]
+    impl Backend for RecordingBackend {
+        fn needs_oauth(&self) -> bool {
+            false
+        }
+
+        fn capabilities(&self) -> BackendCapabilities {
+            BackendCapabilities {
+                supports_labels: false,
+                supports_sync: true,
+                supports_send: false,
+                supports_search: false,
+                supports_login: false,
+                supports_move: false,
+            }
+        }
+
+        async fn do_command(&self, cmd: Command, _plugin_manager: Option<&mut PluginManager>) -> Result<CommandResult, Error> {
+            self.commands.lock().unwrap().push(cmd.name());
+            match cmd {
+                Command::SyncFromCloud { .. } if self.sync_fails => Err(Error::Connection("offline".to_string())),
+                _ => Ok(CommandResult::Empty),
+            }
+        }
+
+        fn requires_authentication(&self, _cmd: &Command) -> Option<bool> {
+            Some(false)
+        }
+    }
+
+    #[tokio::test]
+    async fn refresh_syncs_before_viewing() {
+        let backend = RecordingBackend::default();
+        assert!(matches!(refresh(&backend, 5, true, None).await, Ok(CommandResult::Empty)));
+        assert_eq!(*backend.commands.lock().unwrap(), vec!["sync-from-cloud", "view-mailbox"]);
+
+        // A failed sync does not show stale mail as if it were fresh
+        let offline = RecordingBackend { sync_fails: true, ..RecordingBackend::default() };
+        assert!(matches!(refresh(&offline, 5, true, None).await, Err(Error::Connection(_))));
+        assert_eq!(*offline.commands.lock().unwrap(), vec!["sync-from-cloud"]);
+    }
+
+    #[test]
+    fn every_backend_round_trips_through_its_name() {
+        for backend in BackendType::all() {
+            assert_eq!(backend.to_string().parse::<BackendType>(), Ok(*backend));
+            assert_eq!(backend.to_string().to_uppercase().parse::<BackendType>(), Ok(*backend));
+            assert!(BackendType::names().contains(&backend.to_string()));
+        }
+        let error = "outlook".parse::<BackendType>().unwrap_err();
+        assert!(error.contains(&BackendType::names()), "{}", error);
+    }
+}
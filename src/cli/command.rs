@@ -1,7 +1,9 @@
 // This file defines the types for email messages and command results.
 
 use clap::Subcommand;
+use std::path::PathBuf;
 use crate::core::{email::EmailMessage, label::Label};
+use crate::plugins::events::Hook;
 
 /// We implement CLI commands via clap subcommands and validate backend compatibility at runtime.
 #[derive(Subcommand, Debug, Clone)]
@@ -24,9 +26,31 @@ pub enum Command {
         subject:  Option<String>,
         #[arg(short, long)]
         body: Option<String>,
+        /// Comma separated Cc recipients
+        #[arg(long)]
+        cc: Option<String>,
+        /// Comma separated Bcc recipients
+        #[arg(long)]
+        bcc: Option<String>,
+        /// Email (maildir) id this email replies to, so it is threaded under it
+        #[arg(long)]
+        reply_to: Option<String>,
     },
 
-    SyncFromCloud,
+    /// Compose an email from a mailto: URI (e.g. "mailto:a@b.com?subject=Hi&body=Yo").
+    /// In TUI mode this opens the composer prefilled instead of sending.
+    Compose {
+        uri: String,
+    },
+
+    SyncFromCloud {
+        /// Skip the confirmation before a first (full) sync
+        #[arg(long)]
+        yes: bool,
+    },
+
+    /// Show whether the next sync downloads everything and how many messages that is
+    SyncPlan,
 
     /// View emails from local maildir
     ViewMailbox {
@@ -38,27 +62,238 @@ pub enum Command {
         label: Option<String>,
     },
 
+    /// List the plugins found in the plugin directory and whether they will load
+    ListPlugins,
+
+    /// Print the termail version, backends, plugins and resolved paths
+    Version,
+
+    /// Sync from the cloud, then view the newest emails from the local maildir
+    Refresh {
+        /// Number of emails to view (default: 1)
+        #[arg(default_value_t = 1)]
+        count: usize,
+        /// Skip the confirmation before a first (full) sync
+        #[arg(long)]
+        yes: bool,
+    },
+
     /// Load a single email (with attachments) by id from the local maildir
     LoadEmail {
         /// Email (maildir) id to load
         email_id: String,
     },
 
+    /// Mark an email as read locally and, when online, on the server
+    MarkRead {
+        /// Email (maildir) id to mark as read
+        email_id: String,
+    },
+
+    /// Delete an email. Depending on `delete_behavior` this moves it to Trash
+    /// or removes it permanently.
+    Delete {
+        /// Email (maildir) id to delete
+        email_id: String,
+    },
+
+    /// Mark an email as spam (moves it out of the inbox)
+    MarkSpam {
+        /// Email (maildir) id to mark as spam
+        email_id: String,
+    },
+
+    /// Mark an email as not spam (moves it back to the inbox)
+    NotSpam {
+        /// Email (maildir) id to mark as not spam
+        email_id: String,
+    },
+
+    /// Add and/or remove labels on an email
+    ModifyLabels {
+        /// Email (maildir) id to modify
+        email_id: String,
+        /// Labels to add (repeatable)
+        #[arg(long)]
+        add: Vec<String>,
+        /// Labels to remove (repeatable)
+        #[arg(long)]
+        remove: Vec<String>,
+    },
+
+    /// Move an email to another folder. Like Gmail's "Move to", it leaves the inbox
+    /// and `from`; on IMAP servers it is moved to the folder's mailbox.
+    Move {
+        /// Email (maildir) id to move
+        email_id: String,
+        /// Label id of the folder to move to
+        #[arg(long)]
+        to: String,
+        /// Label id of the folder the email is in, if not the inbox
+        #[arg(long)]
+        from: Option<String>,
+    },
+
+    /// Add and/or remove labels on many emails at once. Gmail applies the change in
+    /// batches of up to 1000 emails per request.
+    BatchModify {
+        /// Email (maildir) ids to modify
+        #[arg(required = true)]
+        email_ids: Vec<String>,
+        /// Labels to add (repeatable)
+        #[arg(long)]
+        add: Vec<String>,
+        /// Labels to remove (repeatable)
+        #[arg(long)]
+        remove: Vec<String>,
+    },
+
+    /// Rebuild the local metadata cache and search index from the maildir files
+    Reindex,
+
+    /// Remove orphaned rows from the sync state database and VACUUM it
+    Compact,
+
+    /// Check the sync state database for rows whose message no longer exists
+    CheckDb {
+        /// Delete the orphaned rows that were found
+        #[arg(long)]
+        repair: bool,
+    },
+
+    /// Show how many local emails carry a label and the subject of the newest one
+    LabelSummary {
+        /// Label name, e.g. INBOX
+        label: String,
+    },
+
+    /// Search the local maildir by subject, sender and body
+    Search {
+        /// Search query (FTS5 syntax, e.g. "exact phrase" or prefix*)
+        query: String,
+        /// Maximum number of results (default: 20)
+        #[arg(long, default_value_t = 20)]
+        count: usize,
+    },
+
+    /// Print the MIME part tree of an email (useful for debugging rendering)
+    MimeTree {
+        /// Email (maildir) id to inspect
+        email_id: String,
+    },
+
+    /// Export a single email from the local maildir as a .eml file
+    Export {
+        /// Email (maildir) id to export
+        email_id: String,
+        /// Path of the .eml file to write
+        out_path: PathBuf,
+        /// Overwrite `out_path` if it already exists
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Import a .eml file or an .mbox file into the local maildir
+    Import {
+        /// Path to a .eml or .mbox file
+        path: PathBuf,
+    },
+
+    /// Show the authenticated account (email address, message count, history id)
+    Whoami,
+
+    /// Sign in again from scratch, ignoring the cached OAuth2 token (e.g. when it is
+    /// corrupt or the scopes changed), and show the account signed in to
+    Login,
+
+    /// Fetch one message straight from the server by its Gmail id, bypassing the maildir
+    FetchMessage {
+        /// Gmail message id
+        id: String,
+    },
+
+    /// Upload local-only maildir messages (imported or synced over IMAP) to Gmail
+    Upload {
+        /// Label to file them under, by name or id (default: INBOX)
+        #[arg(long)]
+        label: Option<String>,
+    },
+
+    /// Run the enabled plugins for a hook on some text and print what they return
+    TestPlugins {
+        /// Hook to run: before_send, after_send, before_receive or after_receive
+        hook: Hook,
+        /// Message body handed to the plugins
+        content: String,
+    },
+
     /// Null command (used for testing plugins))
     Null
 }
 
+impl Command {
+    /// The kebab-case subcommand name, used e.g. as the `feature` of `Error::Unimplemented`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::FetchInbox { .. } => "fetch-inbox",
+            Command::ListLabels => "list-labels",
+            Command::SendEmail { .. } => "send-email",
+            Command::Compose { .. } => "compose",
+            Command::SyncFromCloud { .. } => "sync-from-cloud",
+            Command::SyncPlan => "sync-plan",
+            Command::ViewMailbox { .. } => "view-mailbox",
+            Command::Refresh { .. } => "refresh",
+            Command::ListPlugins => "list-plugins",
+            Command::Version => "version",
+            Command::LoadEmail { .. } => "load-email",
+            Command::MarkRead { .. } => "mark-read",
+            Command::Delete { .. } => "delete",
+            Command::MarkSpam { .. } => "mark-spam",
+            Command::NotSpam { .. } => "not-spam",
+            Command::ModifyLabels { .. } => "modify-labels",
+            Command::Move { .. } => "move",
+            Command::BatchModify { .. } => "batch-modify",
+            Command::Reindex => "reindex",
+            Command::Compact => "compact",
+            Command::CheckDb { .. } => "check-db",
+            Command::LabelSummary { .. } => "label-summary",
+            Command::Search { .. } => "search",
+            Command::MimeTree { .. } => "mime-tree",
+            Command::Export { .. } => "export",
+            Command::Import { .. } => "import",
+            Command::Whoami => "whoami",
+            Command::Login => "login",
+            Command::FetchMessage { .. } => "fetch-message",
+            Command::Upload { .. } => "upload",
+            Command::TestPlugins { .. } => "test-plugins",
+            Command::Null => "null",
+        }
+    }
+}
+
 /// Result type for backend commands - can represent different types of outputs
 #[derive(Debug, Clone)]
 pub enum CommandResult {
     /// A single email message
-    Email(EmailMessage),
+    Email(Box<EmailMessage>),
     /// Multiple email messages
     Emails(Vec<EmailMessage>),
     /// A success message
     Success(String),
     /// List Of Labels
     Labels(Vec<Label>),
+    /// Message count and newest subject of a label
+    LabelSummary {
+        label: String,
+        count: usize,
+        latest_subject: Option<String>,
+    },
+    /// What the next sync does. `total` is the number of messages a full sync
+    /// downloads, when the backend can tell.
+    SyncPlan {
+        full: bool,
+        total: Option<usize>,
+    },
     /// No content to return
     Empty,
 }
@@ -84,6 +319,16 @@ impl std::fmt::Display for CommandResult {
             }
             CommandResult::Success(msg) => write!(f, "{}", msg),
             CommandResult::Labels(labels) => write!(f, "{:?}", labels),
+            CommandResult::LabelSummary { label, count, latest_subject } => {
+                write!(f, "{}: {} emails", label, count)?;
+                match latest_subject {
+                    Some(subject) => write!(f, ", latest: {}", subject),
+                    None => Ok(()),
+                }
+            }
+            CommandResult::SyncPlan { full: false, .. } => write!(f, "Incremental sync"),
+            CommandResult::SyncPlan { full: true, total: Some(total) } => write!(f, "Full sync of {} messages", total),
+            CommandResult::SyncPlan { full: true, total: None } => write!(f, "Full sync"),
             CommandResult::Empty => write!(f, "NO CONTENT"),
         }
     }
@@ -0,0 +1,60 @@
+// This file builds the report printed by the `version` command.
+
+use crate::backends::BackendType;
+use crate::config::Config;
+use std::path::Path;
+
+/// Everything worth pasting into a bug report: the termail version, the compiled
+/// backends, the enabled plugins, the resolved paths and the image protocol.
+pub fn version_report(config: &Config, config_path: Option<&Path>) -> String {
+    let backend = config.termail.default_backend;
+    let maildir = match config.maildir_path(&backend) {
+        Ok(Some(path)) => path,
+        Ok(None) => "(not configured)".to_string(),
+        Err(e) => format!("({})", e),
+    };
+    let plugins = if config.termail.plugins.is_empty() {
+        "(none)".to_string()
+    } else {
+        config.termail.plugins.join(", ")
+    };
+    let image_protocol = config.termail.image_protocol
+        .as_ref()
+        .map(|protocol| format!("{:?}", protocol).to_lowercase())
+        .unwrap_or_else(|| "(disabled)".to_string());
+
+    let lines = [
+        format!("termail {}", env!("CARGO_PKG_VERSION")),
+        format!("backends:       {}", BackendType::names()),
+        format!("active backend: {}", backend),
+        format!("plugins:        {}", plugins),
+        format!("config file:    {}", config_path.map_or("(not found)".to_string(), |path| path.display().to_string())),
+        format!("log file:       {}", config.get_log_path().display()),
+        format!("maildir:        {}", maildir),
+        format!("image protocol: {}", image_protocol),
+    ];
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_report_lists_every_backend() {
+        let config = Config::parse(
+            "[termail]\ncli = false\ndefault_backend = \"greenmail\"\nemail_fetch_count = 10\neditor = \"vim\"\nplugins = []\n\n\
+             [backends.greenmail]\nhost = \"127.0.0.1\"\nport = 1993\nssl = true\nmaildir_path = \"/tmp/termail-maildir\"\n",
+        ).unwrap();
+        let report = version_report(&config, None);
+
+        let backends = report.lines().find(|line| line.starts_with("backends:")).unwrap();
+        for backend in BackendType::all() {
+            assert!(backends.contains(&backend.to_string()), "{}", backends);
+        }
+        assert!(report.starts_with(&format!("termail {}\n", env!("CARGO_PKG_VERSION"))));
+        assert!(report.contains("active backend: greenmail\n"));
+        assert!(report.contains("config file:    (not found)\n"));
+        assert!(report.contains("maildir:        /tmp/termail-maildir\n"));
+    }
+}
@@ -1 +1,3 @@
-pub mod command;
\ No newline at end of file
+pub mod command;
+pub mod info;
+pub mod prompt;
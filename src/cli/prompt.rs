@@ -0,0 +1,39 @@
+// This file asks the user yes/no questions on the terminal in CLI mode.
+
+use std::io::{self, BufRead, Write};
+
+/// Writes `question` followed by ` [y/N] ` to `output` and reads one line from
+/// `input`. Only "y" or "yes" (in any case) confirm; anything else, including
+/// end of input, declines.
+pub fn confirm(question: &str, input: &mut impl BufRead, output: &mut impl Write) -> io::Result<bool> {
+    write!(output, "{} [y/N] ", question)?;
+    output.flush()?;
+
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn answer(input: &str) -> bool {
+        let mut output = Vec::new();
+        let confirmed = confirm("Continue?", &mut Cursor::new(input), &mut output).unwrap();
+        assert_eq!(output, b"Continue? [y/N] ");
+        confirmed
+    }
+
+    #[test]
+    fn only_yes_confirms() {
+        assert!(answer("y\n"));
+        assert!(answer(" YES \n"));
+        assert!(!answer("n\n"));
+        assert!(!answer("\n"));
+        assert!(!answer("yep\n"));
+        // End of input, e.g. stdin closed
+        assert!(!answer(""));
+    }
+}
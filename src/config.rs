@@ -7,10 +7,24 @@ use crate::backends::BackendType;
 use crate::auth::{Credentials};
 use crate::backends::Backend;
 use crate::Args;
+use crate::net::{ProxyConfig, parse_proxy_url};
 
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::fs;
+use std::time::Duration;
+
+/// Default for `snippet_length`.
+pub const DEFAULT_SNIPPET_LENGTH: usize = 100;
+
+/// Default for `max_label_chips`.
+pub const DEFAULT_MAX_LABEL_CHIPS: usize = 3;
+
+/// Default for `log_max_files`: a week of daily logs.
+pub const DEFAULT_LOG_MAX_FILES: usize = 7;
+
+/// Default for `max_render_bytes`: 256 KiB.
+pub const DEFAULT_MAX_RENDER_BYTES: usize = 256 * 1024;
 
 #[derive(Debug, Clone, serde::Deserialize)]
 pub enum ImageProtocol {
@@ -24,19 +38,185 @@ pub enum ImageProtocol {
     Sixel
 }
 
+/// How replies quote the original message.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(default)]
+pub struct QuoteConfig {
+    /// Prefix put in front of every quoted line.
+    pub quote_prefix: String,
+    /// Re-wrap quoted lines so that they are at most this many columns wide
+    /// (prefix included). `None` disables wrapping.
+    pub wrap_quoted_at: Option<usize>,
+    /// Line put above the quote. `{date}` and `{sender}` are substituted.
+    pub attribution: String,
+}
+
+impl Default for QuoteConfig {
+    fn default() -> Self {
+        Self {
+            quote_prefix: "> ".to_string(),
+            wrap_quoted_at: Some(72),
+            attribution: "On {date}, {sender} wrote:".to_string(),
+        }
+    }
+}
+
+/// Bounds on what a single plugin call may use, so a misbehaving plugin cannot
+/// hang termail.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(default)]
+pub struct PluginLimits {
+    /// Fuel (roughly, WASM instructions) a plugin may burn per call before it is cut off.
+    pub fuel: u64,
+    /// Largest linear memory a plugin instance may grow to, in bytes.
+    pub max_memory_bytes: usize,
+    /// Largest number of elements a plugin table may grow to.
+    pub max_table_elements: usize,
+}
+
+impl Default for PluginLimits {
+    fn default() -> Self {
+        Self {
+            fuel: 1_000_000_000,
+            max_memory_bytes: 64 * 1024 * 1024,
+            max_table_elements: 10_000,
+        }
+    }
+}
+
+/// What deleting an email does.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeleteBehavior {
+    /// Move the email to Trash. The local copy is kept and labelled `TRASH`.
+    #[default]
+    Trash,
+    /// Permanently delete the email, on the server and locally.
+    Delete,
+}
+
+/// How often the log file is rotated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    /// One log file that grows forever.
+    Never,
+    Hourly,
+    /// A new file every day, named after the date, e.g. `termail.log.2025-01-31`.
+    #[default]
+    Daily,
+}
+
+/// How the sender is shown in the inbox.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SenderDisplay {
+    /// The sender's name, or the address when there is no name.
+    #[default]
+    NameThenEmail,
+    /// Always the address.
+    EmailOnly,
+    /// Always a name. Senders without one are shown by the local part of their address.
+    NameOnly,
+}
+
+/// What an inbox column shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InboxColumnKind {
+    From,
+    /// The subject, followed by the body snippet when there is room.
+    Subject,
+    Date,
+    /// Status markers: `●` for unread, `📎` for attachments.
+    Flags,
+    /// The labels the email carries.
+    Labels,
+}
+
+/// One inbox column. Columns without a `width` share the space the others leave.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+pub struct InboxColumn {
+    pub name: InboxColumnKind,
+    pub width: Option<usize>,
+}
+
+/// The inbox layout when `columns` is not configured: sender, subject, date.
+pub fn default_inbox_columns() -> Vec<InboxColumn> {
+    vec![
+        InboxColumn { name: InboxColumnKind::From, width: Some(20) },
+        InboxColumn { name: InboxColumnKind::Subject, width: None },
+        InboxColumn { name: InboxColumnKind::Date, width: Some(11) },
+    ]
+}
+
 #[derive(Debug, Clone, serde::Deserialize)]
 pub struct TermailConfig {
     pub cli: bool,
     pub default_backend: BackendType,
     pub email_fetch_count: usize,
+    /// Number of emails loaded when the inbox is first shown.
+    /// Defaults to `email_fetch_count`.
+    pub initial_fetch_count: Option<usize>,
+    /// Upper bound on how many emails "load more" can grow the inbox to.
+    /// Defaults to `email_fetch_count`.
+    pub max_view_count: Option<usize>,
     pub editor: String,
     pub plugins: Vec<String>,
+    /// Directory to load plugins from when `~/.config/termail/plugins` does not
+    /// exist (supports ~/ expansion).
+    pub plugin_dir: Option<String>,
     /// The image protocol to use for displaying images.
     /// If not set, the application will not render any images.
     pub image_protocol: Option<ImageProtocol>,
+    /// Seconds an unread email must stay open before it is marked read. `0` marks it
+    /// read immediately; a negative value or leaving this out disables auto mark-read.
+    pub mark_read_delay_secs: Option<i64>,
+    /// Ask before quitting while a sync or send is still running. Defaults to true.
+    pub confirm_quit: Option<bool>,
+    /// Sync from the cloud before the mailbox is first shown, in the TUI and for
+    /// `view-mailbox` in the CLI. Without a connection the local mail is shown.
+    #[serde(default)]
+    pub auto_sync_on_start: bool,
+    /// Folder (label) the TUI opens on. Defaults to INBOX.
+    pub default_folder: Option<String>,
+    /// Bodies larger than this many bytes are cut short in the message view to keep
+    /// the TUI responsive (`T` shows the rest). `0` never cuts. Defaults to
+    /// `DEFAULT_MAX_RENDER_BYTES`.
+    pub max_render_bytes: Option<usize>,
+    /// Characters of body preview shown after the subject in the inbox, at most
+    /// `SNIPPET_MAX_LENGTH`. `0` hides the preview. Defaults to `DEFAULT_SNIPPET_LENGTH`.
+    pub snippet_length: Option<usize>,
+    /// Colored label chips shown before the subject in the inbox, at most. `0`
+    /// hides them. Defaults to `DEFAULT_MAX_LABEL_CHIPS`.
+    pub max_label_chips: Option<usize>,
+    /// How senders are shown in the inbox.
+    #[serde(default)]
+    pub sender_display: SenderDisplay,
+    /// Inbox columns, left to right. Defaults to `default_inbox_columns`.
+    #[serde(default = "default_inbox_columns")]
+    pub columns: Vec<InboxColumn>,
+    /// Reply quoting style.
+    #[serde(default)]
+    pub quote: QuoteConfig,
+    /// Execution limits for plugins.
+    #[serde(default)]
+    pub plugin_limits: PluginLimits,
+    /// Names shown in the folder pane instead of a label's own, keyed by label id
+    /// or name. Overrides the built-in names of Gmail's system labels.
+    #[serde(default)]
+    pub label_names: HashMap<String, String>,
     /// Optional custom log file path (supports ~/ expansion).
     /// If not specified, defaults to ~/.local/state/termail/termail.log
     pub log_file: Option<String>,
+    /// How often the log file is rotated. Defaults to daily, which writes to
+    /// `termail.log.<date>` files; a `termail.log` from before rotation existed is
+    /// left alone. Set `never` to keep writing to `termail.log` itself.
+    #[serde(default)]
+    pub log_rotation: LogRotation,
+    /// Rotated log files to keep; older ones are deleted. Defaults to
+    /// `DEFAULT_LOG_MAX_FILES`.
+    pub log_max_files: Option<usize>,
 }
 
 #[derive(Debug, Clone, serde::Deserialize)]
@@ -49,7 +229,66 @@ pub struct BackendConfig {
     // The labels to filter out from the list of labels
     // The labels are case-sensitive.
     pub filter_labels: Option<Vec<String>>,
-    pub maildir_path: String
+    /// When set, only these labels are listed as folders (`filter_labels` still
+    /// applies on top). Case-sensitive, like `filter_labels`.
+    pub show_only_labels: Option<Vec<String>>,
+    pub maildir_path: String,
+    /// Name of the account, e.g. its email address. Keeps the account's token cache
+    /// apart from other accounts'. Defaults to the `auth_credentials` username, then
+    /// the backend name.
+    pub account: Option<String>,
+    /// Where to persist OAuth2 tokens (supports ~/ expansion). Defaults to
+    /// `~/.local/state/termail/tokens/<account>.json`.
+    pub token_cache_path: Option<String>,
+    /// Upper bound on how many messages a single `FetchInbox` may request from the
+    /// server. Larger requests are clamped. Defaults to 100 when not set.
+    pub max_fetch: Option<usize>,
+    /// Whether deleting an email moves it to Trash (default) or removes it for good.
+    #[serde(default)]
+    pub delete_behavior: DeleteBehavior,
+    /// Seconds to wait for the IMAP/SMTP server to connect or answer before giving
+    /// up. Also bounds connecting to Gmail or the proxy. Defaults to 30; `0` waits forever.
+    pub connect_timeout_secs: Option<u64>,
+    /// HTTP or SOCKS5 proxy for the Gmail API, IMAP and SMTP, e.g. `socks5://127.0.0.1:1080`.
+    pub proxy_url: Option<String>,
+    /// Whether sent messages are appended to the `Sent` mailbox over IMAP. Gmail
+    /// records them itself. Defaults to true.
+    pub record_sent: Option<bool>,
+}
+
+/// Used when `connect_timeout_secs` is not set.
+const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 30;
+
+impl BackendConfig {
+    /// The timeout for connecting to and reading from the server, or `None` to wait forever.
+    pub fn connect_timeout(&self) -> Option<Duration> {
+        match self.connect_timeout_secs.unwrap_or(DEFAULT_CONNECT_TIMEOUT_SECS) {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        }
+    }
+
+    /// The account name, see `account`. `backend` is the fallback.
+    pub fn account_name(&self, backend: &str) -> String {
+        self.account.clone()
+            .or_else(|| self.auth_credentials.as_ref().map(|credentials| credentials.username.clone()))
+            .unwrap_or_else(|| backend.to_string())
+    }
+
+    /// Where this account's OAuth2 tokens live, see `resolve_token_cache_path`.
+    pub fn token_cache_path(&self, backend: &str) -> PathBuf {
+        resolve_token_cache_path(self.token_cache_path.as_deref(), &self.account_name(backend))
+    }
+
+    /// Whether to keep a copy of sent messages, see `record_sent`.
+    pub fn record_sent(&self) -> bool {
+        self.record_sent.unwrap_or(true)
+    }
+
+    /// The parsed `proxy_url`, if one is configured.
+    pub fn proxy(&self) -> Result<Option<ProxyConfig>, Error> {
+        self.proxy_url.as_deref().map(parse_proxy_url).transpose()
+    }
 }
 
 #[derive(Debug, Clone, serde::Deserialize)]
@@ -69,9 +308,35 @@ fn expand_tilde(path: &str) -> PathBuf {
     PathBuf::from(path)
 }
 
-/// Returns the default log file path following XDG Base Directory spec
+/// Whether two configured maildir paths point at the same directory, ignoring
+/// trailing slashes and `~/` vs. an expanded home directory.
+fn same_maildir(a: &str, b: &str) -> bool {
+    let normalize = |path: &str| expand_tilde(path.trim().trim_end_matches('/'));
+    normalize(a) == normalize(b)
+}
+
+/// Maildir root for `account`: the configured path itself, or `<path>/<account>`
+/// when the path is shared with another account.
+pub fn account_maildir_path(configured: &str, account: &str, shared: bool) -> String {
+    if !shared {
+        return configured.to_string();
+    }
+    Path::new(configured.trim_end_matches('/'))
+        .join(account)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Whether `root` is a maildir from before shared paths were split per account:
+/// it has a sync database of its own and the account's directory `namespaced`
+/// does not exist yet.
+pub fn is_legacy_shared_maildir(root: &Path, namespaced: &Path) -> bool {
+    root.join("sync_state.db").is_file() && !namespaced.exists()
+}
+
+/// Returns termail's state directory following XDG Base Directory spec
 /// See: https://specifications.freedesktop.org/basedir/latest/
-fn get_default_log_path() -> PathBuf {
+fn get_state_dir() -> PathBuf {
     dirs::state_dir()
         .unwrap_or_else(|| {
             dirs::home_dir()
@@ -79,7 +344,78 @@ fn get_default_log_path() -> PathBuf {
                 .unwrap_or_else(|| PathBuf::from("."))
         })
         .join("termail")
-        .join("termail.log")
+}
+
+/// Returns the default log file path
+fn get_default_log_path() -> PathBuf {
+    get_state_dir().join("termail.log")
+}
+
+/// Resolves where the OAuth2 token cache of an account lives.
+///
+/// An explicit `token_cache_path` wins. Otherwise each account gets its own file
+/// under the state dir so that multiple accounts don't overwrite each other's tokens.
+pub fn resolve_token_cache_path(configured: Option<&str>, account: &str) -> PathBuf {
+    match configured {
+        Some(path) => expand_tilde(path),
+        None => get_state_dir().join("tokens").join(format!("{}.json", account.replace(['/', '\\'], "_"))),
+    }
+}
+
+/// Where tokens were cached before: the working directory, then the state dir file
+/// named after the backend rather than the account.
+pub fn legacy_token_cache_paths(backend: &str) -> Vec<PathBuf> {
+    vec![PathBuf::from("tokencache.json"), resolve_token_cache_path(None, backend)]
+}
+
+/// Moves the first existing cache of `legacy` to `target`, unless `target` already
+/// exists, so upgrading does not sign the user out. Returns the path moved from.
+pub fn migrate_token_cache(legacy: &[PathBuf], target: &Path) -> Result<Option<PathBuf>, Error> {
+    if target.exists() {
+        return Ok(None);
+    }
+    let Some(source) = legacy.iter().find(|path| path.as_path() != target && path.is_file()) else {
+        return Ok(None);
+    };
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| Error::Config(format!("Failed to create token cache directory {:?}: {}", parent, e)))?;
+    }
+    // A rename fails across filesystems, e.g. from the working directory
+    fs::rename(source, target)
+        .or_else(|_| fs::copy(source, target).and_then(|_| fs::remove_file(source)))
+        .map_err(|e| Error::Config(format!("Failed to move token cache {:?} to {:?}: {}", source, target, e)))?;
+    Ok(Some(source.clone()))
+}
+
+/// The locations searched for `config.toml` when no path is given, in order.
+pub fn config_search_paths() -> Vec<PathBuf> {
+    let mut paths = vec![PathBuf::from("config.toml")];
+    if let Some(dir) = dirs::config_dir().or_else(|| dirs::home_dir().map(|h| h.join(".config"))) {
+        paths.push(dir.join("termail/config.toml"));
+    }
+    paths.push(PathBuf::from("/etc/termail/config.toml"));
+    paths
+}
+
+/// Picks the config file to use: `explicit` if given (it must exist), otherwise the
+/// first of `candidates` that exists. The error lists every location that was tried.
+pub fn find_config_file(explicit: Option<PathBuf>, candidates: &[PathBuf]) -> Result<PathBuf, Error> {
+    if let Some(path) = explicit {
+        return if path.is_file() {
+            Ok(path)
+        } else {
+            Err(Error::Config(format!("Config file {} does not exist", path.display())))
+        };
+    }
+
+    candidates.iter()
+        .find(|path| path.is_file())
+        .cloned()
+        .ok_or_else(|| {
+            let tried: Vec<String> = candidates.iter().map(|path| path.display().to_string()).collect();
+            Error::Config(format!("No config file found. Looked in: {}", tried.join(", ")))
+        })
 }
 
 impl Config {
@@ -88,44 +424,112 @@ impl Config {
     /// 2. The current directory
     /// 3. `~/.config/termail/config.toml`
     /// 4. `/etc/termail/config.toml`
-    pub fn load(config_file_path: Option<PathBuf>) -> Result<Self, Error> {
-        let config_file = match config_file_path {
-            Some(p) => fs::read_to_string(p)
-                .map_err(|e| Error::Config(e.to_string())),
-            None => {
-                let config_dir = dirs::config_dir()
-                    .map(|d| d.join("termail/config.toml"))
-                    .unwrap_or_else(|| PathBuf::from("~/.config/termail/config.toml"));
-
-                std::fs::read_to_string("config.toml")
-                    .or_else(|_| fs::read_to_string(config_dir))
-                    .or_else(|_| fs::read_to_string("/etc/termail/config.toml"))
-                    .map_err(|e| Error::Other(e.to_string()))
-            },
-        };
+    ///
+    /// The command line (`args`) is merged in before validating, so a bad value it
+    /// overrides does not matter and the merged result is what gets checked.
+    pub fn load(args: &Args) -> Result<Self, Error> {
+        let config_file = Self::read_config_file(args.config_file.clone())?;
+        let mut config = Self::parse(&config_file)?;
+        config.merge(args);
 
-        let config: Config = match config_file {
-            Ok(c) => toml::from_str(c.as_str()).map_err(|e| Error::Config(e.to_string()))?,
-            Err(e) => return Err(e),
-        };
+        let problems = config.validate();
+        if !problems.is_empty() {
+            return Err(Error::Config(problems.join("; ")));
+        }
+        Ok(config)
+    }
+
+    /// Reads the raw config file contents following the precedence documented on `load`.
+    fn read_config_file(config_file_path: Option<PathBuf>) -> Result<String, Error> {
+        let path = Self::resolve_path(config_file_path)?;
+        fs::read_to_string(&path)
+            .map_err(|e| Error::Config(format!("Failed to read config file {}: {}", path.display(), e)))
+    }
+
+    /// Returns the config file `load` would read, following the precedence documented there.
+    pub fn resolve_path(config_file_path: Option<PathBuf>) -> Result<PathBuf, Error> {
+        find_config_file(config_file_path, &config_search_paths())
+    }
+
+    /// Parses the TOML contents of a config file without validating it.
+    pub fn parse(contents: &str) -> Result<Self, Error> {
+        toml::from_str(contents).map_err(|e| Error::Config(e.to_string()))
+    }
+
+    /// Checks the semantic correctness of a parsed config and returns a
+    /// human-readable description of every problem found. An empty list means valid.
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if self.termail.editor.trim().is_empty() {
+            problems.push("[termail] editor must not be empty".to_string());
+        }
 
-        // Validate backend configurations
-        for (be_type, be_config) in config.backends.clone().into_iter() {
+        if !self.backends.contains_key(&self.termail.default_backend) {
+            problems.push(format!(
+                "[termail] default_backend is \"{}\" but there is no [backends.{}] section",
+                self.termail.default_backend, self.termail.default_backend
+            ));
+        }
+
+        for (be_type, be_config) in self.backends.iter() {
+            if be_config.maildir_path.trim().is_empty() {
+                problems.push(format!("[backends.{}] maildir_path must not be empty", be_type));
+            }
+            if be_config.port == 0 {
+                problems.push(format!("[backends.{}] port must not be 0", be_type));
+            }
+            if let Err(e) = be_config.proxy() {
+                problems.push(format!("[backends.{}] {}", be_type, e));
+            }
+            if be_config.show_only_labels.as_ref().is_some_and(|labels| labels.is_empty()) {
+                problems.push(format!("[backends.{}] show_only_labels must not be empty, remove it to show every label", be_type));
+            }
             match be_type {
                 BackendType::GreenMail => {
-                    if be_config.oauth2_client_secret_file != None {
-                        Error::Config("Greenmail does not support OAuth2. Remove it from your config.".to_string());
+                    if be_config.oauth2_client_secret_file.is_some() {
+                        problems.push("[backends.greenmail] Greenmail does not support OAuth2. Remove oauth2_client_secret_file from your config.".to_string());
+                    }
+                    if be_config.auth_credentials.is_none() {
+                        problems.push("[backends.greenmail] Greenmail requires auth_credentials".to_string());
                     }
                 },
                 BackendType::Gmail => {
-                    if be_config.oauth2_client_secret_file == None {
-                        Error::Config("Gmail requires OAuth2.".to_string());
+                    if be_config.oauth2_client_secret_file.is_none() {
+                        problems.push("[backends.gmail] Gmail requires OAuth2. Set oauth2_client_secret_file.".to_string());
                     }
                 },
             }
         }
-        Ok(config)
 
+        problems
+    }
+
+    /// Loads and fully validates a config without connecting to any server.
+    ///
+    /// Unlike `load`, this never fails: read and parse errors are reported as problems
+    /// too. On top of `validate`, it also checks that referenced files exist.
+    pub fn check(config_file_path: Option<PathBuf>) -> Vec<String> {
+        let contents = match Self::read_config_file(config_file_path) {
+            Ok(contents) => contents,
+            Err(e) => return vec![format!("Could not read config file: {}", e)],
+        };
+        let config = match Self::parse(&contents) {
+            Ok(config) => config,
+            Err(e) => return vec![format!("Could not parse config file: {}", e)],
+        };
+
+        let mut problems = config.validate();
+        for (be_type, be_config) in config.backends.iter() {
+            if let Some(secret_file) = &be_config.oauth2_client_secret_file {
+                if !expand_tilde(secret_file).exists() {
+                    problems.push(format!(
+                        "[backends.{}] oauth2_client_secret_file {:?} does not exist", be_type, secret_file
+                    ));
+                }
+            }
+        }
+        problems
     }
 
     pub fn merge(&mut self, args: &Args) -> &mut Self {
@@ -144,19 +548,109 @@ impl Config {
         self
     }
 
-    pub fn get_backend(&self) -> Box<dyn Backend> {
+    pub fn get_backend(&self) -> Result<Box<dyn Backend>, Error> {
         let selected_backend = self.termail.default_backend;
 
-        let backend_config = self.backends.get(&selected_backend)
-            .expect(&format!("No configuration found for backend '{}'", selected_backend));
+        let mut backend_config = self.backends.get(&selected_backend)
+            .unwrap_or_else(|| panic!("No configuration found for backend '{}'", selected_backend))
+            .clone();
+        if let Some(maildir_path) = self.maildir_path(&selected_backend)? {
+            backend_config.maildir_path = maildir_path;
+        }
 
-        selected_backend.get_backend(backend_config, &self.termail.editor)
+        Ok(selected_backend.get_backend(&backend_config, &self.termail.editor))
+    }
+
+    /// The maildir root the given account actually uses. Accounts that share a
+    /// `maildir_path` with another configured account are moved into a subdirectory
+    /// named after their backend (each backend is one account), so neither the
+    /// messages nor the sync database (which lives inside the root) collide.
+    ///
+    /// A shared path that already holds mail from before the split is an error: the
+    /// mail of both accounts is mixed in it, so it can't be handed to either one.
+    pub fn maildir_path(&self, backend_type: &BackendType) -> Result<Option<String>, Error> {
+        let Some(backend_config) = self.backends.get(backend_type) else {
+            return Ok(None);
+        };
+        let shared = self.backends.iter().any(|(other_type, other_config)| {
+            other_type != backend_type
+                && same_maildir(&other_config.maildir_path, &backend_config.maildir_path)
+        });
+        let path = account_maildir_path(&backend_config.maildir_path, &backend_type.to_string(), shared);
+        if shared && is_legacy_shared_maildir(&expand_tilde(&backend_config.maildir_path), &expand_tilde(&path)) {
+            return Err(Error::Config(format!(
+                "{} is shared by several accounts and already holds synced mail; move it to {} or give each account its own maildir_path",
+                backend_config.maildir_path, path
+            )));
+        }
+        Ok(Some(path))
     }
 
     pub fn get_backend_config(&self, backend_type: &BackendType) -> Option<&BackendConfig> {
         self.backends.get(backend_type)
     }
 
+    /// The folder the TUI starts on: `default_folder`, or INBOX when it is unset or blank.
+    pub fn initial_folder(&self) -> String {
+        self.termail.default_folder
+            .as_deref()
+            .map(str::trim)
+            .filter(|folder| !folder.is_empty())
+            .unwrap_or("INBOX")
+            .to_string()
+    }
+
+    /// Number of emails to fetch for the first render of the inbox
+    pub fn initial_fetch_count(&self) -> usize {
+        self.termail.initial_fetch_count.unwrap_or(self.termail.email_fetch_count)
+    }
+
+    /// Maximum number of emails the inbox may grow to via "load more"
+    pub fn max_view_count(&self) -> usize {
+        self.termail.max_view_count
+            .unwrap_or(self.termail.email_fetch_count)
+            .max(self.initial_fetch_count())
+    }
+
+    /// Message view render limit in bytes, or `None` to render bodies whole.
+    pub fn max_render_bytes(&self) -> Option<usize> {
+        match self.termail.max_render_bytes.unwrap_or(DEFAULT_MAX_RENDER_BYTES) {
+            0 => None,
+            limit => Some(limit),
+        }
+    }
+
+    /// Length of the inbox body preview, see `snippet_length`.
+    pub fn snippet_length(&self) -> usize {
+        self.termail.snippet_length.unwrap_or(DEFAULT_SNIPPET_LENGTH)
+    }
+
+    /// Most label chips per inbox row, see `max_label_chips`.
+    pub fn max_label_chips(&self) -> usize {
+        self.termail.max_label_chips.unwrap_or(DEFAULT_MAX_LABEL_CHIPS)
+    }
+
+    /// Whether quitting with tasks in flight asks first, see `confirm_quit`.
+    pub fn confirm_quit(&self) -> bool {
+        self.termail.confirm_quit.unwrap_or(true)
+    }
+
+    /// Auto mark-read delay in seconds, or `None` if auto mark-read is disabled
+    pub fn mark_read_delay_secs(&self) -> Option<u64> {
+        self.termail.mark_read_delay_secs
+            .and_then(|secs| u64::try_from(secs).ok())
+    }
+
+    /// The configured plugin directory (with tilde expansion), if any
+    pub fn plugin_dir(&self) -> Option<PathBuf> {
+        self.termail.plugin_dir.as_deref().map(expand_tilde)
+    }
+
+    /// Number of rotated log files kept, see `log_max_files`.
+    pub fn log_max_files(&self) -> usize {
+        self.termail.log_max_files.unwrap_or(DEFAULT_LOG_MAX_FILES)
+    }
+
     /// Returns the log file path from config (with tilde expansion) or the default path
     pub fn get_log_path(&self) -> PathBuf {
         match &self.termail.log_file {
@@ -164,4 +658,203 @@ impl Config {
             None => get_default_log_path(),
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shared_maildir_with_old_mail_is_detected() {
+        let root = tempfile::tempdir().unwrap();
+        let namespaced = root.path().join("gmail");
+        assert!(!is_legacy_shared_maildir(root.path(), &namespaced));
+
+        fs::write(root.path().join("sync_state.db"), b"").unwrap();
+        assert!(is_legacy_shared_maildir(root.path(), &namespaced));
+
+        fs::create_dir(&namespaced).unwrap();
+        assert!(!is_legacy_shared_maildir(root.path(), &namespaced));
+    }
+
+    /// Both backends configured with the same `maildir_path`.
+    fn shared_maildir_config(maildir_path: &Path) -> Config {
+        let backend = format!(
+            "host = \"127.0.0.1\"\nport = 1993\nssl = true\nmaildir_path = {:?}\n",
+            maildir_path.to_string_lossy()
+        );
+        Config::parse(&format!(
+            "{}\n[backends.gmail]\n{}\n[backends.greenmail]\n{}",
+            MINIMAL_CONFIG.split("[backends.").next().unwrap(), backend, backend
+        )).unwrap()
+    }
+
+    #[test]
+    fn accounts_sharing_a_maildir_get_their_own_roots_and_databases() {
+        let root = tempfile::tempdir().unwrap();
+        let config = shared_maildir_config(root.path());
+        let gmail = config.maildir_path(&BackendType::Gmail).unwrap().unwrap();
+        let greenmail = config.maildir_path(&BackendType::GreenMail).unwrap().unwrap();
+        assert_ne!(gmail, greenmail);
+
+        let databases: Vec<PathBuf> = [gmail, greenmail]
+            .into_iter()
+            .map(|path| {
+                crate::maildir::MaildirManager::new(path.clone()).unwrap();
+                Path::new(&path).join("sync_state.db")
+            })
+            .collect();
+        assert!(databases.iter().all(|db| db.is_file()));
+        assert_ne!(databases[0], databases[1]);
+        assert!(!root.path().join("sync_state.db").exists());
+    }
+
+    #[test]
+    fn shared_maildir_with_old_mail_is_refused() {
+        let root = tempfile::tempdir().unwrap();
+        fs::write(root.path().join("sync_state.db"), b"").unwrap();
+        let config = shared_maildir_config(root.path());
+        assert!(matches!(config.maildir_path(&BackendType::Gmail), Err(Error::Config(_))));
+        assert!(matches!(config.maildir_path(&BackendType::GreenMail), Err(Error::Config(_))));
+    }
+
+    const MINIMAL_CONFIG: &str = r#"
+        [termail]
+        cli = false
+        default_backend = "greenmail"
+        email_fetch_count = 10
+        editor = "vim"
+        plugins = []
+
+        [backends.greenmail]
+        host = "127.0.0.1"
+        port = 1993
+        ssl = true
+        maildir_path = "./Maildir"
+    "#;
+
+    #[test]
+    fn emails_are_only_marked_read_automatically_when_opted_in() {
+        let mut config = Config::parse(MINIMAL_CONFIG).unwrap();
+        assert_eq!(config.mark_read_delay_secs(), None);
+        config.termail.mark_read_delay_secs = Some(0);
+        assert_eq!(config.mark_read_delay_secs(), Some(0));
+        config.termail.mark_read_delay_secs = Some(3);
+        assert_eq!(config.mark_read_delay_secs(), Some(3));
+        config.termail.mark_read_delay_secs = Some(-1);
+        assert_eq!(config.mark_read_delay_secs(), None);
+    }
+
+    #[test]
+    fn a_week_of_rotated_logs_is_kept_by_default() {
+        let mut config = Config::parse(MINIMAL_CONFIG).unwrap();
+        assert_eq!(config.termail.log_rotation, LogRotation::Daily);
+        assert_eq!(config.log_max_files(), DEFAULT_LOG_MAX_FILES);
+        config.termail.log_max_files = Some(0);
+        assert_eq!(config.log_max_files(), 0);
+    }
+
+    #[test]
+    fn token_caches_are_kept_per_account() {
+        let work = resolve_token_cache_path(None, "me@work.example");
+        let home = resolve_token_cache_path(None, "me@home.example");
+        assert_ne!(work, home);
+        assert!(work.ends_with("tokens/me@work.example.json"));
+        assert_eq!(resolve_token_cache_path(Some("/tmp/tokens.json"), "me@work.example"), PathBuf::from("/tmp/tokens.json"));
+    }
+
+    #[test]
+    fn legacy_token_cache_is_moved_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let legacy = dir.path().join("tokencache.json");
+        let target = dir.path().join("tokens/me@example.com.json");
+        fs::write(&legacy, b"token").unwrap();
+
+        assert_eq!(migrate_token_cache(std::slice::from_ref(&legacy), &target).unwrap(), Some(legacy.clone()));
+        assert_eq!(fs::read(&target).unwrap(), b"token");
+        assert!(!legacy.exists());
+
+        // An existing cache is never overwritten
+        fs::write(&legacy, b"old").unwrap();
+        assert_eq!(migrate_token_cache(std::slice::from_ref(&legacy), &target).unwrap(), None);
+        assert_eq!(fs::read(&target).unwrap(), b"token");
+    }
+
+    #[test]
+    fn blank_default_folders_start_on_the_inbox() {
+        let mut config = Config::parse(MINIMAL_CONFIG).unwrap();
+        assert_eq!(config.initial_folder(), "INBOX");
+        config.termail.default_folder = Some("  ".to_string());
+        assert_eq!(config.initial_folder(), "INBOX");
+        config.termail.default_folder = Some(" Work ".to_string());
+        assert_eq!(config.initial_folder(), "Work");
+    }
+
+    #[test]
+    fn backends_without_a_section_are_reported() {
+        let config = Config::parse(&MINIMAL_CONFIG.replace("default_backend = \"greenmail\"", "default_backend = \"gmail\"")).unwrap();
+        let problems = config.validate();
+        assert!(problems.iter().any(|problem| problem.contains("there is no [backends.gmail] section")), "{:?}", problems);
+    }
+
+    #[test]
+    fn unknown_backends_and_missing_maildirs_fail_the_check() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        let check = |contents: &str| {
+            fs::write(&path, contents).unwrap();
+            Config::check(Some(path.clone()))
+        };
+
+        let problems = check(&MINIMAL_CONFIG.replace("default_backend = \"greenmail\"", "default_backend = \"outlook\""));
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("Could not parse config file"), "{:?}", problems);
+
+        let problems = check(&MINIMAL_CONFIG.replace("maildir_path = \"./Maildir\"", ""));
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].contains("maildir_path"), "{:?}", problems);
+
+        let problems = check(&MINIMAL_CONFIG.replace("\"./Maildir\"", "\" \""));
+        assert!(problems.contains(&"[backends.greenmail] maildir_path must not be empty".to_string()), "{:?}", problems);
+
+        assert!(matches!(check("").as_slice(), [problem] if problem.starts_with("Could not parse config file")));
+    }
+
+    #[test]
+    fn config_files_are_found_in_precedence_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let explicit = dir.path().join("explicit.toml");
+        let candidates = [
+            dir.path().join("cwd/config.toml"),
+            dir.path().join("xdg/termail/config.toml"),
+            dir.path().join("etc/termail/config.toml"),
+        ];
+        let create = |path: &Path| {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, MINIMAL_CONFIG).unwrap();
+        };
+
+        let error = find_config_file(None, &candidates).unwrap_err().to_string();
+        assert!(candidates.iter().all(|path| error.contains(&path.display().to_string())), "{}", error);
+        assert!(matches!(find_config_file(Some(explicit.clone()), &candidates), Err(Error::Config(_))));
+
+        create(&candidates[2]);
+        assert_eq!(find_config_file(None, &candidates).unwrap(), candidates[2]);
+        create(&candidates[1]);
+        assert_eq!(find_config_file(None, &candidates).unwrap(), candidates[1]);
+        create(&candidates[0]);
+        assert_eq!(find_config_file(None, &candidates).unwrap(), candidates[0]);
+        create(&explicit);
+        assert_eq!(find_config_file(Some(explicit.clone()), &candidates).unwrap(), explicit);
+
+        // A directory is not a config file
+        assert!(find_config_file(Some(dir.path().to_path_buf()), &candidates).is_err());
+    }
+
+    #[test]
+    fn the_working_directory_is_searched_first_and_etc_last() {
+        let paths = config_search_paths();
+        assert_eq!(paths.first(), Some(&PathBuf::from("config.toml")));
+        assert_eq!(paths.last(), Some(&PathBuf::from("/etc/termail/config.toml")));
+        assert!(paths.iter().any(|path| path.ends_with("termail/config.toml") && path.is_absolute() && !path.starts_with("/etc")));
+    }
+}
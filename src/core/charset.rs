@@ -0,0 +1,76 @@
+// This file turns message bodies and headers in whatever charset they declare into UTF-8 text.
+
+use encoding_rs::Encoding;
+
+/// The `charset` parameter of a `Content-Type` header value, e.g. `iso-8859-1`
+/// for `text/plain; charset="ISO-8859-1"`. Lowercased and unquoted.
+pub fn charset_from_content_type(content_type: &str) -> Option<String> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        if !name.trim().eq_ignore_ascii_case("charset") {
+            return None;
+        }
+        let value = value.trim().trim_matches('"').trim();
+        (!value.is_empty()).then(|| value.to_ascii_lowercase())
+    })
+}
+
+/// Decodes `bytes` as `charset`. Bodies without a charset, or with one
+/// `encoding_rs` does not know, are read as UTF-8; invalid sequences become
+/// U+FFFD instead of losing the whole body.
+pub fn decode_text(bytes: &[u8], charset: Option<&str>) -> String {
+    let encoding = charset
+        .and_then(|charset| Encoding::for_label(charset.as_bytes()))
+        .unwrap_or(encoding_rs::UTF_8);
+    let (text, _, had_errors) = encoding.decode(bytes);
+    if had_errors {
+        tracing::debug!("Body is not valid {}, undecodable bytes were replaced", encoding.name());
+    }
+    text.into_owned()
+}
+
+/// Decodes RFC 2047 encoded-words such as `=?UTF-8?B?w6lt?=` in a header value,
+/// the way `mailparse` does for messages read from the maildir. Values without
+/// encoded-words are returned unchanged.
+pub fn decode_header_value(value: &str) -> String {
+    if !value.contains("=?") {
+        return value.to_string();
+    }
+    match mailparse::parse_header(format!("X: {}", value).as_bytes()) {
+        Ok((header, _)) => header.get_value(),
+        Err(_) => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn charset_is_read_from_the_content_type() {
+        assert_eq!(charset_from_content_type(r#"text/plain; charset="ISO-8859-1""#).as_deref(), Some("iso-8859-1"));
+        assert_eq!(charset_from_content_type("text/html;format=flowed; CHARSET = windows-1252").as_deref(), Some("windows-1252"));
+        assert_eq!(charset_from_content_type("text/plain"), None);
+        assert_eq!(charset_from_content_type(r#"text/plain; charset="""#), None);
+    }
+
+    #[test]
+    fn bodies_are_decoded_from_their_charset() {
+        assert_eq!(decode_text(b"caf\xe9", Some("iso-8859-1")), "café");
+        assert_eq!(decode_text(b"\x93quoted\x94 \x80", Some("windows-1252")), "\u{201c}quoted\u{201d} €");
+        assert_eq!(decode_text("café".as_bytes(), None), "café");
+    }
+
+    #[test]
+    fn unknown_charsets_fall_back_to_utf8() {
+        assert_eq!(decode_text("café".as_bytes(), Some("x-made-up")), "café");
+        assert_eq!(decode_text(b"caf\xe9", Some("x-made-up")), "caf\u{fffd}");
+    }
+
+    #[test]
+    fn encoded_words_in_headers_are_decoded() {
+        assert_eq!(decode_header_value("=?UTF-8?B?w6l0w6k=?="), "été");
+        assert_eq!(decode_header_value("Re: =?ISO-8859-1?Q?caf=E9?= plans"), "Re: café plans");
+        assert_eq!(decode_header_value("plain subject"), "plain subject");
+    }
+}
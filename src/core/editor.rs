@@ -1,29 +1,24 @@
 use crate::core::email::EmailMessage;
 use std::io::{self, Write};
+use std::path::Path;
 use tempfile::NamedTempFile;
 
 pub struct Editor;
 
 impl Editor {    
-    pub fn open(editor: &str, mut draft: EmailMessage) -> io::Result<EmailMessage> {
+    pub fn open(editor: &str, draft: EmailMessage) -> io::Result<EmailMessage> {
         // Create a new temp file to be used by editor
         // File gets deleted once out of scope
         let mut temp_file = NamedTempFile::new()?;
         writeln!(temp_file, "To: {}", draft.to)?;
+        writeln!(temp_file, "Cc: {}", draft.cc)?;
+        writeln!(temp_file, "Bcc: {}", draft.bcc)?;
         writeln!(temp_file, "Subject: {}", draft.subject)?;
         writeln!(temp_file, "Body:\n{}", draft.body)?;
 
         let temp_file_path = temp_file.path().to_owned();
 
-        // Create command to run editor with path as arg
-        let mut command = std::process::Command::new(editor);
-        if editor.contains("code") {
-            // Add wait arg for vscode to ensure file is saved before returning
-            command.arg("--wait").arg(&temp_file_path);
-        }
-        else {
-            command.arg(&temp_file_path);
-        }
+        let mut command = editor_command(editor, &temp_file_path)?;
 
         // Run the editor and check if it was successful
         let status = command.status()?;
@@ -37,24 +32,109 @@ impl Editor {
 
         // After the user exits the editor, read contents of temp file
         let contents = std::fs::read_to_string(&temp_file_path)?;
-        let mut in_body = false;
-        let mut body_lines = Vec::new();
-
-        // Iterate through the lines of the file and parse the email fields
-        // Evertyhing after Body: goes into body_lines
-        for line in contents.lines() {
-            if in_body {
-                body_lines.push(line);
-            } else if line.starts_with("To:") {
-                draft.to = line["To:".len()..].trim().to_string();
-            } else if line.starts_with("Subject:") {
-                draft.subject = line["Subject:".len()..].trim().to_string();
-            } else if line.starts_with("Body:") {
-                in_body = true;
-                body_lines.push(line["Body:".len()..].trim());
+        Ok(parse_template(&contents, draft))
+    }
+}
+
+/// Builds the command that opens `path` in `editor`. The setting may carry
+/// arguments (e.g. `nvim -c startinsert`), separated by whitespace.
+fn editor_command(editor: &str, path: &Path) -> io::Result<std::process::Command> {
+    let mut words = editor.split_whitespace();
+    let program = words.next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "No editor configured"))?;
+    let args: Vec<&str> = words.collect();
+
+    let mut command = std::process::Command::new(program);
+    command.args(&args);
+    if program.contains("code") && !args.contains(&"--wait") {
+        // Add wait arg for vscode to ensure file is saved before returning
+        command.arg("--wait");
+    }
+    command.arg(path);
+    Ok(command)
+}
+
+/// Reads the fields of an edited template back into `draft`.
+///
+/// Headers (`To:`, `Cc:`, `Bcc:`, `Subject:`, matched case-insensitively) may come
+/// in any order. The header block ends at a `Body:` line, at the first blank line,
+/// or at the first line that is not a known header; everything after that is the
+/// body. Headers missing from the template keep the value they had in `draft`, and
+/// so does the body if the template has no body section at all. CRLF line endings
+/// are accepted.
+pub fn parse_template(contents: &str, mut draft: EmailMessage) -> EmailMessage {
+    let mut lines = contents.lines().map(|line| line.trim_end_matches('\r'));
+    let mut body_lines: Option<Vec<&str>> = None;
+
+    for line in lines.by_ref() {
+        if line.trim().is_empty() {
+            body_lines = Some(Vec::new());
+            break;
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            body_lines = Some(vec![line]);
+            break;
+        };
+        let value = value.trim();
+        match name.trim().to_ascii_lowercase().as_str() {
+            "to" => draft.to = value.to_string(),
+            "cc" => draft.cc = value.to_string(),
+            "bcc" => draft.bcc = value.to_string(),
+            "subject" => draft.subject = value.to_string(),
+            "body" => {
+                // Text on the `Body:` line itself is the first line of the body
+                body_lines = Some(if value.is_empty() { Vec::new() } else { vec![value] });
+                break;
+            }
+            _ => {
+                body_lines = Some(vec![line]);
+                break;
             }
         }
+    }
+
+    if let Some(mut body_lines) = body_lines {
+        body_lines.extend(lines);
         draft.body = body_lines.join("\n");
-        Ok(draft)
     }
-}
\ No newline at end of file
+    draft
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(command: &std::process::Command) -> Vec<String> {
+        command.get_args().map(|arg| arg.to_string_lossy().into_owned()).collect()
+    }
+
+    #[test]
+    fn editor_settings_may_carry_arguments() {
+        let path = Path::new("/tmp/draft.eml");
+        let command = editor_command("nvim -c startinsert", path).unwrap();
+        assert_eq!(command.get_program(), "nvim");
+        assert_eq!(args(&command), vec!["-c", "startinsert", "/tmp/draft.eml"]);
+
+        let command = editor_command("code", path).unwrap();
+        assert_eq!(args(&command), vec!["--wait", "/tmp/draft.eml"]);
+        let command = editor_command("code --wait --new-window", path).unwrap();
+        assert_eq!(args(&command), vec!["--wait", "--new-window", "/tmp/draft.eml"]);
+
+        assert_eq!(editor_command("  ", path).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn headers_may_be_reordered_or_missing() {
+        let draft = EmailMessage { to: "old@example.com".to_string(), cc: "cc@example.com".to_string(), ..EmailMessage::new() };
+        let email = parse_template("subject: Hi\r\nTO: ann@example.com\r\nBody: first\r\nsecond\r\n", draft.clone());
+        assert_eq!(email.to, "ann@example.com");
+        assert_eq!(email.cc, "cc@example.com");
+        assert_eq!(email.subject, "Hi");
+        assert_eq!(email.body, "first\nsecond");
+
+        // The body starts at a blank line or at the first line that is not a header
+        assert_eq!(parse_template("To: ann@example.com\n\nHello: world", draft.clone()).body, "Hello: world");
+        assert_eq!(parse_template("Dear Ann,\nhi", draft.clone()).body, "Dear Ann,\nhi");
+        assert_eq!(parse_template("Subject: only", draft).body, "");
+    }
+}
@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
 use crate::error::Error;
+use crate::config::{QuoteConfig, SenderDisplay};
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
 pub enum MimeType {
@@ -55,6 +56,18 @@ impl EmailSender {
         self.name.as_deref().unwrap_or(&self.email)
     }
 
+    /// Returns the sender the way `mode` asks for it.
+    pub fn display_as(&self, mode: SenderDisplay) -> &str {
+        match mode {
+            SenderDisplay::NameThenEmail => self.display_name(),
+            SenderDisplay::EmailOnly => &self.email,
+            SenderDisplay::NameOnly => match &self.name {
+                Some(name) => name,
+                None => self.email.split('@').next().unwrap_or(&self.email),
+            },
+        }
+    }
+
     pub fn formatted_email(&self) -> String {
         format!("<{}>", self.email)
     }
@@ -68,12 +81,41 @@ impl EmailSender {
     }
 }
 
+/// The `Content-Disposition` of a MIME part. Inline parts are meant to be displayed
+/// as part of the body (e.g. images referenced by `cid:` in HTML), attachments are not.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq)]
+pub enum AttachmentDisposition {
+    Inline,
+    #[default]
+    Attachment,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmailAttachment {
     pub filename: String,
     pub content_type: String,
     pub data: Vec<u8>,
     pub mime_type: MimeType,
+    #[serde(default)]
+    pub disposition: AttachmentDisposition,
+    /// The `Content-ID` of the part without the surrounding angle brackets.
+    #[serde(default)]
+    pub content_id: Option<String>,
+}
+
+impl EmailAttachment {
+    pub fn is_inline(&self) -> bool {
+        self.disposition == AttachmentDisposition::Inline
+    }
+}
+
+/// Number and total size of a message's attachments, read from its MIME
+/// structure without loading their data.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AttachmentStats {
+    pub count: usize,
+    /// Decoded size in bytes. Estimated from the encoded size for base64 parts.
+    pub bytes: usize,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -82,11 +124,121 @@ pub struct EmailMessage {
     pub subject: String,
     pub from: EmailSender,
     pub to: String,
+    /// Comma separated `Cc` recipients.
+    #[serde(default)]
+    pub cc: String,
+    /// Comma separated `Bcc` recipients. Only used when composing.
+    #[serde(default)]
+    pub bcc: String,
     pub date: String,
     pub body: String,
     pub mime_type: MimeType,
     pub email_attachments: Vec<EmailAttachment>,
     pub is_unread: bool,
+    /// The Gmail thread the message belongs to. Messages of one conversation share it.
+    #[serde(default)]
+    pub thread_id: Option<String>,
+    /// The `Message-ID` header, which replies refer to.
+    #[serde(default)]
+    pub message_id: Option<String>,
+    /// Ids of the labels the message carries, e.g. `INBOX` or `Label_12`.
+    #[serde(default)]
+    pub labels: Vec<String>,
+    /// Attachment count and size for messages listed without their attachments
+    /// loaded. See `attachment_count`.
+    #[serde(default)]
+    pub attachment_stats: Option<AttachmentStats>,
+    /// The raw `References` header: the `Message-ID`s of the conversation so far.
+    #[serde(default)]
+    pub references: Option<String>,
+    /// Body preview for the inbox, see `make_snippet`. Computed once when the
+    /// message is cached rather than on every frame.
+    #[serde(default)]
+    pub snippet: String,
+}
+
+/// The headers that thread a reply under the message it answers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplyHeaders {
+    /// `Message-ID` of the message being replied to.
+    pub in_reply_to: String,
+    /// The original's `References` followed by its `Message-ID`.
+    pub references: String,
+}
+
+impl ReplyHeaders {
+    /// The reply headers for answering `original`, or `None` if it has no
+    /// `Message-ID` to refer to.
+    pub fn for_message(original: &EmailMessage) -> Option<Self> {
+        let message_id = original.message_id.as_deref().map(str::trim).filter(|id| !id.is_empty())?;
+        let references = match original.references.as_deref().map(str::trim) {
+            Some(references) if !references.is_empty() => format!("{} {}", references, message_id),
+            _ => message_id.to_string(),
+        };
+        Some(Self { in_reply_to: message_id.to_string(), references })
+    }
+
+    /// Adds `In-Reply-To` and `References` to `builder` when replying.
+    pub fn apply(reply: Option<&ReplyHeaders>, builder: lettre::message::MessageBuilder) -> lettre::message::MessageBuilder {
+        match reply {
+            Some(reply) => builder
+                .in_reply_to(reply.in_reply_to.clone())
+                .references(reply.references.clone()),
+            None => builder,
+        }
+    }
+}
+
+/// The subject of a reply to a message with subject `subject`: prefixed with
+/// "Re: " unless it already is.
+pub fn reply_subject(subject: &str) -> String {
+    let subject = subject.trim();
+    if subject.get(..3).is_some_and(|prefix| prefix.eq_ignore_ascii_case("re:")) {
+        subject.to_string()
+    } else {
+        format!("Re: {}", subject)
+    }
+}
+
+/// Splits a comma separated recipient list, skipping empty entries.
+pub fn split_recipients(list: &str) -> impl Iterator<Item = &str> {
+    list.split(',').map(str::trim).filter(|address| !address.is_empty())
+}
+
+/// Shortens a comma separated recipient list for display: the first `shown`
+/// recipients by name (or address), then how many were left out, e.g.
+/// "a@x, b@y and 3 more".
+pub fn collapse_recipients(list: &str, shown: usize) -> String {
+    let recipients: Vec<EmailSender> = split_recipients(list)
+        .map(|address| EmailSender::from(address.to_string()))
+        .collect();
+    let names: Vec<&str> = recipients.iter().take(shown).map(EmailSender::display_name).collect();
+    match recipients.len().saturating_sub(shown) {
+        0 => names.join(", "),
+        hidden => format!("{} and {} more", names.join(", "), hidden),
+    }
+}
+
+/// Longest body preview kept with a message; `snippet_length` shows at most this much.
+pub const SNIPPET_MAX_LENGTH: usize = 200;
+
+/// A one-line preview of `body` at most `length` characters long: quoted reply
+/// lines (starting with `>`) are dropped and runs of whitespace, newlines included,
+/// become a single space.
+pub fn make_snippet(body: &str, length: usize) -> String {
+    body.lines()
+        .filter(|line| !line.trim_start().starts_with('>'))
+        .flat_map(str::split_whitespace)
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .take(length)
+        .collect()
+}
+
+fn parse_mailbox(address: &str) -> Result<lettre::message::Mailbox, Error> {
+    address.parse()
+        .map_err(|e| Error::InvalidInput(format!("Invalid address {:?}: {}", address, e)))
 }
 
 impl EmailMessage {
@@ -96,11 +248,19 @@ impl EmailMessage {
             subject: String::new(),
             from: EmailSender::default(),
             to: String::new(),
+            cc: String::new(),
+            bcc: String::new(),
             date: String::new(),
             body: String::new(),
             mime_type: Default::default(),
             email_attachments: Vec::new(),
             is_unread: false,
+            thread_id: None,
+            message_id: None,
+            labels: Vec::new(),
+            attachment_stats: None,
+            references: None,
+            snippet: String::new(),
         }
     }
 
@@ -119,16 +279,109 @@ impl EmailMessage {
     //     )
     // }
 
-    pub fn to_lettre_email(&self) -> Result<lettre::Message, Error> {
-        lettre::Message::builder()
-            .from("me@localhost".parse().unwrap()) // Gmail ignores this and uses the authenticated user
-            .to(self.to.parse().unwrap())
+    /// Builds the outgoing message. `reply` threads it under the message it answers.
+    pub fn to_lettre_email(&self, reply: Option<&ReplyHeaders>) -> Result<lettre::Message, Error> {
+        let builder = lettre::Message::builder()
+            .from("me@localhost".parse().unwrap()); // Gmail ignores this and uses the authenticated user
+        let builder = ReplyHeaders::apply(reply, builder);
+        self.with_recipients(builder)?
             .subject(self.subject.clone())
             .header(lettre::message::header::ContentType::TEXT_PLAIN)
             .body(self.body.clone())
             .map_err(|e: lettre::error::Error| Error::Other(format!("Failed to build email: {}", e)))
     }
 
+    /// Adds the `To`, `Cc` and `Bcc` recipients of this draft to `builder`, one
+    /// header entry per address of the comma separated lists.
+    pub fn with_recipients(&self, mut builder: lettre::message::MessageBuilder) -> Result<lettre::message::MessageBuilder, Error> {
+        if split_recipients(&self.to).next().is_none() {
+            return Err(Error::InvalidInput("To field cannot be empty".to_string()));
+        }
+        for address in split_recipients(&self.to) {
+            builder = builder.to(parse_mailbox(address)?);
+        }
+        for address in split_recipients(&self.cc) {
+            builder = builder.cc(parse_mailbox(address)?);
+        }
+        for address in split_recipients(&self.bcc) {
+            builder = builder.bcc(parse_mailbox(address)?);
+        }
+        Ok(builder)
+    }
+
+    /// Returns the HTML body with every `cid:<content-id>` reference replaced by a
+    /// `data:` URI built from the matching inline attachment, so the HTML can be
+    /// rendered standalone. References without a matching attachment are left as-is.
+    pub fn resolve_cid_references(&self) -> String {
+        use base64::Engine;
+
+        let mut html = self.body.clone();
+        for attachment in self.email_attachments.iter().filter(|att| att.is_inline()) {
+            let Some(content_id) = &attachment.content_id else {
+                continue;
+            };
+            let reference = format!("cid:{}", content_id);
+            if !html.contains(&reference) {
+                continue;
+            }
+            let data_uri = format!(
+                "data:{};base64,{}",
+                attachment.content_type,
+                base64::engine::general_purpose::STANDARD.encode(&attachment.data)
+            );
+            html = html.replace(&reference, &data_uri);
+        }
+        html
+    }
+
+    /// Returns the body quoted for a reply: an attribution line followed by every
+    /// body line prefixed with `quote_prefix`, re-wrapped at `wrap_quoted_at` columns.
+    pub fn quote_body(&self, cfg: &QuoteConfig) -> String {
+        let attribution = cfg.attribution
+            .replace("{date}", &self.date)
+            .replace("{sender}", &self.from.full_string());
+
+        let mut lines = vec![attribution];
+        for line in self.body.lines() {
+            // Already quoted lines keep their markers on every wrapped line
+            let markers_len = line.len() - line.trim_start_matches(['>', ' ']).len();
+            let (markers, text) = line.split_at(markers_len);
+            let prefix = format!("{}{}", cfg.quote_prefix, markers);
+
+            match cfg.wrap_quoted_at {
+                Some(column) => {
+                    let width = column.saturating_sub(prefix.chars().count()).max(1);
+                    for wrapped in wrap_text(text, width) {
+                        lines.push(format!("{}{}", prefix, wrapped).trim_end().to_string());
+                    }
+                }
+                None => lines.push(format!("{}{}", prefix, text).trim_end().to_string()),
+            }
+        }
+        lines.join("\n")
+    }
+
+    /// Attachments other than inline images. Taken from the loaded attachments, or
+    /// from `attachment_stats` when they were not loaded.
+    pub fn attachment_count(&self) -> usize {
+        match self.attachment_stats {
+            Some(stats) if self.email_attachments.is_empty() => stats.count,
+            _ => self.email_attachments.iter().filter(|att| !att.is_inline()).count(),
+        }
+    }
+
+    /// Total size in bytes of the attachments counted by `attachment_count`.
+    pub fn total_attachment_bytes(&self) -> usize {
+        match self.attachment_stats {
+            Some(stats) if self.email_attachments.is_empty() => stats.bytes,
+            _ => self.email_attachments
+                .iter()
+                .filter(|att| !att.is_inline())
+                .map(|att| att.data.len())
+                .sum(),
+        }
+    }
+
     /// Returns only the image attachments from this email
     pub fn get_image_attachments(&self) -> Vec<&EmailAttachment> {
         self.email_attachments
@@ -136,4 +389,135 @@ impl EmailMessage {
             .filter(|att| att.mime_type == MimeType::AttachmentPNG)
             .collect()
     }
-}
\ No newline at end of file
+}
+
+/// Word-wraps `text` into lines of at most `width` characters. Words longer than
+/// `width` are split. An empty input yields a single empty line.
+fn wrap_text(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+
+    for word in text.split_whitespace() {
+        let mut word: Vec<char> = word.chars().collect();
+        while word.len() > width {
+            if !current.is_empty() {
+                lines.push(std::mem::take(&mut current));
+            }
+            lines.push(word.drain(..width).collect());
+        }
+        let word: String = word.into_iter().collect();
+        if word.is_empty() {
+            continue;
+        }
+
+        let current_len = current.chars().count();
+        if current_len == 0 {
+            current = word;
+        } else if current_len + 1 + word.chars().count() <= width {
+            current.push(' ');
+            current.push_str(&word);
+        } else {
+            lines.push(std::mem::replace(&mut current, word));
+        }
+    }
+
+    if !current.is_empty() || lines.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draft(to: &str) -> EmailMessage {
+        let mut email = EmailMessage::new();
+        email.to = to.to_string();
+        email.subject = "Hi".to_string();
+        email.body = "Hello".to_string();
+        email
+    }
+
+    #[test]
+    fn sends_to_every_address_of_a_recipient_list() {
+        let raw = draft("a@example.com, Bee <b@example.com>").to_lettre_email(None).unwrap().formatted();
+        let raw = String::from_utf8(raw).unwrap();
+        assert!(raw.contains("a@example.com"));
+        assert!(raw.contains("b@example.com"));
+    }
+
+    #[test]
+    fn reply_subject_is_prefixed_once() {
+        assert_eq!(reply_subject("Lunch?"), "Re: Lunch?");
+        assert_eq!(reply_subject("RE: Lunch?"), "RE: Lunch?");
+    }
+
+    #[test]
+    fn rejects_invalid_or_missing_recipients() {
+        assert!(matches!(draft("not an address").to_lettre_email(None), Err(Error::InvalidInput(_))));
+        assert!(matches!(draft(" , ").to_lettre_email(None), Err(Error::InvalidInput(_))));
+    }
+
+    #[test]
+    fn snippets_drop_quotes_and_collapse_whitespace() {
+        let body = "Hi Ann,\n\n  see   below.\n> old reply\n>> older\nThanks";
+        assert_eq!(make_snippet(body, 100), "Hi Ann, see below. Thanks");
+        assert_eq!(make_snippet(body, 6), "Hi Ann");
+        assert_eq!(make_snippet("> only quotes", 100), "");
+    }
+
+    #[test]
+    fn snippets_cut_on_characters_not_bytes() {
+        assert_eq!(make_snippet("héllo wörld", 7), "héllo w");
+    }
+
+    #[test]
+    fn senders_are_shown_as_the_display_mode_asks() {
+        let named = EmailSender::from("Ann Lee <ann@example.com>".to_string());
+        assert_eq!(named.display_as(SenderDisplay::NameThenEmail), "Ann Lee");
+        assert_eq!(named.display_as(SenderDisplay::EmailOnly), "ann@example.com");
+        assert_eq!(named.display_as(SenderDisplay::NameOnly), "Ann Lee");
+
+        let unnamed = EmailSender::from("bob@example.com".to_string());
+        assert_eq!(unnamed.display_as(SenderDisplay::NameThenEmail), "bob@example.com");
+        assert_eq!(unnamed.display_as(SenderDisplay::EmailOnly), "bob@example.com");
+        assert_eq!(unnamed.display_as(SenderDisplay::NameOnly), "bob");
+    }
+
+    #[test]
+    fn long_recipient_lists_are_collapsed() {
+        let list = "Ann <ann@x.org>, bob@y.org, Cy <cy@z.org>, dee@w.org";
+        assert_eq!(collapse_recipients("", 2), "");
+        assert_eq!(collapse_recipients("Ann <ann@x.org>", 2), "Ann");
+        assert_eq!(collapse_recipients("Ann <ann@x.org>, bob@y.org", 2), "Ann, bob@y.org");
+        assert_eq!(collapse_recipients(list, 2), "Ann, bob@y.org and 2 more");
+        assert_eq!(collapse_recipients(list, 3), "Ann, bob@y.org, Cy and 1 more");
+        assert_eq!(collapse_recipients(list, 4), "Ann, bob@y.org, Cy, dee@w.org");
+        // Stray separators are not counted as recipients
+        assert_eq!(collapse_recipients("ann@x.org, , bob@y.org,", 1), "ann@x.org and 1 more");
+    }
+
+    #[test]
+    fn cid_references_without_an_inline_part_are_left_alone() {
+        let mut email = draft("a@example.com");
+        email.body = r#"<img src="cid:logo"><img src="cid:missing">"#.to_string();
+        let part = |disposition| EmailAttachment {
+            filename: "logo.png".to_string(),
+            content_type: "image/png".to_string(),
+            data: b"png".to_vec(),
+            mime_type: MimeType::AttachmentPNG,
+            disposition,
+            content_id: Some("logo".to_string()),
+        };
+
+        email.email_attachments = vec![part(AttachmentDisposition::Attachment)];
+        assert_eq!(email.resolve_cid_references(), email.body);
+
+        email.email_attachments = vec![part(AttachmentDisposition::Inline)];
+        assert_eq!(
+            email.resolve_cid_references(),
+            r#"<img src="data:image/png;base64,cG5n"><img src="cid:missing">"#
+        );
+    }
+}
@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
 use google_gmail1::api::LabelColor;
+use std::collections::HashMap;
 
 /// The `google_gmail1::api::Label` has its own Label type, but we're wrapping 
 /// it in our own type for consistency.
@@ -31,6 +32,93 @@ impl Label {
             name: None,
         }
     }
+
+    /// The name shown for this label. `overrides` (the `label_names` config, keyed
+    /// by label id or name) wins over the built-in names for Gmail system labels;
+    /// other labels show their own name. `name` itself is left untouched, as it
+    /// identifies the folder in commands and in `filter_labels`.
+    pub fn display_name(&self, overrides: &HashMap<String, String>) -> String {
+        let name = self.name.as_deref().unwrap_or("Unknown");
+        [self.id.as_deref(), Some(name)]
+            .into_iter()
+            .flatten()
+            .find_map(|key| overrides.get(key).cloned())
+            .or_else(|| friendly_label_name(name).map(str::to_string))
+            .unwrap_or_else(|| name.to_string())
+    }
+}
+
+/// The display name of the label with id `id`: its entry in `labels` (the folder
+/// list) if there is one, otherwise the configured or built-in name, otherwise
+/// the id itself.
+pub fn label_display_name(id: &str, labels: &[Label], overrides: &HashMap<String, String>) -> String {
+    match labels.iter().find(|label| label.id.as_deref() == Some(id)) {
+        Some(label) => label.display_name(overrides),
+        None => overrides.get(id).cloned()
+            .or_else(|| friendly_label_name(id).map(str::to_string))
+            .unwrap_or_else(|| id.to_string()),
+    }
+}
+
+/// Human names for Gmail's system label ids, e.g. "Promotions" for `CATEGORY_PROMOTIONS`.
+pub fn friendly_label_name(id: &str) -> Option<&'static str> {
+    Some(match id {
+        "INBOX" => "Inbox",
+        "SENT" => "Sent",
+        "DRAFT" => "Drafts",
+        "SPAM" => "Spam",
+        "TRASH" => "Trash",
+        "STARRED" => "Starred",
+        "IMPORTANT" => "Important",
+        "UNREAD" => "Unread",
+        "CHAT" => "Chats",
+        "CATEGORY_PERSONAL" => "Personal",
+        "CATEGORY_SOCIAL" => "Social",
+        "CATEGORY_PROMOTIONS" => "Promotions",
+        "CATEGORY_UPDATES" => "Updates",
+        "CATEGORY_FORUMS" => "Forums",
+        _ => return None,
+    })
+}
+
+/// Labels to add and remove to mark a message as spam (`true`) or not spam. Like
+/// Gmail, spam leaves the inbox and a message rescued from spam goes back to it.
+pub fn spam_label_changes(spam: bool) -> (Vec<String>, Vec<String>) {
+    let spam_labels = vec!["SPAM".to_string()];
+    let inbox_labels = vec!["INBOX".to_string()];
+    if spam {
+        (spam_labels, inbox_labels)
+    } else {
+        (inbox_labels, spam_labels)
+    }
+}
+
+/// Whether a message can be moved into or out of the label with id `id`: INBOX,
+/// SPAM, TRASH and user labels. Other system labels (STARRED, UNREAD, the
+/// categories, ...) describe a message rather than hold it.
+pub fn is_move_target(id: &str) -> bool {
+    matches!(id, "INBOX" | "SPAM" | "TRASH") || friendly_label_name(id).is_none()
+}
+
+/// Labels to add and remove to move a message from the folder with id `from` to
+/// `to`. Like Gmail's "Move to", the message leaves the inbox and `from` (when it
+/// holds messages, see `is_move_target`) and only `to` is added.
+pub fn move_label_changes(from: Option<&str>, to: &str) -> (Vec<String>, Vec<String>) {
+    let mut remove = vec!["INBOX".to_string()];
+    if let Some(from) = from.filter(|from| is_move_target(from) && *from != "INBOX") {
+        remove.push(from.to_string());
+    }
+    remove.retain(|label| label != to);
+    (vec![to.to_string()], remove)
+}
+
+/// Whether a label with display name `name` is listed as a folder. When
+/// `show_only` is set only the labels in it are shown; `filter` then hides
+/// labels from what is left. Both lists are case-sensitive.
+pub fn is_label_shown(name: &str, show_only: Option<&[String]>, filter: Option<&[String]>) -> bool {
+    let allowed = show_only.is_none_or(|show_only| show_only.iter().any(|l| l == name));
+    let filtered = filter.is_some_and(|filter| filter.iter().any(|l| l == name));
+    allowed && !filtered
 }
 
 impl std::fmt::Display for Label {
@@ -43,4 +131,68 @@ impl std::fmt::Display for Label {
             self.messages_unread.as_ref()
         )
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn moving_leaves_the_inbox_and_the_source_folder() {
+        assert_eq!(move_label_changes(Some("Label_1"), "Label_2"), (labels(&["Label_2"]), labels(&["INBOX", "Label_1"])));
+        assert_eq!(move_label_changes(Some("INBOX"), "TRASH"), (labels(&["TRASH"]), labels(&["INBOX"])));
+        assert_eq!(move_label_changes(None, "INBOX"), (labels(&["INBOX"]), labels(&[])));
+        // Labels describing the message are kept
+        assert_eq!(move_label_changes(Some("STARRED"), "SPAM"), (labels(&["SPAM"]), labels(&["INBOX"])));
+    }
+
+    #[test]
+    fn only_folders_are_move_targets() {
+        assert!(is_move_target("INBOX"));
+        assert!(is_move_target("TRASH"));
+        assert!(is_move_target("Label_1"));
+        assert!(!is_move_target("STARRED"));
+        assert!(!is_move_target("CATEGORY_PROMOTIONS"));
+    }
+
+    #[test]
+    fn the_blocklist_hides_labels_from_the_allowlist() {
+        let show_only = labels(&["INBOX", "Work", "Receipts"]);
+        let filter = labels(&["Receipts", "Spam"]);
+        assert!(is_label_shown("Work", Some(&show_only), Some(&filter)));
+        assert!(!is_label_shown("Receipts", Some(&show_only), Some(&filter)));
+        assert!(!is_label_shown("Travel", Some(&show_only), Some(&filter)));
+        // Either list alone
+        assert!(is_label_shown("Travel", None, Some(&filter)));
+        assert!(!is_label_shown("Spam", None, Some(&filter)));
+        assert!(!is_label_shown("Travel", Some(&show_only), None));
+        assert!(is_label_shown("Travel", None, None));
+        // Names are matched exactly
+        assert!(!is_label_shown("work", Some(&show_only), None));
+    }
+
+    #[test]
+    fn system_labels_get_friendly_names_but_keep_their_ids() {
+        let promotions = Label {
+            id: Some("CATEGORY_PROMOTIONS".to_string()),
+            name: Some("CATEGORY_PROMOTIONS".to_string()),
+            ..Label::new()
+        };
+        let folders = vec![promotions.clone()];
+        let no_overrides = HashMap::new();
+        assert_eq!(promotions.display_name(&no_overrides), "Promotions");
+        assert_eq!(label_display_name("CATEGORY_PROMOTIONS", &folders, &no_overrides), "Promotions");
+        assert_eq!(label_display_name("IMPORTANT", &folders, &no_overrides), "Important");
+        assert_eq!(label_display_name("Label_7", &folders, &no_overrides), "Label_7");
+        // Filtering still goes by the raw name
+        assert_eq!(promotions.name.as_deref(), Some("CATEGORY_PROMOTIONS"));
+        assert!(!is_label_shown("CATEGORY_PROMOTIONS", None, Some(&labels(&["CATEGORY_PROMOTIONS"]))));
+
+        let overrides = HashMap::from([("CATEGORY_PROMOTIONS".to_string(), "Deals".to_string())]);
+        assert_eq!(promotions.display_name(&overrides), "Deals");
+        assert_eq!(label_display_name("CATEGORY_PROMOTIONS", &[], &overrides), "Deals");
+    }
+}
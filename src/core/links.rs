@@ -0,0 +1,119 @@
+// This file finds links in a plain text message body.
+
+/// Schemes `extract_urls` recognizes, matched case-insensitively.
+const URL_SCHEMES: [&str; 3] = ["https://", "http://", "mailto:"];
+
+/// Schemes `is_browser_url` lets through to the system browser.
+const BROWSER_SCHEMES: [&str; 2] = ["https://", "http://"];
+
+/// Characters that end a URL in running text.
+fn is_url_terminator(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '<' | '>' | '"' | '\'' | '`' | '{' | '}' | '|' | '\\' | '^')
+}
+
+/// Finds the http, https and mailto URLs in `body`, in the order they first appear
+/// and without duplicates. Trailing punctuation from the surrounding sentence
+/// (e.g. "see https://example.com.") is not part of the URL, and neither is a
+/// closing bracket that has no opening one inside the URL.
+pub fn extract_urls(body: &str) -> Vec<String> {
+    let lower = body.to_ascii_lowercase();
+    let mut urls: Vec<String> = Vec::new();
+    let mut pos = 0;
+
+    while pos < body.len() {
+        let next = URL_SCHEMES
+            .iter()
+            .filter_map(|scheme| lower[pos..].find(scheme).map(|offset| (pos + offset, scheme.len())))
+            .min_by_key(|(start, _)| *start);
+        let Some((start, scheme_len)) = next else {
+            break;
+        };
+
+        let end = body[start..]
+            .find(is_url_terminator)
+            .map_or(body.len(), |offset| start + offset);
+        let url = trim_url(&body[start..end]);
+        if url.len() > scheme_len && !urls.iter().any(|seen| seen == url) {
+            urls.push(url.to_string());
+        }
+        pos = end.max(start + scheme_len);
+    }
+    urls
+}
+
+/// Strips sentence punctuation and unbalanced closing brackets off the end of a URL.
+fn trim_url(url: &str) -> &str {
+    let mut url = url;
+    loop {
+        let Some(last) = url.chars().last() else {
+            return url;
+        };
+        let unbalanced = |open: char, close: char| {
+            last == close && url.matches(close).count() > url.matches(open).count()
+        };
+        if matches!(last, '.' | ',' | ';' | ':' | '!' | '?') || unbalanced('(', ')') || unbalanced('[', ']') {
+            url = &url[..url.len() - last.len_utf8()];
+        } else {
+            return url;
+        }
+    }
+}
+
+/// Whether `url` may be handed to the system browser. Only http(s) URLs with a host
+/// qualify; anything else (`file:`, `javascript:`, custom handlers, ...) could make
+/// the OS launch arbitrary programs on behalf of a message's sender.
+pub fn is_browser_url(url: &str) -> bool {
+    let url = url.trim();
+    let lower = url.to_ascii_lowercase();
+    BROWSER_SCHEMES.iter().any(|scheme| {
+        lower.starts_with(scheme)
+            && url[scheme.len()..]
+                .chars()
+                .next()
+                .is_some_and(|c| c.is_alphanumeric() || c == '[')
+            && !url.chars().any(char::is_control)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sentence_punctuation_is_not_part_of_a_url() {
+        let body = "See https://example.com/a. Or (https://example.com/b), \"http://x.org/q?a=1&b=2\"!\n\
+                    Wiki: https://en.wikipedia.org/wiki/Rust_(programming_language); mail mailto:ann@example.com?";
+        assert_eq!(extract_urls(body), vec![
+            "https://example.com/a",
+            "https://example.com/b",
+            "http://x.org/q?a=1&b=2",
+            "https://en.wikipedia.org/wiki/Rust_(programming_language)",
+            "mailto:ann@example.com",
+        ]);
+    }
+
+    #[test]
+    fn urls_are_listed_once_in_order() {
+        assert_eq!(extract_urls("HTTPS://Example.com then https://b.org and HTTPS://Example.com"), vec![
+            "HTTPS://Example.com",
+            "https://b.org",
+        ]);
+        assert!(extract_urls("ftp://files.example.com, file:///etc/passwd and a bare https://").is_empty());
+    }
+
+    #[test]
+    fn only_http_urls_with_a_host_go_to_the_browser() {
+        assert!(is_browser_url("https://example.com/path?q=1"));
+        assert!(is_browser_url("  HTTP://example.com "));
+        assert!(is_browser_url("http://[::1]:8080/"));
+
+        assert!(!is_browser_url("mailto:ann@example.com"));
+        assert!(!is_browser_url("file:///etc/passwd"));
+        assert!(!is_browser_url("javascript:alert(1)"));
+        assert!(!is_browser_url("ftp://files.example.com"));
+        assert!(!is_browser_url("https://"));
+        assert!(!is_browser_url("https:///etc/passwd"));
+        assert!(!is_browser_url("https://-flag"));
+        assert!(!is_browser_url("https://example.com/\u{7}bell"));
+    }
+}
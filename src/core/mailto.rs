@@ -0,0 +1,145 @@
+// This file contains the parser for `mailto:` URIs (RFC 6068).
+
+use crate::cli::command::Command;
+use crate::core::email::EmailMessage;
+use crate::error::Error;
+
+/// The fields of a `mailto:` URI, URL-decoded.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MailtoDraft {
+    /// Recipients from the path and any `to=` parameters, in order.
+    pub to: Vec<String>,
+    pub cc: Vec<String>,
+    pub bcc: Vec<String>,
+    pub subject: Option<String>,
+    pub body: Option<String>,
+}
+
+impl MailtoDraft {
+    /// Recipients joined the way `SendEmail`'s `to` expects them.
+    pub fn to_header(&self) -> Option<String> {
+        if self.to.is_empty() {
+            None
+        } else {
+            Some(self.to.join(", "))
+        }
+    }
+
+    /// The `SendEmail` command this URI describes. Missing fields are left as
+    /// `None` so the editor opens for them.
+    pub fn into_command(self) -> Command {
+        Command::SendEmail {
+            to: self.to_header(),
+            subject: self.subject,
+            body: self.body,
+            cc: (!self.cc.is_empty()).then(|| self.cc.join(", ")),
+            bcc: (!self.bcc.is_empty()).then(|| self.bcc.join(", ")),
+            reply_to: None,
+        }
+    }
+
+    /// A draft for the TUI composer.
+    pub fn into_email(self) -> EmailMessage {
+        let mut email = EmailMessage::new();
+        email.to = self.to_header().unwrap_or_default();
+        email.cc = self.cc.join(", ");
+        email.bcc = self.bcc.join(", ");
+        email.subject = self.subject.unwrap_or_default();
+        email.body = self.body.unwrap_or_default();
+        email
+    }
+}
+
+/// Parses a `mailto:` URI such as `mailto:a@b.com,c@d.com?subject=Hi&cc=e@f.com&body=Yo`.
+///
+/// Addresses may appear both in the path and in `to=` parameters; comma separated
+/// lists are split. Unknown parameters are ignored.
+pub fn parse_mailto(uri: &str) -> Result<MailtoDraft, Error> {
+    let rest = uri.get(..7)
+        .filter(|scheme| scheme.eq_ignore_ascii_case("mailto:"))
+        .map(|_| &uri[7..])
+        .ok_or_else(|| Error::InvalidInput(format!("Not a mailto: URI: {}", uri)))?;
+
+    let (path, query) = rest.split_once('?').unwrap_or((rest, ""));
+
+    let mut draft = MailtoDraft::default();
+    draft.to.extend(split_addresses(&percent_decode(path)?));
+
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let value = percent_decode(value)?;
+        match key.to_ascii_lowercase().as_str() {
+            "to" => draft.to.extend(split_addresses(&value)),
+            "cc" => draft.cc.extend(split_addresses(&value)),
+            "bcc" => draft.bcc.extend(split_addresses(&value)),
+            "subject" => draft.subject = Some(value),
+            "body" => draft.body = Some(value),
+            other => tracing::debug!("Ignoring mailto parameter {:?}", other),
+        }
+    }
+
+    Ok(draft)
+}
+
+fn split_addresses(list: &str) -> impl Iterator<Item = String> + '_ {
+    list.split(',')
+        .map(str::trim)
+        .filter(|address| !address.is_empty())
+        .map(str::to_string)
+}
+
+/// Decodes `%XX` escapes (as UTF-8). `+` is kept literally, as RFC 6068 requires.
+fn percent_decode(text: &str) -> Result<String, Error> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let byte = text.get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| Error::InvalidInput(format!("Bad percent escape in {:?}", text)))?;
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded)
+        .map_err(|_| Error::InvalidInput(format!("mailto field is not valid UTF-8: {:?}", text)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_keeps_copy_recipients() {
+        let command = parse_mailto("mailto:a@b.com?cc=c@d.com,e@f.com&bcc=g@h.com&subject=Hi").unwrap().into_command();
+        let Command::SendEmail { to, cc, bcc, subject, .. } = command else {
+            panic!("expected send-email");
+        };
+        assert_eq!(to.as_deref(), Some("a@b.com"));
+        assert_eq!(cc.as_deref(), Some("c@d.com, e@f.com"));
+        assert_eq!(bcc.as_deref(), Some("g@h.com"));
+        assert_eq!(subject.as_deref(), Some("Hi"));
+    }
+
+    #[test]
+    fn encoded_fields_are_decoded() {
+        let draft = parse_mailto("MAILTO:ann%40example.com?Subject=Caf%C3%A9%20plans&body=Line%201%0D%0ALine+2&to=bob@example.com,%20cy@example.com").unwrap();
+        assert_eq!(draft.to, vec!["ann@example.com", "bob@example.com", "cy@example.com"]);
+        assert_eq!(draft.subject.as_deref(), Some("Café plans"));
+        // `+` is not a space in mailto URIs
+        assert_eq!(draft.body.as_deref(), Some("Line 1\r\nLine+2"));
+    }
+
+    #[test]
+    fn other_schemes_and_bad_escapes_are_rejected() {
+        assert!(matches!(parse_mailto("https://example.com"), Err(Error::InvalidInput(_))));
+        assert!(matches!(parse_mailto("mail"), Err(Error::InvalidInput(_))));
+        assert!(matches!(parse_mailto("mailto:a@b.com?body=100%"), Err(Error::InvalidInput(_))));
+        assert!(matches!(parse_mailto("mailto:a@b.com?body=%FF"), Err(Error::InvalidInput(_))));
+        assert_eq!(parse_mailto("mailto:").unwrap(), MailtoDraft::default());
+    }
+}
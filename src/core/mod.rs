@@ -1,3 +1,6 @@
 pub mod email;
 pub mod label;
-pub mod editor;
\ No newline at end of file
+pub mod editor;
+pub mod mailto;
+pub mod links;
+pub mod charset;
@@ -1,4 +1,4 @@
-/// This file defines our custom error type for backend operations.
+//! This file defines our custom error type for backend operations.
 
 use std::error::Error as StdError;
 use std::fmt;
@@ -14,6 +14,15 @@ pub enum Error {
     
     /// Connection error
     Connection(String),
+
+    /// The server rejected the request because of rate limiting (HTTP 429 / rate limit 403)
+    RateLimited(String),
+
+    /// The requested resource does not exist on the server (HTTP 404)
+    NotFound(String),
+
+    /// The access token is missing, expired or revoked (HTTP 401)
+    TokenExpired(String),
     
     /// Authentication error
     Authentication(String),
@@ -42,6 +51,9 @@ impl fmt::Display for Error {
             }
             Error::Imap(e) => write!(f, "IMAP error: {}", e),
             Error::Connection(msg) => write!(f, "Connection error: {}", msg),
+            Error::RateLimited(msg) => write!(f, "Rate limited: {}", msg),
+            Error::NotFound(msg) => write!(f, "Not found: {}", msg),
+            Error::TokenExpired(msg) => write!(f, "Token expired: {}", msg),
             Error::Authentication(msg) => write!(f, "Authentication error: {}", msg),
             Error::Parse(msg) => write!(f, "Parse error: {}", msg),
             Error::Config(msg) => write!(f, "Config error: {}", msg),
@@ -53,11 +65,26 @@ impl fmt::Display for Error {
 }
 
 impl From<std::io::Error> for Error {
+    /// Local file and maildir I/O. Network call sites map their errors themselves,
+    /// reporting socket timeouts as `Connection("timeout")` (see `is_timeout`).
     fn from(value: std::io::Error) -> Self {
         Error::Other(value.to_string())
     }
 }
 
+/// Whether an I/O error is a socket timeout. Depending on the platform an expired
+/// read timeout shows up as either kind.
+pub fn is_timeout(err: &std::io::Error) -> bool {
+    matches!(err.kind(), std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock)
+}
+
+impl Error {
+    /// Whether the backend refused because it is not (or no longer) signed in.
+    pub fn is_auth(&self) -> bool {
+        matches!(self, Error::Authentication(_) | Error::TokenExpired(_))
+    }
+}
+
 impl StdError for Error {
     fn source(&self) -> Option<&(dyn StdError + 'static)> {
         match self {
@@ -70,6 +97,22 @@ impl StdError for Error {
 // Automatically convert imap::Error to our Error type
 impl From<imap::Error> for Error {
     fn from(err: imap::Error) -> Self {
-        Error::Imap(err)
+        match err {
+            imap::Error::Io(e) if is_timeout(&e) => Error::Connection("timeout".to_string()),
+            err => Error::Imap(err),
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::ErrorKind;
+
+    #[test]
+    fn only_socket_timeouts_become_connection_timeouts() {
+        let local = Error::from(std::io::Error::from(ErrorKind::WouldBlock));
+        assert!(matches!(local, Error::Other(_)));
+        let imap = Error::from(imap::Error::Io(std::io::Error::from(ErrorKind::TimedOut)));
+        assert!(matches!(imap, Error::Connection(message) if message == "timeout"));
     }
-}
\ No newline at end of file
+}
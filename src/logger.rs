@@ -4,9 +4,11 @@
 // In TUI mode, logs are written to a file to avoid corrupting the terminal UI.
 // In CLI mode, logs go to both stdout and the file for immediate feedback.
 
+use crate::config::LogRotation;
 use crate::error::Error;
-use std::path::PathBuf;
-use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+use std::path::{Path, PathBuf};
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::{fmt, layer::SubscriberExt, registry::LookupSpan, util::SubscriberInitExt, EnvFilter, Layer};
 
 /// Converts verbosity count to log level string
 fn verbosity_to_level(verbosity: u8) -> &'static str {
@@ -18,8 +20,120 @@ fn verbosity_to_level(verbosity: u8) -> &'static str {
     }
 }
 
-/// Initialize the tracing logger with appropriate output based on mode
-pub fn init_logger(is_tui: bool, verbosity: u8, log_path: PathBuf) -> Result<(), Error> {
+/// The appender writing to `log_path`, rotated by `rotation`. Rotated files carry
+/// the period as a suffix (`termail.log.2025-01-31`); beyond `max_files` of them
+/// the oldest are deleted on each rotation.
+fn file_appender(log_path: &Path, rotation: LogRotation, max_files: usize) -> Result<RollingFileAppender, Error> {
+    let rotation = match rotation {
+        LogRotation::Never => Rotation::NEVER,
+        LogRotation::Hourly => Rotation::HOURLY,
+        LogRotation::Daily => Rotation::DAILY,
+    };
+    let mut builder = RollingFileAppender::builder()
+        .rotation(rotation)
+        .filename_prefix(log_path.file_name().and_then(|name| name.to_str()).unwrap_or("termail.log"));
+    if max_files > 0 {
+        builder = builder.max_log_files(max_files);
+    }
+    builder
+        .build(log_path.parent().unwrap_or(Path::new(".")))
+        .map_err(|e| Error::Other(format!("Failed to open log file: {}", e)))
+}
+
+/// The rotated files of `log_path` (`<name>.<period>` next to it), oldest first:
+/// the periods sort chronologically.
+fn rotated_log_files(log_path: &Path) -> Vec<PathBuf> {
+    let (Some(dir), Some(name)) = (log_path.parent(), log_path.file_name().and_then(|name| name.to_str())) else {
+        return Vec::new();
+    };
+    let prefix = format!("{}.", name);
+    let mut files: Vec<String> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|file| file.starts_with(&prefix))
+        .collect();
+    files.sort();
+    files.into_iter().map(|file| dir.join(file)).collect()
+}
+
+/// The file currently written to for `log_path`. With rotation that is the newest
+/// rotated file; without rotation, or before the first rotated file exists, it is
+/// `log_path` itself.
+pub fn current_log_file(log_path: &Path, rotation: LogRotation) -> PathBuf {
+    if rotation == LogRotation::Never {
+        return log_path.to_path_buf();
+    }
+    rotated_log_files(log_path).pop().unwrap_or_else(|| log_path.to_path_buf())
+}
+
+/// Deletes the oldest rotated files of `log_path` until at most `max_files` are
+/// left (`0` keeps them all), returning how many were deleted. The appender only
+/// prunes when it rotates, so this also runs at startup: lowering `log_max_files`
+/// or running termail briefly each day would otherwise keep every file.
+pub fn prune_old_logs(log_path: &Path, max_files: usize) -> usize {
+    if max_files == 0 {
+        return 0;
+    }
+    let files = rotated_log_files(log_path);
+    let excess = files.len().saturating_sub(max_files);
+    files[..excess]
+        .iter()
+        .filter(|file| match std::fs::remove_file(file) {
+            Ok(()) => true,
+            Err(e) => {
+                eprintln!("Failed to remove old log file {}: {}", file.display(), e);
+                false
+            }
+        })
+        .count()
+}
+
+/// Whether terminal output may use ANSI colors: not with `--no-color`, and not when
+/// the `NO_COLOR` environment variable is set to anything non-empty (see no-color.org).
+pub fn color_enabled(no_color_flag: bool, no_color_env: Option<&std::ffi::OsStr>) -> bool {
+    !no_color_flag && no_color_env.is_none_or(|value| value.is_empty())
+}
+
+/// The layer writing log lines to the log file, without colors and with the
+/// target and line of each event.
+fn file_layer<S>(appender: RollingFileAppender) -> impl Layer<S>
+where
+    S: tracing::Subscriber + for<'span> LookupSpan<'span>,
+{
+    fmt::layer()
+        .with_writer(appender)
+        .with_ansi(false)  // No ANSI colors in log files
+        .with_target(true)
+        .with_thread_ids(false)
+        .with_line_number(true)
+}
+
+/// The layer printing log lines to `writer` (stdout) in CLI mode. Without
+/// `use_color` the output carries no ANSI escape sequences.
+fn stdout_layer<S, W>(writer: W, use_color: bool) -> impl Layer<S>
+where
+    S: tracing::Subscriber + for<'span> LookupSpan<'span>,
+    W: for<'writer> fmt::MakeWriter<'writer> + 'static,
+{
+    fmt::layer()
+        .with_writer(writer)
+        .with_ansi(use_color)  // Colors for terminal, unless disabled
+        .with_target(false)
+        .with_line_number(false)
+}
+
+/// Initialize the tracing logger with appropriate output based on mode.
+/// `use_color` controls ANSI colors on stdout in CLI mode, see `color_enabled`.
+pub fn init_logger(
+    is_tui: bool,
+    verbosity: u8,
+    log_path: PathBuf,
+    use_color: bool,
+    rotation: LogRotation,
+    max_files: usize,
+) -> Result<(), Error> {
     let log_level = verbosity_to_level(verbosity);
 
     // Create the log directory if it doesn't exist
@@ -30,10 +144,8 @@ pub fn init_logger(is_tui: bool, verbosity: u8, log_path: PathBuf) -> Result<(),
     }
 
     // Create the file appender for writing logs to disk
-    let file_appender = tracing_appender::rolling::never(
-        log_path.parent().unwrap_or(&PathBuf::from(".")),
-        log_path.file_name().unwrap_or(std::ffi::OsStr::new("termail.log")),
-    );
+    let file_appender = file_appender(&log_path, rotation, max_files)?;
+    let pruned = if rotation == LogRotation::Never { 0 } else { prune_old_logs(&log_path, max_files) };
 
     let env_filter = EnvFilter::try_from_default_env()
         .unwrap_or_else(|_| EnvFilter::new(log_level));
@@ -42,37 +154,124 @@ pub fn init_logger(is_tui: bool, verbosity: u8, log_path: PathBuf) -> Result<(),
         // TUI mode: Only log to file to avoid corrupting the terminal UI
         tracing_subscriber::registry()
             .with(env_filter)
-            .with(fmt::layer()
-                .with_writer(file_appender)
-                .with_ansi(false)  // No ANSI colors in log files
-                .with_target(true)
-                .with_thread_ids(false)
-                .with_line_number(true))
+            .with(file_layer(file_appender))
             .init();
     } else {
         // CLI mode: Log to both stdout and file for immediate feedback
-        let stdout_layer = fmt::layer()
-            .with_writer(std::io::stdout)
-            .with_ansi(true)  // Colors for terminal
-            .with_target(false)
-            .with_line_number(false);
-
-        let file_layer = fmt::layer()
-            .with_writer(file_appender)
-            .with_ansi(false)  // No colors in log file
-            .with_target(true)
-            .with_line_number(true);
-
         tracing_subscriber::registry()
             .with(env_filter)
-            .with(stdout_layer)
-            .with(file_layer)
+            .with(stdout_layer(std::io::stdout, use_color))
+            .with(file_layer(file_appender))
             .init();
     }
 
     tracing::info!("Logger initialized with level: {}", log_level);
     tracing::debug!("Log file: {:?}", log_path);
+    if pruned > 0 {
+        tracing::debug!("Removed {} old log file(s)", pruned);
+    }
 
     Ok(())
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_newest_rotated_file_is_current() {
+        let dir = tempfile::tempdir().unwrap();
+        let log_path = dir.path().join("termail.log");
+        assert_eq!(current_log_file(&log_path, LogRotation::Daily), log_path);
+
+        for file in ["termail.log.2025-01-30", "termail.log.2025-01-31", "other.log.2025-02-01"] {
+            std::fs::write(dir.path().join(file), b"").unwrap();
+        }
+        assert_eq!(current_log_file(&log_path, LogRotation::Daily), dir.path().join("termail.log.2025-01-31"));
+        assert_eq!(current_log_file(&log_path, LogRotation::Never), log_path);
+    }
+
+    #[test]
+    fn log_lines_land_in_the_configured_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let log_path = dir.path().join("logs/termail.log");
+        std::fs::create_dir_all(log_path.parent().unwrap()).unwrap();
+        for rotation in [LogRotation::Never, LogRotation::Daily] {
+            let appender = file_appender(&log_path, rotation, 3).unwrap();
+            let subscriber = tracing_subscriber::registry().with(file_layer(appender));
+            tracing::subscriber::with_default(subscriber, || tracing::warn!("synced {} messages", 42));
+
+            let contents = std::fs::read_to_string(current_log_file(&log_path, rotation)).unwrap();
+            assert!(contents.contains("synced 42 messages"), "{:?}", contents);
+            assert!(!contents.contains('\x1b'));
+        }
+    }
+
+    #[test]
+    fn each_verbose_flag_lowers_the_level() {
+        for (verbosity, level) in [(0, "error"), (1, "info"), (2, "debug"), (3, "trace"), (9, "trace"), (u8::MAX, "trace")] {
+            assert_eq!(verbosity_to_level(verbosity), level, "-v x{}", verbosity);
+        }
+    }
+
+    #[test]
+    fn old_rotated_logs_beyond_the_limit_are_pruned() {
+        let dir = tempfile::tempdir().unwrap();
+        let log_path = dir.path().join("termail.log");
+        for day in 1..=10 {
+            std::fs::write(dir.path().join(format!("termail.log.2025-01-{:02}", day)), b"").unwrap();
+        }
+        std::fs::write(dir.path().join("other.log.2025-01-01"), b"").unwrap();
+
+        assert_eq!(prune_old_logs(&log_path, 0), 0);
+        assert_eq!(prune_old_logs(&log_path, 7), 3);
+        let left: Vec<PathBuf> = rotated_log_files(&log_path);
+        assert_eq!(left.len(), 7);
+        assert_eq!(left[0], dir.path().join("termail.log.2025-01-04"));
+        assert!(dir.path().join("other.log.2025-01-01").exists());
+        assert_eq!(prune_old_logs(&log_path, 7), 0);
+    }
+
+    #[test]
+    fn no_color_env_disables_colors_unless_empty() {
+        assert!(color_enabled(false, None));
+        assert!(color_enabled(false, Some(std::ffi::OsStr::new(""))));
+        assert!(!color_enabled(false, Some(std::ffi::OsStr::new("1"))));
+        assert!(!color_enabled(true, None));
+    }
+
+    /// Everything the CLI would have printed to stdout.
+    #[derive(Clone, Default)]
+    struct Captured(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn cli_output(no_color_flag: bool, no_color_env: Option<&str>) -> String {
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let use_color = color_enabled(no_color_flag, no_color_env.map(std::ffi::OsStr::new));
+        let subscriber = tracing_subscriber::registry().with(stdout_layer(move || writer.clone(), use_color));
+        tracing::subscriber::with_default(subscriber, || tracing::warn!("3 new messages"));
+        let output = captured.0.lock().unwrap().clone();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn cli_output_has_no_escape_sequences_without_color() {
+        assert!(cli_output(false, None).contains('\x1b'));
+        for output in [cli_output(true, None), cli_output(false, Some("1"))] {
+            assert!(output.contains("3 new messages"));
+            assert!(!output.contains('\x1b'), "{:?}", output);
+        }
+    }
+}
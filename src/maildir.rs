@@ -1,19 +1,186 @@
 use google_gmail1::api::Message;
 use crate::error::Error;
-use crate::core::email::{EmailMessage, EmailSender, MimeType, EmailAttachment};
+use crate::core::email::{AttachmentStats, EmailMessage, EmailSender, MimeType, EmailAttachment, AttachmentDisposition, SNIPPET_MAX_LENGTH, make_snippet};
+use crate::core::label::spam_label_changes;
 use maildir::Maildir;
 use mailparse::*;
+use mailparse::body::Body;
 use rusqlite::{params, Connection, OptionalExtension};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 use chrono::DateTime;
 
+/// Prefix for `message_map` keys of messages that only exist locally (e.g. imported
+/// from a file). Sync code must skip these since Gmail does not know about them.
+pub const LOCAL_ID_PREFIX: &str = "local:";
+
+/// Returns true if a `message_map` key refers to a local-only message.
+pub fn is_local_id(id: &str) -> bool {
+    id.starts_with(LOCAL_ID_PREFIX)
+}
+
+/// Splits an mbox file into individual RFC822 messages.
+///
+/// Messages are separated by lines starting with `From ` that begin the file or
+/// follow a blank line. The separator line itself is dropped, and the mboxrd
+/// quoting of body lines is undone: `>From `, `>>From `, ... lose one `>` each.
+pub fn split_mbox(content: &[u8]) -> Vec<Vec<u8>> {
+    let mut messages: Vec<Vec<u8>> = Vec::new();
+    let mut current: Option<Vec<u8>> = None;
+    let mut previous_blank = true;
+
+    for line in content.split_inclusive(|&b| b == b'\n') {
+        if previous_blank && line.starts_with(b"From ") {
+            if let Some(message) = current.take() {
+                messages.push(message);
+            }
+            current = Some(Vec::new());
+            previous_blank = false;
+            continue;
+        }
+
+        let is_blank = line.iter().all(|b| *b == b'\r' || *b == b'\n');
+        if let Some(message) = current.as_mut() {
+            if is_quoted_from_line(line) {
+                message.extend_from_slice(&line[1..]);
+            } else {
+                message.extend_from_slice(line);
+            }
+        }
+        previous_blank = is_blank;
+    }
+
+    if let Some(message) = current {
+        messages.push(message);
+    }
+    messages
+}
+
+/// Whether `line` is an mboxrd-quoted `From ` line: one or more `>` then `From `.
+fn is_quoted_from_line(line: &[u8]) -> bool {
+    let unquoted = line.iter().position(|b| *b != b'>').unwrap_or(line.len());
+    unquoted > 0 && line[unquoted..].starts_with(b"From ")
+}
+
+/// What `MaildirManager::compact` removed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompactReport {
+    pub label_rows_removed: usize,
+    pub metadata_rows_removed: usize,
+    /// Search index rows whose message file is gone.
+    pub search_rows_removed: usize,
+    /// Shrinkage of the database file from `VACUUM` (0 if it did not shrink).
+    pub bytes_freed: u64,
+}
+
+impl std::fmt::Display for CompactReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Removed {} orphaned label row(s), {} orphaned metadata row(s) and {} orphaned search index row(s), freed {} bytes",
+            self.label_rows_removed, self.metadata_rows_removed, self.search_rows_removed, self.bytes_freed
+        )
+    }
+}
+
+/// Rows in the sync state database that refer to messages which no longer exist,
+/// as found by `MaildirManager::find_orphans`. Each list holds maildir_ids.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OrphanReport {
+    /// Messages with `label_map` rows but no `message_map` row or no file.
+    pub labels: Vec<String>,
+    /// Messages with `message_metadata` but no file.
+    pub metadata: Vec<String>,
+    /// Messages in `message_map` whose file is gone.
+    pub mappings: Vec<String>,
+}
+
+impl OrphanReport {
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty() && self.metadata.is_empty() && self.mappings.is_empty()
+    }
+}
+
+impl std::fmt::Display for OrphanReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return write!(f, "Database is consistent with the maildir");
+        }
+        for (what, ids) in [
+            ("label", &self.labels),
+            ("metadata", &self.metadata),
+            ("mapping", &self.mappings),
+        ] {
+            writeln!(f, "{} orphaned {} row(s)", ids.len(), what)?;
+            for id in ids {
+                writeln!(f, "  {}", id)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parsed messages (with attachments) kept by `load_email_with_attachments`.
+const EMAIL_CACHE_CAPACITY: usize = 16;
+
+/// A small least-recently-used cache of parsed messages, keyed by maildir_id.
+#[derive(Debug, Default)]
+pub struct EmailCache {
+    capacity: usize,
+    /// Most recently used last.
+    entries: Vec<(String, EmailMessage)>,
+}
+
+impl EmailCache {
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, entries: Vec::new() }
+    }
+
+    /// The cached message for `maildir_id`, which becomes the most recently used.
+    pub fn get(&mut self, maildir_id: &str) -> Option<EmailMessage> {
+        let index = self.entries.iter().position(|(id, _)| id == maildir_id)?;
+        let entry = self.entries.remove(index);
+        let email = entry.1.clone();
+        self.entries.push(entry);
+        Some(email)
+    }
+
+    /// Caches `email`, evicting the least recently used message when full.
+    pub fn insert(&mut self, maildir_id: String, email: EmailMessage) {
+        self.remove(&maildir_id);
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.remove(0);
+        }
+        self.entries.push((maildir_id, email));
+    }
+
+    pub fn remove(&mut self, maildir_id: &str) {
+        self.entries.retain(|(id, _)| id != maildir_id);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
 
 pub struct MaildirManager {
     maildir: Maildir,
     db_path: PathBuf,
     connection: Mutex<Connection>,
+    /// Whether the linked SQLite has FTS5 and `message_fts` could be created.
+    /// When false, `search` falls back to scanning every message.
+    fts_available: bool,
+    /// Recently opened messages, so reopening one skips reading and parsing it.
+    /// Entries are dropped when their file is moved or deleted.
+    email_cache: Mutex<EmailCache>,
 }
 
 impl MaildirManager {
@@ -29,12 +196,57 @@ impl MaildirManager {
         let db_path = maildir.path().join("sync_state.db");
         
         let conn = Self::open_or_create_database(&db_path)?;
+        let fts_available = Self::create_fts_table(&conn);
         
-        Ok(Self { 
+        let manager = Self { 
             maildir,
             db_path,
             connection: Mutex::new(conn),
-        })
+            fts_available,
+            email_cache: Mutex::new(EmailCache::new(EMAIL_CACHE_CAPACITY)),
+        };
+        if let Err(e) = manager.backfill_search_index() {
+            tracing::warn!("Failed to build the search index: {}", e);
+        }
+        Ok(manager)
+    }
+
+    /// Indexes every message for search when the index is empty, e.g. for mail
+    /// stored before search existed. Returns the number of messages indexed.
+    fn backfill_search_index(&self) -> Result<usize, Error> {
+        if !self.fts_available {
+            return Ok(0);
+        }
+        let indexed_rows: i64 = self.connection.lock()
+            .map_err(|e| Error::Other(format!("Failed to lock connection: {}", e)))?
+            .query_row("SELECT COUNT(*) FROM message_fts", [], |row| row.get(0))
+            .map_err(|e| Error::Other(format!("Failed to count search index rows: {}", e)))?;
+        if indexed_rows > 0 {
+            return Ok(0);
+        }
+
+        let mut indexed = 0;
+        for subdir in ["new", "cur"] {
+            let dir = self.maildir.path().join(subdir);
+            let Ok(entries) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+            for path in entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()).filter(|path| path.is_file()) {
+                let filename = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+                let maildir_id = maildir_id_from_filename(filename);
+                let Ok(raw_content) = std::fs::read(&path) else {
+                    continue;
+                };
+                if let Ok(email) = self.parse_rfc822_email(&raw_content, maildir_id.clone(), false, false) {
+                    self.index_message(&maildir_id, &email.subject, &email.from.full_string(), &email.body)?;
+                    indexed += 1;
+                }
+            }
+        }
+        if indexed > 0 {
+            tracing::info!("Indexed {} existing message(s) for search", indexed);
+        }
+        Ok(indexed)
     }
 
     fn open_or_create_database(sync_state_path: &Path) -> Result<Connection, Error> {
@@ -50,6 +262,26 @@ impl MaildirManager {
         Ok(conn)
     }
 
+    /// Creates the FTS5 table used by `search`. Returns false if this SQLite build
+    /// has no FTS5 module, in which case search falls back to a scan.
+    fn create_fts_table(conn: &Connection) -> bool {
+        match conn.execute(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS message_fts USING fts5(
+                maildir_id UNINDEXED,
+                subject,
+                sender,
+                body
+            )",
+            [],
+        ) {
+            Ok(_) => true,
+            Err(e) => {
+                tracing::info!("FTS5 not available, search will scan messages: {}", e);
+                false
+            }
+        }
+    }
+
     // create tables if don't exist
     fn create_tables(conn: &Connection) -> Result<(), Error> {
         // create sync_state table
@@ -74,7 +306,8 @@ impl MaildirManager {
         conn.execute(
             "CREATE TABLE IF NOT EXISTS message_map (
                 gmail_id TEXT PRIMARY KEY,
-                maildir_id TEXT NOT NULL UNIQUE
+                maildir_id TEXT NOT NULL UNIQUE,
+                thread_id TEXT
             )",
             [],
         )
@@ -87,7 +320,8 @@ impl MaildirManager {
                 maildir_id TEXT PRIMARY KEY,
                 date_timestamp INTEGER NOT NULL,
                 subject TEXT,
-                sender TEXT
+                sender TEXT,
+                snippet TEXT
             )",
             [],
         )
@@ -101,20 +335,113 @@ impl MaildirManager {
         .map_err(|e| Error::Other(format!("Failed to create date index: {}", e)))?;
 
         // create label_map table
+        // Labels go away together with their message_map row. message_metadata has no
+        // such key on purpose: messages synced over IMAP have metadata but no mapping
+        // until they are first labelled (see `add_label_mappings`).
         conn.execute(
             "CREATE TABLE IF NOT EXISTS label_map (
                 maildir_id TEXT NOT NULL,
                 label TEXT NOT NULL,
                 PRIMARY KEY (maildir_id, label),
-                FOREIGN KEY (maildir_id) REFERENCES message_map(maildir_id)
+                FOREIGN KEY (maildir_id) REFERENCES message_map(maildir_id) ON DELETE CASCADE
             )",
             [],
         )
         .map_err(|e| Error::Other(format!("Failed to create label_map table: {}", e)))?;
 
+        // create sync_checkpoint table
+        // a row exists while a full sync is in progress, holding the page it resumes from
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS sync_checkpoint (
+                key TEXT PRIMARY KEY,
+                page_token TEXT
+            )",
+            [],
+        )
+        .map_err(|e| Error::Other(format!("Failed to create sync_checkpoint table: {}", e)))?;
+
+        Self::migrate_label_map_cascade(conn)?;
+        Self::migrate_thread_id_column(conn)?;
+        Self::migrate_snippet_column(conn)?;
+
+        Ok(())
+    }
+
+    /// Adds the `thread_id` column to `message_map` for databases created before
+    /// threads were tracked. Existing rows get their thread id on the next full sync.
+    fn migrate_thread_id_column(conn: &Connection) -> Result<(), Error> {
+        let has_column: bool = conn
+            .query_row(
+                "SELECT COUNT(*) FROM pragma_table_info('message_map') WHERE name = 'thread_id'",
+                [],
+                |row| row.get::<_, i64>(0),
+            )
+            .map(|count| count > 0)
+            .map_err(|e| Error::Other(format!("Failed to inspect message_map: {}", e)))?;
+
+        if !has_column {
+            conn.execute("ALTER TABLE message_map ADD COLUMN thread_id TEXT", [])
+                .map_err(|e| Error::Other(format!("Failed to add thread_id column: {}", e)))?;
+        }
+        Ok(())
+    }
+
+    /// Caches from before snippets were stored lack the column. Their rows get one
+    /// the next time the message's metadata is saved.
+    fn migrate_snippet_column(conn: &Connection) -> Result<(), Error> {
+        let has_column: bool = conn
+            .query_row(
+                "SELECT COUNT(*) FROM pragma_table_info('message_metadata') WHERE name = 'snippet'",
+                [],
+                |row| row.get::<_, i64>(0),
+            )
+            .map(|count| count > 0)
+            .map_err(|e| Error::Other(format!("Failed to inspect message_metadata: {}", e)))?;
+
+        if !has_column {
+            conn.execute("ALTER TABLE message_metadata ADD COLUMN snippet TEXT", [])
+                .map_err(|e| Error::Other(format!("Failed to add snippet column: {}", e)))?;
+        }
         Ok(())
     }
 
+    /// Databases created before `ON DELETE CASCADE` was added keep the old foreign
+    /// key, which makes deleting a mapping that still has labels fail. SQLite cannot
+    /// alter a foreign key, so the table is rebuilt (dropping rows that were already
+    /// orphaned, since they would violate the key).
+    fn migrate_label_map_cascade(conn: &Connection) -> Result<(), Error> {
+        let on_delete: Option<String> = conn
+            .query_row(
+                "SELECT on_delete FROM pragma_foreign_key_list('label_map') WHERE \"table\" = 'message_map'",
+                [],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| Error::Other(format!("Failed to inspect label_map foreign key: {}", e)))?;
+
+        if on_delete.as_deref().is_none_or(|action| action == "CASCADE") {
+            return Ok(());
+        }
+
+        tracing::info!("Migrating label_map to ON DELETE CASCADE");
+        conn.execute_batch(
+            "BEGIN;
+            CREATE TABLE label_map_new (
+                maildir_id TEXT NOT NULL,
+                label TEXT NOT NULL,
+                PRIMARY KEY (maildir_id, label),
+                FOREIGN KEY (maildir_id) REFERENCES message_map(maildir_id) ON DELETE CASCADE
+            );
+            INSERT INTO label_map_new (maildir_id, label)
+                SELECT maildir_id, label FROM label_map
+                WHERE maildir_id IN (SELECT maildir_id FROM message_map);
+            DROP TABLE label_map;
+            ALTER TABLE label_map_new RENAME TO label_map;
+            COMMIT;",
+        )
+        .map_err(|e| Error::Other(format!("Failed to migrate label_map: {}", e)))
+    }
+
 
     // read last_sync_id from the database
     pub fn get_last_sync_id(&self) -> u64 {
@@ -147,6 +474,45 @@ impl MaildirManager {
         Ok(())
     }
 
+    /// The checkpoint of an unfinished full sync: `Some(page_token)` if one was
+    /// interrupted, where a `None` token means it stopped on the first page.
+    /// Messages it already saved are in `message_map`, so resuming skips them.
+    pub fn get_sync_checkpoint(&self) -> Result<Option<Option<String>>, Error> {
+        let conn = self.connection.lock()
+            .map_err(|e| Error::Other(format!("Failed to lock sync_state connection: {}", e)))?;
+
+        conn.query_row(
+            "SELECT page_token FROM sync_checkpoint WHERE key = 'full_sync'",
+            [],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| Error::Other(format!("Failed to read sync checkpoint: {}", e)))
+    }
+
+    /// Records that a full sync has to resume from `page_token` (`None`: from the start).
+    pub fn save_sync_checkpoint(&self, page_token: Option<&str>) -> Result<(), Error> {
+        let conn = self.connection.lock()
+            .map_err(|e| Error::Other(format!("Failed to lock sync_state connection: {}", e)))?;
+
+        conn.execute(
+            "INSERT OR REPLACE INTO sync_checkpoint (key, page_token) VALUES ('full_sync', ?1)",
+            params![page_token],
+        )
+        .map_err(|e| Error::Other(format!("Failed to save sync checkpoint: {}", e)))?;
+        Ok(())
+    }
+
+    /// Forgets the checkpoint once a full sync has finished.
+    pub fn clear_sync_checkpoint(&self) -> Result<(), Error> {
+        let conn = self.connection.lock()
+            .map_err(|e| Error::Other(format!("Failed to lock sync_state connection: {}", e)))?;
+
+        conn.execute("DELETE FROM sync_checkpoint WHERE key = 'full_sync'", [])
+            .map_err(|e| Error::Other(format!("Failed to clear sync checkpoint: {}", e)))?;
+        Ok(())
+    }
+
     // returns the filesystem path to the db
     pub fn get_sync_state_path(&self) -> PathBuf {
         self.db_path.clone()
@@ -235,8 +601,10 @@ impl MaildirManager {
         let conn = self.connection.lock()
             .map_err(|e| Error::Other(format!("Failed to lock sync_state connection: {}", e)))?;
 
+        // An upsert rather than INSERT OR REPLACE keeps the row's thread_id
         conn.execute(
-            "INSERT OR REPLACE INTO message_map (gmail_id, maildir_id) VALUES (?1, ?2)",
+            "INSERT INTO message_map (gmail_id, maildir_id) VALUES (?1, ?2)
+             ON CONFLICT(gmail_id) DO UPDATE SET maildir_id = excluded.maildir_id",
             params![gmail_id, maildir_id],
         )
         .map_err(|e| Error::Other(format!("Failed to add message_map row: {}", e)))?;
@@ -245,7 +613,7 @@ impl MaildirManager {
     }
 
     /// Save or update metadata for an email
-    pub fn save_metadata(&self, maildir_id: &str, date_str: &str, subject: &str, sender: &str) -> Result<(), Error> {
+    pub fn save_metadata(&self, maildir_id: &str, date_str: &str, subject: &str, sender: &str, snippet: &str) -> Result<(), Error> {
         let date_timestamp = DateTime::parse_from_rfc2822(date_str)
             .map(|dt| dt.timestamp())
             .map_err(|e| Error::Other(format!("Failed to parse date: {}", e)))?;
@@ -254,8 +622,8 @@ impl MaildirManager {
             .map_err(|e| Error::Other(format!("Failed to lock connection: {}", e)))?;
 
         conn.execute(
-            "INSERT OR REPLACE INTO message_metadata (maildir_id, date_timestamp, subject, sender) VALUES (?1, ?2, ?3, ?4)",
-            params![maildir_id, date_timestamp, subject, sender],
+            "INSERT OR REPLACE INTO message_metadata (maildir_id, date_timestamp, subject, sender, snippet) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![maildir_id, date_timestamp, subject, sender, snippet],
         ).map_err(|e| Error::Other(format!("Failed to save metadata: {}", e)))?;
 
         tracing::debug!("Saved metadata for {}: {} (timestamp: {})", maildir_id, subject, date_timestamp);
@@ -283,6 +651,33 @@ impl MaildirManager {
     }
 
     // Check if metadata exists for a maildir_id
+    /// The cached `date_timestamp` of a message, if any
+    pub fn get_metadata_timestamp(&self, maildir_id: &str) -> Option<i64> {
+        let conn = self.connection.lock().ok()?;
+        conn.query_row(
+            "SELECT date_timestamp FROM message_metadata WHERE maildir_id = ?1",
+            params![maildir_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .ok()
+        .flatten()
+    }
+
+    /// The body preview cached by `save_metadata`, if there is one.
+    pub fn get_metadata_snippet(&self, maildir_id: &str) -> Option<String> {
+        let conn = self.connection.lock().ok()?;
+        conn.query_row(
+            "SELECT snippet FROM message_metadata WHERE maildir_id = ?1",
+            params![maildir_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .ok()
+        .flatten()
+        .flatten()
+    }
+
     pub fn has_metadata(&self, maildir_id: &str) -> bool {
         let conn = match self.connection.lock() {
             Ok(c) => c,
@@ -297,10 +692,21 @@ impl MaildirManager {
         .is_ok()
     }
 
+    /// Labels `maildir_id`. Messages without a mapping (synced over IMAP) get a
+    /// `local:` one first, since `label_map` rows must belong to a `message_map` row.
     pub fn add_label_mappings(&self, maildir_id: &str, labels: &[String]) -> Result<(), Error> {
         let conn = self.connection.lock()
             .map_err(|e| Error::Other(format!("Failed to lock sync_state connection: {}", e)))?;
-        
+
+        if !labels.is_empty() {
+            // `maildir_id` is unique, so this leaves an existing mapping alone
+            conn.execute(
+                "INSERT OR IGNORE INTO message_map (gmail_id, maildir_id) VALUES (?1, ?2)",
+                params![format!("{}{}", LOCAL_ID_PREFIX, maildir_id), maildir_id],
+            )
+            .map_err(|e| Error::Other(format!("Failed to add message_map row: {}", e)))?;
+        }
+
         for label in labels {
             conn.execute(
                 "INSERT OR REPLACE INTO label_map (maildir_id, label) VALUES (?1, ?2)",
@@ -320,7 +726,7 @@ impl MaildirManager {
                 "DELETE FROM label_map WHERE maildir_id = ?1",
                 params![maildir_id],
             )
-            .map_err(|e| Error::Other(format!("Failed to remove label_map row: {}", e))).unwrap();
+            .map_err(|e| Error::Other(format!("Failed to remove label_map row: {}", e)))?;
         }
         
         Ok(())
@@ -346,6 +752,55 @@ impl MaildirManager {
         Ok(maildir_ids)
     }
 
+    /// The labels `maildir_id` carries, sorted. The reverse of `get_maildir_ids_with_label`.
+    pub fn labels_for(&self, maildir_id: &str) -> Result<Vec<String>, Error> {
+        let conn = self.connection.lock()
+            .map_err(|e| Error::Other(format!("Failed to lock connection: {}", e)))?;
+
+        let mut stmt = conn.prepare("SELECT label FROM label_map WHERE maildir_id = ?1 ORDER BY label")
+            .map_err(|e| Error::Other(format!("Failed to prepare label query: {}", e)))?;
+        let labels = stmt.query_map(params![maildir_id], |row| row.get(0))
+            .map_err(|e| Error::Other(format!("Failed to query labels: {}", e)))?
+            .collect::<Result<Vec<String>, _>>()
+            .map_err(|e| Error::Other(format!("Failed to read label row: {}", e)))?;
+        Ok(labels)
+    }
+
+    /// Number of messages carrying `label`
+    pub fn count_with_label(&self, label: &str) -> Result<usize, Error> {
+        let conn = self.connection.lock()
+            .map_err(|e| Error::Other(format!("Failed to lock sync_state connection: {}", e)))?;
+
+        let count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM label_map WHERE label = ?1",
+            params![label],
+            |row| row.get(0),
+        )
+        .map_err(|e| Error::Other(format!("Failed to count emails with label: {}", e)))?;
+
+        Ok(count as usize)
+    }
+
+    /// Subject of the newest message carrying `label`, read from the metadata cache so
+    /// no message file has to be parsed. `None` if the label has no cached messages.
+    pub fn latest_subject_for_label(&self, label: &str) -> Result<Option<String>, Error> {
+        let conn = self.connection.lock()
+            .map_err(|e| Error::Other(format!("Failed to lock sync_state connection: {}", e)))?;
+
+        conn.query_row(
+            "SELECT m.subject FROM message_metadata m
+             JOIN label_map l ON l.maildir_id = m.maildir_id
+             WHERE l.label = ?1
+             ORDER BY m.date_timestamp DESC
+             LIMIT 1",
+            params![label],
+            |row| row.get::<_, Option<String>>(0),
+        )
+        .optional()
+        .map(|subject| subject.map(|subject| subject.unwrap_or_default()))
+        .map_err(|e| Error::Other(format!("Failed to query latest subject for label: {}", e)))
+    }
+
     /// Check if a maildir_id has a specific label in the database
     pub fn has_label(&self, maildir_id: &str, label: &str) -> Result<bool, Error> {
         let conn = self.connection.lock()
@@ -361,15 +816,191 @@ impl MaildirManager {
         Ok(count > 0)
     }
 
+    /// Removes a single label from a maildir_id, leaving its other labels alone.
+    pub fn remove_label(&self, maildir_id: &str, label: &str) -> Result<(), Error> {
+        let conn = self.connection.lock()
+            .map_err(|e| Error::Other(format!("Failed to lock sync_state connection: {}", e)))?;
+
+        conn.execute(
+            "DELETE FROM label_map WHERE maildir_id = ?1 AND label = ?2",
+            params![maildir_id, label],
+        )
+        .map_err(|e| Error::Other(format!("Failed to remove label_map row: {}", e)))?;
+
+        Ok(())
+    }
+
+    /// Records the Gmail thread a message belongs to.
+    pub fn set_thread_id(&self, gmail_id: &str, thread_id: &str) -> Result<(), Error> {
+        let conn = self.connection.lock()
+            .map_err(|e| Error::Other(format!("Failed to lock sync_state connection: {}", e)))?;
+
+        conn.execute(
+            "UPDATE message_map SET thread_id = ?2 WHERE gmail_id = ?1",
+            params![gmail_id, thread_id],
+        )
+        .map_err(|e| Error::Other(format!("Failed to save thread_id: {}", e)))?;
+        Ok(())
+    }
+
+    /// Returns the Gmail thread id of a message, if it is known.
+    pub fn get_thread_id(&self, maildir_id: &str) -> Result<Option<String>, Error> {
+        let conn = self.connection.lock()
+            .map_err(|e| Error::Other(format!("Failed to lock sync_state connection: {}", e)))?;
+
+        conn.query_row(
+            "SELECT thread_id FROM message_map WHERE maildir_id = ?1",
+            params![maildir_id],
+            |row| row.get::<_, Option<String>>(0),
+        )
+        .optional()
+        .map(Option::flatten)
+        .map_err(|e| Error::Other(format!("Failed to fetch thread_id: {}", e)))
+    }
+
+    /// Reverse lookup of `get_maildir_id`.
+    pub fn get_gmail_id(&self, maildir_id: &str) -> Result<Option<String>, Error> {
+        let conn = self.connection.lock()
+            .map_err(|e| Error::Other(format!("Failed to lock sync_state connection: {}", e)))?;
+
+        conn.query_row(
+            "SELECT gmail_id FROM message_map WHERE maildir_id = ?1",
+            params![maildir_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| Error::Other(format!("Failed to fetch gmail_id: {}", e)))
+    }
+
+    /// Marks a message as read locally: moves it from `new` to `cur` and drops its
+    /// UNREAD label. Returns `false` if the message was already read.
+    pub fn mark_read(&self, maildir_id: &str) -> Result<bool, Error> {
+        let maildir_id = maildir_id.to_string();
+        let was_new = self.get_message_directory(&maildir_id)? == "new";
+        let was_unread = self.has_label(&maildir_id, "UNREAD")?;
+
+        if was_new {
+            self.maildir_move_new_to_cur(&maildir_id)?;
+        }
+        if was_unread {
+            self.remove_label(&maildir_id, "UNREAD")?;
+        }
+        Ok(was_new || was_unread)
+    }
+
     pub fn delete_message(&self, maildir_id: String) -> Result<(), Error> {
-        
+        self.forget_cached(&maildir_id);
+
         // delete message from maildir
         self.maildir.delete(&maildir_id)?;
         
         Ok(())
     }
 
+    /// Marks a message as unread locally: the reverse of `mark_read`. Moving the file
+    /// from `cur` back to `new` stores it under a new maildir id, so the rows about it
+    /// follow it there. Returns the message's maildir id after the move.
+    pub fn mark_unread(&self, maildir_id: &str) -> Result<String, Error> {
+        let mut maildir_id = maildir_id.to_string();
+        if self.get_message_directory(&maildir_id)? == "cur" {
+            let new_maildir_id = self.maildir_move_cur_to_new(&maildir_id)?;
+            self.rename_message(&maildir_id, &new_maildir_id)?;
+            maildir_id = new_maildir_id;
+        }
+        self.add_label_mappings(&maildir_id, &["UNREAD".to_string()])?;
+        Ok(maildir_id)
+    }
+
+    /// Points every row about `old_id` at `new_id`, after its file was stored again.
+    fn rename_message(&self, old_id: &str, new_id: &str) -> Result<(), Error> {
+        let mut conn = self.connection.lock()
+            .map_err(|e| Error::Other(format!("Failed to lock sync_state connection: {}", e)))?;
+        let tx = conn.transaction()
+            .map_err(|e| Error::Other(format!("Failed to start rename transaction: {}", e)))?;
+
+        // label_map references message_map, so its rows are moved around the update
+        let labels = {
+            let mut stmt = tx.prepare("SELECT label FROM label_map WHERE maildir_id = ?1")
+                .map_err(|e| Error::Other(format!("Failed to prepare label query: {}", e)))?;
+            let labels = stmt.query_map(params![old_id], |row| row.get(0))
+                .map_err(|e| Error::Other(format!("Failed to query labels: {}", e)))?
+                .collect::<Result<Vec<String>, _>>()
+                .map_err(|e| Error::Other(format!("Failed to read label row: {}", e)))?;
+            labels
+        };
+        tx.execute("DELETE FROM label_map WHERE maildir_id = ?1", params![old_id])
+            .map_err(|e| Error::Other(format!("Failed to remove label_map rows: {}", e)))?;
+        for table in ["message_map", "message_metadata"] {
+            tx.execute(&format!("UPDATE {} SET maildir_id = ?2 WHERE maildir_id = ?1", table), params![old_id, new_id])
+                .map_err(|e| Error::Other(format!("Failed to update {}: {}", table, e)))?;
+        }
+        if self.fts_available {
+            tx.execute("UPDATE message_fts SET maildir_id = ?2 WHERE maildir_id = ?1", params![old_id, new_id])
+                .map_err(|e| Error::Other(format!("Failed to update search index: {}", e)))?;
+        }
+        for label in &labels {
+            tx.execute("INSERT INTO label_map (maildir_id, label) VALUES (?1, ?2)", params![new_id, label])
+                .map_err(|e| Error::Other(format!("Failed to add label_map row: {}", e)))?;
+        }
+
+        tx.commit()
+            .map_err(|e| Error::Other(format!("Failed to commit rename: {}", e)))
+    }
+
+    /// Adds and removes labels on a message in one go. Adding UNREAD also moves the
+    /// file back to `new` (see `mark_unread`), so the message id may change.
+    pub fn modify_labels(&self, maildir_id: &str, add: &[String], remove: &[String]) -> Result<(), Error> {
+        let maildir_id = if add.iter().any(|label| label == "UNREAD") {
+            self.mark_unread(maildir_id)?
+        } else {
+            maildir_id.to_string()
+        };
+        self.add_label_mappings(&maildir_id, add)?;
+        for label in remove {
+            self.remove_label(&maildir_id, label)?;
+        }
+        Ok(())
+    }
+
+    /// Moves a message to Trash locally by labelling it `TRASH`. The file is kept;
+    /// trashed messages are hidden from every folder except TRASH itself.
+    pub fn trash_message(&self, maildir_id: &str) -> Result<(), Error> {
+        self.add_label_mappings(maildir_id, &["TRASH".to_string()])
+    }
+
+    /// Marks a message as spam or not spam locally. Like TRASH, SPAM hides the message
+    /// from every folder except SPAM itself.
+    pub fn set_spam(&self, maildir_id: &str, spam: bool) -> Result<(), Error> {
+        let (add, remove) = spam_label_changes(spam);
+        self.modify_labels(maildir_id, &add, &remove)
+    }
+
+    /// Permanently removes a message: the maildir file and every database row about it.
+    pub fn purge_message(&self, maildir_id: &str) -> Result<(), Error> {
+        {
+            let conn = self.connection.lock()
+                .map_err(|e| Error::Other(format!("Failed to lock sync_state connection: {}", e)))?;
+
+            // label_map references message_map, so it has to go first
+            for sql in [
+                "DELETE FROM label_map WHERE maildir_id = ?1",
+                "DELETE FROM message_metadata WHERE maildir_id = ?1",
+                "DELETE FROM message_map WHERE maildir_id = ?1",
+            ] {
+                conn.execute(sql, params![maildir_id])
+                    .map_err(|e| Error::Other(format!("Failed to purge {}: {}", maildir_id, e)))?;
+            }
+            if self.fts_available {
+                conn.execute("DELETE FROM message_fts WHERE maildir_id = ?1", params![maildir_id])
+                    .map_err(|e| Error::Other(format!("Failed to purge {} from search index: {}", maildir_id, e)))?;
+            }
+        }
+
+        self.delete_message(maildir_id.to_string())
+    }
+
     pub fn maildir_move_new_to_cur(&self, maildir_id: &String) -> Result<(), Error> {
+        self.forget_cached(maildir_id);
         self.maildir.move_new_to_cur(&maildir_id)?;
         Ok(())
     }
@@ -388,6 +1019,7 @@ impl MaildirManager {
             .map_err(|e| Error::Other(format!("Failed to read message: {}", e)))?;
         
         // delete message from cur
+        self.forget_cached(maildir_id);
         self.maildir.delete(&maildir_id)?;
         
         // move message to new
@@ -416,39 +1048,103 @@ impl MaildirManager {
         let raw_content = message.raw.clone().unwrap();
         
         // save message to correct maildir subdirectory
-        let maildir_id = if maildir_subdir == "cur" {
-            self.maildir.store_cur_with_flags(&raw_content, "")
-                .map_err(|e| Error::Other(format!("Failed to store message in cur: {}", e)))?
-        } else if maildir_subdir == "new" {
-            self.maildir.store_new(&raw_content)
-                .map_err(|e| Error::Other(format!("Failed to store message in new: {}", e)))?
-        } else {
-            return Err(Error::Other(format!("Invalid maildir subdirectory: {}", maildir_subdir)));
-        };
+        let maildir_id = self.store_raw_message(&raw_content, &maildir_subdir)?;
 
         // Parse the message to extract metadata and save it to the database cache
-        match parse_mail(&raw_content) {
-            Ok(parsed) => {
-                let date = parsed.headers.get_first_value("Date").unwrap_or_default();
-                let subject = parsed.headers.get_first_value("Subject").unwrap_or_default();
-                let from = parsed.headers.get_first_value("From").unwrap_or_default();
+        self.save_metadata_from_raw(&maildir_id, &raw_content);
 
-                if let Err(e) = self.save_metadata(&maildir_id, &date, &subject, &from) {
-                    tracing::warn!("Failed to save metadata for {}: {}", maildir_id, e);
-                }
+        // add mapping to message_map table FIRST (before label_map due to foreign key constraint)
+        self.add_mapping(message_id.clone(), maildir_id.clone())?;
+        if let Some(thread_id) = &message.thread_id {
+            self.set_thread_id(&message_id, thread_id)?;
+        }
+
+        // save labels to label_map table (after message_map entry exists)
+        self.add_label_mappings(&maildir_id, labels)?;
+
+        Ok(maildir_id)
+    }
+
+    /// Stores raw RFC822 bytes in `new` or `cur`, caching its metadata and indexing
+    /// it for search. Returns the new maildir_id. Used for messages synced over IMAP.
+    pub fn store_message(&self, raw_content: &[u8], maildir_subdir: &str) -> Result<String, Error> {
+        let maildir_id = self.store_raw_message(raw_content, maildir_subdir)?;
+        self.save_metadata_from_raw(&maildir_id, raw_content);
+        Ok(maildir_id)
+    }
+
+    /// Stores raw RFC822 bytes in `new` or `cur` and returns the new maildir_id.
+    fn store_raw_message(&self, raw_content: &[u8], maildir_subdir: &str) -> Result<String, Error> {
+        if maildir_subdir == "cur" {
+            self.maildir.store_cur_with_flags(raw_content, "")
+                .map_err(|e| Error::Other(format!("Failed to store message in cur: {}", e)))
+        } else if maildir_subdir == "new" {
+            self.maildir.store_new(raw_content)
+                .map_err(|e| Error::Other(format!("Failed to store message in new: {}", e)))
+        } else {
+            Err(Error::Other(format!("Invalid maildir subdirectory: {}", maildir_subdir)))
+        }
+    }
+
+    /// Parses the headers of a raw message and caches them in `message_metadata`.
+    ///
+    /// Failures are logged rather than returned, since the message itself is already stored.
+    fn save_metadata_from_raw(&self, maildir_id: &str, raw_content: &[u8]) {
+        match parse_mail(raw_content) {
+            Ok(parsed) => {
+                let date = parsed.headers.get_first_value("Date").unwrap_or_default();
+                let subject = parsed.headers.get_first_value("Subject").unwrap_or_default();
+                let from = parsed.headers.get_first_value("From").unwrap_or_default();
+                let body = Self::walk_mime_parts(&parsed, false)
+                    .map(|(body, _)| body)
+                    .unwrap_or_default();
+
+                if let Err(e) = self.save_metadata(maildir_id, &date, &subject, &from, &make_snippet(&body, SNIPPET_MAX_LENGTH)) {
+                    tracing::warn!("Failed to save metadata for {}: {}", maildir_id, e);
+                }
+                if let Err(e) = self.index_message(maildir_id, &subject, &from, &body) {
+                    tracing::warn!("Failed to index {} for search: {}", maildir_id, e);
+                }
             }
             Err(e) => {
                 tracing::warn!("Failed to parse email for metadata extraction: {}", e);
             }
         }
+    }
 
-        // add mapping to message_map table FIRST (before label_map due to foreign key constraint)
-        self.add_mapping(message_id.clone(), maildir_id.clone())?;
+    /// Imports a single `.eml` file or an `.mbox` file into `cur`, in the inbox.
+    ///
+    /// Imported messages have no Gmail id, so they are mapped under a `local:` key
+    /// (see `LOCAL_ID_PREFIX`). Returns the number of messages imported.
+    pub fn import_file(&self, path: &Path) -> Result<usize, Error> {
+        let content = std::fs::read(path)
+            .map_err(|e| Error::Other(format!("Failed to read {:?}: {}", path, e)))?;
+
+        let is_mbox = path.extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.eq_ignore_ascii_case("mbox"))
+            .unwrap_or(false);
 
-        // save labels to label_map table (after message_map entry exists)
-        self.add_label_mappings(&maildir_id, labels)?;
+        let messages = if is_mbox {
+            split_mbox(&content)
+        } else {
+            vec![content]
+        };
 
-        Ok(maildir_id)
+        let mut imported = 0;
+        for raw_content in messages {
+            if raw_content.iter().all(|b| b.is_ascii_whitespace()) {
+                continue;
+            }
+            let maildir_id = self.store_raw_message(&raw_content, "cur")?;
+            self.save_metadata_from_raw(&maildir_id, &raw_content);
+            self.add_mapping(format!("{}{}", LOCAL_ID_PREFIX, maildir_id), maildir_id.clone())?;
+            self.add_label_mappings(&maildir_id, &["INBOX".to_string()])?;
+            imported += 1;
+        }
+
+        tracing::info!("Imported {} message(s) from {:?}", imported, path);
+        Ok(imported)
     }
 
     /// Parses an RFC822 email format into termail's EmailMessage struct using the `mailparse` crate.
@@ -470,14 +1166,28 @@ impl MaildirManager {
         email.subject = parsed.headers.get_first_value("Subject").unwrap_or_default();
         email.from = EmailSender::from(parsed.headers.get_first_value("From").unwrap_or_default());
         email.to = parsed.headers.get_first_value("To").unwrap_or_default();
+        email.cc = parsed.headers.get_first_value("Cc").unwrap_or_default();
         email.date = parsed.headers.get_first_value("Date").unwrap_or_default();
-
-        // self.print_email_mime_tree(&raw_content);
+        email.message_id = parsed.headers.get_first_value("Message-ID");
+        email.references = parsed.headers.get_first_value("References");
+        email.thread_id = self.get_thread_id(&email.id).unwrap_or_else(|e| {
+            tracing::warn!("{}", e);
+            None
+        });
+        email.labels = self.labels_for(&email.id).unwrap_or_else(|e| {
+            tracing::warn!("{}", e);
+            Vec::new()
+        });
 
         let (body, attachments) = Self::walk_mime_parts(&parsed, load_attachments)?;
 
+        email.snippet = self.get_metadata_snippet(&email.id)
+            .unwrap_or_else(|| make_snippet(&body, SNIPPET_MAX_LENGTH));
         email.body = body;
         email.email_attachments = attachments;
+        if !load_attachments {
+            email.attachment_stats = Some(Self::count_attachments_without_loading(&parsed));
+        }
         
         // Debug: log attachment info
         if !email.email_attachments.is_empty() {
@@ -512,6 +1222,8 @@ impl MaildirManager {
             .or_else(|| Self::get_filename_from_disposition_static(part));
         
         let is_image = mimetype.starts_with("image/");
+        let content_id = Self::get_content_id(part);
+        let disposition = Self::get_disposition(part, content_id.is_some());
         
         // If it has a filename, is marked as attachment, OR is an image, treat it as an attachment
         if filename.is_some() || is_attachment || is_image {
@@ -525,7 +1237,11 @@ impl MaildirManager {
                 }
             });
             
-            // Get raw binary data for attachments
+            // Get raw binary data for attachments. List views only need the
+            // counts, see `count_attachments_without_loading`.
+            if !load_attachments {
+                return Ok((full_text, full_attachments));
+            }
             if let Ok(data) = part.get_body_raw() {
                 // Set mime_type based on whether it's actually an image
                 let mime_type = if is_image {
@@ -539,6 +1255,8 @@ impl MaildirManager {
                     content_type: mimetype.clone(),
                     data,
                     mime_type,
+                    disposition,
+                    content_id,
                 });
             }
         } else if mimetype.starts_with("multipart/") {
@@ -562,6 +1280,63 @@ impl MaildirManager {
         Ok((full_text, full_attachments))
     }
 
+    /// Counts the attachments `walk_mime_parts` would load, leaving out inline
+    /// parts, without decoding any of them. Sizes of base64 parts are estimated
+    /// from their encoded length.
+    fn count_attachments_without_loading(part: &ParsedMail) -> AttachmentStats {
+        if part.ctype.mimetype.starts_with("multipart/") {
+            return part.subparts.iter().fold(AttachmentStats::default(), |total, subpart| {
+                let stats = Self::count_attachments_without_loading(subpart);
+                AttachmentStats { count: total.count + stats.count, bytes: total.bytes + stats.bytes }
+            });
+        }
+
+        let is_attachment = part.ctype.params.contains_key("name")
+            || Self::get_filename_from_disposition_static(part).is_some()
+            || part.ctype.mimetype.starts_with("image/")
+            || part.headers
+                .get_first_value("Content-Disposition")
+                .is_some_and(|disp| disp.to_lowercase().starts_with("attachment"));
+        let is_inline = Self::get_disposition(part, Self::get_content_id(part).is_some()) == AttachmentDisposition::Inline;
+        if !is_attachment || is_inline {
+            return AttachmentStats::default();
+        }
+
+        let bytes = match part.get_body_encoded() {
+            Body::Base64(body) => body.get_raw().len() / 4 * 3,
+            Body::QuotedPrintable(body) => body.get_raw().len(),
+            Body::SevenBit(body) | Body::EightBit(body) => body.get_raw().len(),
+            Body::Binary(body) => body.get_raw().len(),
+        };
+        AttachmentStats { count: 1, bytes }
+    }
+
+    /// Returns the `Content-ID` of a part with the surrounding angle brackets stripped.
+    fn get_content_id(mail: &ParsedMail) -> Option<String> {
+        let content_id = mail.get_headers().get_first_value("Content-ID")?;
+        let content_id = content_id.trim().trim_start_matches('<').trim_end_matches('>');
+        if content_id.is_empty() {
+            None
+        } else {
+            Some(content_id.to_string())
+        }
+    }
+
+    /// Classifies a part as inline or attachment.
+    ///
+    /// An explicit `Content-Disposition` wins. Without one, parts that carry a
+    /// `Content-ID` are assumed to be referenced from the HTML body and are inline.
+    fn get_disposition(mail: &ParsedMail, has_content_id: bool) -> AttachmentDisposition {
+        match mail.get_headers().get_first_value("Content-Disposition") {
+            Some(disposition) => match parse_content_disposition(&disposition).disposition {
+                DispositionType::Inline => AttachmentDisposition::Inline,
+                _ => AttachmentDisposition::Attachment,
+            },
+            None if has_content_id => AttachmentDisposition::Inline,
+            None => AttachmentDisposition::Attachment,
+        }
+    }
+
     /// Static helper to check Content-Disposition for filenames (used in walk_mime_parts)
     fn get_filename_from_disposition_static(mail: &ParsedMail) -> Option<String> {
         let disposition = mail.get_headers().get_first_value("Content-Disposition")?;
@@ -586,6 +1361,14 @@ impl MaildirManager {
             None
         };
 
+        // Trashed and spam messages only show up when looking at TRASH or SPAM itself
+        let mut hidden_maildir_ids: std::collections::HashSet<String> = std::collections::HashSet::new();
+        for hidden_label in ["TRASH", "SPAM"] {
+            if label != Some(hidden_label) {
+                hidden_maildir_ids.extend(self.get_maildir_ids_with_label(hidden_label)?);
+            }
+        }
+
         // collect entries from both new and cur directories
         let mut entries: Vec<(String, std::path::PathBuf)> = Vec::new();
 
@@ -604,14 +1387,7 @@ impl MaildirManager {
                         .unwrap_or("")
                         .to_string();
                     
-                    // Extract maildir_id from filename (remove flags and size markers)
-                    // Format can be: unique_id:2,flags,S=size or just unique_id
-                    let maildir_id = filename
-                        .split(":2,").next()  // Remove :2,flags
-                        .unwrap_or(&filename)
-                        .split(",S=").next()  // Remove ,S=size marker (GreenMail)
-                        .unwrap_or(&filename)
-                        .to_string();
+                    let maildir_id = maildir_id_from_filename(&filename);
                     
                     // Filter by label if specified
                     if let Some(ref filtered_ids) = filtered_maildir_ids {
@@ -619,6 +1395,9 @@ impl MaildirManager {
                             continue;
                         }
                     }
+                    if hidden_maildir_ids.contains(&maildir_id) {
+                        continue;
+                    }
                     
                     entries.push((maildir_id, path));
                 }
@@ -640,14 +1419,7 @@ impl MaildirManager {
                         .unwrap_or("")
                         .to_string();
                     
-                    // Extract maildir_id from filename (remove flags and size markers)
-                    // Format can be: unique_id:2,flags,S=size or just unique_id
-                    let maildir_id = filename
-                        .split(":2,").next()  // Remove :2,flags
-                        .unwrap_or(&filename)
-                        .split(",S=").next()  // Remove ,S=size marker (GreenMail)
-                        .unwrap_or(&filename)
-                        .to_string();
+                    let maildir_id = maildir_id_from_filename(&filename);
                     
                     // Filter by label if specified
                     if let Some(ref filtered_ids) = filtered_maildir_ids {
@@ -655,6 +1427,9 @@ impl MaildirManager {
                             continue;
                         }
                     }
+                    if hidden_maildir_ids.contains(&maildir_id) {
+                        continue;
+                    }
                     
                     entries.push((maildir_id, path));
                 }
@@ -677,21 +1452,24 @@ impl MaildirManager {
             match self.parse_rfc822_email(&raw_content, maildir_id.clone(), is_unread, false) {
                 Ok(email) => {
                     // Save metadata to cache for future use
-                    if let Err(e) = self.save_metadata(&maildir_id, &email.date, &email.subject, &email.from.email) {
+                    if let Err(e) = self.save_metadata(&maildir_id, &email.date, &email.subject, &email.from.email, &email.snippet) {
                         tracing::warn!("Failed to save metadata for {}: {}", maildir_id, e);
                     }
+                    // Files that were dropped into the maildir by hand are indexed here
+                    if !self.is_indexed(&maildir_id) {
+                        if let Err(e) = self.index_message(&maildir_id, &email.subject, &email.from.full_string(), &email.body) {
+                            tracing::warn!("Failed to index {} for search: {}", maildir_id, e);
+                        }
+                    }
                     emails.push(email);
                 },
                 Err(e) => tracing::warn!("Failed to parse email: {}", e),
             }
         }
 
-        // Sort by email date (parsed from Date header) in descending order (newest first)
-        emails.sort_by(|a, b| {
-            let date_a = DateTime::parse_from_rfc2822(&a.date).ok();
-            let date_b = DateTime::parse_from_rfc2822(&b.date).ok();
-            date_b.cmp(&date_a) // Reverse for descending order (newest first)
-        });
+        // Sort by email date in descending order (newest first). Unparseable dates fall
+        // back to the cached timestamp, then to 0 (last).
+        sort_newest_first(&mut emails, |maildir_id| self.get_metadata_timestamp(maildir_id));
         
         tracing::info!("Built metadata cache for {} emails", emails.len());
         
@@ -707,8 +1485,368 @@ impl MaildirManager {
         Ok(emails)
     }
 
-    /// Load a single email by maildir_id with full attachment data
+    /// Whether `maildir_id` is in the full-text index. Always true without FTS5,
+    /// since there is no index to add it to.
+    fn is_indexed(&self, maildir_id: &str) -> bool {
+        if !self.fts_available {
+            return true;
+        }
+        let Ok(conn) = self.connection.lock() else {
+            return true;
+        };
+        conn.query_row(
+            "SELECT 1 FROM message_fts WHERE maildir_id = ?1",
+            params![maildir_id],
+            |_| Ok(()),
+        )
+        .is_ok()
+    }
+
+    /// Adds (or replaces) a message in the full-text index. No-op without FTS5.
+    pub fn index_message(&self, maildir_id: &str, subject: &str, sender: &str, body: &str) -> Result<(), Error> {
+        if !self.fts_available {
+            return Ok(());
+        }
+        let conn = self.connection.lock()
+            .map_err(|e| Error::Other(format!("Failed to lock connection: {}", e)))?;
+
+        conn.execute("DELETE FROM message_fts WHERE maildir_id = ?1", params![maildir_id])
+            .map_err(|e| Error::Other(format!("Failed to clear search index row: {}", e)))?;
+        conn.execute(
+            "INSERT INTO message_fts (maildir_id, subject, sender, body) VALUES (?1, ?2, ?3, ?4)",
+            params![maildir_id, subject, sender, body],
+        )
+        .map_err(|e| Error::Other(format!("Failed to index message: {}", e)))?;
+        Ok(())
+    }
+
+    /// Searches subject, sender and body. With FTS5 the query understands
+    /// `"exact phrase"`, `prefix*` and `a OR b` (see `fts_query`) and results are ranked; without
+    /// it every message is scanned for all query terms (case-insensitive), newest first.
+    pub fn search(&self, query: &str, limit: usize) -> Result<Vec<EmailMessage>, Error> {
+        if !self.fts_available {
+            return Ok(self.list_emails(usize::MAX)?
+                .into_iter()
+                .filter(|email| scan_matches(email, query))
+                .take(limit)
+                .collect());
+        }
+
+        let fts_query = fts_query(query);
+        if fts_query.is_empty() {
+            return Ok(Vec::new());
+        }
+        let maildir_ids: Vec<String> = {
+            let conn = self.connection.lock()
+                .map_err(|e| Error::Other(format!("Failed to lock connection: {}", e)))?;
+            let mut stmt = conn.prepare(
+                "SELECT maildir_id FROM message_fts WHERE message_fts MATCH ?1 ORDER BY rank LIMIT ?2"
+            )
+            .map_err(|e| Error::Other(format!("Failed to prepare search query: {}", e)))?;
+            let rows = stmt.query_map(params![fts_query, limit as i64], |row| row.get(0))
+                .map_err(|e| Error::Other(format!("Invalid search query {:?}: {}", query, e)))?;
+            rows.collect::<Result<Vec<String>, _>>()
+                .map_err(|e| Error::Other(format!("Invalid search query {:?}: {}", query, e)))?
+        };
+
+        let mut emails = Vec::new();
+        for maildir_id in maildir_ids {
+            match self.load_email(&maildir_id) {
+                Ok(email) => emails.push(email),
+                // The index can outlive a file that was removed behind our back
+                Err(e) => tracing::warn!("Search hit {} could not be loaded: {}", maildir_id, e),
+            }
+        }
+        Ok(emails)
+    }
+
+    /// Rebuilds `message_metadata` (and the search index) from the files in `new`
+    /// and `cur`. Labels are left alone since they can only come from the server.
+    ///
+    /// Everything is parsed up front and written in a single transaction, so a
+    /// failure leaves the old cache untouched. Returns `(indexed, skipped)`.
+    pub fn reindex(&self) -> Result<(usize, usize), Error> {
+        let mut rows = Vec::new();
+        let mut skipped = 0;
+        for subdir in ["new", "cur"] {
+            let dir = self.maildir.path().join(subdir);
+            if !dir.exists() {
+                continue;
+            }
+            let entries = std::fs::read_dir(&dir)
+                .map_err(|e| Error::Other(format!("Failed to read {} directory: {}", subdir, e)))?;
+            for entry in entries {
+                let path = entry
+                    .map_err(|e| Error::Other(format!("Failed to read directory entry: {}", e)))?
+                    .path();
+                if !path.is_file() {
+                    continue;
+                }
+                let filename = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+                let maildir_id = maildir_id_from_filename(filename);
+                let raw_content = std::fs::read(&path)
+                    .map_err(|e| Error::Other(format!("Failed to read maildir entry {}: {}", maildir_id, e)))?;
+
+                let parsed = self.parse_rfc822_email(&raw_content, maildir_id.clone(), false, false);
+                let timestamp = parsed.as_ref().ok()
+                    .and_then(|email| DateTime::parse_from_rfc2822(&email.date).ok())
+                    .map(|dt| dt.timestamp());
+                match (parsed, timestamp) {
+                    (Ok(email), Some(timestamp)) => rows.push((maildir_id, timestamp, email)),
+                    _ => {
+                        tracing::warn!("Skipping {} during reindex: unparsable message or date", maildir_id);
+                        skipped += 1;
+                    }
+                }
+            }
+        }
+
+        let mut conn = self.connection.lock()
+            .map_err(|e| Error::Other(format!("Failed to lock connection: {}", e)))?;
+        let tx = conn.transaction()
+            .map_err(|e| Error::Other(format!("Failed to start reindex transaction: {}", e)))?;
+
+        tx.execute("DELETE FROM message_metadata", [])
+            .map_err(|e| Error::Other(format!("Failed to clear message_metadata: {}", e)))?;
+        if self.fts_available {
+            tx.execute("DELETE FROM message_fts", [])
+                .map_err(|e| Error::Other(format!("Failed to clear search index: {}", e)))?;
+        }
+        for (maildir_id, timestamp, email) in &rows {
+            tx.execute(
+                "INSERT OR REPLACE INTO message_metadata (maildir_id, date_timestamp, subject, sender, snippet) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![maildir_id, timestamp, email.subject, email.from.email, make_snippet(&email.body, SNIPPET_MAX_LENGTH)],
+            )
+            .map_err(|e| Error::Other(format!("Failed to insert metadata for {}: {}", maildir_id, e)))?;
+            if self.fts_available {
+                tx.execute(
+                    "INSERT INTO message_fts (maildir_id, subject, sender, body) VALUES (?1, ?2, ?3, ?4)",
+                    params![maildir_id, email.subject, email.from.full_string(), email.body],
+                )
+                .map_err(|e| Error::Other(format!("Failed to index {}: {}", maildir_id, e)))?;
+            }
+        }
+
+        tx.commit()
+            .map_err(|e| Error::Other(format!("Failed to commit reindex: {}", e)))?;
+        Ok((rows.len(), skipped))
+    }
+
+    /// The maildir_ids of every file currently in `new` or `cur`.
+    pub fn maildir_ids_on_disk(&self) -> Result<std::collections::HashSet<String>, Error> {
+        let mut ids = std::collections::HashSet::new();
+        for subdir in ["new", "cur"] {
+            let dir = self.maildir.path().join(subdir);
+            if !dir.exists() {
+                continue;
+            }
+            let entries = std::fs::read_dir(&dir)
+                .map_err(|e| Error::Other(format!("Failed to read {} directory: {}", subdir, e)))?;
+            for entry in entries {
+                let entry = entry.map_err(|e| Error::Other(format!("Failed to read directory entry: {}", e)))?;
+                if let Some(filename) = entry.file_name().to_str() {
+                    ids.insert(maildir_id_from_filename(filename));
+                }
+            }
+        }
+        Ok(ids)
+    }
+
+    /// Messages on disk that Gmail does not know about: imported ones (mapped under
+    /// a `local:` key) and ones without any mapping, e.g. synced over IMAP. Sorted,
+    /// so uploads go in a stable order.
+    pub fn local_only_maildir_ids(&self) -> Result<Vec<String>, Error> {
+        let synced: std::collections::HashSet<String> = self.get_all_mappings()?
+            .into_iter()
+            .filter(|(gmail_id, _)| !is_local_id(gmail_id))
+            .map(|(_, maildir_id)| maildir_id)
+            .collect();
+        let mut ids: Vec<String> = self.maildir_ids_on_disk()?
+            .into_iter()
+            .filter(|maildir_id| !synced.contains(maildir_id))
+            .collect();
+        ids.sort();
+        Ok(ids)
+    }
+
+    /// Records that the local-only message `maildir_id` now exists on Gmail as
+    /// `gmail_id`: its `local:` mapping (and the labels hanging off it) is replaced
+    /// by a real one carrying Gmail's thread id and labels.
+    pub fn record_upload(&self, maildir_id: &str, gmail_id: &str, thread_id: Option<&str>, labels: &[String]) -> Result<(), Error> {
+        self.remove_mappings(&[format!("{}{}", LOCAL_ID_PREFIX, maildir_id)])?;
+        self.add_mapping(gmail_id.to_string(), maildir_id.to_string())?;
+        if let Some(thread_id) = thread_id {
+            self.set_thread_id(gmail_id, thread_id)?;
+        }
+        if !self.has_metadata(maildir_id) {
+            self.save_metadata_from_raw(maildir_id, &self.read_raw_message(maildir_id)?);
+        }
+        self.add_label_mappings(maildir_id, labels)
+    }
+
+    /// Finds rows that point at messages which no longer exist.
+    ///
+    /// A `label_map` row is orphaned when its message is missing from `message_map`
+    /// or from disk, a `message_metadata` row when its file is gone (metadata is also
+    /// kept for messages that never had a mapping, e.g. ones synced over IMAP), and a
+    /// `message_map` row when its file is gone.
+    pub fn find_orphans(&self) -> Result<OrphanReport, Error> {
+        let on_disk = self.maildir_ids_on_disk()?;
+        let conn = self.connection.lock()
+            .map_err(|e| Error::Other(format!("Failed to lock connection: {}", e)))?;
+
+        let query_ids = |sql: &str| -> Result<Vec<String>, Error> {
+            let mut stmt = conn.prepare(sql)
+                .map_err(|e| Error::Other(format!("Failed to prepare orphan query: {}", e)))?;
+            let rows = stmt.query_map([], |row| row.get(0))
+                .map_err(|e| Error::Other(format!("Failed to run orphan query: {}", e)))?;
+            rows.collect::<Result<Vec<String>, _>>()
+                .map_err(|e| Error::Other(format!("Failed to read orphan row: {}", e)))
+        };
+
+        let unmapped_labels = query_ids(
+            "SELECT DISTINCT maildir_id FROM label_map WHERE maildir_id NOT IN (SELECT maildir_id FROM message_map)"
+        )?;
+        let mut labels: Vec<String> = query_ids("SELECT DISTINCT maildir_id FROM label_map")?
+            .into_iter()
+            .filter(|id| !on_disk.contains(id) || unmapped_labels.contains(id))
+            .collect();
+        labels.sort();
+
+        let missing = |ids: Vec<String>| -> Vec<String> {
+            let mut ids: Vec<String> = ids.into_iter().filter(|id| !on_disk.contains(id)).collect();
+            ids.sort();
+            ids
+        };
+
+        Ok(OrphanReport {
+            labels,
+            metadata: missing(query_ids("SELECT maildir_id FROM message_metadata")?),
+            mappings: missing(query_ids("SELECT maildir_id FROM message_map")?),
+        })
+    }
+
+    /// Deletes the rows listed in `orphans`. Returns the number of rows removed from
+    /// `(label_map, message_metadata, message_map)`.
+    ///
+    /// Dropping a mapping makes the next sync download the message again.
+    pub fn prune_orphans(&self, orphans: &OrphanReport) -> Result<(usize, usize, usize), Error> {
+        let mut conn = self.connection.lock()
+            .map_err(|e| Error::Other(format!("Failed to lock connection: {}", e)))?;
+        let tx = conn.transaction()
+            .map_err(|e| Error::Other(format!("Failed to start prune transaction: {}", e)))?;
+
+        let mut removed = [0usize; 3];
+        for (count, (table, ids)) in removed.iter_mut().zip([
+            ("label_map", &orphans.labels),
+            ("message_metadata", &orphans.metadata),
+            ("message_map", &orphans.mappings),
+        ]) {
+            for id in ids {
+                *count += tx.execute(&format!("DELETE FROM {} WHERE maildir_id = ?1", table), params![id])
+                    .map_err(|e| Error::Other(format!("Failed to prune {}: {}", table, e)))?;
+            }
+        }
+
+        tx.commit()
+            .map_err(|e| Error::Other(format!("Failed to commit prune: {}", e)))?;
+        Ok((removed[0], removed[1], removed[2]))
+    }
+
+    /// Deletes search index rows of messages whose file is gone. Returns the number
+    /// of rows removed.
+    fn prune_orphan_search_rows(&self) -> Result<usize, Error> {
+        if !self.fts_available {
+            return Ok(0);
+        }
+        let on_disk = self.maildir_ids_on_disk()?;
+        let conn = self.connection.lock()
+            .map_err(|e| Error::Other(format!("Failed to lock connection: {}", e)))?;
+        let indexed: Vec<String> = {
+            let mut stmt = conn.prepare("SELECT maildir_id FROM message_fts")
+                .map_err(|e| Error::Other(format!("Failed to prepare search index query: {}", e)))?;
+            let rows = stmt.query_map([], |row| row.get(0))
+                .map_err(|e| Error::Other(format!("Failed to query search index: {}", e)))?;
+            rows.collect::<Result<Vec<String>, _>>()
+                .map_err(|e| Error::Other(format!("Failed to read search index row: {}", e)))?
+        };
+        let mut removed = 0;
+        for maildir_id in indexed.iter().filter(|id| !on_disk.contains(*id)) {
+            removed += conn.execute("DELETE FROM message_fts WHERE maildir_id = ?1", params![maildir_id])
+                .map_err(|e| Error::Other(format!("Failed to prune search index: {}", e)))?;
+        }
+        Ok(removed)
+    }
+
+    /// Removes orphaned label, metadata and search index rows (see `find_orphans`)
+    /// and `VACUUM`s the database. Mappings are left alone; use `CheckDb --repair`
+    /// for those.
+    pub fn compact(&self) -> Result<CompactReport, Error> {
+        let orphans = OrphanReport {
+            mappings: Vec::new(),
+            ..self.find_orphans()?
+        };
+        let size_before = std::fs::metadata(&self.db_path).map(|m| m.len()).unwrap_or(0);
+        let (label_rows_removed, metadata_rows_removed, _) = self.prune_orphans(&orphans)?;
+        let search_rows_removed = self.prune_orphan_search_rows()?;
+
+        self.connection.lock()
+            .map_err(|e| Error::Other(format!("Failed to lock connection: {}", e)))?
+            .execute("VACUUM", [])
+            .map_err(|e| Error::Other(format!("Failed to vacuum database: {}", e)))?;
+
+        let size_after = std::fs::metadata(&self.db_path).map(|m| m.len()).unwrap_or(size_before);
+        Ok(CompactReport {
+            label_rows_removed,
+            metadata_rows_removed,
+            search_rows_removed,
+            bytes_freed: size_before.saturating_sub(size_after),
+        })
+    }
+
+    /// Load a single email by maildir_id without attachment data
+    pub fn load_email(&self, maildir_id: &str) -> Result<EmailMessage, Error> {
+        let raw_content = self.read_raw_message(maildir_id)?;
+        let is_unread = self.has_label(maildir_id, "UNREAD")
+            .unwrap_or(false);
+        self.parse_rfc822_email(&raw_content, maildir_id.to_string(), is_unread, false)
+    }
+
+    /// Load a single email by maildir_id with full attachment data. Recently loaded
+    /// messages come from the cache; their unread state and labels, which change
+    /// without the file changing, are read fresh either way.
     pub fn load_email_with_attachments(&self, maildir_id: &str) -> Result<EmailMessage, Error> {
+        // Check database for UNREAD label
+        let is_unread = self.has_label(maildir_id, "UNREAD")
+            .unwrap_or(false);
+
+        let cached = self.email_cache.lock().ok().and_then(|mut cache| cache.get(maildir_id));
+        if let Some(mut email) = cached {
+            email.is_unread = is_unread;
+            email.labels = self.labels_for(maildir_id).unwrap_or_else(|e| {
+                tracing::warn!("{}", e);
+                Vec::new()
+            });
+            return Ok(email);
+        }
+
+        let raw_content = self.read_raw_message(maildir_id)?;
+        let email = self.parse_rfc822_email(&raw_content, maildir_id.to_string(), is_unread, true)?;
+        if let Ok(mut cache) = self.email_cache.lock() {
+            cache.insert(maildir_id.to_string(), email.clone());
+        }
+        Ok(email)
+    }
+
+    /// Drops `maildir_id` from the parsed message cache.
+    fn forget_cached(&self, maildir_id: &str) {
+        if let Ok(mut cache) = self.email_cache.lock() {
+            cache.remove(maildir_id);
+        }
+    }
+
+    /// Finds the file backing a maildir_id in either `new` or `cur`.
+    pub fn find_message_path(&self, maildir_id: &str) -> Result<PathBuf, Error> {
         let maildir_path = self.maildir.path();
 
         // Try both new and cur directories
@@ -730,22 +1868,9 @@ impl MaildirManager {
                         .and_then(|n| n.to_str())
                         .unwrap_or("");
                     
-                    // Extract the actual maildir_id from filename (strip flags and size markers)
-                    let file_maildir_id = filename
-                        .split(":2,").next()
-                        .unwrap_or(filename)
-                        .split(",S=").next()
-                        .unwrap_or(filename);
-                    
                     // Check if this is the file we're looking for
-                    if file_maildir_id == maildir_id {
-                        let raw_content = std::fs::read(&path)
-                            .map_err(|e| Error::Other(format!("Failed to read {}: {}", maildir_id, e)))?;
-
-                        // Check database for UNREAD label
-                        let is_unread = self.has_label(maildir_id, "UNREAD")
-                            .unwrap_or(false);
-                        return self.parse_rfc822_email(&raw_content, maildir_id.to_string(), is_unread, true);
+                    if maildir_id_from_filename(filename) == maildir_id {
+                        return Ok(path);
                     }
                 }
             }
@@ -754,44 +1879,713 @@ impl MaildirManager {
         Err(Error::Other(format!("Email not found: {}", maildir_id)))
     }
 
-    fn _print_email_mime_tree(&self, raw_content: &[u8]) {
-        let parsed = parse_mail(raw_content)
-            .map_err(|e| Error::Other(format!("Failed to parse email: {}", e))).unwrap();
+    /// Reads the raw RFC822 bytes of a message, headers included.
+    pub fn read_raw_message(&self, maildir_id: &str) -> Result<Vec<u8>, Error> {
+        let path = self.find_message_path(maildir_id)?;
+        std::fs::read(&path)
+            .map_err(|e| Error::Other(format!("Failed to read {}: {}", maildir_id, e)))
+    }
 
-        fn print_tree(mail: &ParsedMail, depth: usize) {
-            let indent = "    ".repeat(depth);
-            
-            // Extract the MIME type (e.g., "text/plain", "multipart/mixed")
-            let mime_type = &mail.ctype.mimetype;
-            
-            // Check if it is an attachment by looking for filename params
-            let filename: Option<String> = mail.ctype.params.get("name").cloned()
-                .or_else(|| MaildirManager::get_filename_from_disposition_static(mail));
-        
-            match filename {
-                Some(name) => println!("{}|-- [Attachment] {} ({})", indent, name, mime_type),
-                None => println!("{}|-- [Part] {}", indent, mime_type),
-            }
-        
-            // Recurse into subparts (Context Frames)
-            for subpart in &mail.subparts {
-                print_tree(subpart, depth + 1);
+    /// Writes the raw message verbatim to `out_path` as a `.eml` file.
+    ///
+    /// Refuses to overwrite an existing file unless `force` is set.
+    pub fn export_message(&self, maildir_id: &str, out_path: &Path, force: bool) -> Result<PathBuf, Error> {
+        if out_path.is_dir() {
+            return Err(Error::InvalidInput(format!("{:?} is a directory", out_path)));
+        }
+        if out_path.exists() && !force {
+            return Err(Error::InvalidInput(format!(
+                "{:?} already exists. Pass --force to overwrite it", out_path
+            )));
+        }
+        if let Some(parent) = out_path.parent() {
+            if !parent.as_os_str().is_empty() && !parent.exists() {
+                return Err(Error::InvalidInput(format!("Directory {:?} does not exist", parent)));
             }
         }
 
-        println!("--------------------------------");
-        println!("Subject: {}", parsed.headers.get_first_value("Subject").unwrap_or_default());
-        println!("From: {}", parsed.headers.get_first_value("From").unwrap_or_default());
-        if let Some(to) = parsed.headers.get_first_value("To") {
-            let to_emails_len = to.split(",").count();
-            if to_emails_len >= 3 {
-                println!("To: {} total emails (>= 3 detected)", to_emails_len);
+        let raw_content = self.read_raw_message(maildir_id)?;
+        std::fs::write(out_path, raw_content)
+            .map_err(|e| Error::Other(format!("Failed to write {:?}: {}", out_path, e)))?;
+        Ok(out_path.to_path_buf())
+    }
+
+    /// Renders the MIME part hierarchy of a stored message, e.g. for `Command::MimeTree`.
+    pub fn mime_tree(&self, maildir_id: &str) -> Result<String, Error> {
+        let raw_content = self.read_raw_message(maildir_id)?;
+        format_mime_tree(&raw_content)
+    }
+}
+
+/// Sorts emails newest first by their `Date` header. Emails whose date does not
+/// parse use `fallback_timestamp(id)` (or 0). Each date is parsed once, and ties are
+/// broken by id so the order is deterministic regardless of directory order.
+pub fn sort_newest_first(emails: &mut Vec<EmailMessage>, fallback_timestamp: impl Fn(&str) -> Option<i64>) {
+    let mut keyed: Vec<(i64, EmailMessage)> = emails.drain(..)
+        .map(|email| {
+            let timestamp = DateTime::parse_from_rfc2822(&email.date)
+                .map(|dt| dt.timestamp())
+                .ok()
+                .or_else(|| fallback_timestamp(&email.id))
+                .unwrap_or(0);
+            (timestamp, email)
+        })
+        .collect();
+    keyed.sort_by(|(ts_a, a), (ts_b, b)| ts_b.cmp(ts_a).then_with(|| a.id.cmp(&b.id)));
+    emails.extend(keyed.into_iter().map(|(_, email)| email));
+}
+
+/// Extracts the maildir_id from a file name in `new` or `cur` by removing the flags.
+/// The format is `unique_id:2,flags` or just `unique_id`; a `,S=size` marker is part
+/// of the id, as it is in the ids `Maildir::store_new` returns.
+pub fn maildir_id_from_filename(filename: &str) -> String {
+    filename
+        .split(":2,").next()  // Remove :2,flags
+        .unwrap_or(filename)
+        .to_string()
+}
+
+/// Fallback matcher for `MaildirManager::search` when FTS5 is unavailable: every
+/// whitespace separated term must appear in the subject, sender or body. FTS5
+/// syntax is loosely honoured by ignoring quotes and a trailing `*`.
+pub fn scan_matches(email: &EmailMessage, query: &str) -> bool {
+    let haystack = format!("{}\n{}\n{}", email.subject, email.from.full_string(), email.body).to_lowercase();
+    query.split_whitespace()
+        .map(|term| term.trim_matches('"').trim_end_matches('*').to_lowercase())
+        .filter(|term| !term.is_empty())
+        .all(|term| haystack.contains(&term))
+}
+
+/// Turns a search box query into an FTS5 `MATCH` expression. Quoted phrases, a
+/// trailing `*` (prefix search) and AND/OR/NOT between terms keep their FTS5
+/// meaning; every other term is quoted, so text like `bob@example.com` or `re:`
+/// is searched for as typed instead of failing as FTS5 syntax.
+pub fn fts_query(query: &str) -> String {
+    let quote = |text: &str| format!("\"{}\"", text.replace('"', "\"\""));
+    // (term, whether it is an operator)
+    let mut terms: Vec<(String, bool)> = Vec::new();
+    let mut rest = query.trim();
+    while !rest.is_empty() {
+        let (text, prefix, tail) = if let Some(phrase) = rest.strip_prefix('"') {
+            let end = phrase.find('"').unwrap_or(phrase.len());
+            let tail = phrase.get(end + 1..).unwrap_or("");
+            (&phrase[..end], tail.starts_with('*'), tail.trim_start_matches('*'))
+        } else {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            let word = &rest[..end];
+            if matches!(word, "AND" | "OR" | "NOT") {
+                terms.push((word.to_string(), true));
+                rest = rest[end..].trim_start();
+                continue;
+            }
+            let stem = word.trim_end_matches('*');
+            (stem, stem.len() < word.len(), &rest[end..])
+        };
+        // Terms without a word character (`-`, `*`, ...) match nothing in FTS5
+        if text.chars().any(char::is_alphanumeric) {
+            terms.push((format!("{}{}", quote(text), if prefix { "*" } else { "" }), false));
+        }
+        rest = tail.trim_start();
+    }
+    // An operator without a term on both sides is searched for as a word
+    let is_term = |index: Option<usize>| index.and_then(|index| terms.get(index)).is_some_and(|(_, operator)| !operator);
+    (0..terms.len())
+        .map(|index| {
+            let (term, operator) = &terms[index];
+            if *operator && !(is_term(index.checked_sub(1)) && is_term(Some(index + 1))) {
+                quote(term)
             } else {
-                println!("To: {}", to);
+                term.clone()
             }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Formats the headers and MIME part hierarchy of a raw message, one part per line,
+/// indented by depth. Attachments show their filename.
+pub fn format_mime_tree(raw_content: &[u8]) -> Result<String, Error> {
+    let parsed = parse_mail(raw_content)
+        .map_err(|e| Error::Other(format!("Failed to parse email: {}", e)))?;
+
+    fn write_tree(out: &mut String, mail: &ParsedMail, depth: usize) {
+        let indent = "    ".repeat(depth);
+
+        // Extract the MIME type (e.g., "text/plain", "multipart/mixed")
+        let mime_type = &mail.ctype.mimetype;
+
+        // Check if it is an attachment by looking for filename params
+        let filename: Option<String> = mail.ctype.params.get("name").cloned()
+            .or_else(|| MaildirManager::get_filename_from_disposition_static(mail));
+
+        match filename {
+            Some(name) => out.push_str(&format!("{}|-- [Attachment] {} ({})\n", indent, name, mime_type)),
+            None => out.push_str(&format!("{}|-- [Part] {}\n", indent, mime_type)),
+        }
+
+        // Recurse into subparts (Context Frames)
+        for subpart in &mail.subparts {
+            write_tree(out, subpart, depth + 1);
         }
-        println!("Date: {}", parsed.headers.get_first_value("Date").unwrap_or_default());
-        print_tree(&parsed, 0);
-        println!("--------------------------------\n");
+    }
+
+    let mut out = String::new();
+    out.push_str(&format!("Subject: {}\n", parsed.headers.get_first_value("Subject").unwrap_or_default()));
+    out.push_str(&format!("From: {}\n", parsed.headers.get_first_value("From").unwrap_or_default()));
+    if let Some(to) = parsed.headers.get_first_value("To") {
+        let to_emails_len = to.split(",").count();
+        if to_emails_len >= 3 {
+            out.push_str(&format!("To: {} total emails (>= 3 detected)\n", to_emails_len));
+        } else {
+            out.push_str(&format!("To: {}\n", to));
+        }
+    }
+    out.push_str(&format!("Date: {}\n", parsed.headers.get_first_value("Date").unwrap_or_default()));
+    write_tree(&mut out, &parsed, 0);
+    Ok(out.trim_end().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manager() -> (tempfile::TempDir, MaildirManager) {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = MaildirManager::new(dir.path().to_string_lossy().into_owned()).unwrap();
+        (dir, manager)
+    }
+
+    #[test]
+    fn labelling_an_unmapped_message_creates_a_local_mapping() {
+        let (_dir, manager) = manager();
+        manager.trash_message("imap-1").unwrap();
+        assert_eq!(manager.labels_for("imap-1").unwrap(), vec!["TRASH".to_string()]);
+        assert_eq!(manager.get_gmail_id("imap-1").unwrap().as_deref(), Some("local:imap-1"));
+    }
+
+    #[test]
+    fn spam_and_label_changes_work_on_unmapped_messages() {
+        let (_dir, manager) = manager();
+        manager.set_spam("imap-1", true).unwrap();
+        assert_eq!(manager.labels_for("imap-1").unwrap(), vec!["SPAM".to_string()]);
+        manager.set_spam("imap-1", false).unwrap();
+        assert_eq!(manager.labels_for("imap-1").unwrap(), vec!["INBOX".to_string()]);
+        manager.modify_labels("imap-2", &["Work".to_string()], &["INBOX".to_string()]).unwrap();
+        assert_eq!(manager.labels_for("imap-2").unwrap(), vec!["Work".to_string()]);
+    }
+
+    const RAW: &[u8] = b"From: Ann <ann@example.com>\r\nSubject: Quarterly report\r\nDate: Mon, 6 Jan 2025 10:00:00 +0000\r\n\r\nNumbers inside.\r\n";
+
+    #[test]
+    fn stored_messages_are_searchable() {
+        let (_dir, manager) = manager();
+        let maildir_id = manager.store_message(RAW, "new").unwrap();
+        let hits = manager.search("quarterly", 10).unwrap();
+        assert_eq!(hits.iter().map(|email| email.id.as_str()).collect::<Vec<_>>(), vec![maildir_id.as_str()]);
+    }
+
+    #[test]
+    fn search_terms_are_quoted_unless_fts_syntax() {
+        assert_eq!(fts_query("bob@example.com"), r#""bob@example.com""#);
+        assert_eq!(fts_query(r#""quarterly report" quart*"#), r#""quarterly report" "quart"*"#);
+        assert_eq!(fts_query("re: (draft) - x"), r#""re:" "(draft)" "x""#);
+        assert_eq!(fts_query("a OR b NOT c"), r#""a" OR "b" NOT "c""#);
+        assert_eq!(fts_query("OR a AND OR b NOT"), r#""OR" "a" "AND" "OR" "b" "NOT""#);
+        assert_eq!(fts_query(r#"say "hi"#), r#""say" "hi""#);
+        assert_eq!(fts_query(" * - "), "");
+    }
+
+    fn search_ids(manager: &MaildirManager, query: &str) -> Vec<String> {
+        manager.search(query, 10).unwrap().into_iter().map(|email| email.id).collect()
+    }
+
+    #[test]
+    fn phrase_and_prefix_queries_match() {
+        let (_dir, manager) = manager();
+        let maildir_id = manager.store_message(RAW, "new").unwrap();
+        assert_eq!(search_ids(&manager, r#""quarterly report""#), vec![maildir_id.clone()]);
+        assert_eq!(search_ids(&manager, "quart*"), vec![maildir_id.clone()]);
+        assert_eq!(search_ids(&manager, "ann@example.com"), vec![maildir_id.clone()]);
+        assert!(search_ids(&manager, "re: (draft").is_empty());
+        if manager.fts_available {
+            // Both words are there, but not as this phrase
+            assert!(search_ids(&manager, r#""report quarterly""#).is_empty());
+            assert!(search_ids(&manager, "quart").is_empty());
+        }
+    }
+
+    #[test]
+    fn snippets_are_cached_with_the_metadata() {
+        let (_dir, manager) = manager();
+        let maildir_id = manager.store_message(RAW, "new").unwrap();
+        assert_eq!(manager.get_metadata_snippet(&maildir_id).as_deref(), Some("Numbers inside."));
+        let email = manager.load_email(&maildir_id).unwrap();
+        assert_eq!(email.snippet, "Numbers inside.");
+    }
+
+    #[test]
+    fn mbox_from_quoting_is_undone_one_level() {
+        let mbox = b"From ann@example.com Mon Jan  6 10:00:00 2025\nSubject: A\n\n>From here\n>>From there\n> From quoted\n";
+        let messages = split_mbox(mbox);
+        assert_eq!(messages, vec![b"Subject: A\n\nFrom here\n>From there\n> From quoted\n".to_vec()]);
+    }
+
+    #[test]
+    fn imported_messages_land_in_the_inbox() {
+        let (dir, manager) = manager();
+        let path = dir.path().join("import.eml");
+        std::fs::write(&path, RAW).unwrap();
+        assert_eq!(manager.import_file(&path).unwrap(), 1);
+        assert_eq!(manager.get_maildir_ids_with_label("INBOX").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn existing_mail_is_indexed_on_open() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().to_string_lossy().into_owned();
+        // Stored behind the manager's back, like mail from before search existed
+        let maildir = Maildir::from(path.clone());
+        maildir.create_dirs().unwrap();
+        maildir.store_new(RAW).unwrap();
+        let manager = MaildirManager::new(path).unwrap();
+        assert_eq!(manager.search("numbers", 10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn compact_prunes_search_rows_of_deleted_files() {
+        let (_dir, manager) = manager();
+        manager.index_message("gone", "Subject", "ann@example.com", "body").unwrap();
+        let report = manager.compact().unwrap();
+        assert_eq!(report.search_rows_removed, if manager.fts_available { 1 } else { 0 });
+    }
+
+    #[test]
+    fn undoing_mark_read_moves_the_file_back_to_new() {
+        let (_dir, manager) = manager();
+        let maildir_id = manager.store_message(RAW, "new").unwrap();
+        manager.add_label_mappings(&maildir_id, &["INBOX".to_string(), "UNREAD".to_string()]).unwrap();
+        manager.mark_read(&maildir_id).unwrap();
+        assert_eq!(manager.get_message_directory(&maildir_id).unwrap(), "cur");
+
+        let unread_id = manager.mark_unread(&maildir_id).unwrap();
+        assert_eq!(manager.get_message_directory(&unread_id).unwrap(), "new");
+        assert_eq!(manager.labels_for(&unread_id).unwrap(), vec!["INBOX".to_string(), "UNREAD".to_string()]);
+        assert_eq!(manager.get_gmail_id(&unread_id).unwrap(), Some(format!("{}{}", LOCAL_ID_PREFIX, maildir_id)));
+        assert!(manager.labels_for(&maildir_id).unwrap().is_empty());
+    }
+
+    #[test]
+    fn labelling_keeps_an_existing_mapping() {
+        let (_dir, manager) = manager();
+        manager.add_mapping("gmail-1".to_string(), "md-1".to_string()).unwrap();
+        manager.add_label_mappings("md-1", &["INBOX".to_string()]).unwrap();
+        assert_eq!(manager.get_gmail_id("md-1").unwrap().as_deref(), Some("gmail-1"));
+    }
+
+    fn cached(subject: &str) -> EmailMessage {
+        EmailMessage { subject: subject.to_string(), ..EmailMessage::new() }
+    }
+
+    #[test]
+    fn email_cache_evicts_the_least_recently_used() {
+        let mut cache = EmailCache::new(2);
+        assert!(cache.get("a").is_none());
+        cache.insert("a".to_string(), cached("A"));
+        cache.insert("b".to_string(), cached("B"));
+        // Reading `a` makes `b` the oldest
+        assert_eq!(cache.get("a").unwrap().subject, "A");
+        cache.insert("c".to_string(), cached("C"));
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("a").is_some());
+
+        cache.insert("a".to_string(), cached("A2"));
+        assert_eq!(cache.get("a").unwrap().subject, "A2");
+        cache.remove("a");
+        assert!(cache.get("a").is_none());
+
+        let mut disabled = EmailCache::new(0);
+        disabled.insert("a".to_string(), cached("A"));
+        assert!(disabled.is_empty());
+    }
+
+    #[test]
+    fn cached_messages_are_forgotten_when_their_file_goes() {
+        let (_dir, manager) = manager();
+        let maildir_id = manager.store_message(RAW, "new").unwrap();
+        manager.load_email_with_attachments(&maildir_id).unwrap();
+        assert_eq!(manager.email_cache.lock().unwrap().len(), 1);
+
+        manager.maildir_move_new_to_cur(&maildir_id).unwrap();
+        assert!(manager.email_cache.lock().unwrap().is_empty());
+        manager.load_email_with_attachments(&maildir_id).unwrap();
+        manager.delete_message(maildir_id.clone()).unwrap();
+        assert!(manager.email_cache.lock().unwrap().is_empty());
+        assert!(manager.load_email_with_attachments(&maildir_id).is_err());
+    }
+
+    /// A report with an HTML body, an inline logo referenced by Content-ID (no
+    /// disposition), a PDF attachment and a CSV attachment nested one level down.
+    const NESTED_MULTIPART: &[u8] = b"From: Ann <ann@example.com>\r\n\
+Subject: Report\r\n\
+Date: Mon, 6 Jan 2025 10:00:00 +0000\r\n\
+MIME-Version: 1.0\r\n\
+Content-Type: multipart/mixed; boundary=\"outer\"\r\n\
+\r\n\
+--outer\r\n\
+Content-Type: multipart/alternative; boundary=\"alt\"\r\n\
+\r\n\
+--alt\r\n\
+Content-Type: text/plain; charset=utf-8\r\n\
+\r\n\
+See the report.\r\n\
+--alt\r\n\
+Content-Type: multipart/related; boundary=\"rel\"\r\n\
+\r\n\
+--rel\r\n\
+Content-Type: text/html; charset=utf-8\r\n\
+\r\n\
+<p>See the report.</p><img src=\"cid:logo@example.com\">\r\n\
+--rel\r\n\
+Content-Type: image/png\r\n\
+Content-ID: <logo@example.com>\r\n\
+Content-Transfer-Encoding: base64\r\n\
+\r\n\
+iVBORw0KGgo=\r\n\
+--rel--\r\n\
+--alt--\r\n\
+--outer\r\n\
+Content-Type: application/pdf; name=\"report.pdf\"\r\n\
+Content-Disposition: attachment; filename=\"report.pdf\"\r\n\
+Content-Transfer-Encoding: base64\r\n\
+\r\n\
+JVBERi0xLjQK\r\n\
+--outer\r\n\
+Content-Type: multipart/mixed; boundary=\"inner\"\r\n\
+\r\n\
+--inner\r\n\
+Content-Type: text/csv; name=\"data.csv\"\r\n\
+Content-Disposition: attachment; filename=\"data.csv\"\r\n\
+Content-Transfer-Encoding: base64\r\n\
+\r\n\
+YSxiCjEsMgo=\r\n\
+--inner--\r\n\
+--outer--\r\n";
+
+    #[test]
+    fn attachments_are_counted_without_loading_them() {
+        let (_dir, manager) = manager();
+        let maildir_id = manager.store_message(NESTED_MULTIPART, "new").unwrap();
+        let email = manager.load_email(&maildir_id).unwrap();
+        assert!(email.email_attachments.is_empty());
+        // The inline logo is not an attachment; 12 base64 characters are ~9 bytes
+        assert_eq!(email.attachment_stats, Some(AttachmentStats { count: 2, bytes: 18 }));
+    }
+
+    #[test]
+    fn content_id_parts_without_disposition_are_inline() {
+        let (_dir, manager) = manager();
+        let maildir_id = manager.store_message(NESTED_MULTIPART, "new").unwrap();
+        let email = manager.load_email_with_attachments(&maildir_id).unwrap();
+
+        let parts: Vec<_> = email.email_attachments
+            .iter()
+            .map(|att| (att.filename.as_str(), att.disposition, att.content_id.as_deref()))
+            .collect();
+        assert_eq!(parts, vec![
+            ("image.png", AttachmentDisposition::Inline, Some("logo@example.com")),
+            ("report.pdf", AttachmentDisposition::Attachment, None),
+            ("data.csv", AttachmentDisposition::Attachment, None),
+        ]);
+        assert_eq!(email.attachment_count(), 2);
+
+        let html = email.resolve_cid_references();
+        assert!(html.contains(r#"<img src="data:image/png;base64,iVBORw0KGgo=">"#));
+        assert!(!html.contains("cid:"));
+    }
+
+    #[test]
+    fn exported_messages_reimport_byte_for_byte() {
+        let (dir, manager) = manager();
+        let maildir_id = manager.store_message(NESTED_MULTIPART, "new").unwrap();
+        let path = dir.path().join("report.eml");
+        manager.export_message(&maildir_id, &path, false).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), NESTED_MULTIPART);
+
+        assert_eq!(manager.import_file(&path).unwrap(), 1);
+        let imported_id = manager.get_maildir_ids_with_label("INBOX").unwrap().remove(0);
+        assert_ne!(imported_id, maildir_id);
+        assert_eq!(manager.read_raw_message(&imported_id).unwrap(), NESTED_MULTIPART);
+        let (original, imported) = (manager.load_email(&maildir_id).unwrap(), manager.load_email(&imported_id).unwrap());
+        assert_eq!((imported.subject, imported.from.full_string(), imported.date), (original.subject, original.from.full_string(), original.date));
+    }
+
+    #[test]
+    fn exports_do_not_overwrite_without_force() {
+        let (dir, manager) = manager();
+        let maildir_id = manager.store_message(RAW, "new").unwrap();
+        let path = dir.path().join("existing.eml");
+        std::fs::write(&path, b"keep me").unwrap();
+
+        assert!(matches!(manager.export_message(&maildir_id, &path, false), Err(Error::InvalidInput(_))));
+        assert_eq!(std::fs::read(&path).unwrap(), b"keep me");
+        manager.export_message(&maildir_id, &path, true).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), RAW);
+    }
+
+    #[test]
+    fn every_message_of_an_mbox_is_imported() {
+        let (dir, manager) = manager();
+        let path = dir.path().join("archive.mbox");
+        std::fs::write(&path, b"From ann@example.com Mon Jan  6 10:00:00 2025\n\
+From: Ann <ann@example.com>\n\
+Subject: First\n\
+Date: Mon, 6 Jan 2025 10:00:00 +0000\n\
+\n\
+One.\n\
+\n\
+From bob@example.com Tue Jan  7 10:00:00 2025\n\
+From: Bob <bob@example.com>\n\
+Subject: Second\n\
+Date: Tue, 7 Jan 2025 10:00:00 +0000\n\
+\n\
+Two.\n").unwrap();
+
+        assert_eq!(manager.import_file(&path).unwrap(), 2);
+        assert_eq!(manager.get_number_of_mappings().unwrap(), 2);
+        let newest_first = manager.get_sorted_maildir_ids(10).unwrap();
+        let subjects: Vec<_> = newest_first.iter().map(|id| manager.load_email(id).unwrap().subject).collect();
+        assert_eq!(subjects, vec!["Second", "First"]);
+    }
+
+    #[test]
+    fn reindex_rebuilds_deleted_metadata() {
+        let (_dir, manager) = manager();
+        let first = manager.store_message(RAW, "new").unwrap();
+        let second = manager.store_message(NESTED_MULTIPART, "cur").unwrap();
+        manager.store_message(b"Subject: No date\r\n\r\nbody\r\n", "cur").unwrap();
+        manager.connection.lock().unwrap().execute("DELETE FROM message_metadata", []).unwrap();
+        assert!(!manager.has_metadata(&first));
+
+        assert_eq!(manager.reindex().unwrap(), (2, 1));
+        assert!(manager.has_metadata(&first));
+        assert!(manager.has_metadata(&second));
+        assert_eq!(manager.get_metadata_snippet(&first).as_deref(), Some("Numbers inside."));
+    }
+
+    #[test]
+    fn failed_reindex_keeps_the_old_metadata() {
+        let (_dir, manager) = manager();
+        let maildir_id = manager.store_message(RAW, "new").unwrap();
+        manager.connection.lock().unwrap().execute_batch(
+            "CREATE TEMP TRIGGER fail_insert BEFORE INSERT ON message_metadata BEGIN SELECT RAISE(ABORT, 'disk full'); END;"
+        ).unwrap();
+
+        assert!(manager.reindex().is_err());
+        assert!(manager.has_metadata(&maildir_id));
+    }
+
+    #[test]
+    fn rows_of_missing_messages_are_found_and_pruned() {
+        let (_dir, manager) = manager();
+        let kept = manager.store_message(RAW, "new").unwrap();
+        manager.add_label_mappings(&kept, &["INBOX".to_string()]).unwrap();
+        // Synced over IMAP: metadata but no mapping, which is fine
+        manager.store_message(RAW, "cur").unwrap();
+        let gone = manager.store_message(NESTED_MULTIPART, "cur").unwrap();
+        manager.add_label_mappings(&gone, &["INBOX".to_string()]).unwrap();
+        std::fs::remove_file(manager.find_message_path(&gone).unwrap()).unwrap();
+        {
+            let conn = manager.connection.lock().unwrap();
+            conn.execute_batch(
+                "PRAGMA foreign_keys = OFF;
+                 INSERT INTO label_map (maildir_id, label) VALUES ('stray', 'Work');
+                 PRAGMA foreign_keys = ON;"
+            ).unwrap();
+        }
+
+        let orphans = manager.find_orphans().unwrap();
+        let mut labels = vec![gone.clone(), "stray".to_string()];
+        labels.sort();
+        assert_eq!(orphans.labels, labels);
+        assert_eq!(orphans.metadata, vec![gone.clone()]);
+        assert_eq!(orphans.mappings, vec![gone.clone()]);
+
+        assert_eq!(manager.prune_orphans(&orphans).unwrap(), (2, 1, 1));
+        assert!(manager.find_orphans().unwrap().is_empty());
+        assert_eq!(manager.labels_for(&kept).unwrap(), vec!["INBOX".to_string()]);
+        assert_eq!(manager.get_number_of_mappings().unwrap(), 1);
+    }
+
+    #[test]
+    fn sorting_is_newest_first_with_invalid_dates_last() {
+        let email = |id: &str, date: &str| EmailMessage { id: id.to_string(), date: date.to_string(), ..EmailMessage::new() };
+        let fallback = |id: &str| (id == "x2").then_some(1_736_330_400); // Wed, 8 Jan 2025 10:00:00 +0000
+        let mut emails = vec![
+            email("x3", ""),
+            email("x5", "Mon, 6 Jan 2025 10:00:00 +0000"),
+            email("x1", "Tue, 7 Jan 2025 10:00:00 +0000"),
+            email("x4", "Mon, 6 Jan 2025 10:00:00 +0000"),
+            email("x2", "not a date"),
+        ];
+        let expected = vec!["x2", "x1", "x4", "x5", "x3"];
+
+        sort_newest_first(&mut emails, fallback);
+        assert_eq!(emails.iter().map(|email| email.id.as_str()).collect::<Vec<_>>(), expected);
+        emails.reverse();
+        sort_newest_first(&mut emails, fallback);
+        assert_eq!(emails.iter().map(|email| email.id.as_str()).collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn mime_trees_are_indented_by_depth() {
+        let raw = b"From: Ann <ann@example.com>\r\n\
+To: bob@example.com\r\n\
+Subject: Notes\r\n\
+Date: Mon, 6 Jan 2025 10:00:00 +0000\r\n\
+Content-Type: multipart/mixed; boundary=\"outer\"\r\n\
+\r\n\
+--outer\r\n\
+Content-Type: multipart/alternative; boundary=\"alt\"\r\n\
+\r\n\
+--alt\r\n\
+Content-Type: text/plain\r\n\
+\r\n\
+Notes.\r\n\
+--alt\r\n\
+Content-Type: text/html\r\n\
+\r\n\
+<p>Notes.</p>\r\n\
+--alt--\r\n\
+--outer\r\n\
+Content-Type: application/pdf; name=\"notes.pdf\"\r\n\
+Content-Disposition: attachment; filename=\"notes.pdf\"\r\n\
+\r\n\
+%PDF\r\n\
+--outer--\r\n";
+
+        assert_eq!(format_mime_tree(raw).unwrap(), "\
+Subject: Notes
+From: Ann <ann@example.com>
+To: bob@example.com
+Date: Mon, 6 Jan 2025 10:00:00 +0000
+|-- [Part] multipart/mixed
+    |-- [Part] multipart/alternative
+        |-- [Part] text/plain
+        |-- [Part] text/html
+    |-- [Attachment] notes.pdf (application/pdf)");
+    }
+
+    #[test]
+    fn mime_trees_summarise_long_recipient_lists() {
+        let tree = format_mime_tree(b"To: a@example.com, b@example.com, c@example.com\r\n\r\nHi\r\n").unwrap();
+        assert!(tree.contains("To: 3 total emails (>= 3 detected)\n"));
+        assert!(tree.ends_with("|-- [Part] text/plain"));
+    }
+
+    #[test]
+    fn label_summary_uses_the_newest_message() {
+        let (_dir, manager) = manager();
+        assert_eq!(manager.latest_subject_for_label("Work").unwrap(), None);
+        assert_eq!(manager.count_with_label("Work").unwrap(), 0);
+
+        let older = manager.store_message(RAW, "cur").unwrap();
+        let newer = manager.store_message(b"Subject: Follow-up\r\nDate: Tue, 7 Jan 2025 10:00:00 +0000\r\n\r\nMore.\r\n", "cur").unwrap();
+        // Insert the newer one first so the order comes from the dates
+        manager.add_label_mappings(&newer, &["Work".to_string()]).unwrap();
+        manager.add_label_mappings(&older, &["Work".to_string()]).unwrap();
+
+        assert_eq!(manager.latest_subject_for_label("Work").unwrap().as_deref(), Some("Follow-up"));
+        assert_eq!(manager.count_with_label("Work").unwrap(), 2);
+        assert_eq!(manager.latest_subject_for_label("INBOX").unwrap(), None);
+    }
+
+    #[test]
+    fn sync_checkpoints_survive_a_restart_until_cleared() {
+        let dir = tempfile::tempdir().unwrap();
+        let open = || MaildirManager::new(dir.path().to_string_lossy().into_owned()).unwrap();
+
+        let manager = open();
+        assert_eq!(manager.get_sync_checkpoint().unwrap(), None);
+        manager.save_sync_checkpoint(None).unwrap();
+        assert_eq!(manager.get_sync_checkpoint().unwrap(), Some(None));
+        manager.save_sync_checkpoint(Some("page-2")).unwrap();
+        drop(manager);
+
+        let manager = open();
+        assert_eq!(manager.get_sync_checkpoint().unwrap(), Some(Some("page-2".to_string())));
+        manager.clear_sync_checkpoint().unwrap();
+        drop(manager);
+
+        assert_eq!(open().get_sync_checkpoint().unwrap(), None);
+    }
+
+    #[test]
+    fn thread_ids_are_stored_with_the_mapping() {
+        let (_dir, manager) = manager();
+        manager.add_mapping("gmail-1".to_string(), "md-1".to_string()).unwrap();
+        assert_eq!(manager.get_thread_id("md-1").unwrap(), None);
+        manager.set_thread_id("gmail-1", "thread-9").unwrap();
+        assert_eq!(manager.get_thread_id("md-1").unwrap().as_deref(), Some("thread-9"));
+        assert_eq!(manager.get_thread_id("md-unknown").unwrap(), None);
+    }
+
+    #[test]
+    fn old_databases_gain_the_thread_id_column() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sync_state.db");
+        Connection::open(&path).unwrap().execute_batch(
+            "CREATE TABLE message_map (gmail_id TEXT PRIMARY KEY, maildir_id TEXT NOT NULL UNIQUE);
+             INSERT INTO message_map (gmail_id, maildir_id) VALUES ('gmail-1', 'md-1');"
+        ).unwrap();
+
+        let conn = MaildirManager::open_or_create_database(&path).unwrap();
+        let (maildir_id, thread_id): (String, Option<String>) = conn.query_row(
+            "SELECT maildir_id, thread_id FROM message_map WHERE gmail_id = 'gmail-1'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        ).unwrap();
+        assert_eq!((maildir_id.as_str(), thread_id), ("md-1", None));
+        // Opening it again must not try to add the column twice
+        drop(conn);
+        MaildirManager::open_or_create_database(&path).unwrap();
+    }
+
+    #[test]
+    fn uploads_replace_the_local_mapping() {
+        let (dir, manager) = manager();
+        let synced = manager.store_message(RAW, "cur").unwrap();
+        manager.add_mapping("gmail-1".to_string(), synced.clone()).unwrap();
+        let over_imap = manager.store_message(RAW, "cur").unwrap();
+        let path = dir.path().join("import.eml");
+        std::fs::write(&path, NESTED_MULTIPART).unwrap();
+        manager.import_file(&path).unwrap();
+        let imported = manager.get_maildir_ids_with_label("INBOX").unwrap().remove(0);
+
+        let mut local_only = vec![imported.clone(), over_imap.clone()];
+        local_only.sort();
+        assert_eq!(manager.local_only_maildir_ids().unwrap(), local_only);
+
+        manager.record_upload(&imported, "gmail-2", Some("thread-2"), &["INBOX".to_string(), "Work".to_string()]).unwrap();
+        assert_eq!(manager.get_gmail_id(&imported).unwrap().as_deref(), Some("gmail-2"));
+        assert_eq!(manager.get_thread_id(&imported).unwrap().as_deref(), Some("thread-2"));
+        assert_eq!(manager.labels_for(&imported).unwrap(), vec!["INBOX".to_string(), "Work".to_string()]);
+        assert_eq!(manager.get_maildir_id(&format!("{}{}", LOCAL_ID_PREFIX, imported)).unwrap(), None);
+        assert_eq!(manager.local_only_maildir_ids().unwrap(), vec![over_imap.clone()]);
+
+        manager.record_upload(&over_imap, "gmail-3", None, &[]).unwrap();
+        assert_eq!(manager.get_gmail_id(&over_imap).unwrap().as_deref(), Some("gmail-3"));
+        assert!(manager.local_only_maildir_ids().unwrap().is_empty());
+    }
+
+    #[test]
+    fn ids_from_file_names_match_the_stored_ids() {
+        assert_eq!(maildir_id_from_filename("123.M1P2.host"), "123.M1P2.host");
+        assert_eq!(maildir_id_from_filename("123.M1P2.host,S=984:2,RS"), "123.M1P2.host,S=984");
+
+        let (_dir, manager) = manager();
+        let maildir_id = manager.store_message(RAW, "cur").unwrap();
+        let path = manager.find_message_path(&maildir_id).unwrap();
+        assert_eq!(maildir_id_from_filename(path.file_name().unwrap().to_str().unwrap()), maildir_id);
     }
 }
@@ -8,17 +8,25 @@ pub mod plugins;
 pub mod maildir;
 pub mod core;
 pub mod logger;
-use plugins::plugins::PluginManager;
+pub mod net;
+use plugins::{events::Hook, plugins::{PluginManager, discover_plugins, find_plugin_dir}};
 use clap::{Parser, ArgAction};
 use backends::{BackendType, Backend};
-use cli::command::Command;
+use cli::command::{Command, CommandResult};
+use cli::info::version_report;
+use cli::prompt::confirm;
 use config::Config;
+use crate::core::{email::EmailMessage, mailto::parse_mailto};
 use ui::app::App;
+use ui::event::{AppEvent, Event};
 use std::path::PathBuf;
 use std::sync::Arc;
 
 async fn create_authenticated_backend(config: &Config) -> Box<dyn Backend> {
-    let mut backend: Box<dyn Backend> = config.get_backend();
+    let mut backend: Box<dyn Backend> = config.get_backend().unwrap_or_else(|e| {
+        tracing::error!("{}", e);
+        std::process::exit(1);
+    });
     
     if backend.needs_oauth() {
         if let Err(e) = backend.authenticate().await {
@@ -35,8 +43,9 @@ pub struct Args {
     #[arg(long, action = ArgAction::SetTrue)]
     cli: bool,
 
-    /// Use a specific email backend (available: greenmail, gmail)
-    #[arg(long, value_parser = clap::value_parser!(BackendType))]
+    // Help text is built from `BackendType::all()` so new backends show up automatically.
+    #[arg(long, value_parser = clap::value_parser!(BackendType),
+        help = format!("Use a specific email backend (available: {})", BackendType::names()))]
     backend: Option<BackendType>,
 
     /// The command to execute
@@ -51,28 +60,78 @@ pub struct Args {
     #[arg(long, value_parser = clap::value_parser!(PathBuf))]
     log_dir: Option<String>,
 
-    /// Verbosity level
+    /// Validate the config file, print any problems and exit
+    #[arg(long, action = ArgAction::SetTrue)]
+    check_config: bool,
+
+    /// Print the path of the config file that would be loaded and exit
+    #[arg(long, action = ArgAction::SetTrue)]
+    print_config_path: bool,
+
+    /// Disable colored output (also disabled when NO_COLOR is set)
+    #[arg(long, action = ArgAction::SetTrue)]
+    no_color: bool,
+
+    /// Log more: -v for info, -vv for debug, -vvv for trace. RUST_LOG, if set, wins
     #[arg(short, long, action = ArgAction::Count)]
-    verbosity: Option<u8>,
+    verbose: u8,
 }
 
 #[tokio::main]
 async fn main() {
     let args = Args::parse();
-    let mut config = Config::load(args.config_file.clone()).unwrap_or_else(|e| {
+
+    if args.print_config_path {
+        match Config::resolve_path(args.config_file.clone()) {
+            Ok(path) => println!("{}", path.display()),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if args.check_config {
+        let problems = Config::check(args.config_file.clone());
+        if problems.is_empty() {
+            println!("Config OK");
+            return;
+        }
+        println!("Found {} problem(s) in the config:", problems.len());
+        for problem in problems {
+            println!("  - {}", problem);
+        }
+        std::process::exit(1);
+    }
+
+    let config = Config::load(&args).unwrap_or_else(|e| {
         eprintln!("Error loading config: {}", e);
         std::process::exit(1);
     });
-    config.merge(&args);
 
-    if let Err(e) = logger::init_logger(!config.termail.cli, args.verbosity.unwrap_or(0), config.get_log_path()) {
+    if let Some(Command::Version) = &args.command {
+        let config_path = Config::resolve_path(args.config_file.clone()).ok();
+        println!("{}", version_report(&config, config_path.as_deref()));
+        return;
+    }
+
+    let use_color = logger::color_enabled(args.no_color, std::env::var_os("NO_COLOR").as_deref());
+    if let Err(e) = logger::init_logger(
+        !config.termail.cli,
+        args.verbose,
+        config.get_log_path(),
+        use_color,
+        config.termail.log_rotation,
+        config.log_max_files(),
+    ) {
         eprintln!("Error initializing logger: {}", e);
         std::process::exit(1);
     }
 
     tracing::info!("Logger initialized at {:?}", config.get_log_path());
 
-    let mut plugin_manager = PluginManager::new().unwrap();
+    let mut plugin_manager = PluginManager::new(config.termail.default_backend, config.termail.plugin_limits.clone()).unwrap();
     let enabled_plugins = config.termail.plugins.clone();
 
     if config.termail.cli {
@@ -87,10 +146,23 @@ async fn main() {
         return;
     }
 
+    // `termail compose <mailto:...>` without --cli opens the composer prefilled
+    let compose_draft = match &args.command {
+        Some(Command::Compose { uri }) => match parse_mailto(uri) {
+            Ok(draft) => Some(draft.into_email()),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        },
+        _ => None,
+    };
+
     if let Err(code) = run_tui(
         config, 
         plugin_manager, 
-        enabled_plugins
+        enabled_plugins,
+        compose_draft,
     ).await {
         std::process::exit(code);
     }
@@ -100,15 +172,32 @@ async fn run_tui(
     config: Config,
     plugin_manager: PluginManager,
     enabled_plugins: Vec<String>,
+    compose_draft: Option<EmailMessage>,
 ) -> Result<(), i32> {
     let backend: Box<dyn Backend> = create_authenticated_backend(&config).await;
+    let plugin_dir = config.plugin_dir();
     let terminal = ratatui::init();
-    let app = App::new(config, backend, plugin_manager);
+    let mut app = App::new(config, backend, plugin_manager);
+    if let Some(draft) = compose_draft {
+        app.open_composer(draft);
+    }
 
+    // Failures do not stop the TUI; they are logged and shown in the status bar
     let plugin_loader_manager = Arc::clone(&app.plugin_manager);
+    let sender = app.events.get_sender();
     tokio::spawn(async move {
         let mut manager = plugin_loader_manager.lock().await;
-        let _ = manager.load_plugins(&enabled_plugins);
+        let failures = match manager.load_plugins(&enabled_plugins, plugin_dir) {
+            Ok(report) => {
+                tracing::info!("Loaded successfully: {} plugins", report.loaded);
+                report.failure_summary()
+            }
+            Err(e) => Some(e.to_string()),
+        };
+        if let Some(failures) = failures {
+            tracing::error!("Error loading plugins: {}", failures);
+            let _ = sender.send(Event::App(AppEvent::PluginLoadFailed(failures)));
+        }
     });
 
     let tui_result = app.run(terminal).await;
@@ -125,6 +214,40 @@ async fn run_tui(
     }
 }
 
+/// Prints every plugin in the plugin directory with the reasons it would not load.
+fn list_plugins(configured_dir: Option<PathBuf>, enabled_plugins: &[String]) -> Result<(), i32> {
+    let Some(plugin_dir) = find_plugin_dir(configured_dir) else {
+        tracing::info!("RESULT:\nNo plugin directory found");
+        return Ok(());
+    };
+    match discover_plugins(&plugin_dir, enabled_plugins) {
+        Ok(plugins) => {
+            let report: Vec<String> = plugins.iter().map(|plugin| plugin.to_string()).collect();
+            tracing::info!("RESULT:\nPlugins in {}:\n{}", plugin_dir.display(), report.join("\n"));
+            Ok(())
+        }
+        Err(e) => {
+            tracing::error!("Error: {}", e);
+            Err(1)
+        }
+    }
+}
+
+/// `test-plugins`: runs the loaded plugins for `hook` on `content` and prints what
+/// they return. No backend is involved.
+async fn test_plugins(plugin_manager: &mut PluginManager, hook: &Hook, content: &str) -> Result<(), i32> {
+    match plugin_manager.dispatch_body(hook, content).await {
+        Ok(result) => {
+            tracing::info!("RESULT:\n{}", result);
+            Ok(())
+        }
+        Err(e) => {
+            tracing::error!("Error: {}", e);
+            Err(1)
+        }
+    }
+}
+
 async fn run_cli(
     command: Option<Command>,
     config: Config,
@@ -139,21 +262,41 @@ async fn run_cli(
         }
     };
 
-    match plugin_manager.load_plugins(enabled_plugins) {
-        Ok(count) => tracing::info!("Loaded successfully: {} plugins", count),
+    if let Command::ListPlugins = command {
+        return list_plugins(config.plugin_dir(), enabled_plugins);
+    }
+
+    match plugin_manager.load_plugins(enabled_plugins, config.plugin_dir()) {
+        Ok(report) => {
+            tracing::info!("Loaded successfully: {} plugins", report.loaded);
+            if let Some(failures) = report.failure_summary() {
+                tracing::error!("Error loading plugins: {}", failures);
+                return Err(1);
+            }
+        }
         Err(e) => {
             tracing::error!("Error loading plugins: {}", e);
             return Err(1);
         }
     }
 
+    if let Command::TestPlugins { hook, content } = &command {
+        return test_plugins(plugin_manager, hook, content).await;
+    }
+
     // Some commands do not require authentication. In particular, we might just want to read
     // from Maildir directly, so we can create a backend that does not require authentication
     // and only do the authentication if we need to. 
     // 
     // The commands that require authentication should be defined by the particular backennd 
     // implementations. 
-    let mut backend = config.get_backend();
+    let mut backend = match config.get_backend() {
+        Ok(backend) => backend,
+        Err(e) => {
+            tracing::error!("{}", e);
+            return Err(1);
+        }
+    };
     match backend.requires_authentication(&command) {
         Some(true) => {
             backend.authenticate().await.unwrap_or_else(|e| {
@@ -161,6 +304,11 @@ async fn run_cli(
                 std::process::exit(1);
             });
         },
+        Some(false) if backend.uses_authentication(&command) => {
+            if let Err(e) = backend.authenticate().await {
+                tracing::warn!("Not signed in ({}), only changing the local copy", e);
+            }
+        }
         Some(false) => {}
         None => {
             tracing::warn!("Command undefined for authentication.");
@@ -168,7 +316,23 @@ async fn run_cli(
         }
     }
 
+    if let Command::Login = command {
+        if let Err(e) = backend.reauthenticate().await {
+            tracing::error!("Login failed: {}", e);
+            return Err(1);
+        }
+    }
+
     tracing::debug!("Backend Created: {}", config.termail.default_backend);
+    if config.termail.auto_sync_on_start && matches!(command, Command::ViewMailbox { .. }) {
+        auto_sync(backend.as_mut()).await;
+    }
+    if let Command::SyncFromCloud { yes } | Command::Refresh { yes, .. } = command {
+        if !confirm_full_sync(backend.as_ref(), yes).await {
+            println!("Sync cancelled");
+            return Ok(());
+        }
+    }
     match backend.do_command(command, Some(plugin_manager)).await {
         Ok(result) => {
             tracing::info!("RESULT:\n{}", result);
@@ -182,3 +346,36 @@ async fn run_cli(
         }
     }
 }
+
+/// `auto_sync_on_start`: syncs before the mailbox is read. When the backend cannot
+/// connect, or the sync fails, the local mail is shown as it is.
+async fn auto_sync(backend: &mut dyn Backend) {
+    if let Err(e) = backend.authenticate().await {
+        tracing::warn!("Offline, skipping the sync on start: {}", e);
+        return;
+    }
+    if !confirm_full_sync(backend, false).await {
+        println!("Sync skipped");
+        return;
+    }
+    if let Err(e) = backend.do_command(Command::SyncFromCloud { yes: true }, None).await {
+        tracing::warn!("Sync on start failed, showing local mail: {}", e);
+    }
+}
+
+/// Before a full sync, prints how many messages it downloads and, unless `yes`,
+/// asks whether to go ahead. Incremental syncs go ahead without asking.
+async fn confirm_full_sync(backend: &dyn Backend, yes: bool) -> bool {
+    match backend.do_command(Command::SyncPlan, None).await {
+        Ok(CommandResult::SyncPlan { full: false, .. }) => return true,
+        Ok(CommandResult::SyncPlan { full: true, total: Some(total) }) => {
+            println!("This is a full sync and will download {} messages.", total);
+        }
+        Ok(_) => println!("This is a full sync and will download every message."),
+        Err(e) => {
+            tracing::warn!("Could not determine the size of the sync: {}", e);
+            println!("This may be a full sync that downloads every message.");
+        }
+    }
+    yes || confirm("Continue?", &mut std::io::stdin().lock(), &mut std::io::stdout()).unwrap_or(false)
+}
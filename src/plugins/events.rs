@@ -1,3 +1,4 @@
+use crate::core::email::EmailMessage;
 use crate::plugins::plugins::bindings;
 use bindings::tm::plugin_system::event_api;
 
@@ -22,6 +23,37 @@ pub enum Hook {
     AfterReceive,
 }
 
+impl std::fmt::Display for Hook {
+    /// Formats the hook the way it is written in a plugin manifest.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Hook::BeforeSend => "before_send",
+            Hook::AfterSend => "after_send",
+            Hook::BeforeReceive => "before_receive",
+            Hook::AfterReceive => "after_receive",
+        };
+        f.write_str(name)
+    }
+}
+
+impl std::str::FromStr for Hook {
+    type Err = String;
+
+    /// Parses a hook written the way it is in a plugin manifest, e.g. `before_send`.
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "before_send" => Ok(Hook::BeforeSend),
+            "after_send" => Ok(Hook::AfterSend),
+            "before_receive" => Ok(Hook::BeforeReceive),
+            "after_receive" => Ok(Hook::AfterReceive),
+            _ => Err(format!(
+                "unknown hook {:?} (expected before_send, after_send, before_receive or after_receive)",
+                name
+            )),
+        }
+    }
+}
+
 /// Convert from `event_api::Event` (WIT type) to `Hook` (manifest/config type)
 impl From<event_api::Event> for Hook {
     fn from(event: event_api::Event) -> Self {
@@ -35,51 +67,78 @@ impl From<event_api::Event> for Hook {
 }
 
 /// Convert from `Hook` (manifest/config type) to `event_api::Event` (WIT type)
-/// Note: This requires content, so we provide helper functions instead
+/// Note: This requires a message, so we provide helper functions instead
 impl Hook {
-    /// Get the corresponding WIT event variant for a given hook and content
-    pub fn to_wit_event(&self, content: String) -> event_api::Event {
+    /// Get the corresponding WIT event variant for a given hook and message
+    pub fn to_wit_event(&self, message: event_api::Message) -> event_api::Event {
         match self {
-            Hook::BeforeSend => event_api::Event::BeforeSend(content),
-            Hook::AfterSend => event_api::Event::AfterSend(content),
-            Hook::BeforeReceive => event_api::Event::BeforeReceive(content),
-            Hook::AfterReceive => event_api::Event::AfterReceive(content),
+            Hook::BeforeSend => event_api::Event::BeforeSend(message),
+            Hook::AfterSend => event_api::Event::AfterSend(message),
+            Hook::BeforeReceive => event_api::Event::BeforeReceive(message),
+            Hook::AfterReceive => event_api::Event::AfterReceive(message),
         }
     }
 }
 
-/// Helper functions to create WIT events from content
+/// The WIT view of an email. Labels are left empty; set them where they are known.
+impl From<&EmailMessage> for event_api::Message {
+    fn from(email: &EmailMessage) -> Self {
+        event_api::Message {
+            sender: email.from.full_string(),
+            to: email.to.clone(),
+            cc: email.cc.clone(),
+            subject: email.subject.clone(),
+            body: email.body.clone(),
+            attachments: email.email_attachments
+                .iter()
+                .map(|attachment| event_api::AttachmentInfo {
+                    filename: attachment.filename.clone(),
+                    content_type: attachment.content_type.clone(),
+                    size: attachment.data.len() as u64,
+                })
+                .collect(),
+            labels: Vec::new(),
+        }
+    }
+}
+
+/// Helper functions to create WIT events from messages
 impl event_api::Event {
-    /// Create a BeforeSend event with the given content
-    pub fn before_send(content: String) -> Self {
-        event_api::Event::BeforeSend(content)
+    /// Create a BeforeSend event with the given message
+    pub fn before_send(message: event_api::Message) -> Self {
+        event_api::Event::BeforeSend(message)
     }
 
-    /// Create an AfterSend event with the given content
-    pub fn after_send(content: String) -> Self {
-        event_api::Event::AfterSend(content)
+    /// Create an AfterSend event with the given message
+    pub fn after_send(message: event_api::Message) -> Self {
+        event_api::Event::AfterSend(message)
     }
 
-    /// Create a BeforeReceive event with the given content
-    pub fn before_receive(content: String) -> Self {
-        event_api::Event::BeforeReceive(content)
+    /// Create a BeforeReceive event with the given message
+    pub fn before_receive(message: event_api::Message) -> Self {
+        event_api::Event::BeforeReceive(message)
     }
 
-    /// Create an AfterReceive event with the given content
-    pub fn after_receive(content: String) -> Self {
-        event_api::Event::AfterReceive(content)
+    /// Create an AfterReceive event with the given message
+    pub fn after_receive(message: event_api::Message) -> Self {
+        event_api::Event::AfterReceive(message)
     }
 
-    /// Extract the content string from any event variant
-    pub fn content(&self) -> &str {
+    /// The message carried by any event variant
+    pub fn message(&self) -> &event_api::Message {
         match self {
-            event_api::Event::BeforeSend(content) => content,
-            event_api::Event::AfterSend(content) => content,
-            event_api::Event::BeforeReceive(content) => content,
-            event_api::Event::AfterReceive(content) => content,
+            event_api::Event::BeforeSend(message) => message,
+            event_api::Event::AfterSend(message) => message,
+            event_api::Event::BeforeReceive(message) => message,
+            event_api::Event::AfterReceive(message) => message,
         }
     }
 
+    /// Extract the (possibly plugin-modified) body from any event variant
+    pub fn content(&self) -> &str {
+        &self.message().body
+    }
+
     /// Get the Hook variant that corresponds to this event
     pub fn hook(&self) -> Hook {
         match self {
@@ -90,3 +149,17 @@ impl event_api::Event {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hooks_parse_as_written_in_manifests() {
+        for hook in [Hook::BeforeSend, Hook::AfterSend, Hook::BeforeReceive, Hook::AfterReceive] {
+            assert_eq!(hook.to_string().parse::<Hook>(), Ok(hook));
+        }
+        assert!("BeforeSend".parse::<Hook>().is_err());
+        assert!("".parse::<Hook>().is_err());
+    }
+}
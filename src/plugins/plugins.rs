@@ -1,13 +1,15 @@
+use crate::core::email::EmailMessage;
 use crate::error::Error;
 use crate::plugins::events::Hook;
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
-use wasmtime::component::{Component, Linker, ResourceTable};
-use wasmtime::{Config, Engine, Store};
+use wasmtime::component::{Component, Linker, ResourceTable, TypedFunc};
+use wasmtime::{Config, Engine, Store, StoreLimits, StoreLimitsBuilder};
 use wasmtime_wasi::{WasiCtx, WasiCtxBuilder, WasiCtxView, WasiView};
 
 use crate::backends::BackendType;
+use crate::config::PluginLimits;
 
 pub mod bindings {
     wasmtime::component::bindgen!({
@@ -20,6 +22,152 @@ use bindings::Plugin;
 use bindings::tm::plugin_system::host_api;
 use bindings::tm::plugin_system::event_api;
 
+/// Picks the plugin directory: the XDG one (`~/.config/termail/plugins`), then the
+/// configured `plugin_dir`, then `./plugins` for development. Only the first
+/// directory that exists is used.
+pub fn resolve_plugin_dir(xdg_dir: Option<PathBuf>, configured_dir: Option<PathBuf>) -> Option<PathBuf> {
+    [xdg_dir, configured_dir, Some(PathBuf::from("./plugins"))]
+        .into_iter()
+        .flatten()
+        .find(|dir| dir.is_dir())
+}
+
+/// Builds the error for a plugin that could not be loaded or instantiated, adding a
+/// hint on how to fix the common causes.
+fn plugin_load_error(name: &str, path: &Path, action: &str, error: &wasmtime::Error) -> Error {
+    // `{:#}` includes the whole cause chain, which is where the useful detail lives
+    let detail = format!("{:#}", error);
+    let mut message = format!("Failed to {} plugin \"{}\" ({}): {}", action, name, path.display(), detail);
+    if let Some(hint) = plugin_error_hint(&detail) {
+        message.push_str("\nHint: ");
+        message.push_str(hint);
+    }
+    Error::Plugin(message)
+}
+
+/// Recognizes the usual reasons a plugin fails to load and suggests a fix.
+pub fn plugin_error_hint(detail: &str) -> Option<&'static str> {
+    let detail = detail.to_lowercase();
+    if detail.contains("incompatible") || detail.contains("compiled with") || detail.contains("version") {
+        Some("the plugin was compiled for a different version of termail/wasmtime. Recompile it (and regenerate plugin.cwasm).")
+    } else if detail.contains("growing memory") || detail.contains("growing table") {
+        Some("the plugin needs more memory than allowed. Raise max_memory_bytes/max_table_elements under [termail.plugin_limits] if you trust it.")
+    } else if detail.contains("import") {
+        Some("the plugin imports something termail does not provide. Rebuild it against the current wit/main.wit world.")
+    } else if detail.contains("export") {
+        Some("the plugin is missing an export termail needs. Check that it implements the `plugin` world from wit/main.wit and recompile it.")
+    } else if detail.contains("component") {
+        Some("the file is not a WASM component. Build the plugin as a component (e.g. with cargo-component) targeting wit/main.wit.")
+    } else {
+        None
+    }
+}
+
+/// Whether a plugin call trapped because it ran out of fuel.
+fn is_out_of_fuel(error: &wasmtime::Error) -> bool {
+    error.downcast_ref::<wasmtime::Trap>() == Some(&wasmtime::Trap::OutOfFuel)
+}
+
+/// Whether a plugin trapped because it tried to grow a memory or table past the
+/// `PluginLimits` caps (see `trap_on_grow_failure`).
+fn is_over_resource_limit(error: &wasmtime::Error) -> bool {
+    let detail = format!("{:#}", error);
+    detail.contains("growing memory") || detail.contains("growing table")
+}
+
+/// The plugin directory termail uses, given the configured `plugin_dir`.
+pub fn find_plugin_dir(configured_dir: Option<PathBuf>) -> Option<PathBuf> {
+    let xdg_dir = dirs::config_dir().map(|dir| dir.join("termail/plugins"));
+    resolve_plugin_dir(xdg_dir, configured_dir)
+}
+
+/// Reads and parses a plugin's `manifest.toml`.
+fn parse_manifest(manifest_path: &Path) -> Result<PluginManifest, Error> {
+    let content = std::fs::read_to_string(manifest_path)
+        .map_err(|e| Error::Plugin(format!("Failed to read manifest: {}", e)))?;
+
+    toml::from_str(&content)
+        .map_err(|e| Error::Plugin(format!("Failed to parse manifest: {}", e)))
+}
+
+/// A plugin directory found on disk, as reported by `list-plugins`.
+#[derive(Debug)]
+pub struct DiscoveredPlugin {
+    pub dir: PathBuf,
+    /// The parsed manifest, or why it could not be read.
+    pub manifest: Result<PluginManifest, Error>,
+    /// Whether the plugin is in the config's `plugins` list.
+    pub enabled: bool,
+    /// Whether `plugin.wasm` or `plugin.cwasm` exists.
+    pub has_component: bool,
+}
+
+impl DiscoveredPlugin {
+    /// Reasons the plugin will not be loaded, if any.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        match &self.manifest {
+            Ok(manifest) if manifest.backends.is_empty() => {
+                problems.push("\"backends\" is empty".to_string());
+            }
+            Ok(_) => {}
+            Err(e) => problems.push(e.to_string()),
+        }
+        if !self.has_component {
+            problems.push("missing \"plugin.wasm\" or \"plugin.cwasm\"".to_string());
+        }
+        if !self.enabled {
+            problems.push("not listed in [termail] plugins".to_string());
+        }
+        problems
+    }
+}
+
+impl std::fmt::Display for DiscoveredPlugin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.manifest {
+            Ok(manifest) => {
+                let backends: Vec<String> = manifest.backends.iter().map(|b| b.to_string()).collect();
+                let hooks: Vec<String> = manifest.hooks.iter().map(|h| h.to_string()).collect();
+                writeln!(f, "{} ({})", manifest.name, self.dir.display())?;
+                writeln!(f, "  description: {}", manifest.description)?;
+                writeln!(f, "  backends:    {}", backends.join(", "))?;
+                writeln!(f, "  hooks:       {}", hooks.join(", "))?;
+            }
+            Err(_) => writeln!(f, "? ({})", self.dir.display())?,
+        }
+        let problems = self.problems();
+        if problems.is_empty() {
+            write!(f, "  status:      enabled")
+        } else {
+            write!(f, "  status:      not loaded: {}", problems.join("; "))
+        }
+    }
+}
+
+/// Lists every plugin directory (one containing a `manifest.toml`) in `plugin_dir`,
+/// sorted by directory name. Manifests that fail to parse are reported, not skipped.
+pub fn discover_plugins(plugin_dir: &Path, enabled_plugins: &[String]) -> Result<Vec<DiscoveredPlugin>, Error> {
+    let mut dirs: Vec<PathBuf> = std::fs::read_dir(plugin_dir)
+        .map_err(|e| Error::Plugin(format!("Failed to read plugin dir {:?}: {}", plugin_dir, e)))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|dir| dir.join("manifest.toml").exists())
+        .collect();
+    dirs.sort();
+
+    Ok(dirs.into_iter()
+        .map(|dir| {
+            let manifest = parse_manifest(&dir.join("manifest.toml"));
+            let enabled = manifest.as_ref()
+                .map(|m| enabled_plugins.iter().any(|name| name.eq_ignore_ascii_case(&m.name)))
+                .unwrap_or(false);
+            let has_component = dir.join("plugin.cwasm").exists() || dir.join("plugin.wasm").exists();
+            DiscoveredPlugin { dir, manifest, enabled, has_component }
+        })
+        .collect())
+}
+
 /// Manifest structure for plugin.toml
 #[derive(Debug, serde::Deserialize)]
 pub struct PluginManifest {
@@ -35,10 +183,23 @@ pub struct PluginManifest {
 
 /// Plugin Manager - owns all loaded plugins
 pub struct PluginManager {
-    plugins: HashMap<Hook, Vec<LoadedPlugin>>,
+    /// One instance per plugin, in load order.
+    plugins: Vec<LoadedPlugin>,
+    /// Names of the plugins registered for each hook, in dispatch order. A plugin
+    /// registered for several hooks shares its single instance (and state) between them.
+    hooks: HashMap<Hook, Vec<String>>,
     engine: Engine,
     linker: Linker<PluginState>,
     host_state: TermailHostState,
+    limits: PluginLimits,
+    /// The backend termail is running with. Plugins whose manifest doesn't list it are skipped.
+    backend: BackendType,
+}
+
+impl Drop for PluginManager {
+    fn drop(&mut self) {
+        self.unload_all();
+    }
 }
 
 impl std::fmt::Debug for PluginManager {
@@ -46,7 +207,7 @@ impl std::fmt::Debug for PluginManager {
         write!(
             f,
             "PluginManager {{ plugins: {:?}, engine: {:?} }}",
-            self.plugins.keys(),
+            self.hooks,
             self.engine
         )
     }
@@ -59,8 +220,68 @@ pub struct LoadedPlugin {
     // Not sure if we actually need the name of the plugin for anything. Maybe for
     // logging/debugging purposes in the future?
     pub name: String,
+    /// Hooks the plugin is registered for.
+    pub hooks: Vec<Hook>,
+    /// Backends the plugin may run under, from its manifest.
+    pub backends: Vec<BackendType>,
+    /// Disabled plugins stay loaded but are skipped by `dispatch`.
+    pub enabled: bool,
     store: Store<PluginState>,
     instance: Plugin,
+    /// The optional `on-unload` export (see the `plugin-with-lifecycle` world).
+    on_unload: Option<TypedFunc<(), ()>>,
+}
+
+/// What the plugin manager view shows about one plugin.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PluginSummary {
+    pub name: String,
+    /// Hooks the plugin is registered for, in a stable order.
+    pub hooks: Vec<Hook>,
+    pub enabled: bool,
+}
+
+/// What `load_plugins` did: how many plugins loaded, and which could not be and why.
+#[derive(Debug, Default)]
+pub struct PluginLoadReport {
+    pub loaded: u32,
+    /// `(plugin, error)` for every plugin that failed to load.
+    pub failed: Vec<(String, String)>,
+}
+
+impl PluginLoadReport {
+    /// One line listing every failed plugin and why, or `None` if all loaded.
+    pub fn failure_summary(&self) -> Option<String> {
+        if self.failed.is_empty() {
+            return None;
+        }
+        Some(self.failed
+            .iter()
+            .map(|(plugin, error)| format!("{} ({})", plugin, error))
+            .collect::<Vec<_>>()
+            .join(", "))
+    }
+}
+
+impl LoadedPlugin {
+    /// Calls the plugin's `on-unload` export, if it has one, with a fresh fuel budget.
+    fn run_on_unload(mut self, fuel: u64) {
+        let Some(on_unload) = self.on_unload.take() else {
+            return;
+        };
+        if let Err(e) = self.store.set_fuel(fuel) {
+            tracing::error!("Failed to set fuel for plugin {}: {}", self.name, e);
+        }
+        if let Err(e) = call_lifecycle(&mut self.store, on_unload) {
+            tracing::error!("Plugin {} failed in on-unload: {:#}", self.name, e);
+        }
+    }
+}
+
+/// Calls a lifecycle export (`on-load`/`on-unload`) that takes and returns nothing.
+fn call_lifecycle(store: &mut Store<PluginState>, func: TypedFunc<(), ()>) -> wasmtime::Result<()> {
+    func.call(&mut *store, ())?;
+    func.post_return(&mut *store)
 }
 
 /// Global Host State shared across all plugins
@@ -90,6 +311,8 @@ struct PluginState {
     // Resource table shared with wasi_ctx; required by wasmtime's preview2 runtime.
     wasi_table: ResourceTable,
     host_state: TermailHostState,
+    // Caps on linear memory and table growth, enforced through `Store::limiter`.
+    limits: StoreLimits,
 }
 
 /// Implement the host API for plugins to call the host as defined in the `main.wit` file.
@@ -117,9 +340,11 @@ impl WasiView for PluginState {
 }
 
 impl PluginManager {
-    pub fn new() -> Result<Self, Error> {
+    pub fn new(backend: BackendType, limits: PluginLimits) -> Result<Self, Error> {
         let mut config = Config::new();
         config.wasm_component_model(true);
+        // Every call gets `limits.fuel` to spend, so a plugin stuck in a loop traps
+        config.consume_fuel(true);
         config.cranelift_opt_level(wasmtime::OptLevel::Speed);
 
         let engine = Engine::new(&config)
@@ -152,32 +377,34 @@ impl PluginManager {
             .map_err(|e| Error::Plugin(format!("Failed to define call-host: {}", e)))?;
 
         Ok(Self {
-            plugins: HashMap::new(),
+            plugins: Vec::new(),
+            hooks: HashMap::new(),
             engine,
             linker,
             host_state: TermailHostState::new(),
+            limits,
+            backend,
         })
     }
 
-    /// Load plugins from directories
+    /// Load plugins from the directory picked by `resolve_plugin_dir`.
     ///
-    /// If no plugin directory is found, nothing is loaded.
-    pub fn load_plugins(&mut self, enabled_plugins: &[String]) -> Result<u32, Error> {
-        // Check .config/termail/plugins first, fall back to ./plugins
-        let plugin_dir = PathBuf::from(".config/termail/plugins");
-        let plugin_dir = if plugin_dir.exists() {
-            plugin_dir
-        } else {
-            PathBuf::from("./plugins")
-        };
-
-        if !plugin_dir.exists() {
-            return Ok(0);
+    /// If no plugin directory is found, nothing is loaded. A plugin that fails to
+    /// load does not stop the others; it is listed in the report's `failed` instead.
+    /// Only an unreadable plugin directory is an error.
+    pub fn load_plugins(&mut self, enabled_plugins: &[String], configured_dir: Option<PathBuf>) -> Result<PluginLoadReport, Error> {
+        match find_plugin_dir(configured_dir) {
+            Some(plugin_dir) => self.load_plugins_from(&plugin_dir, enabled_plugins),
+            None => Ok(PluginLoadReport::default()),
         }
+    }
 
-        let mut loaded_plugins_count = 0;
+    /// Loads the enabled plugins found in `plugin_dir`, see `load_plugins`.
+    fn load_plugins_from(&mut self, plugin_dir: &Path, enabled_plugins: &[String]) -> Result<PluginLoadReport, Error> {
+        let mut report = PluginLoadReport::default();
+        tracing::info!("Loading plugins from {:?}", plugin_dir);
 
-        for entry in std::fs::read_dir(&plugin_dir)
+        for entry in std::fs::read_dir(plugin_dir)
             .map_err(|e| {
                 Error::Plugin(format!("Failed to read plugin dir {:?}: {}", plugin_dir, e))
             })?
@@ -190,46 +417,47 @@ impl PluginManager {
                 false => continue,
             };
 
-            let Some(manifest) = self.load_manifest(&manifest_path).map_err(|e| {
-                Error::Plugin(format!(
-                    "Failed to load manifest for plugin {:?}: {}",
-                    manifest_path, e
-                ))
-            })? else {
-                continue;
+            let manifest = match self.load_manifest(&manifest_path) {
+                Ok(Some(manifest)) => manifest,
+                Ok(None) => continue,
+                Err(e) => {
+                    // Without a manifest there is no plugin name; the directory name stands in
+                    let name = plugin_dir.file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_else(|| manifest_path.display().to_string());
+                    tracing::error!("Failed to load manifest for plugin {:?}: {}", manifest_path, e);
+                    report.failed.push((name, format!("invalid manifest: {}", e)));
+                    continue;
+                }
             };
 
             if enabled_plugins.contains(&manifest.name.to_lowercase()) {
-                self.load_plugin(&plugin_dir, manifest)?;
-                loaded_plugins_count += 1;
+                let name = manifest.name.clone();
+                match self.load_plugin(&plugin_dir, manifest) {
+                    Ok(()) => report.loaded += 1,
+                    Err(e) => {
+                        tracing::error!("Failed to load plugin {}: {}", name, e);
+                        report.failed.push((name, e.to_string()));
+                    }
+                }
             } else {
                 tracing::info!("Plugin {} is not enabled, skipping", manifest.name);
             }
         }
 
-        Ok(loaded_plugins_count)
+        Ok(report)
     }
 
     /// Load a single plugin manifest
     ///
     /// If a plugin has no backends it can operate on, it should not be loaded.
     fn load_manifest(&self, manifest_path: &Path) -> Result<Option<PluginManifest>, Error> {
-        let content = std::fs::read_to_string(manifest_path)
-            .map_err(|e| Error::Plugin(format!("Failed to read manifest: {}", e)))?;
-
-        let manifest: Result<PluginManifest, Error> = toml::from_str(&content)
-            .map_err(|e| Error::Plugin(format!("Failed to parse manifest: {}", e)));
-
-        match manifest {
-            Ok(m) => {
-                if m.backends.is_empty() {
-                    tracing::error!("Warning! Plugin {} has an empty \"backends\" field, and will NOT be loaded.", m.name);
-                    return Ok(None);
-                }
-                return Ok(Some(m));
-            }
-            Err(e) => Err(e),
+        let m = parse_manifest(manifest_path)?;
+        if m.backends.is_empty() {
+            tracing::error!("Warning! Plugin {} has an empty \"backends\" field, and will NOT be loaded.", m.name);
+            return Ok(None);
         }
+        Ok(Some(m))
     }
 
     /// Load a single plugin from directory
@@ -240,12 +468,13 @@ impl PluginManager {
         let cwasm_path = plugin_dir.join("plugin.cwasm");
         let wasm_path = plugin_dir.join("plugin.wasm");
 
+        let component_path = if cwasm_path.exists() { &cwasm_path } else { &wasm_path };
         let component = if cwasm_path.exists() {
             unsafe { Component::deserialize_file(&self.engine, &cwasm_path) }
-                .map_err(|e| Error::Plugin(format!("Failed to load pre-compiled WASM: {}", e)))?
+                .map_err(|e| plugin_load_error(&manifest.name, component_path, "load pre-compiled WASM", &e))?
         } else if wasm_path.exists() {
             Component::from_file(&self.engine, &wasm_path)
-                .map_err(|e| Error::Plugin(format!("Failed to load WASM: {}", e)))?
+                .map_err(|e| plugin_load_error(&manifest.name, component_path, "load WASM", &e))?
         } else {
             return Err(Error::Plugin(format!(
                 "Plugin {} missing \"plugin.wasm\" or \"plugin.cwasm\"",
@@ -253,40 +482,129 @@ impl PluginManager {
             )));
         };
 
-        for hook in manifest.hooks {
-            let mut wasi_builder = WasiCtxBuilder::new();
-            // If we need stdin/env, inherit_* helpers can expose them here.
-            let wasi_ctx = wasi_builder.build();
-
-            let mut store = Store::new(
-                &self.engine,
-                PluginState {
-                    wasi_ctx,
-                    wasi_table: ResourceTable::new(),
-                    host_state: self.host_state.clone(),
-                },
-            );
-
-            // TODO: A maintainable/readable error message that tells users potential fixes. For example, 
-            // sometimes the user may have forgotten to recompile the plugin (this has happened to me).
-            let instance = Plugin::instantiate(&mut store, &component, &self.linker)
-                .map_err(|e| Error::Plugin(format!("Failed to instantiate plugin \"{}\" with error: {}", manifest.name, e)))?;
-
-            let loaded_plugin = LoadedPlugin {
-                name: manifest.name.clone(),
-                store,
-                instance,
-            };
+        let mut wasi_builder = WasiCtxBuilder::new();
+        // If we need stdin/env, inherit_* helpers can expose them here.
+        let wasi_ctx = wasi_builder.build();
+
+        let mut store = Store::new(
+            &self.engine,
+            PluginState {
+                wasi_ctx,
+                wasi_table: ResourceTable::new(),
+                host_state: self.host_state.clone(),
+                limits: StoreLimitsBuilder::new()
+                    .memory_size(self.limits.max_memory_bytes)
+                    .table_elements(self.limits.max_table_elements)
+                    // Trap instead of failing the grow, so the plugin can't carry on half-broken
+                    .trap_on_grow_failure(true)
+                    .build(),
+            },
+        );
+        store.limiter(|state| &mut state.limits);
+
+        store.set_fuel(self.limits.fuel)
+            .map_err(|e| Error::Plugin(format!("Failed to set plugin fuel: {}", e)))?;
+
+        // Instantiate by hand (rather than `Plugin::instantiate`) to keep the raw
+        // instance around for looking up the optional lifecycle exports.
+        let raw_instance = self.linker.instantiate(&mut store, &component)
+            .map_err(|e| plugin_load_error(&manifest.name, component_path, "instantiate", &e))?;
+        let instance = Plugin::new(&mut store, &raw_instance)
+            .map_err(|e| plugin_load_error(&manifest.name, component_path, "instantiate", &e))?;
+
+        let on_load = raw_instance.get_typed_func::<(), ()>(&mut store, "on-load").ok();
+        let on_unload = raw_instance.get_typed_func::<(), ()>(&mut store, "on-unload").ok();
+        if let Some(on_load) = on_load {
+            store.set_fuel(self.limits.fuel)
+                .map_err(|e| Error::Plugin(format!("Failed to set plugin fuel: {}", e)))?;
+            call_lifecycle(&mut store, on_load)
+                .map_err(|e| plugin_load_error(&manifest.name, component_path, "run on-load of", &e))?;
+        }
 
-            self.plugins
-                .entry(hook)
+        let mut hooks = manifest.hooks;
+        hooks.sort_by_key(|hook| hook.to_string());
+        hooks.dedup();
+        for hook in hooks.iter() {
+            self.hooks
+                .entry(hook.clone())
                 .or_insert_with(Vec::new)
-                .push(loaded_plugin);
+                .push(manifest.name.clone());
         }
 
+        self.plugins.push(LoadedPlugin {
+            name: manifest.name,
+            hooks,
+            backends: manifest.backends,
+            enabled: true,
+            store,
+            instance,
+            on_unload,
+        });
+
         Ok(())
     }
 
+    /// Lists the loaded plugins (sorted by name) with the hooks they are registered for.
+    pub fn summaries(&self) -> Vec<PluginSummary> {
+        let mut summaries: Vec<PluginSummary> = self.plugins
+            .iter()
+            .map(|plugin| {
+                PluginSummary {
+                    name: plugin.name.clone(),
+                    hooks: plugin.hooks.clone(),
+                    enabled: plugin.enabled,
+                }
+            })
+            .collect();
+        summaries.sort_by_key(|summary| summary.name.to_lowercase());
+        summaries
+    }
+
+    /// Enables or disables the plugin called `name` (case-insensitive) for every hook
+    /// it is registered for. Returns false if no such plugin is loaded.
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> bool {
+        let mut found = false;
+        for plugin in self.plugins.iter_mut().filter(|plugin| plugin.name.eq_ignore_ascii_case(name)) {
+            plugin.enabled = enabled;
+            found = true;
+        }
+        found
+    }
+
+    /// Removes the plugin called `name` (case-insensitive) from every hook bucket,
+    /// dropping its instance. Returns false if no such plugin was loaded.
+    pub fn unload(&mut self, name: &str) -> bool {
+        let before = self.plugins.len();
+        let (unloaded, kept): (Vec<LoadedPlugin>, Vec<LoadedPlugin>) = std::mem::take(&mut self.plugins)
+            .into_iter()
+            .partition(|plugin| plugin.name.eq_ignore_ascii_case(name));
+        self.plugins = kept;
+        for plugin in unloaded {
+            plugin.run_on_unload(self.limits.fuel);
+        }
+        for names in self.hooks.values_mut() {
+            names.retain(|plugin_name| !plugin_name.eq_ignore_ascii_case(name));
+        }
+        self.hooks.retain(|_, names| !names.is_empty());
+        self.plugins.len() != before
+    }
+
+    /// Unloads every plugin, giving each the chance to run its `on-unload`.
+    pub fn unload_all(&mut self) {
+        self.hooks.clear();
+        for plugin in std::mem::take(&mut self.plugins) {
+            plugin.run_on_unload(self.limits.fuel);
+        }
+    }
+
+    /// Runs the plugins registered for `hook` on a message whose body is `content`
+    /// and returns the body they produce. Used by `test-plugins`.
+    pub async fn dispatch_body(&mut self, hook: &Hook, content: &str) -> Result<String, Error> {
+        let mut email = EmailMessage::new();
+        email.body = content.to_string();
+        self.dispatch(hook.to_wit_event((&email).into())).await
+    }
+
     /// Dispatch an event to the appropriate plugins
     ///
     /// Plugins are called in sequence, each receiving the output of the previous plugin.
@@ -296,8 +614,8 @@ impl PluginManager {
         let hook = event.hook();
         
         // Get the plugins registered for this hook
-        let plugins = match self.plugins.get_mut(&hook) {
-            Some(plugins) if !plugins.is_empty() => plugins,
+        let names = match self.hooks.get(&hook) {
+            Some(names) if !names.is_empty() => names.clone(),
             _ => {
                 // No plugins registered for this hook, return the content as-is
                 return Ok(event.content().to_string());
@@ -306,7 +624,15 @@ impl PluginManager {
 
         let mut current_event = event;
 
-        for plugin in plugins.iter_mut() {
+        for name in names {
+            let backend = &self.backend;
+            let Some(plugin) = self.plugins.iter_mut().find(|plugin| plugin.name == name && plugin.enabled) else {
+                continue;
+            };
+            if !plugin.backends.contains(backend) {
+                tracing::debug!("Skipping plugin {}: it does not support the {} backend", plugin.name, backend);
+                continue;
+            }
             let invocation_id = uuid::Uuid::new_v4().to_string();
 
             self.host_state
@@ -315,14 +641,17 @@ impl PluginManager {
                 .unwrap()
                 .insert(invocation_id.clone(), current_event.clone());
 
+            if let Err(e) = plugin.store.set_fuel(self.limits.fuel) {
+                tracing::error!("Failed to set fuel for plugin {}: {}", plugin.name, e);
+            }
+
             // Call the plugin's on-notify function and get the modified event back
             // Use block_in_place to allow sync WASI calls without crossing thread boundaries
-            current_event = tokio::task::block_in_place(|| {
+            let result = tokio::task::block_in_place(|| {
                 plugin
                     .instance
                     .call_on_notify(&mut plugin.store, &invocation_id, &current_event)
-            })
-            .map_err(|e| Error::Plugin(format!("Plugin {} failed: {}", plugin.name, e)))?;
+            });
 
             // Remove from active_invocations after processing
             self.host_state
@@ -331,6 +660,22 @@ impl PluginManager {
                 .unwrap()
                 .remove(&invocation_id);
 
+            current_event = match result {
+                Ok(event) => event,
+                Err(e) if is_out_of_fuel(&e) || is_over_resource_limit(&e) => {
+                    // A trapped instance cannot be entered again, so take it out of the chain
+                    let error = if is_out_of_fuel(&e) {
+                        Error::Plugin("plugin timed out".to_string())
+                    } else {
+                        Error::Plugin(format!("plugin exceeded its memory limit: {:#}", e))
+                    };
+                    tracing::error!("Plugin {}: {}. Disabling it and continuing without its output", plugin.name, error);
+                    plugin.enabled = false;
+                    continue;
+                }
+                Err(e) => return Err(Error::Plugin(format!("Plugin {} failed: {}", plugin.name, e))),
+            };
+
             tracing::info!("[Host] Plugin {} processed event", plugin.name);
         }
 
@@ -338,3 +683,293 @@ impl PluginManager {
         Ok(current_event.content().to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn content_passes_through_without_plugins() {
+        let mut manager = PluginManager::new(BackendType::GreenMail, PluginLimits::default()).unwrap();
+        let content = manager.dispatch_body(&Hook::BeforeSend, "Hello").await.unwrap();
+        assert_eq!(content, "Hello");
+    }
+
+    #[test]
+    fn the_xdg_plugin_dir_is_preferred() {
+        let xdg = tempfile::tempdir().unwrap();
+        let configured = tempfile::tempdir().unwrap();
+        let xdg_dir = Some(xdg.path().to_path_buf());
+        let configured_dir = Some(configured.path().to_path_buf());
+
+        assert_eq!(resolve_plugin_dir(xdg_dir, configured_dir.clone()), Some(xdg.path().to_path_buf()));
+        // Directories that do not exist are passed over
+        let missing = Some(xdg.path().join("missing"));
+        assert_eq!(resolve_plugin_dir(missing, configured_dir), Some(configured.path().to_path_buf()));
+    }
+
+    #[test]
+    fn common_load_failures_come_with_a_hint() {
+        let hint = |detail| plugin_error_hint(detail).unwrap_or_default();
+        assert!(hint("attempted to parse a wasm module with a component parser").contains("not a WASM component"));
+        assert!(hint("Module was compiled with incompatible Wasmtime version '37.0.0'").contains("Recompile"));
+        assert!(hint("component imports instance `tm:plugin-system/host-api`, but a matching implementation was not found").contains("wit/main.wit"));
+        assert!(hint("no exported function named `on-notify`").contains("missing an export"));
+        assert!(hint("memory minimum size of 4 pages exceeds memory limits: growing memory").contains("max_memory_bytes"));
+        assert_eq!(plugin_error_hint("disk on fire"), None);
+    }
+
+    #[test]
+    fn discovery_reports_invalid_manifests_beside_valid_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let valid = dir.path().join("a-valid");
+        let invalid = dir.path().join("b-invalid");
+        for plugin_dir in [&valid, &invalid, &dir.path().join("not-a-plugin")] {
+            std::fs::create_dir(plugin_dir).unwrap();
+        }
+        std::fs::write(valid.join("manifest.toml"), "name = \"Valid\"\ndescription = \"ok\"\nbackends = [\"greenmail\"]\nhooks = [\"before_send\"]\n").unwrap();
+        std::fs::write(valid.join("plugin.wasm"), "").unwrap();
+        std::fs::write(invalid.join("manifest.toml"), "name = ").unwrap();
+
+        let plugins = discover_plugins(dir.path(), &["valid".to_string()]).unwrap();
+        assert_eq!(plugins.len(), 2);
+        assert_eq!(plugins[0].dir, valid);
+        assert!(plugins[0].enabled);
+        assert!(plugins[0].problems().is_empty(), "{:?}", plugins[0].problems());
+        assert_eq!(plugins[1].dir, invalid);
+        assert!(plugins[1].manifest.is_err());
+        assert!(plugins[1].problems().iter().any(|problem| problem.contains("Failed to parse manifest")));
+        assert!(plugins[1].to_string().contains("not loaded"));
+    }
+
+    /// Core body of an `on-notify` that hands the event back unchanged: the lowered
+    /// arguments are `(invocation-id, event)` at `ptr`, so the event starts at `ptr + 8`
+    /// in exactly the layout the result needs.
+    const ECHO: &str = "(i32.add (local.get 0) (i32.const 8))";
+    /// Core body that never returns.
+    const SPIN: &str = "(loop $spin (br $spin)) (unreachable)";
+
+    /// A component in the WAT text format implementing the `plugin-with-lifecycle`
+    /// world. `on_load`, `on_notify` and `on_unload` are core function bodies;
+    /// `on_notify` gets a pointer to its arguments and returns a pointer to the event.
+    fn component(memory_pages: u32, on_load: &str, on_notify: &str, on_unload: &str) -> String {
+        format!(r#"(component
+  (core module $m
+    (memory (export "memory") {memory_pages})
+    (global $heap (mut i32) (i32.const 1024))
+    (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+      (local $ptr i32)
+      (local.set $ptr (i32.and (i32.add (global.get $heap) (i32.const 7)) (i32.const -8)))
+      (global.set $heap (i32.add (local.get $ptr) (local.get 3)))
+      (local.get $ptr))
+    (func (export "on-load") {on_load})
+    (func (export "on-notify") (param i32) (result i32) {on_notify})
+    (func (export "on-unload") {on_unload}))
+  (core instance $i (instantiate $m))
+  (type $attachment-info-def (record (field "filename" string) (field "content-type" string) (field "size" u64)))
+  (export $attachment-info "attachment-info" (type $attachment-info-def))
+  (type $message-def (record
+    (field "sender" string) (field "to" string) (field "cc" string) (field "subject" string) (field "body" string)
+    (field "attachments" (list $attachment-info)) (field "labels" (list string))))
+  (export $message "message" (type $message-def))
+  (type $event-def (variant
+    (case "before-send" $message) (case "after-send" $message)
+    (case "before-receive" $message) (case "after-receive" $message)))
+  (export $event "event" (type $event-def))
+  (func (export "on-notify") (param "invocation-id" string) (param "event" $event) (result $event)
+    (canon lift (core func $i "on-notify") (memory $i "memory") (realloc (func $i "realloc"))))
+  (func (export "on-load") (canon lift (core func $i "on-load")))
+  (func (export "on-unload") (canon lift (core func $i "on-unload"))))"#)
+    }
+
+    /// The manifest of a plugin `name` registered for `BeforeSend` on greenmail.
+    fn manifest(name: &str) -> PluginManifest {
+        PluginManifest {
+            name: name.to_string(),
+            description: String::new(),
+            website: String::new(),
+            backends: vec![BackendType::GreenMail],
+            hooks: vec![Hook::BeforeSend],
+        }
+    }
+
+    /// Loads `wat` as the plugin described by `manifest`.
+    fn load_as(manager: &mut PluginManager, dir: &Path, manifest: PluginManifest, wat: &str) -> Result<(), Error> {
+        let plugin_dir = dir.join(&manifest.name);
+        std::fs::create_dir_all(&plugin_dir).unwrap();
+        std::fs::write(plugin_dir.join("plugin.wasm"), wat).unwrap();
+        manager.load_plugin(&plugin_dir, manifest)
+    }
+
+    /// Loads `wat` as the plugin `name`, registered for `BeforeSend` on greenmail.
+    fn load(manager: &mut PluginManager, dir: &Path, name: &str, wat: &str) -> Result<(), Error> {
+        load_as(manager, dir, manifest(name), wat)
+    }
+
+    fn limits(fuel: u64, max_memory_bytes: usize) -> PluginLimits {
+        PluginLimits { fuel, max_memory_bytes, ..PluginLimits::default() }
+    }
+
+    fn enabled(manager: &PluginManager) -> Vec<(String, bool)> {
+        manager.summaries().into_iter().map(|summary| (summary.name, summary.enabled)).collect()
+    }
+
+    #[test]
+    fn load_errors_name_the_plugin_and_its_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut manager = PluginManager::new(BackendType::GreenMail, PluginLimits::default()).unwrap();
+        let error = load(&mut manager, dir.path(), "broken", "not a component").unwrap_err().to_string();
+        assert!(error.contains("\"broken\""), "{}", error);
+        assert!(error.contains("plugin.wasm"), "{}", error);
+    }
+
+    #[test]
+    fn plugins_that_fail_to_load_are_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = |name: &str| format!("name = \"{}\"\ndescription = \"\"\nbackends = [\"greenmail\"]\nhooks = [\"before_send\"]\n", name);
+        let echo = component(1, "", ECHO, "");
+        for (name, wasm) in [("Broken", "not a component"), ("Echo", echo.as_str())] {
+            let plugin_dir = dir.path().join(name);
+            std::fs::create_dir(&plugin_dir).unwrap();
+            std::fs::write(plugin_dir.join("manifest.toml"), manifest(name)).unwrap();
+            std::fs::write(plugin_dir.join("plugin.wasm"), wasm).unwrap();
+        }
+        let garbled = dir.path().join("garbled");
+        std::fs::create_dir(&garbled).unwrap();
+        std::fs::write(garbled.join("manifest.toml"), "name = ").unwrap();
+
+        let mut manager = PluginManager::new(BackendType::GreenMail, PluginLimits::default()).unwrap();
+        let enabled = ["broken".to_string(), "echo".to_string()];
+        let mut report = manager.load_plugins_from(dir.path(), &enabled).unwrap();
+        assert_eq!(report.loaded, 1);
+        report.failed.sort();
+        let failed: Vec<&str> = report.failed.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(failed, vec!["Broken", "garbled"]);
+
+        let summary = report.failure_summary().unwrap();
+        assert!(summary.starts_with("Broken ("), "{}", summary);
+        assert!(summary.contains("garbled (invalid manifest"), "{}", summary);
+        assert_eq!(PluginLoadReport::default().failure_summary(), None);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn plugins_pass_the_event_through() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut manager = PluginManager::new(BackendType::GreenMail, PluginLimits::default()).unwrap();
+        load(&mut manager, dir.path(), "echo", &component(1, "", ECHO, "")).unwrap();
+        assert_eq!(manager.dispatch_body(&Hook::BeforeSend, "Hello").await.unwrap(), "Hello");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn plugins_keep_their_state_across_hooks() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut manager = PluginManager::new(BackendType::GreenMail, PluginLimits::default()).unwrap();
+        // Remembers the first event it sees (at address 0) and returns that one from then on
+        let remember_first = "(if (i32.eqz (i32.load (i32.const 0))) \
+            (then (i32.store (i32.const 0) (i32.add (local.get 0) (i32.const 8))))) \
+            (i32.load (i32.const 0))";
+        let both_hooks = PluginManifest { hooks: vec![Hook::BeforeSend, Hook::AfterSend], ..manifest("memo") };
+        load_as(&mut manager, dir.path(), both_hooks, &component(1, "", remember_first, "")).unwrap();
+
+        assert_eq!(manager.summaries().len(), 1);
+        assert_eq!(manager.dispatch_body(&Hook::BeforeSend, "first").await.unwrap(), "first");
+        assert_eq!(manager.dispatch_body(&Hook::AfterSend, "second").await.unwrap(), "first");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn on_load_runs_once_when_the_plugin_is_loaded() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut manager = PluginManager::new(BackendType::GreenMail, PluginLimits::default()).unwrap();
+        // on-load counts its calls at address 4; on-notify traps unless the count is 1
+        let count_load = "(i32.store (i32.const 4) (i32.add (i32.load (i32.const 4)) (i32.const 1)))";
+        let check_loaded_once = format!("(if (i32.ne (i32.load (i32.const 4)) (i32.const 1)) (then unreachable)) {}", ECHO);
+        load(&mut manager, dir.path(), "once", &component(1, count_load, &check_loaded_once, "")).unwrap();
+
+        assert_eq!(manager.dispatch_body(&Hook::BeforeSend, "Hello").await.unwrap(), "Hello");
+        assert_eq!(manager.dispatch_body(&Hook::BeforeSend, "Again").await.unwrap(), "Again");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn plugins_can_read_the_sender() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut manager = PluginManager::new(BackendType::GreenMail, PluginLimits::default()).unwrap();
+        // The message starts 4 bytes into the event: the sender string is at +4, the body at +36
+        let sender_as_body = "(local $event i32) \
+            (local.set $event (i32.add (local.get 0) (i32.const 8))) \
+            (i32.store offset=36 (local.get $event) (i32.load offset=4 (local.get $event))) \
+            (i32.store offset=40 (local.get $event) (i32.load offset=8 (local.get $event))) \
+            (local.get $event)";
+        load(&mut manager, dir.path(), "whois", &component(1, "", sender_as_body, "")).unwrap();
+
+        let mut email = EmailMessage::new();
+        email.from = "Ann Lee <ann@example.com>".to_string().into();
+        email.body = "Hello".to_string();
+        let content = manager.dispatch(Hook::BeforeSend.to_wit_event((&email).into())).await.unwrap();
+        assert_eq!(content, "Ann Lee <ann@example.com>");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn plugins_for_other_backends_are_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut manager = PluginManager::new(BackendType::GreenMail, PluginLimits::default()).unwrap();
+        // Traps if it is ever called
+        let gmail_only = PluginManifest { backends: vec![BackendType::Gmail], ..manifest("gmail-only") };
+        load_as(&mut manager, dir.path(), gmail_only, &component(1, "", "(unreachable)", "")).unwrap();
+
+        assert_eq!(manager.dispatch_body(&Hook::BeforeSend, "Hello").await.unwrap(), "Hello");
+        assert_eq!(enabled(&manager), vec![("gmail-only".to_string(), true)]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn spinning_plugins_run_out_of_fuel_and_are_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut manager = PluginManager::new(BackendType::GreenMail, limits(100_000, 1 << 20)).unwrap();
+        load(&mut manager, dir.path(), "spin", &component(1, "", SPIN, "")).unwrap();
+        load(&mut manager, dir.path(), "echo", &component(1, "", ECHO, "")).unwrap();
+
+        assert_eq!(manager.dispatch_body(&Hook::BeforeSend, "Hello").await.unwrap(), "Hello");
+        assert_eq!(enabled(&manager), vec![("echo".to_string(), true), ("spin".to_string(), false)]);
+        // Later dispatches no longer reach it
+        assert_eq!(manager.dispatch_body(&Hook::BeforeSend, "Again").await.unwrap(), "Again");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn plugins_growing_past_the_memory_limit_are_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut manager = PluginManager::new(BackendType::GreenMail, limits(100_000, 128 * 1024)).unwrap();
+        let grow = format!("(drop (memory.grow (i32.const 16))) {}", ECHO);
+        load(&mut manager, dir.path(), "hog", &component(1, "", &grow, "")).unwrap();
+
+        assert_eq!(manager.dispatch_body(&Hook::BeforeSend, "Hello").await.unwrap(), "Hello");
+        assert_eq!(enabled(&manager), vec![("hog".to_string(), false)]);
+    }
+
+    #[test]
+    fn plugins_needing_more_memory_than_allowed_do_not_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut manager = PluginManager::new(BackendType::GreenMail, limits(100_000, 128 * 1024)).unwrap();
+        let error = load(&mut manager, dir.path(), "big", &component(4, "", ECHO, "")).unwrap_err().to_string();
+        assert!(error.contains("max_memory_bytes"), "{}", error);
+        assert!(manager.summaries().is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn unloading_removes_the_plugin_from_every_hook() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut manager = PluginManager::new(BackendType::GreenMail, limits(100_000, 1 << 20)).unwrap();
+        // A runaway on-unload is cut off by the fuel limit as well
+        load(&mut manager, dir.path(), "echo", &component(1, "", ECHO, SPIN)).unwrap();
+        load(&mut manager, dir.path(), "other", &component(1, "", ECHO, "")).unwrap();
+        manager.hooks.entry(Hook::AfterSend).or_default().push("echo".to_string());
+
+        assert!(manager.unload("ECHO"));
+        assert_eq!(enabled(&manager), vec![("other".to_string(), true)]);
+        assert!(manager.hooks.values().flatten().all(|name| name != "echo"));
+        assert!(!manager.hooks.contains_key(&Hook::AfterSend));
+        assert!(!manager.unload("echo"));
+
+        assert!(manager.unload("other"));
+        assert!(manager.hooks.is_empty());
+        assert_eq!(manager.dispatch_body(&Hook::BeforeSend, "Hello").await.unwrap(), "Hello");
+    }
+}
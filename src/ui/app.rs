@@ -1,20 +1,27 @@
 // This file contains the application logic for the termail UI.
 
 use crossterm::{
+    event::{KeyCode, KeyEvent},
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     execute,
 };
 use ratatui::DefaultTerminal;
 use crate::cli::command::{Command, CommandResult};
-use crate::core::{email::EmailMessage, label::Label, editor::Editor};
+use crate::core::{email::{EmailMessage, EmailSender, MimeType, reply_subject}, label::{Label, move_label_changes}, editor::Editor, links::is_browser_url};
 use crate::ui::{
-    event::{AppEvent, Event, EventHandler},
-    components::{composer_view::Composer, message_view::Messager},
+    event::{AppEvent, Event, EventHandler, TICK_FPS},
+    inputs::is_ctrl_c,
+    components::{composer_view::Composer, folder_pane::{FolderPreview, selected_folder_index}, log_pane::LogTail, message_view::Messager, move_picker::{MovePicker, basic_move_targets, move_targets}, plugin_view::PluginView},
 };
-use crate::config::Config;
+use crate::config::{Config, DeleteBehavior};
 use crate::error::Error;
-use crate::backends::Backend;
+use crate::logger::current_log_file;
+use crate::backends::{Backend, BackendCapabilities};
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use tokio::sync::Mutex;
 use crate::plugins::plugins::PluginManager;
 use ratatui_image::{thread::ThreadProtocol, picker::Picker};
@@ -34,6 +41,171 @@ pub enum ActiveViewState {
     MessageView(Messager),
     /// This state indicates that the user is writing a new email message.
     ComposeView(Composer),
+    /// The plugin manager, listing loaded plugins with an enable/disable toggle.
+    PluginView(PluginView),
+    /// Picking the folder to move an email to.
+    MovePicker(MovePicker),
+}
+
+/// An action that can be applied to every email selected in multi-select mode.
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
+pub enum BulkAction {
+    MarkRead,
+    /// Trash or permanently delete, depending on the backend's `delete_behavior`.
+    Delete,
+    /// Remove from the inbox, keeping every other label.
+    Archive,
+    /// Move to Trash, whatever the `delete_behavior`.
+    Trash,
+}
+
+impl BulkAction {
+    /// The backend command that applies this action to a single email.
+    pub fn command(&self, email_id: String) -> Command {
+        match self {
+            BulkAction::MarkRead => Command::MarkRead { email_id },
+            BulkAction::Delete => Command::Delete { email_id },
+            BulkAction::Archive => Command::ModifyLabels {
+                email_id,
+                add: vec![],
+                remove: vec!["INBOX".to_string()],
+            },
+            BulkAction::Trash => Command::ModifyLabels {
+                email_id,
+                add: vec!["TRASH".to_string()],
+                remove: vec![],
+            },
+        }
+    }
+
+    /// The command that reverses this action on a single email, if it can be reversed.
+    /// A permanent delete cannot.
+    pub fn inverse(&self, email_id: String, delete_behavior: DeleteBehavior) -> Option<Command> {
+        match (self, delete_behavior) {
+            (BulkAction::MarkRead, _) => Some(Command::ModifyLabels {
+                email_id,
+                add: vec!["UNREAD".to_string()],
+                remove: vec![],
+            }),
+            (BulkAction::Delete, DeleteBehavior::Trash) | (BulkAction::Trash, _) => Some(Command::ModifyLabels {
+                email_id,
+                add: vec![],
+                remove: vec!["TRASH".to_string()],
+            }),
+            (BulkAction::Delete, DeleteBehavior::Delete) => None,
+            (BulkAction::Archive, _) => Some(Command::ModifyLabels {
+                email_id,
+                add: vec!["INBOX".to_string()],
+                remove: vec![],
+            }),
+        }
+    }
+}
+
+/// The last destructive action, kept around so that `U` can reverse it.
+#[derive(Clone, Debug)]
+pub struct UndoEntry {
+    pub action: BulkAction,
+    /// Commands that restore the state from before the action.
+    pub inverse: Vec<Command>,
+}
+
+/// Builds the undo entry for applying `action` to `email_ids`, or `None` if the
+/// action cannot be undone.
+pub fn undo_entry(action: BulkAction, email_ids: &[String], delete_behavior: DeleteBehavior) -> Option<UndoEntry> {
+    let inverse: Option<Vec<Command>> = email_ids.iter()
+        .map(|id| action.inverse(id.clone(), delete_behavior))
+        .collect();
+    inverse
+        .filter(|commands| !commands.is_empty())
+        .map(|inverse| UndoEntry { action, inverse })
+}
+
+/// Counts the background tasks that are still running.
+///
+/// Tasks spawned through [`InFlight::spawn`] count as in flight from the moment
+/// they are spawned until they finish (or panic).
+#[derive(Clone, Debug, Default)]
+pub struct InFlight(Arc<AtomicUsize>);
+
+impl InFlight {
+    /// Number of tasks currently in flight.
+    pub fn count(&self) -> usize {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Marks one task as in flight until the returned guard is dropped.
+    pub fn start(&self) -> InFlightGuard {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        InFlightGuard(Arc::clone(&self.0))
+    }
+
+    /// Spawns `task` on the tokio runtime, tracking it until it completes.
+    pub fn spawn<F>(&self, task: F)
+    where
+        F: std::future::Future<Output = ()> + Send + 'static,
+    {
+        let guard = self.start();
+        tokio::spawn(async move {
+            let _guard = guard;
+            task.await;
+        });
+    }
+}
+
+/// Decrements the [`InFlight`] count when dropped.
+#[derive(Debug)]
+pub struct InFlightGuard(Arc<AtomicUsize>);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Whether a mailbox fetch result tagged `generation` is still wanted, i.e. no
+/// fetch was issued after it.
+pub fn is_current_fetch(generation: u64, current: u64) -> bool {
+    generation == current
+}
+
+/// Whether quitting should ask first: only when `confirm` is set and background
+/// tasks are still running.
+pub fn should_confirm_quit(confirm: bool, in_flight: usize) -> bool {
+    confirm && in_flight > 0
+}
+
+/// Frames of the spinner shown while background tasks run.
+const SPINNER_FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+/// The spinner frame for `tick`, advancing every few ticks so it stays readable at 30 FPS.
+pub fn spinner_frame(tick: u64) -> char {
+    SPINNER_FRAMES[(tick / 3) as usize % SPINNER_FRAMES.len()]
+}
+
+/// Loading state of asynchronously fetched data.
+#[derive(Clone, Debug)]
+pub enum LoadState<T> {
+    /// The fetch has been issued but no result has arrived yet.
+    Loading,
+    /// The fetch completed. Note that the data itself may still be empty.
+    Loaded(T),
+    /// The fetch failed with the given error message.
+    Failed(String),
+}
+
+impl<T> LoadState<T> {
+    /// Returns the loaded data, if any.
+    pub fn loaded(&self) -> Option<&T> {
+        match self {
+            LoadState::Loaded(data) => Some(data),
+            _ => None,
+        }
+    }
+
+    pub fn is_failed(&self) -> bool {
+        matches!(self, LoadState::Failed(_))
+    }
 }
 
 pub struct App {
@@ -41,9 +213,11 @@ pub struct App {
     pub running: bool,
     pub events: EventHandler, 
     pub config: Config,
-    /// Email storage. None means not loaded yet, Some(vec![]) means loaded but empty.
-    pub emails: Option<Vec<EmailMessage>>,
+    /// Email storage. `Loaded(vec![])` means loaded but empty.
+    pub emails: LoadState<Vec<EmailMessage>>,
     pub labels: Option<Vec<Label>>,
+    /// Count and newest subject of the selected folder, refreshed after each fetch.
+    pub folder_preview: Option<FolderPreview>,
     /// Thread-safe backend for sharing across async tasks
     /// 
     /// We use this to allow multiple async tasks to access the backend concurrently. In 
@@ -55,10 +229,43 @@ pub struct App {
     pub selected_email_index: Option<usize>,
     /// Name of the currently selected folder
     pub selected_folder: String,
+    /// First visible row of the folder pane, so the selected folder stays in view.
+    pub folder_offset: usize,
+    /// Number of emails currently requested for the inbox. Starts at the configured
+    /// initial fetch count and grows with "load more".
+    pub view_count: usize,
     /// Plugin manager for executing plugins
     pub plugin_manager: Arc<Mutex<PluginManager>>,
     /// Thread protocol for async image rendering (None when no image is being viewed)
     pub async_state: Option<ThreadProtocol>,
+    /// Unread email that was just opened, with the tick it was opened at. It is marked
+    /// read once it has stayed open for `mark_read_delay_secs`, so skimming through
+    /// messages neither marks them read nor spams the backend.
+    pub pending_mark_read: Option<(String, u64)>,
+    /// Whether the inbox is in multi-select mode (toggled with `v`).
+    pub select_mode: bool,
+    /// Indices (into `emails`) of the emails marked in multi-select mode.
+    pub selected_emails: HashSet<usize>,
+    /// Single-level undo for the last bulk/delete action.
+    pub last_action: Option<UndoEntry>,
+    /// Transient message for the bottom bar, with the tick it was set at.
+    pub status_message: Option<(String, u64)>,
+    /// Background tasks that are still running; drives the spinner.
+    pub in_flight: InFlight,
+    /// What the backend supports; unsupported actions are refused with a status message.
+    pub capabilities: BackendCapabilities,
+    /// Quit was requested while tasks were in flight and awaits confirmation.
+    pub quit_pending: bool,
+    /// Generation of the latest mailbox fetch. Results of older fetches are dropped,
+    /// so a slow fetch for a folder left behind cannot replace the current one.
+    pub fetch_generation: u64,
+    /// A sync from the cloud is running; shown in the top bar.
+    pub syncing: bool,
+    /// The log file followed by the log pane (toggled with `L`), while it is open.
+    pub log_tail: Option<LogTail>,
+    /// System clipboard, opened on first copy. It is kept open because on X11 the
+    /// copied text is served by this process and disappears with the handle.
+    clipboard: Option<arboard::Clipboard>,
 }
 
 impl App {
@@ -67,37 +274,77 @@ impl App {
         backend: Box<dyn Backend>,
         plugin_manager: PluginManager,
     ) -> Self {
+        let capabilities = backend.capabilities();
         let backend = Arc::new(Mutex::new(backend));
         let plugin_manager = Arc::new(Mutex::new(plugin_manager));
         let events = EventHandler::new();
+        let in_flight = InFlight::default();
 
-        // Spawn initial label fetch
-        Self::spawn_label_fetch(
-            Arc::clone(&backend),
-            events.get_sender(),
-        );
+        // Spawn initial label fetch. Backends without labels show an empty folder pane
+        let labels = if capabilities.supports_labels {
+            Self::spawn_label_fetch(
+                &in_flight,
+                Arc::clone(&backend),
+                events.get_sender(),
+            );
+            None
+        } else {
+            Some(Vec::new())
+        };
 
-        // Spawn initial email fetch
-        Self::spawn_email_fetch(
-            Arc::clone(&backend),
-            events.get_sender(),
-            config.termail.email_fetch_count,
-            None,
-        );
+        // Spawn initial email fetch, scoped to the configured start folder. With
+        // `auto_sync_on_start` the fetch follows a sync from the cloud.
+        let selected_folder = config.initial_folder();
+        let view_count = config.initial_fetch_count();
+        let syncing = config.termail.auto_sync_on_start && capabilities.supports_sync;
+        if syncing {
+            Self::spawn_sync_from_cloud(
+                &in_flight,
+                Arc::clone(&backend),
+                events.get_sender(),
+                0,
+                view_count,
+                folder_label(&selected_folder),
+            );
+        } else {
+            Self::spawn_email_fetch(
+                &in_flight,
+                Arc::clone(&backend),
+                events.get_sender(),
+                0,
+                view_count,
+                folder_label(&selected_folder),
+            );
+        }
 
         Self { 
             state: ActiveViewState::BaseView(BaseViewState::Labels), 
             running: true,
             events,
             config,
-            emails: None,  // Start with None to indicate loading state
-            labels: None,  // Start with None to indicate loading state
+            emails: LoadState::Loading,
+            labels,  // None while loading
+            folder_preview: None,
             backend,
             tick_counter: 0,
             selected_email_index: Some(0),  // Start with first email selected
-            selected_folder: "INBOX".to_string(),
+            selected_folder,
+            folder_offset: 0,
+            view_count,
             plugin_manager,
             async_state: None,  // No image protocol until we enter message view
+            pending_mark_read: None,
+            select_mode: false,
+            selected_emails: HashSet::new(),
+            last_action: None,
+            status_message: None,
+            in_flight,
+            capabilities,
+            quit_pending: false,
+            log_tail: None,
+            fetch_generation: 0,
+            syncing,
+            clipboard: None,
         }
     }
 
@@ -115,14 +362,40 @@ impl App {
                     _ => {}
                 }
                 Event::App(app_event) => match app_event {
-                    AppEvent::Quit => self.quit(),
-                    AppEvent::EmailsFetched(emails) => self.emails = Some(emails),
+                    AppEvent::Quit => self.request_quit(),
+                    AppEvent::EmailsFetched(generation, emails) => {
+                        self.syncing = false;
+                        // A newer fetch (e.g. for another folder) was issued since
+                        if !is_current_fetch(generation, self.fetch_generation) {
+                            tracing::debug!("Dropping stale fetch result {} (current {})", generation, self.fetch_generation);
+                            continue;
+                        }
+                        // Indices may point at different emails after a refetch.
+                        self.selected_emails.clear();
+                        self.emails = LoadState::Loaded(emails);
+                        Self::spawn_label_summary(
+                            &self.in_flight,
+                            Arc::clone(&self.backend),
+                            self.events.get_sender(),
+                            self.selected_folder.clone(),
+                        );
+                    }
+                    AppEvent::EmailsFetchFailed(generation, error) => self.fetch_failed(generation, error),
+                    AppEvent::RetryFetch => self.retry_email_fetch(),
                     AppEvent::EmailLoaded(email) => {
                         tracing::info!("EmailLoaded event received for email: {}", email.id);
-                        self.init_image_protocol_for_email(&email);
-                        self.state = ActiveViewState::MessageView(Messager::new(email));
+                        self.open_email(*email);
+                    }
+                    AppEvent::NotAuthenticated(error) => {
+                        // Stop showing the folders as loading; they will not arrive
+                        self.labels.get_or_insert_with(Vec::new);
+                        self.set_status(format!("Not signed in to {}: {}", self.config.termail.default_backend, error));
                     }
-                    AppEvent::LabelsFetched(labels) => self.labels = Some(labels),
+                    AppEvent::LabelsFetched(labels) => {
+                        self.labels = Some(labels);
+                        self.check_selected_folder();
+                    }
+                    AppEvent::LabelSummaryFetched(preview) => self.folder_preview = Some(preview),
                     AppEvent::SpawnEditor => {
                         if let ActiveViewState::ComposeView(composer) = &mut self.state {
                             let editor_cmd = self.config.termail.editor.clone();
@@ -151,52 +424,104 @@ impl App {
                             }
                         }
                     },
-                    AppEvent::SendEmail(email) => {
-                        let backend = self.backend.lock().await;
-                        let mut plugin_manager = self.plugin_manager.lock().await;
+                    AppEvent::Login if !self.capabilities.supports_login => {
+                        self.set_status(format!("Signing in again is not supported by {}", self.config.termail.default_backend));
+                    }
+                    AppEvent::Login => {
+                        // A sync or send holds the backend; waiting for it would leave
+                        // the terminal blank, so only go ahead when it is free
+                        let Ok(mut backend) = self.backend.try_lock() else {
+                            self.set_status("Busy syncing or sending, try again in a moment");
+                            continue;
+                        };
+
+                        // The OAuth flow prints its URL and may read from the terminal,
+                        // so suspend the TUI the same way as for the editor
+                        self.events.stop_events();
+                        let _ = execute!(std::io::stdout(), LeaveAlternateScreen);
+                        let _ = disable_raw_mode();
 
-                        let result = backend.do_command(Command::SendEmail {
-                            to: Some(email.to),
-                            subject: Some(email.subject),
-                            body: Some(email.body),
-                        }, Some(&mut plugin_manager)).await?;
+                        let result = match backend.reauthenticate().await {
+                            Ok(()) => backend.do_command(Command::Login, None).await,
+                            Err(e) => Err(e),
+                        };
+                        drop(backend);
+
+                        let _ = enable_raw_mode();
+                        let _ = execute!(std::io::stdout(), EnterAlternateScreen);
+                        terminal.clear()?;
+                        self.events.start_events();
 
                         match result {
-                            CommandResult::Empty => {
-                                // TODO: some kind of status bar / message? maybe use the bottom bar?
-                                tracing::info!("Email sent successfully!");
-                            },
-                            _ => return Err(Error::Other("Unexpected command result from send_email".to_string())),
+                            Ok(CommandResult::Success(message)) => {
+                                self.set_status(message.replace('\n', " - "));
+                                if self.capabilities.supports_labels {
+                                    self.labels = None;
+                                    Self::spawn_label_fetch(&self.in_flight, Arc::clone(&self.backend), self.events.get_sender());
+                                }
+                            }
+                            Ok(other) => self.set_status(format!("Signed in: {}", other)),
+                            Err(e) => self.set_status(format!("Login failed: {}", e)),
                         }
                     }
+                    AppEvent::SendEmail(email, reply_to) => {
+                        self.set_status("Sending email...");
+                        Self::spawn_send_email(
+                            &self.in_flight,
+                            Arc::clone(&self.backend),
+                            Arc::clone(&self.plugin_manager),
+                            self.events.get_sender(),
+                            *email,
+                            reply_to,
+                        );
+                    }
+                    AppEvent::EmailSent => self.set_status("Email sent"),
+                    AppEvent::SendFailed(error) => self.set_status(format!("Failed to send email: {}", error)),
+                    AppEvent::EmailMoved(folder) => self.set_status(format!("Moved to {}", folder)),
+                    AppEvent::MoveFailed(error) => {
+                        self.set_status(format!("Failed to move email: {}", error));
+                        // Bring back the email that was already taken off the list
+                        self.retry_email_fetch();
+                    }
+                    AppEvent::PluginLoadFailed(failures) => self.set_status(format!("Failed to load plugins: {}", failures)),
+                    AppEvent::SyncFromCloud if !self.capabilities.supports_sync => {
+                        self.set_status(format!("Syncing is not supported by {}", self.config.termail.default_backend));
+                    }
                     AppEvent::SyncFromCloud => {
-                        // same here can add status bar to show sync progress
-                        let label = if self.selected_folder == "INBOX" {
-                            None
-                        } else {
-                            Some(self.selected_folder.clone())
-                        };
+                        self.set_status("Syncing...");
+                        self.syncing = true;
+                        let generation = self.next_fetch_generation();
                         Self::spawn_sync_from_cloud(
+                            &self.in_flight,
                             Arc::clone(&self.backend),
                             self.events.get_sender(),
-                            self.config.termail.email_fetch_count,
-                            label,
+                            generation,
+                            self.view_count,
+                            self.current_label(),
                         );
                     },
+                    AppEvent::SyncFailed(error) => {
+                        self.set_status(format!("Sync failed, showing local mail: {}", error));
+                    }
+                    AppEvent::FullSyncStarted(total) => match total {
+                        Some(total) => self.set_status(format!("Full sync: downloading {} messages...", total)),
+                        None => self.set_status("Full sync: downloading every message..."),
+                    },
                     AppEvent::FolderChanged => {
-                        // Refresh emails when folder selection changes
-                        let label = if self.selected_folder == "INBOX" {
-                            None
-                        } else {
-                            Some(self.selected_folder.clone())
-                        };
+                        // Refresh emails when folder selection changes, starting over
+                        // from the initial count for the new folder
+                        self.view_count = self.config.initial_fetch_count();
+                        let generation = self.next_fetch_generation();
                         Self::spawn_email_fetch(
+                            &self.in_flight,
                             Arc::clone(&self.backend),
                             self.events.get_sender(),
-                            self.config.termail.email_fetch_count,
-                            label,
+                            generation,
+                            self.view_count,
+                            self.current_label(),
                         );
                     },
+                    AppEvent::LoadMore => self.load_more_emails(),
                     AppEvent::ImageResizeRequest(request) => {
                         // Process the resize request and update the protocol
                         if let Some(async_state) = &mut self.async_state {
@@ -220,6 +545,28 @@ impl App {
         self.running = false;
     }
 
+    /// Quits, unless a sync or send is still running and `confirm_quit` is set. In
+    /// that case the user is asked first; see `handle_quit_confirmation`.
+    pub fn request_quit(&mut self) {
+        if should_confirm_quit(self.config.confirm_quit(), self.in_flight.count()) {
+            self.quit_pending = true;
+            self.set_status("A sync or send is still running. Quit anyway? (y/n)");
+        } else {
+            self.quit();
+        }
+    }
+
+    /// Answers the pending quit confirmation: `y` (or the quit key, Esc or Ctrl-C,
+    /// again) quits, any other key stays.
+    pub fn handle_quit_confirmation(&mut self, key_event: KeyEvent) {
+        self.quit_pending = false;
+        match key_event.code {
+            KeyCode::Char('y') | KeyCode::Char('Y') | KeyCode::Esc => self.quit(),
+            _ if is_ctrl_c(&key_event) => self.quit(),
+            _ => self.status_message = None,
+        }
+    }
+
     /// Main render function that has access to Frame for stateful widgets
     pub fn init_image_protocol_for_email(&mut self, email: &EmailMessage) {
         let image_attachments = email.get_image_attachments();
@@ -263,69 +610,612 @@ impl App {
     /// Also handles periodic email refresh (every 120 seconds).
     pub fn tick(&mut self) {
         self.tick_counter += 1;
+        self.flush_pending_mark_read();
+        if let Some((_, shown_at)) = &self.status_message {
+            // The quit question stays up until it is answered
+            if !self.quit_pending && status_expired(*shown_at, self.tick_counter) {
+                self.status_message = None;
+            }
+        }
+
+        // Follow the log twice a second while the log pane is open
+        if self.log_tail.is_some() && self.tick_counter.is_multiple_of(TICK_FPS as u64 / 2) {
+            let path = self.current_log_file();
+            if let Some(tail) = &mut self.log_tail {
+                tail.follow(path);
+                tail.poll();
+            }
+        }
 
         // Refresh emails every 120 seconds (30 FPS * 120 seconds = 3600 ticks)
         const REFRESH_INTERVAL: u64 = 3600;
 
         if self.tick_counter % REFRESH_INTERVAL == 0 {
             // Refresh with current selected folder
-            let label = if self.selected_folder == "INBOX" {
-                None
-            } else {
-                Some(self.selected_folder.clone())
-            };
+            let generation = self.next_fetch_generation();
             Self::spawn_email_fetch(
+                &self.in_flight,
                 Arc::clone(&self.backend),
                 self.events.get_sender(),
-                self.config.termail.email_fetch_count,
-                label,
+                generation,
+                self.view_count,
+                self.current_label(),
             );
         }
     }
 
+    /// Opens or closes the log pane below the inbox.
+    pub fn toggle_log_pane(&mut self) {
+        self.log_tail = match self.log_tail {
+            Some(_) => None,
+            None => Some(LogTail::open(self.current_log_file())),
+        };
+    }
+
+    /// The log file being written to right now, which moves as the log rotates.
+    fn current_log_file(&self) -> std::path::PathBuf {
+        current_log_file(&self.config.get_log_path(), self.config.termail.log_rotation)
+    }
+
+    /// Opens the plugin manager view.
+    pub fn open_plugin_view(&mut self) {
+        let summaries = self.plugin_manager.try_lock().map(|manager| manager.summaries());
+        match summaries {
+            Ok(summaries) => self.state = ActiveViewState::PluginView(PluginView::new(summaries)),
+            Err(_) => self.set_status("Plugins are busy, try again in a moment"),
+        }
+    }
+
+    /// Enables or disables the plugin highlighted in the plugin manager view.
+    pub fn toggle_selected_plugin(&mut self) {
+        let ActiveViewState::PluginView(view) = &mut self.state else {
+            return;
+        };
+        let Some(plugin) = view.selected_plugin().cloned() else {
+            return;
+        };
+        // The manager is only held for the duration of a send/load, so don't wait for it
+        let summaries = self.plugin_manager.try_lock().map(|mut manager| {
+            manager.set_enabled(&plugin.name, !plugin.enabled);
+            manager.summaries()
+        });
+        match summaries {
+            Ok(summaries) => {
+                view.update(summaries);
+                tracing::info!("Plugin {} {}", plugin.name, if plugin.enabled { "disabled" } else { "enabled" });
+            }
+            Err(_) => self.set_status("Plugins are busy, try again in a moment"),
+        }
+    }
+
+    /// Shows `message` in the bottom bar for `STATUS_MESSAGE_SECS`.
+    pub fn set_status(&mut self, message: impl Into<String>) {
+        self.status_message = Some((message.into(), self.tick_counter));
+    }
+
+    /// Opens `url` in the system browser. Refuses anything that is not an http(s) URL.
+    pub fn open_in_browser(url: &str) -> Result<(), Error> {
+        if !is_browser_url(url) {
+            return Err(Error::InvalidInput(format!("Refusing to open non-http link: {}", url)));
+        }
+        open::that_detached(url)
+            .map_err(|e| Error::Other(format!("Failed to open browser: {}", e)))
+    }
+
+    /// Writes the HTML body of `email` (with inline images embedded) to a temporary
+    /// file and opens it in the system browser. The file is left behind on purpose:
+    /// the browser reads it after this returns.
+    pub fn open_html_in_browser(email: &EmailMessage) -> Result<PathBuf, Error> {
+        if !matches!(email.mime_type, MimeType::TextHtml) {
+            return Err(Error::InvalidInput("This email has no HTML body".to_string()));
+        }
+        let mut file = tempfile::Builder::new()
+            .prefix("termail-")
+            .suffix(".html")
+            .tempfile()
+            .map_err(|e| Error::Other(format!("Failed to create temporary file: {}", e)))?;
+        file.write_all(email.resolve_cid_references().as_bytes())
+            .map_err(|e| Error::Other(format!("Failed to write temporary file: {}", e)))?;
+        let (_, path) = file.keep()
+            .map_err(|e| Error::Other(format!("Failed to keep temporary file: {}", e)))?;
+        open::that_detached(&path)
+            .map_err(|e| Error::Other(format!("Failed to open browser: {}", e)))?;
+        Ok(path)
+    }
+
+    /// Puts `text` on the system clipboard.
+    pub fn copy_to_clipboard(&mut self, text: &str) -> Result<(), Error> {
+        if self.clipboard.is_none() {
+            let clipboard = arboard::Clipboard::new()
+                .map_err(|e| Error::Other(format!("Clipboard unavailable: {}", e)))?;
+            self.clipboard = Some(clipboard);
+        }
+        self.clipboard
+            .as_mut()
+            .unwrap()
+            .set_text(text)
+            .map_err(|e| Error::Other(format!("Failed to copy to clipboard: {}", e)))
+    }
+
+    /// Copies the bare address of `sender` (no display name) to the clipboard and
+    /// reports the outcome in the status bar.
+    pub fn copy_sender_address(&mut self, sender: &EmailSender) {
+        let Some(address) = copied_address(sender) else {
+            self.set_status("This email has no sender address");
+            return;
+        };
+        match self.copy_to_clipboard(address) {
+            Ok(()) => self.set_status(format!("Copied {}", address)),
+            Err(e) => self.set_status(e.to_string()),
+        }
+    }
+
+    /// Shows `email` in the message view. Unread emails are marked read once they
+    /// have been open for `mark_read_delay_secs`, see `flush_pending_mark_read`.
+    pub fn open_email(&mut self, email: EmailMessage) {
+        self.init_image_protocol_for_email(&email);
+        let auto_mark_read = email.is_unread && self.config.mark_read_delay_secs().is_some();
+        if auto_mark_read {
+            self.pending_mark_read = Some((email.id.clone(), self.tick_counter));
+        }
+        self.state = ActiveViewState::MessageView(
+            Messager::new(email).with_render_limit(self.config.max_render_bytes())
+                .with_labels(self.labels.as_deref().unwrap_or(&[]), &self.config.termail.label_names)
+        );
+        // A zero delay marks the email read right away
+        self.flush_pending_mark_read();
+    }
+
+    /// Marks the pending email read once its delay has passed, provided it is still open.
+    fn flush_pending_mark_read(&mut self) {
+        let Some((email_id, opened_at)) = &self.pending_mark_read else {
+            return;
+        };
+        let Some(delay) = mark_read_delay_ticks(self.config.mark_read_delay_secs()) else {
+            // Auto mark-read is disabled
+            self.pending_mark_read = None;
+            return;
+        };
+        if !mark_read_due(*opened_at, self.tick_counter, delay) {
+            return;
+        }
+        let email_id = email_id.clone();
+        self.pending_mark_read = None;
+
+        let still_open = matches!(&self.state, ActiveViewState::MessageView(messager) if messager.email.id == email_id);
+        if !still_open {
+            return;
+        }
+
+        // Clear the unread indicator right away rather than waiting for the next refresh.
+        if let LoadState::Loaded(emails) = &mut self.emails {
+            for email in emails.iter_mut().filter(|email| email.id == email_id) {
+                email.is_unread = false;
+            }
+        }
+        if let ActiveViewState::MessageView(messager) = &mut self.state {
+            messager.email.is_unread = false;
+        }
+
+        Self::spawn_mark_read(&self.in_flight, Arc::clone(&self.backend), email_id);
+    }
+
+    /// Switches to the compose view with `draft` prefilled.
+    pub fn open_composer(&mut self, draft: EmailMessage) {
+        if !self.capabilities.supports_send {
+            self.set_status(format!("Sending is not supported by {}", self.config.termail.default_backend));
+            return;
+        }
+        let mut composer = Composer::new(self.config.termail.editor.clone());
+        composer.cursor_to = draft.to.len();
+        composer.cursor_subject = draft.subject.len();
+        composer.draft = draft;
+        self.state = ActiveViewState::ComposeView(composer);
+    }
+
+    /// Opens the composer on a reply to the email open in the message view. The
+    /// reply is sent with the original's id, so it is threaded under it.
+    pub fn reply_to_open_email(&mut self) {
+        let ActiveViewState::MessageView(messager) = &self.state else {
+            return;
+        };
+        let original = &messager.email;
+        // The reply is typed above the quoted original
+        let draft = EmailMessage {
+            to: original.from.full_string(),
+            subject: reply_subject(&original.subject),
+            body: format!("\n\n{}", original.quote_body(&self.config.termail.quote)),
+            ..EmailMessage::new()
+        };
+        let reply_to = original.id.clone();
+        self.open_composer(draft);
+        if let ActiveViewState::ComposeView(composer) = &mut self.state {
+            composer.reply_to = Some(reply_to);
+        }
+    }
+
+    /// Opens the composer on a fresh message (not a reply) addressed to the sender
+    /// of the hovered email.
+    pub fn compose_to_hovered_sender(&mut self) {
+        let Some(email) = self.selected_email_index.and_then(|index| self.emails.loaded()?.get(index)) else {
+            return;
+        };
+        let draft = EmailMessage {
+            to: email.from.full_string(),
+            ..EmailMessage::new()
+        };
+        self.open_composer(draft);
+    }
+
+    /// Enters or leaves multi-select mode. Leaving it drops the selection.
+    pub fn toggle_select_mode(&mut self) {
+        self.select_mode = !self.select_mode;
+        if !self.select_mode {
+            self.selected_emails.clear();
+        }
+    }
+
+    /// Marks or unmarks the hovered email in multi-select mode.
+    pub fn toggle_hovered_selection(&mut self) {
+        if !self.select_mode {
+            return;
+        }
+        if let Some(index) = self.selected_email_index {
+            toggle_selection(&mut self.selected_emails, index);
+        }
+    }
+
+    /// Applies `action` to every selected email, then leaves multi-select mode.
+    pub fn apply_bulk_action(&mut self, action: BulkAction) {
+        let Some(emails) = self.emails.loaded() else {
+            return;
+        };
+        let commands = bulk_commands(action, emails, &self.selected_emails);
+        if commands.is_empty() {
+            return;
+        }
+        let mut email_ids: Vec<String> = self.selected_emails.iter()
+            .filter_map(|index| emails.get(*index))
+            // Marking an already read email read again has nothing to undo
+            .filter(|email| action != BulkAction::MarkRead || email.is_unread)
+            .map(|email| email.id.clone())
+            .collect();
+        email_ids.sort();
+        self.record_undo(action, &email_ids);
+
+        // Archived emails only leave the list when it is the inbox
+        let leaves_list = match action {
+            BulkAction::MarkRead => false,
+            BulkAction::Delete | BulkAction::Trash => true,
+            BulkAction::Archive => self.current_label().as_deref() == Some("INBOX"),
+        };
+        if let LoadState::Loaded(emails) = &mut self.emails {
+            match action {
+                BulkAction::MarkRead => {
+                    for index in &self.selected_emails {
+                        if let Some(email) = emails.get_mut(*index) {
+                            email.is_unread = false;
+                        }
+                    }
+                }
+                _ if leaves_list => {
+                    let mut index = 0;
+                    emails.retain(|_| {
+                        let keep = !self.selected_emails.contains(&index);
+                        index += 1;
+                        keep
+                    });
+                    self.clamp_selected_email_index();
+                }
+                _ => {}
+            }
+        }
+
+        tracing::info!("Applying {:?} to {} email(s)", action, self.selected_emails.len());
+        Self::spawn_bulk_commands(&self.in_flight, Arc::clone(&self.backend), commands);
+        self.select_mode = false;
+        self.selected_emails.clear();
+    }
+
+    /// Deletes the hovered email (see `BulkAction::Delete`).
+    pub fn delete_hovered_email(&mut self) {
+        let Some(index) = self.selected_email_index else {
+            return;
+        };
+        let Some(email_id) = self.emails.loaded().and_then(|emails| emails.get(index)).map(|email| email.id.clone()) else {
+            return;
+        };
+
+        if let LoadState::Loaded(emails) = &mut self.emails {
+            emails.remove(index);
+        }
+        self.clamp_selected_email_index();
+        self.record_undo(BulkAction::Delete, std::slice::from_ref(&email_id));
+        Self::spawn_bulk_commands(&self.in_flight, Arc::clone(&self.backend), vec![BulkAction::Delete.command(email_id)]);
+    }
+
+    /// Marks the email open in the message view as spam or not spam and returns to
+    /// the inbox. The email is dropped from the list when it no longer belongs in
+    /// the current folder.
+    pub fn set_open_email_spam(&mut self, spam: bool) {
+        let ActiveViewState::MessageView(messager) = &self.state else {
+            return;
+        };
+        let email_id = messager.email.id.clone();
+        let command = if spam {
+            Command::MarkSpam { email_id: email_id.clone() }
+        } else {
+            Command::NotSpam { email_id: email_id.clone() }
+        };
+
+        let leaves_folder = spam != (self.selected_folder == "SPAM");
+        if leaves_folder {
+            if let LoadState::Loaded(emails) = &mut self.emails {
+                emails.retain(|email| email.id != email_id);
+            }
+            self.clamp_selected_email_index();
+        }
+        Self::spawn_bulk_commands(&self.in_flight, Arc::clone(&self.backend), vec![command]);
+        self.set_status(if spam { "Marked as spam" } else { "Marked as not spam" });
+        self.state = ActiveViewState::BaseView(BaseViewState::Inbox);
+    }
+
+    /// The label id of the selected folder, if it is in the folder list.
+    fn selected_folder_id(&self) -> Option<String> {
+        let labels = self.labels.as_deref().unwrap_or(&[]);
+        match selected_folder_index(labels, &self.selected_folder) {
+            Some(index) => labels[index].id.clone(),
+            None => (self.selected_folder == "INBOX").then(|| "INBOX".to_string()),
+        }
+    }
+
+    /// Opens the "move to" picker for `email_id`. `return_to` is the message view
+    /// it was opened from, if any.
+    pub fn open_move_picker(&mut self, email_id: String, return_to: Option<Messager>) {
+        if !self.capabilities.supports_move {
+            self.set_status(format!("Moving emails is not supported by {}", self.config.termail.default_backend));
+            return;
+        }
+        let current = self.selected_folder_id();
+        let overrides = &self.config.termail.label_names;
+        let targets = if self.capabilities.supports_labels {
+            move_targets(self.labels.as_deref().unwrap_or(&[]), current.as_deref(), overrides)
+        } else {
+            basic_move_targets(current.as_deref(), overrides)
+        };
+        self.state = ActiveViewState::MovePicker(MovePicker::new(email_id, targets, return_to));
+    }
+
+    /// Opens the "move to" picker for the hovered email.
+    pub fn move_hovered_email(&mut self) {
+        let email_id = self.selected_email_index
+            .and_then(|index| self.emails.loaded()?.get(index))
+            .map(|email| email.id.clone());
+        if let Some(email_id) = email_id {
+            self.open_move_picker(email_id, None);
+        }
+    }
+
+    /// Leaves the "move to" picker without moving, back to where it was opened from.
+    pub fn close_move_picker(&mut self) {
+        let state = std::mem::replace(&mut self.state, ActiveViewState::BaseView(BaseViewState::Inbox));
+        if let ActiveViewState::MovePicker(picker) = state {
+            if let Some(messager) = picker.return_to {
+                self.state = ActiveViewState::MessageView(*messager);
+            }
+        }
+    }
+
+    /// Moves the email in the "move to" picker to the highlighted folder (see
+    /// `move_label_changes`) and returns to the inbox. The email is dropped from
+    /// the list when it no longer belongs in the current folder; the outcome is
+    /// reported via `AppEvent::EmailMoved` or `AppEvent::MoveFailed`.
+    pub fn move_to_picked_folder(&mut self) {
+        let ActiveViewState::MovePicker(picker) = &self.state else {
+            return;
+        };
+        let Some((target, name)) = picker.selected_target().cloned() else {
+            return;
+        };
+        let email_id = picker.email_id.clone();
+        let current = self.selected_folder_id();
+        let (add, remove) = move_label_changes(current.as_deref(), &target);
+
+        let leaves_folder = current.as_ref().is_some_and(|current| remove.contains(current));
+        if let LoadState::Loaded(emails) = &mut self.emails {
+            if leaves_folder {
+                emails.retain(|email| email.id != email_id);
+            } else if let Some(email) = emails.iter_mut().find(|email| email.id == email_id) {
+                email.labels.retain(|label| !remove.contains(label));
+                email.labels.extend(add.iter().cloned());
+            }
+        }
+        self.clamp_selected_email_index();
+        Self::spawn_move_email(
+            &self.in_flight,
+            Arc::clone(&self.backend),
+            self.events.get_sender(),
+            Command::Move { email_id, to: target, from: current },
+            name,
+        );
+        self.state = ActiveViewState::BaseView(BaseViewState::Inbox);
+    }
+
+    /// The delete behavior of the backend the app is running against.
+    fn delete_behavior(&self) -> DeleteBehavior {
+        self.config.backends
+            .get(&self.config.termail.default_backend)
+            .map(|backend| backend.delete_behavior)
+            .unwrap_or_default()
+    }
+
+    /// Remembers how to reverse `action` on `email_ids`, replacing any earlier entry.
+    fn record_undo(&mut self, action: BulkAction, email_ids: &[String]) {
+        self.last_action = undo_entry(action, email_ids, self.delete_behavior());
+    }
+
+    /// Reverses the last recorded action, then refetches the mailbox so the
+    /// restored emails show up again.
+    pub fn undo_last_action(&mut self) {
+        let Some(entry) = self.last_action.take() else {
+            tracing::info!("Nothing to undo");
+            return;
+        };
+        tracing::info!("Undoing {:?} on {} email(s)", entry.action, entry.inverse.len());
+
+        let backend = Arc::clone(&self.backend);
+        let sender = self.events.get_sender();
+        let count = self.view_count;
+        let label = self.current_label();
+        let in_flight = self.in_flight.clone();
+        let generation = self.next_fetch_generation();
+        self.in_flight.spawn(async move {
+            {
+                let backend_guard = backend.lock().await;
+                for command in entry.inverse {
+                    let name = command.name();
+                    if let Err(e) = backend_guard.do_command(command, None).await {
+                        tracing::error!("Undo {} failed: {}", name, e);
+                    }
+                }
+            }
+            Self::spawn_email_fetch(&in_flight, backend, sender, generation, count, label);
+        });
+    }
+
+    /// Keeps the hovered email inside the list after emails were removed from it.
+    fn clamp_selected_email_index(&mut self) {
+        let len = self.emails.loaded().map_or(0, |emails| emails.len());
+        if let Some(index) = self.selected_email_index {
+            self.selected_email_index = Some(index.min(len.saturating_sub(1)));
+        }
+    }
+
+    /// Shows the error of a failed mailbox fetch, unless a newer fetch has been issued since.
+    fn fetch_failed(&mut self, generation: u64, error: String) {
+        self.syncing = false;
+        if is_current_fetch(generation, self.fetch_generation) {
+            self.emails = LoadState::Failed(error);
+        }
+    }
+
+    /// Re-issues the mailbox fetch for the current folder after a failure.
+    pub fn retry_email_fetch(&mut self) {
+        self.emails = LoadState::Loading;
+        let generation = self.next_fetch_generation();
+        Self::spawn_email_fetch(
+            &self.in_flight,
+            Arc::clone(&self.backend),
+            self.events.get_sender(),
+            generation,
+            self.view_count,
+            self.current_label(),
+        );
+    }
+
+    /// Starts a new fetch generation, making the results of every earlier fetch stale.
+    fn next_fetch_generation(&mut self) -> u64 {
+        self.fetch_generation += 1;
+        self.fetch_generation
+    }
+
+    /// The label to filter the mailbox by. INBOX means no filter.
+    pub fn current_label(&self) -> Option<String> {
+        folder_label(&self.selected_folder)
+    }
+
+    /// Falls back to INBOX if the selected folder (e.g. a mistyped `default_folder`)
+    /// is not among the labels the backend returned.
+    fn check_selected_folder(&mut self) {
+        let Some(labels) = &self.labels else {
+            return;
+        };
+        if self.selected_folder == "INBOX" || selected_folder_index(labels, &self.selected_folder).is_some() {
+            return;
+        }
+        tracing::warn!("Folder {:?} does not exist, falling back to INBOX", self.selected_folder);
+        self.set_status(format!("Folder \"{}\" not found, showing INBOX", self.selected_folder));
+        self.selected_folder = "INBOX".to_string();
+        self.events.send(AppEvent::FolderChanged);
+    }
+
+    /// Grows the inbox by another `initial_fetch_count` emails (up to `max_view_count`)
+    /// and refetches the current folder.
+    pub fn load_more_emails(&mut self) {
+        let next = next_view_count(
+            self.view_count,
+            self.config.initial_fetch_count(),
+            self.config.max_view_count(),
+        );
+        if next == self.view_count {
+            tracing::info!("Already showing the maximum of {} emails", self.view_count);
+            return;
+        }
+        self.view_count = next;
+        let generation = self.next_fetch_generation();
+        Self::spawn_email_fetch(
+            &self.in_flight,
+            Arc::clone(&self.backend),
+            self.events.get_sender(),
+            generation,
+            self.view_count,
+            self.current_label(),
+        );
+    }
+
     /// Spawns an async task to sync emails from the cloud backend into the local maildir
     /// and then refresh the mailbox view.
     fn spawn_sync_from_cloud(
+        in_flight: &InFlight,
         backend: Arc<Mutex<Box<dyn Backend>>>,
         sender: tokio::sync::mpsc::UnboundedSender<Event>,
+        generation: u64,
         count: usize,
         label: Option<String>,
     ) {
-        tokio::spawn(async move {
-            // start by syncing from cloud
+        in_flight.spawn(async move {
+            // start by syncing from cloud. A first sync downloads everything, so say
+            // how much that is while it runs
             let sync_result = {
                 let backend_guard = backend.lock().await;
-                backend_guard.do_command(Command::SyncFromCloud, None)
+                match backend_guard.do_command(Command::SyncPlan, None).await {
+                    Ok(CommandResult::SyncPlan { full: true, total }) => {
+                        let _ = sender.send(Event::App(AppEvent::FullSyncStarted(total)));
+                    }
+                    Ok(_) => {}
+                    Err(e) => tracing::warn!("Failed to plan sync: {}", e),
+                }
+                backend_guard.do_command(Command::SyncFromCloud { yes: true }, None)
                     .await
             };
 
-            let result = match sync_result {
-                Ok(_) => {
-                    // after sync finishes, refresh the mailbox with view_mailbox
-                    let backend_guard = backend.lock().await;
-                    backend_guard
-                        .do_command(Command::ViewMailbox { count, label }, None)
-                        .await
-                }
-                Err(e) => {
-                    tracing::error!("Failed to sync from cloud: {}", e);
-                    // bail out of this async task, return right away without refreshing the mailbox
-                    return;
-                }
+            // Offline or not, the local mail is shown; a failed sync only says so
+            if let Err(e) = sync_result {
+                tracing::error!("Failed to sync from cloud: {}", e);
+                let _ = sender.send(Event::App(AppEvent::SyncFailed(e.to_string())));
+            }
+
+            // after sync finishes, refresh the mailbox with view_mailbox
+            let result = {
+                let backend_guard = backend.lock().await;
+                backend_guard
+                    .do_command(Command::ViewMailbox { count, label }, None)
+                    .await
             };
 
             match result {
                 Ok(CommandResult::Emails(emails)) => {
-                    let _ = sender.send(Event::App(AppEvent::EmailsFetched(emails)));
+                    let _ = sender.send(Event::App(AppEvent::EmailsFetched(generation, emails)));
                 }
                 Ok(CommandResult::Email(email)) => {
-                    let _ = sender.send(Event::App(AppEvent::EmailsFetched(vec![email])));
+                    let _ = sender.send(Event::App(AppEvent::EmailsFetched(generation, vec![*email])));
                 }
                 Ok(CommandResult::Empty) => {
-                    let _ = sender.send(Event::App(AppEvent::EmailsFetched(vec![])));
+                    let _ = sender.send(Event::App(AppEvent::EmailsFetched(generation, vec![])));
                 }
                 Err(e) => {
                     tracing::error!("Failed to fetch emails: {}", e);
+                    let _ = sender.send(Event::App(AppEvent::EmailsFetchFailed(generation, e.to_string())));
                 }
                 _ => {
                     tracing::error!("Unexpected command result from view_mailbox");
@@ -343,12 +1233,14 @@ impl App {
     /// * `count` - Number of emails to fetch
     /// * `label` - Optional label name to filter emails by
     fn spawn_email_fetch(
+        in_flight: &InFlight,
         backend: Arc<Mutex<Box<dyn Backend>>>,
         sender: tokio::sync::mpsc::UnboundedSender<Event>,
+        generation: u64,
         count: usize,
         label: Option<String>,
     ) {
-        tokio::spawn(async move {
+        in_flight.spawn(async move {
             // Acquire lock and fetch emails from maildir (no plugin manager needed for basic fetch)
             let result = {
                 let backend_guard = backend.lock().await;
@@ -358,16 +1250,17 @@ impl App {
             
             match result {
                 Ok(CommandResult::Emails(emails)) => {
-                    let _ = sender.send(Event::App(AppEvent::EmailsFetched(emails)));
+                    let _ = sender.send(Event::App(AppEvent::EmailsFetched(generation, emails)));
                 }
                 Ok(CommandResult::Email(email)) => {
-                    let _ = sender.send(Event::App(AppEvent::EmailsFetched(vec![email])));
+                    let _ = sender.send(Event::App(AppEvent::EmailsFetched(generation, vec![*email])));
                 }
                 Ok(CommandResult::Empty) => {
-                    let _ = sender.send(Event::App(AppEvent::EmailsFetched(vec![])));
+                    let _ = sender.send(Event::App(AppEvent::EmailsFetched(generation, vec![])));
                 }
                 Err(e) => {
                     tracing::error!("Failed to fetch emails: {}", e);
+                    let _ = sender.send(Event::App(AppEvent::EmailsFetchFailed(generation, e.to_string())));
                 }
                 _ => {
                     tracing::error!("Unexpected command result from view_mailbox");
@@ -378,11 +1271,12 @@ impl App {
 
     /// Spawns an async task to load a single email (with attachments) by id.
     pub fn spawn_single_email_fetch(
+        in_flight: &InFlight,
         backend: Arc<Mutex<Box<dyn Backend>>>,
         sender: tokio::sync::mpsc::UnboundedSender<Event>,
         email_id: String,
     ) {
-        tokio::spawn(async move {
+        in_flight.spawn(async move {
             let result = {
                 let backend_guard = backend.lock().await;
                 backend_guard.do_command(Command::LoadEmail { email_id }, None).await
@@ -405,11 +1299,109 @@ impl App {
         });
     }
 
+    /// Spawns an async task that marks an email read locally and on the server.
+    fn spawn_mark_read(
+        in_flight: &InFlight,
+        backend: Arc<Mutex<Box<dyn Backend>>>,
+        email_id: String,
+    ) {
+        in_flight.spawn(async move {
+            let backend_guard = backend.lock().await;
+            if let Err(e) = backend_guard.do_command(Command::MarkRead { email_id }, None).await {
+                tracing::error!("Failed to mark email as read: {}", e);
+            }
+        });
+    }
+
+    /// Spawns an async task that runs `commands` one after another, logging failures.
+    fn spawn_bulk_commands(
+        in_flight: &InFlight,
+        backend: Arc<Mutex<Box<dyn Backend>>>,
+        commands: Vec<Command>,
+    ) {
+        in_flight.spawn(async move {
+            let backend_guard = backend.lock().await;
+            for command in commands {
+                let name = command.name();
+                if let Err(e) = backend_guard.do_command(command, None).await {
+                    tracing::error!("Bulk {} failed: {}", name, e);
+                }
+            }
+        });
+    }
+
+    /// Spawns an async task that runs the `Command::Move` `command`. The outcome is
+    /// reported via `AppEvent::EmailMoved` (with `folder`, the target's display
+    /// name) or `AppEvent::MoveFailed`.
+    fn spawn_move_email(
+        in_flight: &InFlight,
+        backend: Arc<Mutex<Box<dyn Backend>>>,
+        sender: tokio::sync::mpsc::UnboundedSender<Event>,
+        command: Command,
+        folder: String,
+    ) {
+        in_flight.spawn(async move {
+            let result = {
+                let backend_guard = backend.lock().await;
+                backend_guard.do_command(command, None).await
+            };
+            let event = match result {
+                Ok(_) => AppEvent::EmailMoved(folder),
+                Err(e) => {
+                    tracing::error!("Failed to move email: {}", e);
+                    AppEvent::MoveFailed(e.to_string())
+                }
+            };
+            let _ = sender.send(Event::App(event));
+        });
+    }
+
+    /// Spawns an async task that sends `email`, so a slow SMTP/Gmail send does not
+    /// block the event loop. `reply_to` threads it under the email it answers. The
+    /// outcome is reported via `AppEvent::EmailSent` or `AppEvent::SendFailed`.
+    fn spawn_send_email(
+        in_flight: &InFlight,
+        backend: Arc<Mutex<Box<dyn Backend>>>,
+        plugin_manager: Arc<Mutex<PluginManager>>,
+        sender: tokio::sync::mpsc::UnboundedSender<Event>,
+        email: EmailMessage,
+        reply_to: Option<String>,
+    ) {
+        in_flight.spawn(async move {
+            let result = {
+                let backend_guard = backend.lock().await;
+                let mut plugin_manager = plugin_manager.lock().await;
+                backend_guard.do_command(Command::SendEmail {
+                    to: Some(email.to),
+                    subject: Some(email.subject),
+                    body: Some(email.body),
+                    cc: Some(email.cc),
+                    bcc: Some(email.bcc),
+                    reply_to,
+                }, Some(&mut plugin_manager)).await
+            };
+
+            let event = match result {
+                Ok(CommandResult::Empty) => {
+                    tracing::info!("Email sent successfully!");
+                    AppEvent::EmailSent
+                }
+                Ok(_) => AppEvent::SendFailed("Unexpected command result from send_email".to_string()),
+                Err(e) => {
+                    tracing::error!("Failed to send email: {}", e);
+                    AppEvent::SendFailed(e.to_string())
+                }
+            };
+            let _ = sender.send(Event::App(event));
+        });
+    }
+
     fn spawn_label_fetch(
+        in_flight: &InFlight,
         backend: Arc<Mutex<Box<dyn Backend>>>,
         sender: tokio::sync::mpsc::UnboundedSender<Event>,
     ) {
-        tokio::spawn(async move {
+        in_flight.spawn(async move {
             let result = {
                 let backend_guard = backend.lock().await;
                 backend_guard.do_command(Command::ListLabels, None).await
@@ -419,10 +1411,584 @@ impl App {
                 Ok(CommandResult::Labels(labels)) => {
                     let _ = sender.send(Event::App(AppEvent::LabelsFetched(labels)));
                 }
+                Err(e) if e.is_auth() => {
+                    tracing::error!("Failed to fetch labels: {}", e);
+                    let _ = sender.send(Event::App(AppEvent::NotAuthenticated(e.to_string())));
+                }
                 Err(e) => tracing::error!("Failed to fetch labels: {}", e),
                 _ => tracing::error!("Unexpected command result from list_labels"),
             }
         });
     }
 
-}
\ No newline at end of file
+    fn spawn_label_summary(
+        in_flight: &InFlight,
+        backend: Arc<Mutex<Box<dyn Backend>>>,
+        sender: tokio::sync::mpsc::UnboundedSender<Event>,
+        label: String,
+    ) {
+        in_flight.spawn(async move {
+            let result = {
+                let backend_guard = backend.lock().await;
+                backend_guard.do_command(Command::LabelSummary { label }, None).await
+            };
+
+            match result {
+                Ok(CommandResult::LabelSummary { label, count, latest_subject }) => {
+                    let preview = FolderPreview { label, count, latest_subject };
+                    let _ = sender.send(Event::App(AppEvent::LabelSummaryFetched(preview)));
+                }
+                Err(e) => tracing::error!("Failed to fetch label summary: {}", e),
+                _ => tracing::error!("Unexpected command result from label_summary"),
+            }
+        });
+    }
+
+}
+
+/// The label to filter the mailbox by for `folder`. INBOX is the unfiltered view.
+pub fn folder_label(folder: &str) -> Option<String> {
+    if folder == "INBOX" {
+        None
+    } else {
+        Some(folder.to_string())
+    }
+}
+
+/// What "copy sender address" puts on the clipboard: the bare address without the
+/// display name, or `None` if the sender has no address.
+pub fn copied_address(sender: &EmailSender) -> Option<&str> {
+    Some(sender.email.as_str()).filter(|address| !address.is_empty())
+}
+
+/// How long a status message stays in the bottom bar.
+pub const STATUS_MESSAGE_SECS: u64 = 5;
+
+/// Whether a status message set at `shown_at` should be cleared by tick `now`.
+pub fn status_expired(shown_at: u64, now: u64) -> bool {
+    now.saturating_sub(shown_at) >= STATUS_MESSAGE_SECS * TICK_FPS as u64
+}
+
+/// Computes the inbox size after a "load more": grows by `step`, never shrinks,
+/// and never exceeds `max` (unless `current` already does).
+pub fn next_view_count(current: usize, step: usize, max: usize) -> usize {
+    current.saturating_add(step.max(1)).min(max).max(current)
+}
+
+/// Converts the `mark_read_delay_secs` setting into ticks. `None` (never) stays `None`.
+pub fn mark_read_delay_ticks(delay_secs: Option<u64>) -> Option<u64> {
+    delay_secs.map(|secs| (secs as f64 * TICK_FPS).round() as u64)
+}
+
+/// Whether an email opened at tick `opened_at` has been open for at least `delay` ticks.
+pub fn mark_read_due(opened_at: u64, now: u64, delay: u64) -> bool {
+    now.saturating_sub(opened_at) >= delay
+}
+
+/// Index of the first unread email after `current` (from the top if nothing is
+/// selected). With `wrap`, the search continues from the top of the list up to
+/// `current` itself; `None` if no email in range is unread.
+pub fn next_unread_index(emails: &[EmailMessage], current: Option<usize>, wrap: bool) -> Option<usize> {
+    let start = current.map_or(0, |index| index + 1);
+    let after = (start..emails.len()).find(|&index| emails[index].is_unread);
+    if after.is_some() || !wrap {
+        return after;
+    }
+    (0..start.min(emails.len())).find(|&index| emails[index].is_unread)
+}
+
+/// Adds `index` to the selection if it is absent, removes it otherwise.
+/// Returns whether it is selected afterwards.
+pub fn toggle_selection(selected: &mut HashSet<usize>, index: usize) -> bool {
+    if selected.remove(&index) {
+        false
+    } else {
+        selected.insert(index)
+    }
+}
+
+/// The commands that apply `action` to the selected emails, in inbox order. Stale
+/// indices are skipped. Marking read and archiving are a single `BatchModify`;
+/// deleting and trashing are one command per email since trashing cannot be batched.
+pub fn bulk_commands(action: BulkAction, emails: &[EmailMessage], selected: &HashSet<usize>) -> Vec<Command> {
+    let mut indices: Vec<usize> = selected.iter().copied().collect();
+    indices.sort_unstable();
+    let email_ids: Vec<String> = indices.into_iter()
+        .filter_map(|index| emails.get(index))
+        .map(|email| email.id.clone())
+        .collect();
+    match action {
+        BulkAction::MarkRead if !email_ids.is_empty() => vec![Command::BatchModify {
+            email_ids,
+            add: vec![],
+            remove: vec!["UNREAD".to_string()],
+        }],
+        BulkAction::Archive if !email_ids.is_empty() => vec![Command::BatchModify {
+            email_ids,
+            add: vec![],
+            remove: vec!["INBOX".to_string()],
+        }],
+        _ => email_ids.into_iter().map(|email_id| action.command(email_id)).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backends::BackendType;
+    use crate::config::PluginLimits;
+
+    fn inbox(ids: &[&str]) -> Vec<EmailMessage> {
+        ids.iter().map(|id| EmailMessage { id: id.to_string(), ..EmailMessage::new() }).collect()
+    }
+
+    /// A backend without mail or features, for driving an `App` in tests. It
+    /// records the name of every command it is given, and fails them all if
+    /// `failing` is set. With `sync` it claims to support syncing.
+    #[derive(Default)]
+    struct NullBackend {
+        commands: Arc<std::sync::Mutex<Vec<&'static str>>>,
+        failing: bool,
+        sync: bool,
+    }
+
+    #[async_trait::async_trait]
+    impl Backend for NullBackend {
+        fn needs_oauth(&self) -> bool {
+            false
+        }
+
+        fn capabilities(&self) -> BackendCapabilities {
+            BackendCapabilities {
+                supports_labels: false,
+                supports_sync: self.sync,
+                supports_send: false,
+                supports_search: false,
+                supports_login: false,
+                supports_move: false,
+            }
+        }
+
+        async fn do_command(&self, cmd: Command, _plugin_manager: Option<&mut PluginManager>) -> Result<CommandResult, Error> {
+            self.commands.lock().unwrap().push(cmd.name());
+            if self.failing {
+                return Err(Error::Connection("offline".to_string()));
+            }
+            Ok(CommandResult::Emails(Vec::new()))
+        }
+
+        fn requires_authentication(&self, _cmd: &Command) -> Option<bool> {
+            Some(false)
+        }
+    }
+
+    /// An `App` on `NullBackend`, with `extra` appended to the `[termail]` table.
+    fn app(extra: &str) -> App {
+        app_with(extra, NullBackend::default())
+    }
+
+    fn app_with(extra: &str, backend: NullBackend) -> App {
+        let config = Config::parse(&format!(
+            "[termail]\ncli = false\ndefault_backend = \"greenmail\"\nemail_fetch_count = 10\neditor = \"vim\"\nplugins = []\n{}\n\n[backends.greenmail]\nhost = \"127.0.0.1\"\nport = 1993\nssl = true\nmaildir_path = \"./Maildir\"\n",
+            extra
+        )).unwrap();
+        let plugin_manager = PluginManager::new(BackendType::GreenMail, PluginLimits::default()).unwrap();
+        let mut app = App::new(config, Box::new(backend), plugin_manager);
+        // Tests send their own events
+        app.events.stop_events();
+        app
+    }
+
+    /// `app` showing the unread email `id`, opened at tick 0.
+    fn app_reading(extra: &str, id: &str) -> App {
+        let mut app = app(extra);
+        let email = EmailMessage { id: id.to_string(), is_unread: true, ..EmailMessage::new() };
+        app.emails = LoadState::Loaded(vec![email.clone()]);
+        app.state = ActiveViewState::MessageView(Messager::new(email));
+        app.pending_mark_read = Some((id.to_string(), 0));
+        app
+    }
+
+    /// Waits for the tasks `app` spawned to finish.
+    async fn settle(app: &App) {
+        for _ in 0..1000 {
+            if app.in_flight.count() == 0 {
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+        }
+        panic!("{} task(s) still in flight", app.in_flight.count());
+    }
+
+    fn is_unread(app: &App, id: &str) -> bool {
+        app.emails.loaded().unwrap().iter().any(|email| email.id == id && email.is_unread)
+    }
+
+    #[test]
+    fn toggling_selects_then_unselects() {
+        let mut selected = HashSet::new();
+        assert!(toggle_selection(&mut selected, 2));
+        assert!(selected.contains(&2));
+        assert!(!toggle_selection(&mut selected, 2));
+        assert!(selected.is_empty());
+    }
+
+    #[test]
+    fn archiving_is_one_batch_in_inbox_order() {
+        let emails = inbox(&["a", "b", "c"]);
+        let commands = bulk_commands(BulkAction::Archive, &emails, &HashSet::from([2, 0, 7]));
+        assert!(matches!(
+            commands.as_slice(),
+            [Command::BatchModify { email_ids, add, remove }]
+                if email_ids == &["a", "c"] && add.is_empty() && remove == &["INBOX"]
+        ));
+    }
+
+    #[test]
+    fn trashing_is_one_command_per_email() {
+        let emails = inbox(&["a", "b"]);
+        let commands = bulk_commands(BulkAction::Trash, &emails, &HashSet::from([0, 1]));
+        assert_eq!(commands.len(), 2);
+        assert!(commands.iter().all(|command| matches!(command, Command::ModifyLabels { add, .. } if add == &["TRASH"])));
+    }
+
+    #[test]
+    fn archive_and_trash_can_be_undone() {
+        let ids = ["a".to_string()];
+        let archive = undo_entry(BulkAction::Archive, &ids, DeleteBehavior::Delete).unwrap();
+        assert!(matches!(archive.inverse.as_slice(), [Command::ModifyLabels { add, .. }] if add == &["INBOX"]));
+        // Trash always trashes, so it stays undoable when deleting is permanent
+        let trash = undo_entry(BulkAction::Trash, &ids, DeleteBehavior::Delete).unwrap();
+        assert!(matches!(trash.inverse.as_slice(), [Command::ModifyLabels { remove, .. }] if remove == &["TRASH"]));
+        assert!(undo_entry(BulkAction::Delete, &ids, DeleteBehavior::Delete).is_none());
+    }
+
+    #[test]
+    fn next_unread_is_found_after_the_hovered_email() {
+        let mut emails = inbox(&["a", "b", "c", "d"]);
+        emails[0].is_unread = true;
+        emails[2].is_unread = true;
+        assert_eq!(next_unread_index(&emails, None, false), Some(0));
+        assert_eq!(next_unread_index(&emails, Some(0), false), Some(2));
+        assert_eq!(next_unread_index(&emails, Some(2), false), None);
+        // Wrapping goes back to the top, and can land on the hovered email itself
+        assert_eq!(next_unread_index(&emails, Some(2), true), Some(0));
+        assert_eq!(next_unread_index(&emails[..1], Some(0), true), Some(0));
+        assert_eq!(next_unread_index(&inbox(&["a"]), Some(0), true), None);
+    }
+
+    #[test]
+    fn only_the_latest_fetch_is_current() {
+        // Switching labels twice issues fetches 1 and 2; the slower first one is stale
+        assert!(!is_current_fetch(1, 2));
+        assert!(is_current_fetch(2, 2));
+        // A result can never be newer than the last fetch issued
+        assert!(!is_current_fetch(3, 2));
+    }
+
+    #[test]
+    fn mark_read_delay_is_counted_in_ticks() {
+        assert_eq!(mark_read_delay_ticks(None), None);
+        assert_eq!(mark_read_delay_ticks(Some(0)), Some(0));
+        assert_eq!(mark_read_delay_ticks(Some(2)), Some(2 * TICK_FPS as u64));
+        assert!(!mark_read_due(10, 69, 60));
+        assert!(mark_read_due(10, 70, 60));
+        assert!(mark_read_due(10, 10, 0));
+    }
+
+    #[tokio::test]
+    async fn emails_are_marked_read_once_open_long_enough() {
+        let mut app = app_reading("mark_read_delay_secs = 1", "a");
+        app.tick_counter = TICK_FPS as u64 - 1;
+        app.flush_pending_mark_read();
+        assert!(is_unread(&app, "a"));
+
+        app.tick_counter = TICK_FPS as u64;
+        app.flush_pending_mark_read();
+        assert!(!is_unread(&app, "a"));
+        assert!(app.pending_mark_read.is_none());
+    }
+
+    #[tokio::test]
+    async fn emails_closed_before_the_delay_stay_unread() {
+        let mut app = app_reading("mark_read_delay_secs = 1", "a");
+        app.state = ActiveViewState::BaseView(BaseViewState::Inbox);
+        app.tick_counter = TICK_FPS as u64;
+        app.flush_pending_mark_read();
+        assert!(is_unread(&app, "a"));
+        assert!(app.pending_mark_read.is_none());
+    }
+
+    #[tokio::test]
+    async fn emails_are_not_marked_read_without_a_delay_configured() {
+        let mut app = app_reading("", "a");
+        app.tick_counter = 10 * TICK_FPS as u64;
+        app.flush_pending_mark_read();
+        assert!(is_unread(&app, "a"));
+    }
+
+    fn labels(names: &[&str]) -> Vec<Label> {
+        names.iter().map(|name| Label { name: Some(name.to_string()), ..Label::new() }).collect()
+    }
+
+    #[tokio::test]
+    async fn unknown_default_folders_fall_back_to_inbox() {
+        let mut typo = app("default_folder = \"Wrok\"");
+        assert_eq!(typo.selected_folder, "Wrok");
+        typo.labels = Some(labels(&["INBOX", "Work"]));
+        typo.check_selected_folder();
+        assert_eq!(typo.selected_folder, "INBOX");
+        assert!(typo.status_message.as_ref().is_some_and(|(message, _)| message.contains("\"Wrok\" not found")));
+
+        let mut work = app("default_folder = \"Work\"");
+        work.labels = Some(labels(&["INBOX", "Work"]));
+        work.check_selected_folder();
+        assert_eq!(work.selected_folder, "Work");
+        assert!(work.status_message.is_none());
+    }
+
+    #[tokio::test]
+    async fn opening_an_unread_email_marks_it_read_on_the_server() {
+        let backend = NullBackend::default();
+        let commands = Arc::clone(&backend.commands);
+        let mut app = app_with("mark_read_delay_secs = 2", backend);
+        let email = EmailMessage { id: "a".to_string(), is_unread: true, ..EmailMessage::new() };
+        app.emails = LoadState::Loaded(vec![email.clone()]);
+        settle(&app).await;
+        commands.lock().unwrap().clear();
+
+        app.open_email(email);
+        // Debounced: nothing is sent while the email has only just been opened
+        app.tick_counter += 1;
+        app.flush_pending_mark_read();
+        settle(&app).await;
+        assert!(commands.lock().unwrap().is_empty());
+        assert!(is_unread(&app, "a"));
+
+        app.tick_counter += 2 * TICK_FPS as u64;
+        app.flush_pending_mark_read();
+        assert!(!is_unread(&app, "a"));
+        settle(&app).await;
+        assert_eq!(*commands.lock().unwrap(), vec!["mark-read"]);
+
+        // Only once, however long it stays open
+        app.tick_counter += 10 * TICK_FPS as u64;
+        app.flush_pending_mark_read();
+        settle(&app).await;
+        assert_eq!(commands.lock().unwrap().len(), 1);
+    }
+
+    /// Waits for the failure of the current mailbox fetch, skipping other events.
+    async fn fetch_failure(app: &mut App) -> (u64, String) {
+        loop {
+            if let Event::App(AppEvent::EmailsFetchFailed(generation, error)) = app.events.next().await.unwrap() {
+                if generation == app.fetch_generation {
+                    return (generation, error);
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_fetches_can_be_retried() {
+        let backend = NullBackend { failing: true, ..NullBackend::default() };
+        let commands = Arc::clone(&backend.commands);
+        let mut app = app_with("", backend);
+        settle(&app).await;
+
+        let (generation, error) = fetch_failure(&mut app).await;
+        app.fetch_failed(generation, error);
+        assert!(matches!(&app.emails, LoadState::Failed(error) if error.contains("offline")));
+
+        commands.lock().unwrap().clear();
+        app.retry_email_fetch();
+        assert!(matches!(app.emails, LoadState::Loading));
+        settle(&app).await;
+        assert_eq!(*commands.lock().unwrap(), vec!["view-mailbox"]);
+
+        // The result of the fetch that was retried is stale by now
+        app.fetch_failed(generation, "old".to_string());
+        assert!(matches!(app.emails, LoadState::Loading));
+        let (generation, error) = fetch_failure(&mut app).await;
+        app.fetch_failed(generation, error);
+        assert!(app.emails.is_failed());
+    }
+
+    #[tokio::test]
+    async fn tasks_count_as_in_flight_until_they_finish() {
+        let in_flight = InFlight::default();
+        let guard = in_flight.start();
+        assert_eq!(in_flight.count(), 1);
+        drop(guard);
+        assert_eq!(in_flight.count(), 0);
+
+        let (finish, finished) = tokio::sync::oneshot::channel::<()>();
+        in_flight.spawn(async move {
+            let _ = finished.await;
+        });
+        in_flight.spawn(async { panic!("task failed") });
+        assert_eq!(in_flight.count(), 2);
+
+        finish.send(()).unwrap();
+        for _ in 0..1000 {
+            if in_flight.count() == 0 {
+                break;
+            }
+            tokio::task::yield_now().await;
+        }
+        // Panicking tasks are not counted forever either
+        assert_eq!(in_flight.count(), 0);
+    }
+
+    #[test]
+    fn the_spinner_cycles_through_its_frames() {
+        assert_eq!(spinner_frame(0), SPINNER_FRAMES[0]);
+        assert_eq!(spinner_frame(3), SPINNER_FRAMES[1]);
+        assert_eq!(spinner_frame(3 * SPINNER_FRAMES.len() as u64), SPINNER_FRAMES[0]);
+    }
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, crossterm::event::KeyModifiers::NONE)
+    }
+
+    #[tokio::test]
+    async fn quitting_with_tasks_running_asks_first() {
+        let mut app = app("");
+        settle(&app).await;
+        let task = app.in_flight.start();
+
+        app.request_quit();
+        assert!(app.running && app.quit_pending);
+        // Declining keeps termail running
+        app.handle_key_events(key(KeyCode::Char('n'))).unwrap();
+        assert!(app.running && !app.quit_pending);
+        assert!(app.status_message.is_none());
+
+        app.request_quit();
+        app.handle_key_events(key(KeyCode::Char('y'))).unwrap();
+        assert!(!app.running);
+        drop(task);
+    }
+
+    #[tokio::test]
+    async fn quitting_is_immediate_when_idle_or_not_confirmed() {
+        let mut idle = app("");
+        settle(&idle).await;
+        idle.request_quit();
+        assert!(!idle.running);
+
+        let mut unconfirmed = app("confirm_quit = false");
+        let _task = unconfirmed.in_flight.start();
+        unconfirmed.request_quit();
+        assert!(!unconfirmed.running);
+        assert!(should_confirm_quit(true, 1));
+        assert!(!should_confirm_quit(true, 0));
+        assert!(!should_confirm_quit(false, 3));
+    }
+
+    fn ctrl(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), crossterm::event::KeyModifiers::CONTROL)
+    }
+
+    #[test]
+    fn ctrl_c_is_recognised_with_either_case() {
+        assert!(is_ctrl_c(&ctrl('c')));
+        assert!(is_ctrl_c(&ctrl('C')));
+        assert!(is_ctrl_c(&KeyEvent::new(
+            KeyCode::Char('c'),
+            crossterm::event::KeyModifiers::CONTROL | crossterm::event::KeyModifiers::SHIFT,
+        )));
+        assert!(!is_ctrl_c(&key(KeyCode::Char('c'))));
+        assert!(!is_ctrl_c(&ctrl('x')));
+    }
+
+    #[tokio::test]
+    async fn ctrl_c_quits_from_every_view() {
+        let mut app = app("");
+        settle(&app).await;
+        app.state = ActiveViewState::MessageView(Messager::new(EmailMessage::new()));
+        app.handle_key_events(ctrl('c')).unwrap();
+        assert!(!app.running);
+    }
+
+    #[tokio::test]
+    async fn ctrl_c_asks_before_discarding_a_draft() {
+        let mut empty = app("");
+        let mut app = app("");
+        settle(&app).await;
+        let mut composer = Composer::new("vim".to_string());
+        composer.draft.subject = "Half written".to_string();
+        app.state = ActiveViewState::ComposeView(composer);
+
+        app.handle_key_events(ctrl('c')).unwrap();
+        assert!(app.running && app.quit_pending);
+        app.handle_key_events(key(KeyCode::Char('n'))).unwrap();
+        assert!(app.running);
+        assert!(matches!(&app.state, ActiveViewState::ComposeView(composer) if composer.draft.subject == "Half written"));
+
+        app.handle_key_events(ctrl('c')).unwrap();
+        app.handle_key_events(ctrl('c')).unwrap();
+        assert!(!app.running);
+
+        // An empty composer has nothing to lose
+        settle(&empty).await;
+        empty.state = ActiveViewState::ComposeView(Composer::new("vim".to_string()));
+        empty.handle_key_events(ctrl('c')).unwrap();
+        assert!(!empty.running);
+    }
+
+    #[tokio::test]
+    async fn composing_to_the_sender_addresses_a_fresh_message() {
+        let mut app = app("");
+        app.capabilities.supports_send = true;
+        let email = EmailMessage {
+            from: "Ann Lee <ann@example.com>".to_string().into(),
+            subject: "Lunch?".to_string(),
+            ..EmailMessage::new()
+        };
+        app.emails = LoadState::Loaded(vec![EmailMessage::new(), email]);
+        app.selected_email_index = Some(1);
+        app.state = ActiveViewState::BaseView(BaseViewState::Inbox);
+
+        app.handle_key_events(key(KeyCode::Char('C'))).unwrap();
+        let ActiveViewState::ComposeView(composer) = &app.state else {
+            panic!("the composer did not open");
+        };
+        assert_eq!(composer.draft.to, "Ann Lee <ann@example.com>");
+        assert_eq!(composer.draft.subject, "");
+        assert!(composer.reply_to.is_none());
+    }
+
+    #[test]
+    fn the_bare_sender_address_is_copied() {
+        let named = EmailSender::from("Ann Lee <ann@example.com>".to_string());
+        assert_eq!(copied_address(&named), Some("ann@example.com"));
+        let bare = EmailSender::from("bob@example.com".to_string());
+        assert_eq!(copied_address(&bare), Some("bob@example.com"));
+        assert_eq!(copied_address(&EmailSender::default()), None);
+    }
+
+    #[tokio::test]
+    async fn startup_syncs_before_viewing_when_enabled() {
+        let backend = NullBackend { sync: true, ..NullBackend::default() };
+        let commands = Arc::clone(&backend.commands);
+        let app = app_with("auto_sync_on_start = true", backend);
+        assert!(app.syncing);
+        settle(&app).await;
+        assert_eq!(*commands.lock().unwrap(), vec!["sync-plan", "sync-from-cloud", "view-mailbox"]);
+
+        let backend = NullBackend { sync: true, ..NullBackend::default() };
+        let commands = Arc::clone(&backend.commands);
+        let app = app_with("", backend);
+        assert!(!app.syncing);
+        settle(&app).await;
+        assert_eq!(*commands.lock().unwrap(), vec!["view-mailbox"]);
+
+        // Backends that cannot sync just show the local mail
+        let backend = NullBackend::default();
+        let commands = Arc::clone(&backend.commands);
+        let app = app_with("auto_sync_on_start = true", backend);
+        settle(&app).await;
+        assert_eq!(*commands.lock().unwrap(), vec!["view-mailbox"]);
+    }
+}
@@ -0,0 +1,172 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, BorderType, Borders, List, ListItem, ListState, StatefulWidget, Widget},
+};
+use std::path::{Path, PathBuf};
+use crate::core::email::EmailMessage;
+
+/// One row of the attachments pane.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AttachmentRow {
+    /// Position of the attachment in `EmailMessage::email_attachments`.
+    pub index: usize,
+    pub filename: String,
+    /// Size of the decoded attachment in bytes.
+    pub size: usize,
+    pub content_type: String,
+}
+
+/// Builds the list model shown in the attachments pane, in the order the
+/// attachments appear in the message.
+pub fn attachment_rows(email: &EmailMessage) -> Vec<AttachmentRow> {
+    email.email_attachments
+        .iter()
+        .enumerate()
+        .map(|(index, attachment)| AttachmentRow {
+            index,
+            filename: attachment.filename.clone(),
+            size: attachment.data.len(),
+            content_type: attachment.content_type.clone(),
+        })
+        .collect()
+}
+
+/// Formats a byte count as a short human readable size (e.g. `1.5 KB`).
+pub fn format_size(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Picks a path in `dir` to save `filename` to without overwriting an existing file.
+///
+/// Only the final component of `filename` is used, so a hostile attachment name
+/// like `../../.bashrc` cannot escape `dir`. Collisions get a ` (n)` suffix.
+pub fn unique_save_path(dir: &Path, filename: &str) -> PathBuf {
+    let name = Path::new(filename)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "attachment".to_string());
+
+    let candidate = dir.join(&name);
+    if !candidate.exists() {
+        return candidate;
+    }
+
+    let stem = Path::new(&name).file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let extension = Path::new(&name).extension().map(|e| format!(".{}", e.to_string_lossy()));
+    (1..)
+        .map(|n| dir.join(format!("{} ({}){}", stem, n, extension.as_deref().unwrap_or(""))))
+        .find(|path| !path.exists())
+        .unwrap()
+}
+
+pub struct AttachmentsPane<'a> {
+    pub rows: &'a [AttachmentRow],
+    /// Index (into `rows`) of the highlighted attachment.
+    pub selected: usize,
+}
+
+impl<'a> Widget for AttachmentsPane<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let block = Block::default()
+            .title(format!("Attachments ({})", self.rows.len()))
+            .title_style(Style::default().fg(Color::Magenta).add_modifier(Modifier::BOLD))
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(Color::White));
+
+        let items: Vec<ListItem> = if self.rows.is_empty() {
+            vec![ListItem::new("No attachments")]
+        } else {
+            self.rows
+                .iter()
+                .map(|row| {
+                    ListItem::new(Line::from(vec![
+                        Span::styled(format!("{:>2}. ", row.index + 1), Style::default().fg(Color::DarkGray)),
+                        Span::styled(row.filename.clone(), Style::default().fg(Color::White)),
+                        Span::raw("  "),
+                        Span::styled(format_size(row.size), Style::default().fg(Color::Green)),
+                        Span::raw("  "),
+                        Span::styled(row.content_type.clone(), Style::default().fg(Color::Cyan)),
+                    ]))
+                })
+                .collect()
+        };
+
+        let list = List::new(items)
+            .block(block)
+            .highlight_symbol("▶ ")
+            .highlight_style(
+                Style::default()
+                    .fg(Color::Yellow)
+                    .bg(Color::DarkGray)
+                    .add_modifier(Modifier::BOLD),
+            );
+
+        let mut state = ListState::default();
+        if !self.rows.is_empty() {
+            state.select(Some(self.selected.min(self.rows.len() - 1)));
+        }
+
+        StatefulWidget::render(list, area, buf, &mut state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::email::{AttachmentDisposition, EmailAttachment, MimeType};
+
+    fn attachment(filename: &str, content_type: &str, size: usize) -> EmailAttachment {
+        EmailAttachment {
+            filename: filename.to_string(),
+            content_type: content_type.to_string(),
+            data: vec![0; size],
+            mime_type: MimeType::default(),
+            disposition: AttachmentDisposition::Attachment,
+            content_id: None,
+        }
+    }
+
+    #[test]
+    fn rows_list_every_attachment_in_message_order() {
+        let email = EmailMessage {
+            email_attachments: vec![
+                attachment("report.pdf", "application/pdf", 2048),
+                attachment("photo.png", "image/png", 10),
+                attachment("notes.txt", "text/plain", 0),
+            ],
+            ..EmailMessage::new()
+        };
+
+        let rows = attachment_rows(&email);
+        assert_eq!(rows, vec![
+            AttachmentRow { index: 0, filename: "report.pdf".to_string(), size: 2048, content_type: "application/pdf".to_string() },
+            AttachmentRow { index: 1, filename: "photo.png".to_string(), size: 10, content_type: "image/png".to_string() },
+            AttachmentRow { index: 2, filename: "notes.txt".to_string(), size: 0, content_type: "text/plain".to_string() },
+        ]);
+        assert!(attachment_rows(&EmailMessage::new()).is_empty());
+    }
+
+    #[test]
+    fn sizes_are_shown_in_the_largest_whole_unit() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(3 * 1024 * 1024), "3.0 MB");
+    }
+}
@@ -20,6 +20,9 @@ pub struct Composer {
     pub cursor_to: usize,
     pub cursor_subject: usize,
     pub editor_name: String, 
+    /// Email (maildir) id of the message this draft replies to, if any. Sent along
+    /// so the reply is threaded under it.
+    pub reply_to: Option<String>,
 }
 
 impl Widget for Composer {
@@ -52,9 +55,17 @@ impl Composer {
             cursor_to: 0,
             cursor_subject: 0,
             editor_name,
+            reply_to: None,
         }
     }
     
+    /// Whether anything has been written that would be lost by leaving.
+    pub fn has_draft(&self) -> bool {
+        [&self.draft.to, &self.draft.subject, &self.draft.body]
+            .iter()
+            .any(|field| !field.trim().is_empty())
+    }
+
     fn is_selected(&self, target: &ComposeViewField) -> bool {
         self.current_field == *target
     }
@@ -7,6 +7,8 @@ use ratatui::{
 };
 use crate::core::label::Label;
 use crate::ui::app::BaseViewState;
+use std::collections::HashMap;
+use unicode_width::UnicodeWidthStr;
 
 pub struct FolderPane<'a> {
     /// Reference to the list of labels. None implies loading state.
@@ -15,13 +17,37 @@ pub struct FolderPane<'a> {
     pub state: &'a BaseViewState,
     /// Currently selected folder name for highlighting.
     pub selected_folder: &'a str,
+    /// Count and newest subject of the selected folder, shown under the list.
+    pub preview: Option<&'a FolderPreview>,
+    /// Index of the first visible label, see `folder_scroll_offset`.
+    pub offset: usize,
+    /// Configured display names, see `Label::display_name`.
+    pub label_names: &'a HashMap<String, String>,
+}
+
+/// Summary of a folder from the local cache, shown as the pane's bottom title.
+#[derive(Debug, Clone)]
+pub struct FolderPreview {
+    pub label: String,
+    pub count: usize,
+    pub latest_subject: Option<String>,
+}
+
+impl FolderPreview {
+    /// e.g. "12 · Re: lunch?", or just the count when the folder is empty.
+    pub fn subtitle(&self) -> String {
+        match self.latest_subject.as_deref() {
+            Some(subject) if !subject.is_empty() => format!("{} · {}", self.count, subject),
+            _ => self.count.to_string(),
+        }
+    }
 }
 
 impl<'a> Widget for FolderPane<'a> {
     fn render(self, area: Rect, buf: &mut Buffer) {
         let is_active = matches!(self.state, BaseViewState::Labels);
         
-        let block = Block::default()
+        let mut block = Block::default()
             .title("Folders")
             .borders(Borders::ALL)
             .border_type(BorderType::Rounded)
@@ -32,6 +58,13 @@ impl<'a> Widget for FolderPane<'a> {
                 Style::default().fg(Color::White)
             });
         
+        // Only show the preview once it matches the folder that is actually selected
+        if let Some(preview) = self.preview.filter(|preview| preview.label == self.selected_folder) {
+            block = block.title_bottom(
+                Line::from(format!(" {} ", preview.subtitle())).style(Style::default().fg(Color::Gray))
+            );
+        }
+
         // Create list items from labels
         let items: Vec<ListItem> = match &self.labels {
             None => {
@@ -44,7 +77,7 @@ impl<'a> Widget for FolderPane<'a> {
             }
             Some(labels) => {
                 // Create a list item for each label using our reusable component
-                labels.iter().map(create_label_item).collect()
+                labels.iter().map(|label| create_label_item(label, self.label_names)).collect()
             }
         };
         
@@ -60,31 +93,58 @@ impl<'a> Widget for FolderPane<'a> {
             );
         
         // Determine selected folder index for highlighting
-        let selected_index = self.labels.and_then(|labels| {
-            labels
-                .iter()
-                .enumerate()
-                .find_map(|(idx, label)| {
-                    label
-                        .name
-                        .as_deref()
-                        .and_then(|name| (name == self.selected_folder).then_some(idx))
-                })
-        });
-
-        let mut state = ListState::default();
+        let selected_index = self.labels
+            .and_then(|labels| selected_folder_index(labels, self.selected_folder));
+
+        let mut state = ListState::default().with_offset(self.offset);
         state.select(selected_index);
 
         ratatui::widgets::StatefulWidget::render(list, area, buf, &mut state);
     }
 }
 
+/// Width of the folder pane for labels shown as `names`: the widest name plus the
+/// highlight symbol, clamped to 10..=50 columns, plus 2 for the borders. With no
+/// names (an account without labels) the pane keeps the default of 20, which fits
+/// the "No labels found" placeholder.
+pub fn folder_pane_width(names: impl IntoIterator<Item = String>) -> u16 {
+    const HIGHLIGHT_WIDTH: usize = 2; // "▶ "
+    names
+        .into_iter()
+        .map(|name| UnicodeWidthStr::width(name.as_str()) + HIGHLIGHT_WIDTH)
+        .max()
+        .map_or(20, |width| width.clamp(10, 50) as u16 + 2)
+}
+
+/// Position of `selected_folder` in `labels`, if it is there.
+pub fn selected_folder_index(labels: &[Label], selected_folder: &str) -> Option<usize> {
+    labels
+        .iter()
+        .position(|label| label.name.as_deref() == Some(selected_folder))
+}
+
+/// The first visible row of a list of `len` items shown `rows` at a time, scrolled
+/// as little as possible from `offset` so that `selected` is on screen.
+pub fn folder_scroll_offset(selected: usize, offset: usize, len: usize, rows: usize) -> usize {
+    if rows == 0 {
+        return 0;
+    }
+    let offset = if selected < offset {
+        selected
+    } else if selected >= offset + rows {
+        selected + 1 - rows
+    } else {
+        offset
+    };
+    offset.min(len.saturating_sub(rows))
+}
+
 /// Helper function to create a ListItem from a Label
-pub fn create_label_item(label: &Label) -> ListItem<'static> {
-    let name = label.name.as_ref().map(|s| s.as_str()).unwrap_or("Unknown");
+pub fn create_label_item(label: &Label, label_names: &HashMap<String, String>) -> ListItem<'static> {
+    let name = label.display_name(label_names);
 
     if label.messages_total.is_none() || label.messages_unread.is_none() {
-        return ListItem::new(name.to_string());
+        return ListItem::new(name);
     }
 
     // let unread = label.messages_unread.unwrap();
@@ -110,4 +170,36 @@ pub fn create_label_item(label: &Label) -> ListItem<'static> {
     };
     
     ListItem::new(line)
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scrolling_only_moves_to_keep_the_selection_visible() {
+        assert_eq!(folder_scroll_offset(5, 3, 20, 10), 3);
+        assert_eq!(folder_scroll_offset(1, 3, 20, 10), 1);
+        assert_eq!(folder_scroll_offset(15, 3, 20, 10), 6);
+        assert_eq!(folder_scroll_offset(19, 0, 20, 10), 10);
+    }
+
+    #[test]
+    fn scrolling_never_leaves_rows_empty_at_the_bottom() {
+        assert_eq!(folder_scroll_offset(2, 5, 4, 10), 0);
+        assert_eq!(folder_scroll_offset(11, 15, 12, 10), 2);
+        assert_eq!(folder_scroll_offset(3, 2, 20, 0), 0);
+    }
+
+    fn width_for(names: &[&str]) -> u16 {
+        folder_pane_width(names.iter().map(|name| name.to_string()))
+    }
+
+    #[test]
+    fn the_pane_fits_the_widest_label_within_limits() {
+        assert_eq!(width_for(&[]), 20);
+        assert_eq!(width_for(&["Inbox"]), 12);
+        assert_eq!(width_for(&["Inbox", "Receipts and Invoices"]), 25);
+        assert_eq!(width_for(&["日本語のラベル名です"]), 24);
+        assert_eq!(width_for(&["x".repeat(80).as_str()]), 52);
+    }
+}
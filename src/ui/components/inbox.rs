@@ -1,3 +1,4 @@
+use std::collections::{HashMap, HashSet};
 use ratatui::{
     buffer::Buffer,
     layout::Rect,
@@ -6,17 +7,109 @@ use ratatui::{
     widgets::{Block, BorderType, Borders, List, ListItem, ListState, Widget}
 };
 use chrono::DateTime;
-use unicode_width::UnicodeWidthChar;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 use crate::{
-    core::email::EmailMessage,
-    ui::app::BaseViewState,
+    config::{InboxColumn, InboxColumnKind, SenderDisplay},
+    core::{email::EmailMessage, label::{Label, label_display_name}},
+    ui::app::{BaseViewState, LoadState},
 };
 
 pub struct Inbox<'a> {
-    pub emails: Option<&'a Vec<EmailMessage>>,
+    pub emails: &'a LoadState<Vec<EmailMessage>>,
     pub selected_index: Option<usize>,
     pub state: &'a BaseViewState,
+    /// Emails marked in multi-select mode, or `None` outside of it.
+    pub marked: Option<&'a HashSet<usize>>,
+    pub sender_display: SenderDisplay,
+    /// Characters of body preview shown after the subject; `0` shows none.
+    pub snippet_length: usize,
+    /// Most colored label chips shown before the subject; `0` shows none.
+    pub max_label_chips: usize,
+    /// Columns to show, left to right.
+    pub columns: &'a [InboxColumn],
+    /// The folder list, to name the labels in the labels column.
+    pub folders: &'a [Label],
+    /// Configured label display names, see `Label::display_name`.
+    pub label_names: &'a HashMap<String, String>,
+}
+
+/// Splits `available` terminal columns between `columns`: fixed-width columns get
+/// their width (cut short from the right when space runs out), the others share
+/// what is left evenly. Without flexible columns the last one takes the leftover,
+/// so the widths always add up to `available`.
+pub fn column_widths(columns: &[InboxColumn], available: usize) -> Vec<usize> {
+    let mut remaining = available;
+    let mut widths: Vec<usize> = columns
+        .iter()
+        .map(|column| {
+            let width = column.width.unwrap_or(0).min(remaining);
+            remaining -= width;
+            width
+        })
+        .collect();
+
+    let mut flexible: Vec<usize> = (0..columns.len()).filter(|&i| columns[i].width.is_none()).collect();
+    if flexible.is_empty() {
+        flexible.extend(columns.len().checked_sub(1));
+    }
+    if let Some(count) = std::num::NonZeroUsize::new(flexible.len()) {
+        let (share, extra) = (remaining / count, remaining % count);
+        for (n, &index) in flexible.iter().enumerate() {
+            widths[index] += share + usize::from(n < extra);
+        }
+    }
+    widths
+}
+
+/// The terminal colors label chips are drawn in, with the RGB value they are
+/// matched against.
+const CHIP_PALETTE: [(Color, (u8, u8, u8)); 14] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (170, 0, 0)),
+    (Color::Green, (0, 170, 0)),
+    (Color::Yellow, (170, 85, 0)),
+    (Color::Blue, (0, 0, 170)),
+    (Color::Magenta, (170, 0, 170)),
+    (Color::Cyan, (0, 170, 170)),
+    (Color::Gray, (170, 170, 170)),
+    (Color::DarkGray, (85, 85, 85)),
+    (Color::LightRed, (255, 85, 85)),
+    (Color::LightGreen, (85, 255, 85)),
+    (Color::LightYellow, (255, 255, 85)),
+    (Color::LightBlue, (85, 85, 255)),
+    (Color::LightMagenta, (255, 85, 255)),
+];
+
+/// Maps a Gmail label color such as `#fb4c2f` to the nearest named terminal
+/// color. `None` if `hex` is not a `#rrggbb` color.
+pub fn hex_to_color(hex: &str) -> Option<Color> {
+    let hex = hex.trim().strip_prefix('#')?;
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok().map(i32::from);
+    let (r, g, b) = (channel(0)?, channel(2)?, channel(4)?);
+
+    CHIP_PALETTE
+        .iter()
+        .min_by_key(|(_, (pr, pg, pb))| {
+            let (dr, dg, db) = (r - i32::from(*pr), g - i32::from(*pg), b - i32::from(*pb));
+            dr * dr + dg * dg + db * db
+        })
+        .map(|(color, _)| *color)
+}
+
+/// The chip colors for the labels with ids `label_ids`, in order, skipping
+/// labels without a color. At most `max` chips.
+pub fn label_chip_colors(label_ids: &[String], folders: &[Label], max: usize) -> Vec<Color> {
+    label_ids
+        .iter()
+        .filter_map(|id| folders.iter().find(|label| label.id.as_deref() == Some(id.as_str())))
+        .filter_map(|label| label.color.as_ref()?.background_color.as_deref())
+        .filter_map(hex_to_color)
+        .take(max)
+        .collect()
 }
 
 /// Formats a date string to MM/DD/YYYY format
@@ -27,41 +120,148 @@ fn format_date(date_str: &str) -> String {
         .unwrap_or_else(|_| "??/??/????".to_string())
 }
 
-/// Strip emojis and other wide characters from text
-fn strip_emojis(text: &str) -> String {
+/// Replace control characters (tabs, stray newlines in folded headers, ...) with
+/// spaces. They have no defined width and would throw off the column layout.
+fn sanitize_for_display(text: &str) -> String {
     text.chars()
-        .filter(|ch| ch.width().unwrap_or(1) <= 1)
+        .map(|ch| if ch.is_control() { ' ' } else { ch })
         .collect()
 }
 
 /// Truncate and pad string to exact visual width (handles emojis)
-fn fit_to_width(text: &str, target_width: usize) -> String {
+///
+/// When the text does not fit it is cut with a trailing `…`, preferring the last
+/// word boundary if that one is near the limit (within the last third).
+pub fn fit_to_width(text: &str, target_width: usize) -> String {
     let text = text.trim_start();
-    let mut result = String::new();
-    let mut current_width = 0;
+    // This is where `unicode_width` is used.
+    if UnicodeWidthStr::width(text) <= target_width {
+        return pad_to_width(text, target_width);
+    }
+    if target_width == 0 {
+        return String::new();
+    }
 
-    for ch in text.chars() {
-        // This is where `unicode_width::UnicodeWidthChar` is used.
+    // Leave one column for the ellipsis.
+    let budget = target_width - 1;
+    let mut cut = 0;
+    let mut current_width = 0;
+    let mut last_space: Option<(usize, usize)> = None; // (byte index, width before it)
+    for (i, ch) in text.char_indices() {
         let ch_width = ch.width().unwrap_or(0);
-        if current_width + ch_width > target_width {
+        if current_width + ch_width > budget {
             break;
         }
-        result.push(ch);
+        if ch.is_whitespace() {
+            last_space = Some((i, current_width));
+        }
         current_width += ch_width;
+        cut = i + ch.len_utf8();
+    }
+
+    if let Some((space, width_before)) = last_space {
+        if width_before > 0 && width_before * 3 >= budget * 2 {
+            cut = space;
+        }
+    }
+
+    let mut result = text[..cut].trim_end().to_string();
+    result.push('…');
+    pad_to_width(&result, target_width)
+}
+
+/// Splits a column `width` wide between the subject and the body preview after it:
+/// the subject gets the room it needs, the preview (behind a separator) whatever is
+/// left. Both parts together are exactly `width` columns.
+pub fn subject_and_snippet(subject: &str, snippet: &str, width: usize) -> (String, String) {
+    const SEPARATOR: &str = " - ";
+    let subject = subject.trim();
+    let subject_width = UnicodeWidthStr::width(subject);
+    let room = width.saturating_sub(subject_width + SEPARATOR.len());
+    if snippet.is_empty() || room == 0 {
+        return (fit_to_width(subject, width), String::new());
     }
+    (subject.to_string(), format!("{}{}", SEPARATOR, fit_to_width(snippet, room)))
+}
 
-    // Pad to exact width
-    if current_width < target_width {
-        result.push_str(&" ".repeat(target_width - current_width));
+/// Pads `text` with spaces up to `target_width` columns.
+fn pad_to_width(text: &str, target_width: usize) -> String {
+    let width = UnicodeWidthStr::width(text);
+    let mut result = text.to_string();
+    if width < target_width {
+        result.push_str(&" ".repeat(target_width - width));
     }
     result
 }
 
+impl<'a> Inbox<'a> {
+    /// The spans of one cell, exactly `width` columns wide.
+    fn cell(&self, email: &EmailMessage, kind: InboxColumnKind, width: usize) -> Vec<Span<'static>> {
+        match kind {
+            InboxColumnKind::From => {
+                // Style unread emails: white and bold, read emails: dark gray
+                let style = if email.is_unread {
+                    Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD).add_modifier(Modifier::ITALIC)
+                } else {
+                    Style::default().fg(Color::Cyan).add_modifier(Modifier::ITALIC)
+                };
+                let from = sanitize_for_display(email.from.display_as(self.sender_display));
+                vec![Span::styled(fit_to_width(&from, width), style)]
+            }
+            InboxColumnKind::Subject => {
+                let style = if email.is_unread {
+                    Style::default().fg(Color::White).add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default().fg(Color::DarkGray)
+                };
+                // One "■ " per colored label, if they leave room for some subject
+                let chips = label_chip_colors(&email.labels, self.folders, self.max_label_chips);
+                let chips = if chips.len() * 2 < width / 2 { chips } else { Vec::new() };
+                let width = width - chips.len() * 2;
+
+                let snippet: String = email.snippet.chars().take(self.snippet_length).collect();
+                let (subject, snippet) = subject_and_snippet(&sanitize_for_display(&email.subject), &snippet, width);
+                let mut spans: Vec<Span<'static>> = chips
+                    .into_iter()
+                    .map(|color| Span::styled("■ ", Style::default().fg(color)))
+                    .collect();
+                spans.push(Span::styled(subject, style));
+                spans.push(Span::styled(snippet, Style::default().fg(Color::DarkGray)));
+                spans
+            }
+            InboxColumnKind::Date => {
+                let date = format!("{:>width$}", format_date(&email.date), width = width);
+                vec![Span::styled(fit_to_width(&date, width), Style::default().fg(Color::Green))]
+            }
+            InboxColumnKind::Flags => {
+                let mut flags = String::new();
+                if email.is_unread {
+                    flags.push('●');
+                }
+                if email.attachment_count() > 0 {
+                    flags.push('📎');
+                }
+                vec![Span::styled(fit_to_width(&flags, width), Style::default().fg(Color::Yellow))]
+            }
+            InboxColumnKind::Labels => {
+                // UNREAD already shows as a flag and in the styling
+                let labels = email.labels
+                    .iter()
+                    .filter(|label| *label != "UNREAD")
+                    .map(|label| label_display_name(label, self.folders, self.label_names))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                vec![Span::styled(fit_to_width(&labels, width), Style::default().fg(Color::Magenta))]
+            }
+        }
+    }
+}
+
 impl<'a> Widget for Inbox<'a> {
     /// Renders the Inbox view of the BaseView state.
     ///
-    /// The email subjects have their emojis strip. In the future, we will
-    /// support displaying emojis in the subject.
+    /// Emojis and other wide characters are kept; `fit_to_width` counts them as
+    /// two columns so the date column stays aligned.
     fn render(self, area: Rect, buf: &mut Buffer) {
         let is_active = matches!(self.state, BaseViewState::Inbox);
         
@@ -76,45 +276,47 @@ impl<'a> Widget for Inbox<'a> {
             .border_type(BorderType::Rounded)
             .border_style(Style::default().fg(Color::White));
 
-        let width = area.width as usize;
-        let from_max_width: usize = 20;
-        let date_width: usize = 10 + 1; // MM/DD/YYYY = 10 chars + 1 space (see format_date function)
-        let spacing: usize = 2; // spaces between columns
-        // Calculate remaining space for subject (accounting for highlight symbol "▶ " = 2 chars)
-        let subject_width: usize = width.saturating_sub(from_max_width + date_width + (spacing * 2) + 2);
+        // Checkbox column shown in multi-select mode ("✓ " or "  ")
+        let marker_width: usize = if self.marked.is_some() { 2 } else { 0 };
+        // Borders, the highlight symbol "▶ " and one space after every column
+        let available = (area.width as usize).saturating_sub(2 + 2 + marker_width + self.columns.len());
+        let widths = column_widths(self.columns, available);
     
         // Create list items (each email = one row)
-        let items: Vec<ListItem> = match &self.emails {
-            None => vec![ListItem::new("Loading...")],
-            Some(emails) if emails.is_empty() => vec![ListItem::new("No emails found")],
-            Some(emails) => emails
+        let items: Vec<ListItem> = match self.emails {
+            LoadState::Loading => vec![ListItem::new("Loading...")],
+            LoadState::Failed(error) => vec![
+                ListItem::new(Line::from(Span::styled(
+                    format!("Failed to load emails: {}", error),
+                    Style::default().fg(Color::Red),
+                ))),
+                ListItem::new("Press R to retry"),
+            ],
+            LoadState::Loaded(emails) if emails.is_empty() => vec![ListItem::new("No emails found")],
+            LoadState::Loaded(emails) => emails
                 .iter()
-                .map(|email| {
-                    let from = fit_to_width(email.from.display_name(), from_max_width);
-                    let subject = fit_to_width(&strip_emojis(&email.subject), subject_width);
-                    let date = format_date(&email.date);
-                    
-                    // Style unread emails: white and bold, read emails: dark gray
-                    let from_style = if email.is_unread {
-                        Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD).add_modifier(Modifier::ITALIC)
-                    } else {
-                        Style::default().fg(Color::Cyan).add_modifier(Modifier::ITALIC)
-                    };
-                    
-                    let subject_style = if email.is_unread {
-                        Style::default().fg(Color::White).add_modifier(Modifier::BOLD)
+                .enumerate()
+                .map(|(index, email)| {
+                    let is_marked = self.marked.is_some_and(|marked| marked.contains(&index));
+                    let mut spans = Vec::new();
+                    if self.marked.is_some() {
+                        spans.push(if is_marked {
+                            Span::styled("✓ ", Style::default().fg(Color::Green).add_modifier(Modifier::BOLD))
+                        } else {
+                            Span::raw("  ")
+                        });
+                    }
+                    for (column, &width) in self.columns.iter().zip(&widths) {
+                        spans.extend(self.cell(email, column.name, width));
+                        spans.push(Span::raw(" "));
+                    }
+
+                    let item = ListItem::new(Line::from(spans));
+                    if is_marked {
+                        item.style(Style::default().bg(Color::Indexed(236)))
                     } else {
-                        Style::default().fg(Color::DarkGray)
-                    };
-                    
-                    ListItem::new(Line::from(vec![
-                        Span::styled(from, from_style),
-                        Span::raw(" "), // space between from and subject
-                        Span::styled(subject, subject_style),
-                        Span::raw(" "), // space between subject and date
-                        Span::styled(format!("{:>width$}", date, width = date_width), Style::default().fg(Color::Green)),
-                        Span::raw(" "), // space between date and border
-                    ]))
+                        item
+                    }
                 })
                 .collect(),
         };
@@ -136,4 +338,117 @@ impl<'a> Widget for Inbox<'a> {
         // Render with highlight state
         ratatui::widgets::StatefulWidget::render(list, area, buf, &mut state);
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::default_inbox_columns;
+
+    fn column(name: InboxColumnKind, width: Option<usize>) -> InboxColumn {
+        InboxColumn { name, width }
+    }
+
+    #[test]
+    fn column_widths_always_fill_the_row() {
+        let columns = default_inbox_columns();
+        for available in [0, 10, 31, 80, 200] {
+            assert_eq!(column_widths(&columns, available).iter().sum::<usize>(), available);
+        }
+        assert_eq!(column_widths(&columns, 80), vec![20, 49, 11]);
+    }
+
+    #[test]
+    fn flexible_columns_share_the_rest_evenly() {
+        let columns = [
+            column(InboxColumnKind::From, None),
+            column(InboxColumnKind::Flags, Some(2)),
+            column(InboxColumnKind::Subject, None),
+        ];
+        assert_eq!(column_widths(&columns, 13), vec![6, 2, 5]);
+    }
+
+    #[test]
+    fn without_flexible_columns_the_last_takes_the_leftover() {
+        let columns = [column(InboxColumnKind::From, Some(10)), column(InboxColumnKind::Date, Some(5))];
+        assert_eq!(column_widths(&columns, 30), vec![10, 20]);
+        assert_eq!(column_widths(&columns, 12), vec![10, 2]);
+    }
+
+    #[test]
+    fn short_text_is_padded_to_the_width() {
+        assert_eq!(fit_to_width("abc", 5), "abc  ");
+        assert_eq!(fit_to_width("  abc", 3), "abc");
+        assert_eq!(fit_to_width("abc", 0), "");
+    }
+
+    #[test]
+    fn long_text_is_cut_with_an_ellipsis() {
+        assert_eq!(fit_to_width("abcdefghij", 5), "abcd…");
+        // A word boundary near the limit is preferred, one far from it is not
+        assert_eq!(fit_to_width("hello world again", 14), "hello world…  ");
+        assert_eq!(fit_to_width("hi abcdefghijkl", 10), "hi abcdef…");
+    }
+
+    #[test]
+    fn wide_characters_count_as_two_columns() {
+        assert_eq!(fit_to_width("日本語テキスト", 7), "日本語…");
+        // Half a character does not fit, so the cut leaves a column of padding
+        assert_eq!(fit_to_width("日本語テキスト", 6), "日本… ");
+        assert_eq!(UnicodeWidthStr::width(fit_to_width("日本語テキスト", 6).as_str()), 6);
+    }
+
+    /// The column at which `text` starts in row `y` of `buf`.
+    fn column_of(buf: &Buffer, y: u16, text: &str) -> Option<u16> {
+        let len = text.chars().count() as u16;
+        (0..buf.area.width.saturating_sub(len)).find(|&x| {
+            (x..x + len).map(|x| buf[(x, y)].symbol()).collect::<String>() == text
+        })
+    }
+
+    #[test]
+    fn emoji_subjects_keep_the_date_column_aligned() {
+        let email = |subject: &str| EmailMessage {
+            subject: subject.to_string(),
+            date: "Tue, 02 Jan 2024 10:00:00 +0000".to_string(),
+            ..EmailMessage::new()
+        };
+        assert_eq!(UnicodeWidthStr::width("🚀 Launch 🎉"), 12);
+        let emails = LoadState::Loaded(vec![email("Plain subject"), email("🚀 Launch 🎉"), email("🚀".repeat(40).as_str())]);
+        let columns = default_inbox_columns();
+        let label_names = HashMap::new();
+        let inbox = Inbox {
+            emails: &emails,
+            selected_index: None,
+            state: &BaseViewState::Inbox,
+            marked: None,
+            sender_display: SenderDisplay::default(),
+            snippet_length: 0,
+            max_label_chips: 0,
+            columns: &columns,
+            folders: &[],
+            label_names: &label_names,
+        };
+
+        let area = Rect::new(0, 0, 80, 5);
+        let mut buf = Buffer::empty(area);
+        inbox.render(area, &mut buf);
+
+        let plain = column_of(&buf, 1, "01/02/2024").unwrap();
+        assert_eq!(column_of(&buf, 2, "01/02/2024"), Some(plain));
+        assert_eq!(column_of(&buf, 3, "01/02/2024"), Some(plain));
+    }
+
+    #[test]
+    fn label_colors_map_to_the_nearest_terminal_color() {
+        assert_eq!(hex_to_color("#000000"), Some(Color::Black));
+        assert_eq!(hex_to_color("#fb4c2f"), Some(Color::LightRed));
+        assert_eq!(hex_to_color(" #10B010 "), Some(Color::Green));
+    }
+
+    #[test]
+    fn malformed_label_colors_are_ignored() {
+        for hex in ["", "fb4c2f", "#fb4c2", "#fb4c2fa", "#gggggg", "#ééé"] {
+            assert_eq!(hex_to_color(hex), None, "{:?}", hex);
+        }
+    }
+}
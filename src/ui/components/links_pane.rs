@@ -0,0 +1,57 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, BorderType, Borders, List, ListItem, ListState, StatefulWidget, Widget},
+};
+
+/// Numbered list of the links found in the open message.
+pub struct LinksPane<'a> {
+    pub links: &'a [String],
+    /// Index (into `links`) of the highlighted link.
+    pub selected: usize,
+}
+
+impl<'a> Widget for LinksPane<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let block = Block::default()
+            .title(format!("Links ({})", self.links.len()))
+            .title_style(Style::default().fg(Color::Magenta).add_modifier(Modifier::BOLD))
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(Color::White));
+
+        let items: Vec<ListItem> = if self.links.is_empty() {
+            vec![ListItem::new("No links")]
+        } else {
+            self.links
+                .iter()
+                .enumerate()
+                .map(|(index, link)| {
+                    ListItem::new(Line::from(vec![
+                        Span::styled(format!("{:>2}. ", index + 1), Style::default().fg(Color::DarkGray)),
+                        Span::styled(link.clone(), Style::default().fg(Color::Cyan)),
+                    ]))
+                })
+                .collect()
+        };
+
+        let list = List::new(items)
+            .block(block)
+            .highlight_symbol("▶ ")
+            .highlight_style(
+                Style::default()
+                    .fg(Color::Yellow)
+                    .bg(Color::DarkGray)
+                    .add_modifier(Modifier::BOLD),
+            );
+
+        let mut state = ListState::default();
+        if !self.links.is_empty() {
+            state.select(Some(self.selected.min(self.links.len() - 1)));
+        }
+
+        StatefulWidget::render(list, area, buf, &mut state);
+    }
+}
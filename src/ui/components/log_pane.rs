@@ -0,0 +1,180 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::Line,
+    widgets::{Block, BorderType, Borders, Paragraph, Widget},
+};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::PathBuf;
+
+/// Lines of log kept in memory for the log pane.
+pub const LOG_TAIL_LINES: usize = 200;
+
+/// How much of an existing log file is read when the pane opens. Enough for
+/// `LOG_TAIL_LINES` of ordinary log lines without reading a huge file whole.
+const INITIAL_READ_BYTES: u64 = 64 * 1024;
+
+/// The last lines of `text`, at most `n` of them, oldest first.
+pub fn last_lines(text: &str, n: usize) -> Vec<String> {
+    let lines: Vec<&str> = text.lines().collect();
+    lines[lines.len().saturating_sub(n)..].iter().map(|line| line.to_string()).collect()
+}
+
+/// Follows a log file like `tail -f`: each `poll` reads only what was appended
+/// since the last one.
+#[derive(Debug)]
+pub struct LogTail {
+    path: PathBuf,
+    /// Bytes of `path` read so far.
+    offset: u64,
+    /// The end of the file when it does not end in a newline yet.
+    partial: String,
+    pub lines: VecDeque<String>,
+}
+
+impl LogTail {
+    /// Starts following `path`, beginning with its last `LOG_TAIL_LINES` lines.
+    pub fn open(path: PathBuf) -> Self {
+        let len = std::fs::metadata(&path).map(|meta| meta.len()).unwrap_or(0);
+        let mut tail = Self {
+            path,
+            offset: len.saturating_sub(INITIAL_READ_BYTES),
+            partial: String::new(),
+            lines: VecDeque::new(),
+        };
+        // Starting mid-file, the first line read is likely cut; drop it
+        let skip_first = tail.offset > 0;
+        tail.poll();
+        if skip_first {
+            tail.lines.pop_front();
+        }
+        tail
+    }
+
+    /// The file being followed.
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    /// Switches to `path` (e.g. after the log rotated), starting at its beginning.
+    pub fn follow(&mut self, path: PathBuf) {
+        if path != self.path {
+            self.path = path;
+            self.offset = 0;
+            self.partial.clear();
+        }
+    }
+
+    /// Reads whatever was appended since the last call. A file that shrank was
+    /// truncated or replaced and is read again from the start.
+    pub fn poll(&mut self) {
+        let Ok(mut file) = File::open(&self.path) else {
+            return;
+        };
+        let len = file.metadata().map(|meta| meta.len()).unwrap_or(0);
+        if len < self.offset {
+            self.offset = 0;
+            self.partial.clear();
+        }
+        if len == self.offset || file.seek(SeekFrom::Start(self.offset)).is_err() {
+            return;
+        }
+
+        let mut bytes = Vec::new();
+        let Ok(read) = file.take(len - self.offset).read_to_end(&mut bytes) else {
+            return;
+        };
+        self.offset += read as u64;
+
+        self.partial.push_str(&String::from_utf8_lossy(&bytes));
+        let complete = match self.partial.rfind('\n') {
+            Some(end) => {
+                let rest = self.partial.split_off(end + 1);
+                std::mem::replace(&mut self.partial, rest)
+            }
+            None => return,
+        };
+        self.lines.extend(last_lines(&complete, LOG_TAIL_LINES));
+        while self.lines.len() > LOG_TAIL_LINES {
+            self.lines.pop_front();
+        }
+    }
+}
+
+/// The newest lines of the log file, scrolled so the last line is always visible.
+pub struct LogPane<'a> {
+    pub tail: &'a LogTail,
+}
+
+impl<'a> Widget for LogPane<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let block = Block::default()
+            .title(format!("Log ({})", self.tail.path().display()))
+            .title_style(Style::default().fg(Color::Magenta).add_modifier(Modifier::BOLD))
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(Color::White));
+
+        // Borders take two lines
+        let rows = area.height.saturating_sub(2) as usize;
+        let skip = self.tail.lines.len().saturating_sub(rows);
+        let lines: Vec<Line> = if self.tail.lines.is_empty() {
+            vec![Line::styled("No log output yet", Style::default().fg(Color::DarkGray))]
+        } else {
+            self.tail.lines
+                .iter()
+                .skip(skip)
+                .map(|line| Line::styled(line.clone(), log_line_style(line)))
+                .collect()
+        };
+
+        Paragraph::new(lines).block(block).render(area, buf);
+    }
+}
+
+/// Colors a log line by its level.
+fn log_line_style(line: &str) -> Style {
+    if line.contains(" ERROR ") {
+        Style::default().fg(Color::Red)
+    } else if line.contains(" WARN ") {
+        Style::default().fg(Color::Yellow)
+    } else {
+        Style::default().fg(Color::Gray)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn last_lines_keeps_the_newest() {
+        assert_eq!(last_lines("a\nb\nc\n", 2), vec!["b", "c"]);
+        assert_eq!(last_lines("a\nb", 5), vec!["a", "b"]);
+        assert!(last_lines("", 5).is_empty());
+        assert!(last_lines("a\nb", 0).is_empty());
+    }
+
+    #[test]
+    fn tail_waits_for_complete_lines_and_survives_truncation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("termail.log");
+        std::fs::write(&path, "first\nsec").unwrap();
+
+        let mut tail = LogTail::open(path.clone());
+        assert_eq!(tail.lines, ["first"]);
+
+        let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"ond\nthird\n").unwrap();
+        tail.poll();
+        assert_eq!(tail.lines, ["first", "second", "third"]);
+
+        std::fs::write(&path, "fresh\n").unwrap();
+        tail.poll();
+        assert_eq!(tail.lines, ["first", "second", "third", "fresh"]);
+    }
+}
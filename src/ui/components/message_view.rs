@@ -2,10 +2,20 @@ use ratatui::{
     buffer::Buffer,
     layout::Rect,
     style::{Color, Modifier, Style},
+    text::{Line, Span},
     widgets::{Block, BorderType, Borders, Paragraph, Widget, StatefulWidget},
 };
+use std::borrow::Cow;
 use std::cell::RefCell;
-use crate::core::email::EmailMessage;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use crate::core::{email::{EmailMessage, collapse_recipients}, label::{Label, label_display_name}, links::extract_urls};
+
+/// Recipients listed by name in the `To:`/`Cc:` header before the rest are counted.
+const SHOWN_RECIPIENTS: usize = 3;
+use crate::error::Error;
+use crate::ui::components::attachments_pane::{format_size, unique_save_path};
+use crate::ui::components::inbox::hex_to_color;
 use ratatui_image::{StatefulImage, thread::ThreadProtocol};
 
 #[derive(Clone, Debug)]
@@ -15,6 +25,10 @@ pub struct Messager {
     /// To be more precise, scroll is the number of lines from the top of
     /// the content of the email.
     pub scroll: u16,
+    /// Horizontal scroll offset (in columns), only used when `wrap` is off.
+    pub h_scroll: u16,
+    /// Whether long lines are wrapped. When off, Left/Right scroll horizontally.
+    pub wrap: bool,
     /// The height of the Paragraph widget
     /// We wrap with a RefCell to allow for mutable access from the render function.
     /// (text_height, attachment_height)
@@ -24,19 +38,157 @@ pub struct Messager {
     view_width: RefCell<Option<u16>>,
     /// The height of the view. Used to determine the maximum scroll offset.
     view_height: RefCell<Option<u16>>,
+    /// Whether the attachments pane is shown below the message.
+    pub show_attachments: bool,
+    /// Index of the highlighted attachment in the attachments pane.
+    pub selected_attachment: usize,
+    /// URLs found in the body, numbered in the links pane.
+    pub links: Vec<String>,
+    /// Whether the links pane is shown below the message.
+    pub show_links: bool,
+    /// Index of the highlighted link in the links pane.
+    pub selected_link: usize,
+    /// Bodies longer than this many bytes are cut for display (`max_render_bytes`),
+    /// until `show_full_body` is called. `None` renders everything.
+    render_limit: Option<usize>,
+    /// The email's labels as (display name, chip color), shown in the header.
+    label_chips: Vec<(String, Color)>,
 }
 
 impl Messager {
     pub fn new(email: EmailMessage) -> Self {
+        let links = extract_urls(&email.body);
         Self {
             email,
             scroll: 0,
+            h_scroll: 0,
+            wrap: true,
             content_height: RefCell::new(None),
             view_width: RefCell::new(None),
             view_height: RefCell::new(None),
+            show_attachments: false,
+            selected_attachment: 0,
+            links,
+            show_links: false,
+            selected_link: 0,
+            render_limit: None,
+            label_chips: Vec::new(),
         }
     }
 
+    /// Truncates bodies over `limit` bytes for display, see `truncate_for_display`.
+    pub fn with_render_limit(mut self, limit: Option<usize>) -> Self {
+        self.render_limit = limit;
+        self
+    }
+
+    /// Resolves the email's labels to named, colored chips for the header.
+    /// Labels without a color in `folders` are drawn in magenta.
+    pub fn with_labels(mut self, folders: &[Label], overrides: &HashMap<String, String>) -> Self {
+        self.label_chips = self.email.labels
+            .iter()
+            // UNREAD is already conveyed by marking the email read
+            .filter(|id| *id != "UNREAD")
+            .map(|id| {
+                let color = folders.iter()
+                    .find(|label| label.id.as_deref() == Some(id.as_str()))
+                    .and_then(|label| label.color.as_ref()?.background_color.as_deref())
+                    .and_then(hex_to_color)
+                    .unwrap_or(Color::Magenta);
+                (label_display_name(id, folders, overrides), color)
+            })
+            .collect();
+        self
+    }
+
+    /// Whether part of the body is hidden by the render limit.
+    pub fn is_truncated(&self) -> bool {
+        self.render_limit.is_some_and(|limit| self.email.body.len() > limit)
+    }
+
+    /// Renders the whole body, however large.
+    pub fn show_full_body(&mut self) {
+        self.render_limit = None;
+    }
+
+    /// The body as rendered: cut at the render limit, if any.
+    fn shown_body(&self) -> Cow<'_, str> {
+        match self.render_limit {
+            Some(limit) => truncate_for_display(&self.email.body, limit),
+            None => Cow::Borrowed(&self.email.body),
+        }
+    }
+
+    pub fn toggle_attachments(&mut self) {
+        self.show_attachments = !self.show_attachments;
+        self.show_links = false;
+    }
+
+    pub fn toggle_links(&mut self) {
+        self.show_links = !self.show_links;
+        self.show_attachments = false;
+    }
+
+    pub fn select_next_link(&mut self) {
+        if self.selected_link + 1 < self.links.len() {
+            self.selected_link += 1;
+        }
+    }
+
+    pub fn select_previous_link(&mut self) {
+        self.selected_link = self.selected_link.saturating_sub(1);
+    }
+
+    /// The highlighted link, if the message has any.
+    pub fn selected_link(&self) -> Option<&str> {
+        self.links.get(self.selected_link).map(String::as_str)
+    }
+
+    /// Switches between wrapped lines and horizontal scrolling.
+    pub fn toggle_wrap(&mut self) {
+        self.wrap = !self.wrap;
+        self.h_scroll = 0;
+    }
+
+    /// Scroll right by one column, clamped so the longest line stays in view.
+    /// Does nothing while lines are wrapped.
+    pub fn scroll_right(&mut self) {
+        if self.wrap {
+            return;
+        }
+        // The view width includes the two border columns
+        let width = self.view_width.borrow().unwrap_or(0).saturating_sub(2);
+        let max_scroll = max_horizontal_scroll(&self.shown_body(), width);
+        self.h_scroll = self.h_scroll.saturating_add(1).min(max_scroll);
+    }
+
+    pub fn scroll_left(&mut self) {
+        self.h_scroll = self.h_scroll.saturating_sub(1);
+    }
+
+    pub fn select_next_attachment(&mut self) {
+        let count = self.email.email_attachments.len();
+        if self.selected_attachment + 1 < count {
+            self.selected_attachment += 1;
+        }
+    }
+
+    pub fn select_previous_attachment(&mut self) {
+        self.selected_attachment = self.selected_attachment.saturating_sub(1);
+    }
+
+    /// Writes the highlighted attachment into `dir`, never overwriting an existing file.
+    /// Returns the path it was saved to.
+    pub fn save_selected_attachment(&self, dir: &Path) -> Result<PathBuf, Error> {
+        let attachment = self.email.email_attachments
+            .get(self.selected_attachment)
+            .ok_or_else(|| Error::Other("No attachment selected".to_string()))?;
+        let path = unique_save_path(dir, &attachment.filename);
+        std::fs::write(&path, &attachment.data)
+            .map_err(|e| Error::Other(format!("Failed to save attachment to {:?}: {}", path, e)))?;
+        Ok(path)
+    }
+
     /// Calculate the total height of the content and attachment
     /// # Arguments
     /// * `width` - The width of the view.
@@ -44,10 +196,7 @@ impl Messager {
     /// # Returns
     /// * `(text_height, attachment_height)` - The total height of the content and attachment.
     fn calculate_total_height(&self, width: u16, attachment_height: Option<u16>) -> (u16, u16) {
-        let content_height = self.email.body
-            .lines()
-            .map(|line| line.chars().count() / width as usize + 1) // +1 for the \n
-            .sum::<usize>() as u16;
+        let content_height = body_height(&self.shown_body(), width, self.wrap);
         if attachment_height.is_some() {
             (content_height, attachment_height.unwrap())
         } else {
@@ -65,22 +214,98 @@ impl Messager {
         self.content_height.replace(Some((text_height, attachment_height)));
     }
 
+    /// The largest useful scroll offset, from the content and view height of the
+    /// last render.
+    fn max_scroll(&self) -> u16 {
+        let (text_h, attach_h) = self.content_height.borrow().unwrap_or((0, 0));
+        let view_h = self.view_height.borrow().unwrap_or(0);
+        let total_content_height = text_h.saturating_add(attach_h);
+        total_content_height.saturating_sub(view_h)
+    }
+
+    /// Rows of body visible at once (the view height without its borders).
+    fn page_height(&self) -> u16 {
+        self.view_height.borrow().unwrap_or(0).saturating_sub(2).max(1)
+    }
+
+    fn scroll_by(&mut self, delta: i32) {
+        self.scroll = scroll_offset(self.scroll, delta, self.max_scroll());
+    }
+
     /// Scroll down by one line, clamped to content bounds.
     ///
     /// Uses content height from the last render to determine maximum scroll.
     pub fn scroll_down(&mut self) {
-        let (text_h, attach_h) = self.content_height.borrow().unwrap_or((0, 0));
-        let view_h = self.view_height.borrow().unwrap_or(0);
-        let total_content_height = text_h.saturating_add(attach_h);
-        let max_scroll = total_content_height.saturating_sub(view_h);
-        self.scroll = self.scroll.saturating_add(1).clamp(0, max_scroll);
+        self.scroll_by(1);
     }
     pub fn scroll_up(&mut self) {
-        let (text_h, attach_h) = self.content_height.borrow().unwrap_or((0, 0));
-        let view_h = self.view_height.borrow().unwrap_or(0);
-        let total_content_height = text_h.saturating_add(attach_h);
-        let max_scroll = total_content_height.saturating_sub(view_h);
-        self.scroll = self.scroll.saturating_sub(1).clamp(0, max_scroll);
+        self.scroll_by(-1);
+    }
+
+    pub fn scroll_half_page_down(&mut self) {
+        self.scroll_by(half_page(self.page_height()));
+    }
+
+    pub fn scroll_half_page_up(&mut self) {
+        self.scroll_by(-half_page(self.page_height()));
+    }
+
+    pub fn scroll_page_down(&mut self) {
+        self.scroll_by(self.page_height() as i32);
+    }
+
+    pub fn scroll_page_up(&mut self) {
+        self.scroll_by(-(self.page_height() as i32));
+    }
+
+    pub fn scroll_to_top(&mut self) {
+        self.scroll = 0;
+    }
+
+    pub fn scroll_to_bottom(&mut self) {
+        self.scroll = self.max_scroll();
+    }
+
+    /// The `To:`, `Cc:` and `Attachments:` lines shown above the body, each only
+    /// when there is something to show.
+    fn header_lines(&self) -> Vec<Line<'static>> {
+        let label_style = Style::default().fg(Color::Gray);
+        let mut lines: Vec<Line<'static>> = [("To: ", &self.email.to), ("Cc: ", &self.email.cc)]
+            .into_iter()
+            .filter(|(_, list)| !list.trim().is_empty())
+            .map(|(label, list)| Line::from(vec![
+                Span::styled(label, label_style),
+                Span::raw(collapse_recipients(list, SHOWN_RECIPIENTS)),
+            ]))
+            .collect();
+        if !self.label_chips.is_empty() {
+            let mut spans = vec![Span::styled("Labels: ", label_style)];
+            for (name, color) in &self.label_chips {
+                spans.push(Span::styled("■ ", Style::default().fg(*color)));
+                spans.push(Span::raw(format!("{} ", name)));
+            }
+            lines.push(Line::from(spans));
+        }
+        let attachments = self.email.attachment_count();
+        if attachments > 0 {
+            lines.push(Line::from(vec![
+                Span::styled("Attachments: ", label_style),
+                Span::raw(format!("{} ({})", attachments, format_size(self.email.total_attachment_bytes()))),
+            ]));
+        }
+        lines
+    }
+
+    /// The message body, wrapped or horizontally scrolled depending on `wrap`.
+    fn body_paragraph(&self) -> Paragraph<'_> {
+        let paragraph = Paragraph::new(self.shown_body());
+        if self.wrap {
+            paragraph
+                .wrap(ratatui::widgets::Wrap { trim: false })
+                .scroll((self.scroll, 0))
+        } else {
+            paragraph.scroll((self.scroll, self.h_scroll))
+        }
     }
 
     /// Render the message view with images
@@ -91,10 +316,11 @@ impl Messager {
         buf: &mut Buffer,
         image_state: &mut Option<ThreadProtocol>
     ) {
+        let header_lines = self.header_lines();
+        let header_height = (header_lines.len() as u16).min(area.height);
         self.view_width.replace(Some(area.width));
-        self.view_height.replace(Some(area.height));
+        self.view_height.replace(Some(area.height.saturating_sub(header_height)));
         let email_from = &self.email.from;
-        let email_body = &self.email.body;
 
         // This block defines the entire border of the text and attachments.
         let total_block = Block::default()
@@ -108,14 +334,20 @@ impl Messager {
         let inner_area = total_block.inner(area);
         total_block.render(area, buf);
 
+        // Recipients sit above the body and do not scroll with it
+        let header_height = header_height.min(inner_area.height);
+        Paragraph::new(header_lines).render(Rect { height: header_height, ..inner_area }, buf);
+        let inner_area = Rect {
+            y: inner_area.y + header_height,
+            height: inner_area.height - header_height,
+            ..inner_area
+        };
+
         let attachment_height = if image_state.is_some() { 20 } else { 0 };
         let (text_height, _) = self.calculate_total_height(inner_area.width, Some(attachment_height));
         self.update_content_height(Some(attachment_height));
 
-        Paragraph::new(self.email.body.as_str())
-            .wrap(ratatui::widgets::Wrap { trim: false })
-            .scroll((self.scroll, 0))
-            .render(inner_area, buf);
+        self.body_paragraph().render(inner_area, buf);
 
         if let Some(protocol) = image_state {
             // Calculate where the image starts relative to the viewport top
@@ -152,11 +384,157 @@ impl Messager {
                 );
             }
         } else {
-            let paragraph = Paragraph::new(email_body.to_string())
-                .wrap(ratatui::widgets::Wrap { trim: false })
-                .scroll((self.scroll, 0));
-            paragraph.render(inner_area, buf);
+            self.body_paragraph().render(inner_area, buf);
             self.update_content_height(None);
         }
     }
-}
\ No newline at end of file
+}
+
+/// `body` cut to at most `max_bytes` bytes (on a character boundary) with a
+/// "[truncated, N KB more]" marker, or unchanged when it fits.
+pub fn truncate_for_display(body: &str, max_bytes: usize) -> Cow<'_, str> {
+    if body.len() <= max_bytes {
+        return Cow::Borrowed(body);
+    }
+    let mut cut = max_bytes;
+    while !body.is_char_boundary(cut) {
+        cut -= 1;
+    }
+    let hidden_kb = (body.len() - cut).div_ceil(1024);
+    Cow::Owned(format!("{}\n\n[truncated, {} KB more, press T to show all]", &body[..cut], hidden_kb))
+}
+
+/// `current` moved by `delta` rows, kept within `0..=max`.
+pub fn scroll_offset(current: u16, delta: i32, max: u16) -> u16 {
+    (current as i32 + delta).clamp(0, max as i32) as u16
+}
+
+/// Rows a half-page scroll moves in a view showing `page_height` rows; at least one.
+pub fn half_page(page_height: u16) -> i32 {
+    (page_height as i32 / 2).max(1)
+}
+
+/// Number of rows `body` takes up in a view `width` columns wide. Wrapped lines take
+/// one row per started `width` columns; unwrapped lines always take exactly one.
+pub fn body_height(body: &str, width: u16, wrap: bool) -> u16 {
+    if !wrap || width == 0 {
+        return body.lines().count() as u16;
+    }
+    body.lines()
+        .map(|line| line.chars().count() / width as usize + 1) // +1 for the \n
+        .sum::<usize>() as u16
+}
+
+/// How far `body` can be scrolled to the right before its longest line has fully
+/// scrolled into a view `width` columns wide.
+pub fn max_horizontal_scroll(body: &str, width: u16) -> u16 {
+    let longest = body.lines().map(|line| line.chars().count()).max().unwrap_or(0);
+    u16::try_from(longest.saturating_sub(width as usize)).unwrap_or(u16::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scrolling_stays_between_top_and_bottom() {
+        assert_eq!(scroll_offset(5, 3, 20), 8);
+        assert_eq!(scroll_offset(2, -10, 20), 0);
+        assert_eq!(scroll_offset(18, 10, 20), 20);
+        assert_eq!(scroll_offset(0, 1, 0), 0);
+    }
+
+    #[test]
+    fn half_page_moves_at_least_one_row() {
+        assert_eq!(half_page(24), 12);
+        assert_eq!(half_page(25), 12);
+        assert_eq!(half_page(1), 1);
+        assert_eq!(half_page(0), 1);
+    }
+
+    #[test]
+    fn wrapped_lines_take_a_row_per_started_width() {
+        assert_eq!(body_height("short\nline", 10, true), 2);
+        assert_eq!(body_height(&"x".repeat(25), 10, true), 3);
+        assert_eq!(body_height(&"x".repeat(25), 10, false), 1);
+    }
+
+    #[test]
+    fn unwrapped_lines_take_one_row_each() {
+        let body = format!("{}\nshort\n\n{}", "x".repeat(100), "y".repeat(30));
+        assert_eq!(body_height(&body, 10, false), 4);
+        assert_eq!(body_height(&body, 10, true), 11 + 1 + 1 + 4);
+    }
+
+    #[test]
+    fn horizontal_scroll_stops_at_the_longest_line() {
+        assert_eq!(max_horizontal_scroll("short\n0123456789abcdef", 10), 6);
+        assert_eq!(max_horizontal_scroll("short", 10), 0);
+        assert_eq!(max_horizontal_scroll("", 10), 0);
+
+        let email = EmailMessage { body: "short\n0123456789abcdef".to_string(), ..EmailMessage::new() };
+        let mut messager = Messager::new(email);
+        // The view width includes the two border columns
+        *messager.view_width.borrow_mut() = Some(12);
+        messager.scroll_right();
+        assert_eq!(messager.h_scroll, 0, "wrapped lines do not scroll sideways");
+
+        messager.toggle_wrap();
+        for _ in 0..20 {
+            messager.scroll_right();
+        }
+        assert_eq!(messager.h_scroll, 6);
+        messager.scroll_left();
+        assert_eq!(messager.h_scroll, 5);
+        messager.toggle_wrap();
+        assert_eq!(messager.h_scroll, 0);
+    }
+
+    #[test]
+    fn large_bodies_are_truncated_with_a_marker() {
+        let body = "a".repeat(1000);
+        assert!(matches!(truncate_for_display(&body, 1000), Cow::Borrowed(shown) if shown == body));
+
+        let body = "a".repeat(3000);
+        let shown = truncate_for_display(&body, 1000);
+        assert_eq!(shown, format!("{}\n\n[truncated, 2 KB more, press T to show all]", "a".repeat(1000)));
+
+        // Never cut inside a character
+        let body = "é".repeat(10);
+        assert_eq!(truncate_for_display(&body, 5), "éé\n\n[truncated, 1 KB more, press T to show all]");
+    }
+
+    #[test]
+    fn the_full_body_can_be_shown_on_request() {
+        let email = EmailMessage { body: "a".repeat(3000), ..EmailMessage::new() };
+        let mut messager = Messager::new(email).with_render_limit(Some(1000));
+        assert!(messager.is_truncated());
+        assert!(messager.shown_body().ends_with("press T to show all]"));
+
+        messager.show_full_body();
+        assert!(!messager.is_truncated());
+        assert_eq!(messager.shown_body().len(), 3000);
+    }
+
+    #[test]
+    fn labels_become_named_colored_chips() {
+        let work = Label {
+            id: Some("Label_1".to_string()),
+            name: Some("Work".to_string()),
+            color: Some(google_gmail1::api::LabelColor {
+                background_color: Some("#fb4c2f".to_string()),
+                ..Default::default()
+            }),
+            ..Label::new()
+        };
+        let email = EmailMessage {
+            labels: vec!["INBOX".to_string(), "UNREAD".to_string(), "Label_1".to_string()],
+            ..EmailMessage::new()
+        };
+        let messager = Messager::new(email).with_labels(&[work], &HashMap::new());
+        assert_eq!(messager.label_chips, vec![
+            ("Inbox".to_string(), Color::Magenta),
+            ("Work".to_string(), Color::LightRed),
+        ]);
+    }
+}
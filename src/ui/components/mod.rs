@@ -1,4 +1,9 @@
 pub mod composer_view;
 pub mod message_view;
 pub mod folder_pane;
-pub mod inbox;
\ No newline at end of file
+pub mod inbox;
+pub mod attachments_pane;
+pub mod plugin_view;
+pub mod links_pane;
+pub mod log_pane;
+pub mod move_picker;
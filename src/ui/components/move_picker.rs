@@ -0,0 +1,128 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    widgets::{Block, BorderType, Borders, List, ListItem, ListState, StatefulWidget, Widget},
+};
+use std::collections::HashMap;
+use crate::core::label::{Label, is_move_target, label_display_name};
+use crate::ui::components::message_view::Messager;
+
+/// The "move to" picker: lists the folders an email can be moved to.
+#[derive(Clone, Debug)]
+pub struct MovePicker {
+    /// Email (maildir) id of the email being moved.
+    pub email_id: String,
+    /// The folders to pick from, as (label id, display name).
+    pub targets: Vec<(String, String)>,
+    /// Index (into `targets`) of the highlighted folder.
+    pub selected: usize,
+    /// The message view the picker was opened from, returned to on Esc.
+    pub return_to: Option<Box<Messager>>,
+}
+
+impl MovePicker {
+    pub fn new(email_id: String, targets: Vec<(String, String)>, return_to: Option<Messager>) -> Self {
+        Self { email_id, targets, selected: 0, return_to: return_to.map(Box::new) }
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < self.targets.len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    /// The highlighted folder as (label id, display name), if there are any.
+    pub fn selected_target(&self) -> Option<&(String, String)> {
+        self.targets.get(self.selected)
+    }
+}
+
+/// The folders an email in the folder with id `current` can be moved to, as
+/// (label id, display name), in folder list order.
+pub fn move_targets(labels: &[Label], current: Option<&str>, overrides: &HashMap<String, String>) -> Vec<(String, String)> {
+    labels
+        .iter()
+        .filter_map(|label| Some((label.id.as_deref()?, label)))
+        .filter(|(id, _)| is_move_target(id) && Some(*id) != current)
+        .map(|(id, label)| (id.to_string(), label.display_name(overrides)))
+        .collect()
+}
+
+/// Folders offered by backends without a folder list (see `supports_labels`).
+pub const BASIC_MOVE_TARGETS: [&str; 3] = ["INBOX", "SPAM", "TRASH"];
+
+/// `move_targets` for backends without a folder list: the `BASIC_MOVE_TARGETS`
+/// other than `current`.
+pub fn basic_move_targets(current: Option<&str>, overrides: &HashMap<String, String>) -> Vec<(String, String)> {
+    BASIC_MOVE_TARGETS
+        .iter()
+        .filter(|id| Some(**id) != current)
+        .map(|id| (id.to_string(), label_display_name(id, &[], overrides)))
+        .collect()
+}
+
+impl Widget for &MovePicker {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let block = Block::default()
+            .title("Move to")
+            .title_style(Style::default().fg(Color::Magenta).add_modifier(Modifier::BOLD))
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(Color::White));
+
+        let items: Vec<ListItem> = if self.targets.is_empty() {
+            vec![ListItem::new("No folders to move to")]
+        } else {
+            self.targets
+                .iter()
+                .map(|(_, name)| ListItem::new(name.clone()).style(Style::default().fg(Color::White)))
+                .collect()
+        };
+
+        let list = List::new(items)
+            .block(block)
+            .highlight_symbol("▶ ")
+            .highlight_style(
+                Style::default()
+                    .fg(Color::Yellow)
+                    .bg(Color::DarkGray)
+                    .add_modifier(Modifier::BOLD),
+            );
+
+        let mut state = ListState::default();
+        if !self.targets.is_empty() {
+            state.select(Some(self.selected.min(self.targets.len() - 1)));
+        }
+
+        StatefulWidget::render(list, area, buf, &mut state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basic_targets_leave_out_the_current_folder() {
+        let targets = basic_move_targets(Some("INBOX"), &HashMap::new());
+        assert_eq!(targets, vec![
+            ("SPAM".to_string(), "Spam".to_string()),
+            ("TRASH".to_string(), "Trash".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn targets_are_folders_other_than_the_current_one() {
+        let folders: Vec<Label> = ["INBOX", "STARRED", "Label_1"]
+            .iter()
+            .map(|id| Label { id: Some(id.to_string()), name: Some(id.to_string()), ..Label::new() })
+            .collect();
+        let overrides = HashMap::from([("Label_1".to_string(), "Work".to_string())]);
+        assert_eq!(move_targets(&folders, Some("INBOX"), &overrides), vec![("Label_1".to_string(), "Work".to_string())]);
+    }
+}
@@ -0,0 +1,93 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, BorderType, Borders, List, ListItem, ListState, StatefulWidget, Widget},
+};
+use crate::plugins::plugins::PluginSummary;
+
+/// The plugin manager view: lists the loaded plugins and lets the user toggle them.
+#[derive(Clone, Debug, Default)]
+pub struct PluginView {
+    pub plugins: Vec<PluginSummary>,
+    /// Index (into `plugins`) of the highlighted plugin.
+    pub selected: usize,
+}
+
+impl PluginView {
+    pub fn new(plugins: Vec<PluginSummary>) -> Self {
+        Self { plugins, selected: 0 }
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < self.plugins.len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    /// The highlighted plugin, if any are loaded.
+    pub fn selected_plugin(&self) -> Option<&PluginSummary> {
+        self.plugins.get(self.selected)
+    }
+
+    /// Replaces the list after it changed, keeping the selection in range.
+    pub fn update(&mut self, plugins: Vec<PluginSummary>) {
+        self.plugins = plugins;
+        self.selected = self.selected.min(self.plugins.len().saturating_sub(1));
+    }
+}
+
+impl Widget for &PluginView {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let block = Block::default()
+            .title(format!("Plugins ({})", self.plugins.len()))
+            .title_style(Style::default().fg(Color::Magenta).add_modifier(Modifier::BOLD))
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(Color::White));
+
+        let items: Vec<ListItem> = if self.plugins.is_empty() {
+            vec![ListItem::new("No plugins loaded")]
+        } else {
+            self.plugins
+                .iter()
+                .map(|plugin| {
+                    let (marker, marker_style) = if plugin.enabled {
+                        ("[x] ", Style::default().fg(Color::Green))
+                    } else {
+                        ("[ ] ", Style::default().fg(Color::DarkGray))
+                    };
+                    let hooks: Vec<String> = plugin.hooks.iter().map(|hook| hook.to_string()).collect();
+                    ListItem::new(Line::from(vec![
+                        Span::styled(marker, marker_style),
+                        Span::styled(plugin.name.clone(), Style::default().fg(Color::White)),
+                        Span::raw("  "),
+                        Span::styled(hooks.join(", "), Style::default().fg(Color::Cyan)),
+                    ]))
+                })
+                .collect()
+        };
+
+        let list = List::new(items)
+            .block(block)
+            .highlight_symbol("▶ ")
+            .highlight_style(
+                Style::default()
+                    .fg(Color::Yellow)
+                    .bg(Color::DarkGray)
+                    .add_modifier(Modifier::BOLD),
+            );
+
+        let mut state = ListState::default();
+        if !self.plugins.is_empty() {
+            state.select(Some(self.selected.min(self.plugins.len() - 1)));
+        }
+
+        StatefulWidget::render(list, area, buf, &mut state);
+    }
+}
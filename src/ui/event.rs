@@ -6,9 +6,11 @@ use std::time::Duration;
 use tokio::sync::mpsc;
 
 use crate::core::{email::EmailMessage, label::Label};
+use crate::ui::components::folder_pane::FolderPreview;
 use crate::error::Error;
 
-const TICK_FPS: f64 = 30.0;
+/// Number of `Event::Tick`s per second.
+pub const TICK_FPS: f64 = 30.0;
 
 /// Terminal event.
 pub enum Event {
@@ -21,17 +23,45 @@ pub enum Event {
 }
 
 pub enum AppEvent {
-    EmailsFetched(Vec<EmailMessage>),
+    /// The mailbox, from the fetch with the given generation (see `App::fetch_generation`).
+    EmailsFetched(u64, Vec<EmailMessage>),
+    /// Fetching (or syncing) the mailbox failed with the given error message.
+    EmailsFetchFailed(u64, String),
+    /// Re-issue the mailbox fetch after a failure.
+    RetryFetch,
     /// An email has been loaded and is ready to be displayed. This
     /// exists to optimize the email attachment loadng by sending this to
     /// a non-blocking thread.
-    EmailLoaded(EmailMessage),
+    EmailLoaded(Box<EmailMessage>),
     LabelsFetched(Vec<Label>),
+    /// The backend is not signed in; carries the error to show.
+    NotAuthenticated(String),
+    /// Run the backend's sign-in flow again, ignoring the cached token.
+    Login,
+    /// Count and newest subject of a folder, for the folder pane.
+    LabelSummaryFetched(FolderPreview),
     SpawnEditor,
-    SendEmail(EmailMessage),
+    /// Send the draft; the second field is the email (maildir) id it replies to.
+    SendEmail(Box<EmailMessage>, Option<String>),
+    /// The email handed to `SendEmail` was sent.
+    EmailSent,
+    /// Sending the email failed with the given error message.
+    SendFailed(String),
+    /// An email was moved; carries the display name of the folder it went to.
+    EmailMoved(String),
+    /// Moving an email failed with the given error message.
+    MoveFailed(String),
+    /// Some plugins failed to load; lists them with the reasons.
+    PluginLoadFailed(String),
     SyncFromCloud,
+    /// Syncing failed with the given error message. The local mail is fetched anyway.
+    SyncFailed(String),
+    /// A full sync started; carries the number of messages it downloads, if known.
+    FullSyncStarted(Option<usize>),
     ImageResizeRequest(ResizeRequest),
     FolderChanged,
+    /// Grow the inbox and refetch more emails for the current folder.
+    LoadMore,
     Quit,
 }
 
@@ -1,14 +1,20 @@
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 use crate::ui::{
     event::AppEvent,
-    app::{App, ActiveViewState, BaseViewState},
-    components::composer_view::{Composer, ComposeViewField},
+    app::{App, ActiveViewState, BaseViewState, BulkAction, next_unread_index},
+    components::composer_view::ComposeViewField,
     components::message_view::Messager,
 };
 use crate::core::email::EmailMessage;
 use crate::error::Error;
 use std::sync::Arc;
 
+/// Whether `key_event` is Ctrl-C, which quits from every view.
+pub fn is_ctrl_c(key_event: &KeyEvent) -> bool {
+    key_event.modifiers.contains(KeyModifiers::CONTROL)
+        && matches!(key_event.code, KeyCode::Char('c') | KeyCode::Char('C'))
+}
+
 /// Input handling for the App
 impl App {
     /// Handles key events for the application.
@@ -16,6 +22,22 @@ impl App {
     /// First, `handle_key_events()` checks the current view state, and delegates to 
     /// the appropriate handler for the current view state. 
     pub fn handle_key_events(&mut self, key_event: KeyEvent) -> Result<(), Error> {
+        if self.quit_pending {
+            self.handle_quit_confirmation(key_event);
+            return Ok(());
+        }
+        if is_ctrl_c(&key_event) {
+            // An unsent draft is not thrown away without asking, nor is a running
+            // sync or send (see `request_quit`)
+            match &self.state {
+                ActiveViewState::ComposeView(composer) if composer.has_draft() => {
+                    self.quit_pending = true;
+                    self.set_status("Discard this draft and quit? (y/n)");
+                }
+                _ => self.request_quit(),
+            }
+            return Ok(());
+        }
         match &self.state {
             ActiveViewState::BaseView(b) => self.handle_base_view(key_event, *b)?,
             ActiveViewState::MessageView(_) => self.handle_message_view(key_event)?,
@@ -23,6 +45,8 @@ impl App {
             // such that we can write the email there. If the email is done being
             // written, exiting the program should return back to termail. 
             ActiveViewState::ComposeView(_) => self.handle_compose_view(key_event)?,
+            ActiveViewState::PluginView(_) => self.handle_plugin_view(key_event),
+            ActiveViewState::MovePicker(_) => self.handle_move_picker(key_event),
         }
         Ok(())
     }
@@ -34,9 +58,11 @@ impl App {
             (_, KeyCode::Esc) => self.events.send(AppEvent::Quit),
             // Sync from cloud (refresh local maildir from backend)
             (_, KeyCode::Char('r')) => self.events.send(AppEvent::SyncFromCloud),
+            // Retry the mailbox fetch after a failure
+            (_, KeyCode::Char('R')) if self.emails.is_failed() => self.events.send(AppEvent::RetryFetch),
             
             // Handle Compose View
-            (_, KeyCode::Char('c')) => self.state = ActiveViewState::ComposeView(Composer::new(self.config.termail.editor.clone())),
+            (_, KeyCode::Char('c')) => self.open_composer(EmailMessage::new()),
 
             // Handle View Cycling
             (BaseViewState::Labels, KeyCode::Tab) => self.state = ActiveViewState::BaseView(BaseViewState::Inbox),
@@ -48,16 +74,46 @@ impl App {
             // TODO: Handle scrolling through the labels.
             (BaseViewState::Inbox, KeyCode::Down) => self.hover_next_email(),
             (BaseViewState::Inbox, KeyCode::Up) => self.hover_previous_email(),
+            // Load more emails into the inbox
+            (BaseViewState::Inbox, KeyCode::Char('l')) => self.events.send(AppEvent::LoadMore),
+            // Multi-select: toggle the mode, mark emails, then apply an action to all of them
+            (BaseViewState::Inbox, KeyCode::Char('v')) => self.toggle_select_mode(),
+            (BaseViewState::Inbox, KeyCode::Char(' ')) if self.select_mode => self.toggle_hovered_selection(),
+            (BaseViewState::Inbox, KeyCode::Char('m')) if self.select_mode => self.apply_bulk_action(BulkAction::MarkRead),
+            (BaseViewState::Inbox, KeyCode::Char('d')) if self.select_mode => self.apply_bulk_action(BulkAction::Delete),
+            (BaseViewState::Inbox, KeyCode::Char('a')) if self.select_mode => self.apply_bulk_action(BulkAction::Archive),
+            (BaseViewState::Inbox, KeyCode::Char('t')) if self.select_mode => self.apply_bulk_action(BulkAction::Trash),
+            (BaseViewState::Inbox, KeyCode::Char('d')) => self.delete_hovered_email(),
+            (BaseViewState::Inbox, KeyCode::Char('m')) => self.move_hovered_email(),
+            (BaseViewState::Inbox, KeyCode::Char('C')) => self.compose_to_hovered_sender(),
+            (BaseViewState::Inbox, KeyCode::Char('n')) => self.hover_next_unread_email(),
+            (BaseViewState::Inbox, KeyCode::Char('Y')) => {
+                let sender = self.selected_email_index
+                    .and_then(|index| self.emails.loaded()?.get(index))
+                    .map(|email| email.from.clone());
+                if let Some(sender) = sender {
+                    self.copy_sender_address(&sender);
+                }
+            }
+            // Undo the last mark-read/delete/archive/trash
+            (_, KeyCode::Char('U')) => self.undo_last_action(),
+            // Open the plugin manager
+            (_, KeyCode::Char('P')) => self.open_plugin_view(),
+            // Show the log file below the inbox
+            (_, KeyCode::Char('L')) => self.toggle_log_pane(),
+            // Sign in again, discarding the cached token
+            (_, KeyCode::Char('A')) => self.events.send(AppEvent::Login),
             (BaseViewState::Inbox, KeyCode::Enter) => {
                 // Enter the message view: fetch full email (with attachments) by id
                 // The initial scroll position will be the top of the email body.
                 let selected_email = self.selected_email_index
-                    .and_then(|index| self.emails.as_ref()?.get(index))
+                    .and_then(|index| self.emails.loaded()?.get(index))
                     .cloned()
                     .unwrap_or_else(EmailMessage::new);
 
                 // kick off async load of the full email (with attachments)
                 Self::spawn_single_email_fetch(
+                    &self.in_flight,
                     Arc::clone(&self.backend),
                     self.events.get_sender(),
                     selected_email.id.clone(),
@@ -65,16 +121,30 @@ impl App {
 
                 // Optimistically enter message view with current (partial) email while loading
                 self.async_state = None;
-                self.state = ActiveViewState::MessageView(Messager::new(selected_email));
+                self.state = ActiveViewState::MessageView(
+                    Messager::new(selected_email).with_render_limit(self.config.max_render_bytes())
+                        .with_labels(self.labels.as_deref().unwrap_or(&[]), &self.config.termail.label_names)
+                );
             }
             _ => {}
         }
         Ok(())
     }
 
+    /// Hovers the next unread email below the current one, wrapping around to the top.
+    fn hover_next_unread_email(&mut self) {
+        let Some(emails) = self.emails.loaded() else {
+            return;
+        };
+        match next_unread_index(emails, self.selected_email_index, true) {
+            Some(index) => self.selected_email_index = Some(index),
+            None => self.set_status("No unread emails"),
+        }
+    }
+
     /// Hovers the next email in the list
     fn hover_next_email(&mut self) {
-        if let Some(emails) = &self.emails {
+        if let Some(emails) = self.emails.loaded() {
             if emails.is_empty() {
                 return;
             }
@@ -152,19 +222,126 @@ impl App {
 
     /// Handles key events for the message view.
     /// 
-    /// Supports scrolling through the message body.
+    /// Supports scrolling through the message body. While the attachments pane is
+    /// open, the arrow keys move through the attachments instead.
     fn handle_message_view(&mut self, key_event: KeyEvent) -> Result<(), Error> {
         let messager = match &mut self.state {
             ActiveViewState::MessageView(messager) => messager,
             _ => unreachable!("Not in message view"),
         };
+        if messager.show_links {
+            let link = match key_event.code {
+                KeyCode::Char('u') | KeyCode::Esc => {
+                    messager.toggle_links();
+                    None
+                }
+                KeyCode::Down => {
+                    messager.select_next_link();
+                    None
+                }
+                KeyCode::Up => {
+                    messager.select_previous_link();
+                    None
+                }
+                KeyCode::Char('o') => {
+                    if let Some(Err(e)) = messager.selected_link().map(Self::open_in_browser) {
+                        self.set_status(e.to_string());
+                    }
+                    None
+                }
+                KeyCode::Char('y') | KeyCode::Enter => messager.selected_link().map(str::to_string),
+                // 1-9 copy the link with that number directly
+                KeyCode::Char(digit @ '1'..='9') => {
+                    let index = digit.to_digit(10).unwrap() as usize - 1;
+                    messager.links.get(index).cloned()
+                }
+                _ => None,
+            };
+            if let Some(link) = link {
+                match self.copy_to_clipboard(&link) {
+                    Ok(()) => self.set_status(format!("Copied {}", link)),
+                    Err(e) => self.set_status(e.to_string()),
+                }
+            }
+            return Ok(());
+        }
+        let ctrl = key_event.modifiers.contains(KeyModifiers::CONTROL);
+        match (messager.show_attachments, key_event.code) {
+            // Half-page scrolling, vim style; checked before plain `u` toggles the links
+            (false, KeyCode::Char('d')) if ctrl => messager.scroll_half_page_down(),
+            (false, KeyCode::Char('u')) if ctrl => messager.scroll_half_page_up(),
+            // Toggle the attachments pane
+            (_, KeyCode::Char('a')) => messager.toggle_attachments(),
+            (_, KeyCode::Char('u')) => messager.toggle_links(),
+            (true, KeyCode::Esc) => messager.toggle_attachments(),
+            (true, KeyCode::Down) => messager.select_next_attachment(),
+            (true, KeyCode::Up) => messager.select_previous_attachment(),
+            (true, KeyCode::Char('s')) => {
+                let dir = dirs::download_dir().unwrap_or_else(|| std::path::PathBuf::from("."));
+                match messager.save_selected_attachment(&dir) {
+                    Ok(path) => tracing::info!("Saved attachment to {:?}", path),
+                    Err(e) => tracing::error!("{}", e),
+                }
+            }
+            (false, KeyCode::Esc) => self.state = ActiveViewState::BaseView(BaseViewState::Inbox),
+            (false, KeyCode::Down) => messager.scroll_down(),
+            (false, KeyCode::Up) => messager.scroll_up(),
+            (false, KeyCode::PageDown) => messager.scroll_page_down(),
+            (false, KeyCode::PageUp) => messager.scroll_page_up(),
+            (false, KeyCode::Char('T')) if messager.is_truncated() => messager.show_full_body(),
+            (false, KeyCode::Char('Y')) => {
+                let sender = messager.email.from.clone();
+                self.copy_sender_address(&sender);
+            }
+            (false, KeyCode::Char('g')) => messager.scroll_to_top(),
+            (false, KeyCode::Char('G')) => messager.scroll_to_bottom(),
+            (false, KeyCode::Right) => messager.scroll_right(),
+            (false, KeyCode::Left) => messager.scroll_left(),
+            (false, KeyCode::Char('w')) => messager.toggle_wrap(),
+            (false, KeyCode::Char('S')) => self.set_open_email_spam(true),
+            (false, KeyCode::Char('N')) => self.set_open_email_spam(false),
+            (false, KeyCode::Char('r')) => self.reply_to_open_email(),
+            (false, KeyCode::Char('m')) => {
+                let email_id = messager.email.id.clone();
+                let return_to = messager.clone();
+                self.open_move_picker(email_id, Some(return_to));
+            }
+            (false, KeyCode::Char('o')) => {
+                if let Err(e) = Self::open_html_in_browser(&messager.email) {
+                    self.set_status(e.to_string());
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Handles key events for the plugin manager view.
+    fn handle_plugin_view(&mut self, key_event: KeyEvent) {
+        let ActiveViewState::PluginView(view) = &mut self.state else {
+            return;
+        };
         match key_event.code {
             KeyCode::Esc => self.state = ActiveViewState::BaseView(BaseViewState::Inbox),
-            KeyCode::Down => messager.scroll_down(),
-            KeyCode::Up => messager.scroll_up(),
+            KeyCode::Down => view.select_next(),
+            KeyCode::Up => view.select_previous(),
+            KeyCode::Char(' ') | KeyCode::Enter => self.toggle_selected_plugin(),
+            _ => {}
+        }
+    }
+
+    /// Handles key events for the "move to" picker.
+    fn handle_move_picker(&mut self, key_event: KeyEvent) {
+        let ActiveViewState::MovePicker(picker) = &mut self.state else {
+            return;
+        };
+        match key_event.code {
+            KeyCode::Esc => self.close_move_picker(),
+            KeyCode::Down => picker.select_next(),
+            KeyCode::Up => picker.select_previous(),
+            KeyCode::Enter => self.move_to_picked_folder(),
             _ => {}
         }
-        Ok(())
     }
 
     /// Handles the key events for the compose view.
@@ -182,7 +359,7 @@ impl App {
                 KeyCode::Enter => {
                     // TODO: check if the email is valid
                     tracing::info!("Sending email: {:?}", cvs.draft);
-                    self.events.send(AppEvent::SendEmail(cvs.draft.clone()));
+                    self.events.send(AppEvent::SendEmail(Box::new(cvs.draft.clone()), cvs.reply_to.clone()));
                     self.state = ActiveViewState::BaseView(BaseViewState::Inbox);
                     // Return early to avoid borrowing `self.state` again. Alternatively,
                     // we could wrap the match in an else block, but that would be more verbose.
@@ -247,7 +424,7 @@ impl App {
             // Spawn the editor to write the email body
             (ComposeViewField::Body, KeyCode::Enter) => self.events.send(AppEvent::SpawnEditor),
             (_, KeyCode::Char('p')) => {
-                self.events.send(AppEvent::SendEmail(cvs.draft.clone()));
+                self.events.send(AppEvent::SendEmail(Box::new(cvs.draft.clone()), cvs.reply_to.clone()));
                 self.state = ActiveViewState::BaseView(BaseViewState::Inbox);
             }
             _ => {}
@@ -7,11 +7,43 @@ use ratatui::{
 
 use crate::{
     ui::{
-        app::{ActiveViewState, App},
-        components::{folder_pane::FolderPane, inbox::Inbox}
+        app::{ActiveViewState, App, LoadState, spinner_frame},
+        components::{
+            attachments_pane::{AttachmentsPane, attachment_rows},
+            folder_pane::{FolderPane, folder_pane_width, folder_scroll_offset, selected_folder_index},
+            inbox::Inbox,
+            links_pane::LinksPane,
+            log_pane::LogPane,
+        }
     },
 };
 
+/// The base view's top bar, e.g. `termail - gmail (me@example.com) | Inbox | 3 unread`.
+/// While syncing or loading that is said instead of the unread count, which is
+/// left out until the folder counts are known.
+pub fn top_bar_text(
+    backend: &str,
+    account: Option<&str>,
+    folder: &str,
+    unread: Option<usize>,
+    loading: bool,
+    syncing: bool,
+) -> String {
+    let mut text = format!("termail - {}", backend);
+    if let Some(account) = account {
+        text.push_str(&format!(" ({})", account));
+    }
+    text.push_str(&format!(" | {}", folder));
+    if syncing {
+        text.push_str(" | syncing…");
+    } else if loading {
+        text.push_str(" | loading…");
+    } else if let Some(unread) = unread {
+        text.push_str(&format!(" | {} unread", unread));
+    }
+    text
+}
+
 /// Layout structure containing all UI component rectangles
 struct AppLayouts {
     top_bar: Rect,
@@ -39,7 +71,15 @@ impl App {
         AppLayouts { top_bar, middle, bottom_bar }
     }
 
+    /// Renders the top bar. While background tasks are running, a spinner is shown
+    /// in front of `text`.
     pub fn render_top_bar(&self, area: Rect, buf: &mut Buffer, text: String) {
+        let text = if self.in_flight.count() > 0 {
+            format!("{} {}", spinner_frame(self.tick_counter), text)
+        } else {
+            text
+        };
+
         let block = Block::default()
             .borders(Borders::ALL)
             .border_type(BorderType::Rounded)
@@ -67,31 +107,15 @@ impl App {
         paragraph.render(area, buf);
     }
 
-    /// Calculate the optimal folder pane width based on loaded labels
-    /// Returns the width in characters + 2 for the borders, or 20 if labels aren't loaded yet
+    /// Calculate the optimal folder pane width based on loaded labels, see
+    /// `folder_pane_width`. 20 while labels are loading.
     pub fn calculate_folder_pane_width(&self) -> u16 {
-        let max_label_len = self.labels.as_ref().and_then(|labels| {
-            labels.iter()
-                .filter_map(|label| {
-                    // Only calculate for labels with all required fields
-                    let name = label.name.as_ref()?;
-                    // let unread = label.messages_unread?;
-                    // let total = label.messages_total?;
-
-                    // Calculate the display width: "Name (unread/total)"
-                    let width = name.len();
-
-                    Some(width)
-                })
-                .max()
-                .map(|max_width| {
-                    // Add some padding (title + borders = ~4 chars)
-                    // Clamp between reasonable min/max values
-                    (max_width).clamp(10, 50) as u16
-                })
-        });
-        match max_label_len {
-            Some(l) => l.saturating_add(2),  // Add 2 for the borders
+        match self.labels.as_ref() {
+            Some(labels) => folder_pane_width(
+                labels.iter()
+                    .filter(|label| label.name.is_some())
+                    .map(|label| label.display_name(&self.config.termail.label_names))
+            ),
             None => 20,  // Default to 20 if labels not loaded
         }
     }
@@ -113,9 +137,35 @@ impl App {
 
         match &self.state {
             ActiveViewState::BaseView(bv) => {
-                let text = format!("termail - {}", self.config.termail.default_backend);
+                let backend = self.config.termail.default_backend;
+                let account = self.config.get_backend_config(&backend)
+                    .and_then(|config| config.auth_credentials.as_ref())
+                    .map(|credentials| credentials.username.as_str());
+                let folder = self.labels.as_deref()
+                    .and_then(|labels| labels.iter().find(|label| label.name.as_deref() == Some(self.selected_folder.as_str())));
+                let text = top_bar_text(
+                    &backend.to_string(),
+                    account,
+                    &folder.map_or_else(|| self.selected_folder.clone(), |label| label.display_name(&self.config.termail.label_names)),
+                    folder.and_then(|label| label.messages_unread),
+                    matches!(self.emails, LoadState::Loading),
+                    self.syncing,
+                );
                 self.render_top_bar(layouts.top_bar, buf, text);
 
+                // The log pane, when open, takes the lower third below folders and inbox
+                let middle = match &self.log_tail {
+                    Some(tail) => {
+                        let split = Layout::default()
+                            .direction(Direction::Vertical)
+                            .constraints(vec![Constraint::Min(0), Constraint::Length(layouts.middle.height / 3)])
+                            .split(layouts.middle);
+                        LogPane { tail }.render(split[1], buf);
+                        split[0]
+                    }
+                    None => layouts.middle,
+                };
+
                 // Middle section: folder | inbox
                 let middle_layout = Layout::default()
                     .direction(Direction::Horizontal)
@@ -123,38 +173,147 @@ impl App {
                         Constraint::Length(self.calculate_folder_pane_width()),  // Fixed width based on content
                         Constraint::Min(0),
                     ])
-                    .split(layouts.middle);
+                    .split(middle);
 
+                // Keep the selected folder in view; borders take two rows
+                if let Some(labels) = &self.labels {
+                    if let Some(selected) = selected_folder_index(labels, &self.selected_folder) {
+                        let rows = middle_layout[0].height.saturating_sub(2) as usize;
+                        self.folder_offset = folder_scroll_offset(selected, self.folder_offset, labels.len(), rows);
+                    }
+                }
                 FolderPane {
                     labels: self.labels.as_ref(),
                     state: bv,
                     selected_folder: &self.selected_folder,
+                    preview: self.folder_preview.as_ref(),
+                    offset: self.folder_offset,
+                    label_names: &self.config.termail.label_names,
                 }.render(middle_layout[0], buf);
 
                 Inbox {
-                    emails: self.emails.as_ref(),
+                    emails: &self.emails,
                     selected_index: self.selected_email_index,
                     state: bv,
+                    marked: self.select_mode.then_some(&self.selected_emails),
+                    sender_display: self.config.termail.sender_display,
+                    snippet_length: self.config.snippet_length(),
+                    max_label_chips: self.config.max_label_chips(),
+                    columns: &self.config.termail.columns,
+                    folders: self.labels.as_deref().unwrap_or(&[]),
+                    label_names: &self.config.termail.label_names,
                 }.render(middle_layout[1], buf);
 
                 let status = match &self.emails {
-                    None => "Loading emails...".to_string(),
-                    Some(emails) => format!("{} email(s) | Press ESC to quit | Tab to cycle views", emails.len()),
+                    LoadState::Loaded(_) if self.select_mode => format!(
+                        "SELECT: {} marked | Space to mark | m to mark read | d to delete | v to cancel",
+                        self.selected_emails.len()
+                    ),
+                    LoadState::Loading => "Loading emails...".to_string(),
+                    LoadState::Loaded(emails) => format!("{} email(s) | Press ESC to quit | Tab to cycle views", emails.len()),
+                    LoadState::Failed(error) => format!("Error: {} | Press R to retry", error),
+                };
+                // A transient message (e.g. the outcome of a send) takes precedence
+                let status = match &self.status_message {
+                    Some((message, _)) if !self.select_mode => message.clone(),
+                    _ => status,
                 };
                 self.render_bottom_bar(layouts.bottom_bar, buf, status);
             },
             ActiveViewState::MessageView(messager) => {
                 self.render_top_bar(layouts.top_bar, buf, messager.email.subject.clone());
 
-                messager.render_with_images(layouts.middle, buf, &mut self.async_state);
-                let status = format!("{} image attachment(s) | Press ESC to quit", messager.email.get_image_attachments().len());
+                if messager.show_links {
+                    // Borders take two lines; cap the pane so the message stays readable.
+                    let pane_height = (messager.links.len().max(1) as u16 + 2).min(layouts.middle.height / 2);
+                    let message_layout = Layout::default()
+                        .direction(Direction::Vertical)
+                        .constraints(vec![Constraint::Min(0), Constraint::Length(pane_height)])
+                        .split(layouts.middle);
+
+                    messager.render_with_images(message_layout[0], buf, &mut self.async_state);
+                    LinksPane {
+                        links: &messager.links,
+                        selected: messager.selected_link,
+                    }.render(message_layout[1], buf);
+                } else if messager.show_attachments {
+                    let rows = attachment_rows(&messager.email);
+                    // Borders take two lines; cap the pane so the message stays readable.
+                    let pane_height = (rows.len().max(1) as u16 + 2).min(layouts.middle.height / 2);
+                    let message_layout = Layout::default()
+                        .direction(Direction::Vertical)
+                        .constraints(vec![Constraint::Min(0), Constraint::Length(pane_height)])
+                        .split(layouts.middle);
+
+                    messager.render_with_images(message_layout[0], buf, &mut self.async_state);
+                    AttachmentsPane {
+                        rows: &rows,
+                        selected: messager.selected_attachment,
+                    }.render(message_layout[1], buf);
+                } else {
+                    messager.render_with_images(layouts.middle, buf, &mut self.async_state);
+                }
+                let status = if let Some((message, _)) = &self.status_message {
+                    message.clone()
+                } else if messager.show_links {
+                    "Up/Down to select | y/Enter or 1-9 to copy | o to open | u/ESC to close".to_string()
+                } else if messager.show_attachments {
+                    "Up/Down to select | s to save to Downloads | a/ESC to close".to_string()
+                } else {
+                    format!(
+                        "{} attachment(s) | {} link(s) | a attachments | u links | Press ESC to quit",
+                        messager.email.email_attachments.len(),
+                        messager.links.len()
+                    )
+                };
                 self.render_bottom_bar(layouts.bottom_bar, buf, status);
 
             },
             ActiveViewState::ComposeView(composer) => {
-                self.render_top_bar(layouts.top_bar, buf, "Compose Email".to_string());
+                // The composer has no bottom bar, so a pending question goes on top
+                let title = match &self.status_message {
+                    Some((question, _)) if self.quit_pending => question.clone(),
+                    _ => "Compose Email".to_string(),
+                };
+                self.render_top_bar(layouts.top_bar, buf, title);
                 frame.render_widget(composer.clone(), layouts.middle);
             },
+            ActiveViewState::PluginView(view) => {
+                self.render_top_bar(layouts.top_bar, buf, "Plugins".to_string());
+                view.render(layouts.middle, buf);
+                let status = match &self.status_message {
+                    Some((message, _)) => message.clone(),
+                    None => "Up/Down to select | Space/Enter to enable/disable | ESC to go back".to_string(),
+                };
+                self.render_bottom_bar(layouts.bottom_bar, buf, status);
+            },
+            ActiveViewState::MovePicker(picker) => {
+                self.render_top_bar(layouts.top_bar, buf, "Move Email".to_string());
+                picker.render(layouts.middle, buf);
+                let status = match &self.status_message {
+                    Some((message, _)) => message.clone(),
+                    None => "Up/Down to select | Enter to move | ESC to cancel".to_string(),
+                };
+                self.render_bottom_bar(layouts.bottom_bar, buf, status);
+            },
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_top_bar_says_what_is_happening() {
+        assert_eq!(
+            top_bar_text("gmail", Some("me@example.com"), "Inbox", Some(3), false, false),
+            "termail - gmail (me@example.com) | Inbox | 3 unread"
+        );
+        assert_eq!(top_bar_text("greenmail", None, "Inbox", None, false, false), "termail - greenmail | Inbox");
+        assert_eq!(top_bar_text("gmail", None, "Work", Some(3), true, false), "termail - gmail | Work | loading…");
+        // Syncing is reported over loading, which it ends in
+        assert_eq!(top_bar_text("gmail", None, "Work", Some(3), true, true), "termail - gmail | Work | syncing…");
+        assert_eq!(top_bar_text("gmail", None, "Work", None, false, true), "termail - gmail | Work | syncing…");
+    }
+}